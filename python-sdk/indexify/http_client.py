@@ -250,6 +250,12 @@ class IndexifyClient:
                 params=params,
             ) as event_source:
                 for sse in event_source.iter_sse():
+                    if sse.event == "lagged":
+                        skipped = json.loads(sse.data)["skipped"]
+                        print(
+                            f"[bold red]event stream lagged, {skipped} events were skipped[/bold red]"
+                        )
+                        continue
                     obj = json.loads(sse.data)
                     for k, v in obj.items():
                         if k == "id":
@@ -262,6 +268,11 @@ class IndexifyClient:
                                 f"[bold red]scheduler diagnostic: [/bold red]{message}"
                             )
                             continue
+                        if k == "OutputProduced":
+                            print(
+                                f"[bold green]{k}[/bold green]: {v['fn_name']} {v['output_id']}"
+                            )
+                            continue
                         event_payload = InvocationEventPayload.model_validate(v)
                         event = InvocationEvent(event_name=k, payload=event_payload)
                         if (
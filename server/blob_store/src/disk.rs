@@ -1,10 +1,10 @@
+use std::{fs, path::Path, time::UNIX_EPOCH};
+
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures::{stream::BoxStream, StreamExt};
 use object_store::{local::LocalFileSystem, ObjectStore};
-use tokio::sync::mpsc;
-use tokio_stream::wrappers::UnboundedReceiverStream;
 
 use super::BlobStorageReader;
 
@@ -21,23 +21,111 @@ impl DiskFileReader {
 
 #[async_trait]
 impl BlobStorageReader for DiskFileReader {
+    /// Chunks are read from the file as the stream is polled, so a slow
+    /// consumer doesn't make the file pile up in memory.
     async fn get(&self) -> Result<BoxStream<'static, Result<Bytes>>> {
-        let (tx, rx) = mpsc::unbounded_channel();
         let file_path = &self.file_path.trim_start_matches("file://").to_string();
         let client = LocalFileSystem::new();
-        let get_result = client
-            .get(&file_path.clone().into())
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to read file: {:?}, error: {}", file_path, e))?;
+        let get_result = client.get(&file_path.clone().into()).await.map_err(|e| {
+            let message = format!("Failed to read file: {:?}, error: {}", file_path, e);
+            anyhow::Error::from(e).context(message)
+        })?;
         let file_path = file_path.clone();
-        tokio::spawn(async move {
-            let mut stream = get_result.into_stream();
-            while let Some(chunk) = stream.next().await {
-                let _ = tx.send(chunk.map_err(|e| {
-                    anyhow!("error reading s3 object {:?}: {:?}", file_path.clone(), e)
-                }));
+        let stream = get_result.into_stream().map(move |chunk| {
+            chunk.map_err(|e| anyhow!("error reading file {:?}: {:?}", file_path, e))
+        });
+        Ok(stream.boxed())
+    }
+}
+
+/// A file found by `list_files`.
+pub(crate) struct ListedFile {
+    pub key: String,
+    pub size_bytes: u64,
+    /// Milliseconds since the epoch.
+    pub last_modified: u64,
+}
+
+/// Returns up to `limit` files under `root` whose key starts with `prefix`
+/// and sorts after `after`, in key order. Directories are read in key order
+/// and only descended into when they can hold a matching key, so reading a
+/// page doesn't depend on the number of files after it.
+pub(crate) fn list_files(
+    root: &Path,
+    prefix: &str,
+    after: Option<&str>,
+    limit: usize,
+) -> Result<Vec<ListedFile>> {
+    let mut files = Vec::new();
+    list_directory(root, "", prefix, after, limit, &mut files)?;
+    Ok(files)
+}
+
+fn list_directory(
+    directory: &Path,
+    key_prefix: &str,
+    prefix: &str,
+    after: Option<&str>,
+    limit: usize,
+    files: &mut Vec<ListedFile>,
+) -> Result<()> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        // Staging files of the uploads in progress.
+        if name.contains('#') {
+            continue;
+        }
+        // Keys of a directory sort after the directory name followed by `/`.
+        let is_dir = entry.file_type()?.is_dir();
+        let key = if is_dir {
+            format!("{}{}/", key_prefix, name)
+        } else {
+            format!("{}{}", key_prefix, name)
+        };
+        entries.push((key, is_dir, entry));
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    for (key, is_dir, entry) in entries {
+        if files.len() >= limit {
+            break;
+        }
+        let matches = key.starts_with(prefix) || (is_dir && prefix.starts_with(&key));
+        if !matches {
+            if key.as_str() > prefix {
+                // Every following key sorts after the prefix too.
+                break;
             }
+            continue;
+        }
+        if let Some(after) = after {
+            let listed = if is_dir {
+                key.as_str() < after && !after.starts_with(&key)
+            } else {
+                key.as_str() <= after
+            };
+            if listed {
+                continue;
+            }
+        }
+        if is_dir {
+            list_directory(&entry.path(), &key, prefix, after, limit, files)?;
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        let last_modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        files.push(ListedFile {
+            key,
+            size_bytes: metadata.len(),
+            last_modified,
         });
-        Ok(Box::pin(UnboundedReceiverStream::new(rx)))
     }
+    Ok(())
 }
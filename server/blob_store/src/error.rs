@@ -0,0 +1,106 @@
+use std::{error::Error, fmt};
+
+/// A blob storage failure that is expected to go away on retry, such as a
+/// request or connect timeout against the storage backend.
+#[derive(Debug)]
+pub struct TransientError(anyhow::Error);
+
+impl fmt::Display for TransientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "transient blob storage error: {:#}", self.0)
+    }
+}
+
+impl Error for TransientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// A conditional write was rejected because the blob already exists.
+#[derive(Debug)]
+pub struct AlreadyExistsError {
+    pub key: String,
+}
+
+impl fmt::Display for AlreadyExistsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "blob {} already exists", self.key)
+    }
+}
+
+impl Error for AlreadyExistsError {}
+
+/// A blob read with `BlobStorage::get_verified` doesn't hash to the digest
+/// recorded when it was written.
+#[derive(Debug)]
+pub struct HashMismatchError {
+    pub key: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for HashMismatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "blob {} has sha256 {}, expected {}",
+            self.key, self.actual, self.expected
+        )
+    }
+}
+
+impl Error for HashMismatchError {}
+
+/// Returns true if the error is a blob whose content doesn't match its
+/// recorded hash.
+pub fn is_hash_mismatch(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<HashMismatchError>().is_some()
+}
+
+/// Returns true if the error is a conditional write that lost to an existing
+/// blob.
+pub fn is_already_exists(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<AlreadyExistsError>().is_some()
+}
+
+/// Returns true if the error is a read or delete of a blob which doesn't
+/// exist.
+pub fn is_not_found(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<object_store::Error>(),
+        Some(object_store::Error::NotFound { .. })
+    )
+}
+
+/// Returns true if the error was classified as transient by the blob store
+/// and the operation can be retried.
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<TransientError>().is_some()
+}
+
+/// Converts a backend error into an `anyhow::Error`, wrapping timeouts and
+/// connection failures of the underlying HTTP client in `TransientError`.
+pub(crate) fn classify<E>(err: E) -> anyhow::Error
+where
+    E: Error + Send + Sync + 'static,
+{
+    if has_transient_source(&err) {
+        TransientError(err.into()).into()
+    } else {
+        err.into()
+    }
+}
+
+fn has_transient_source(err: &(dyn Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+            if err.is_timeout() || err.is_connect() {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+    false
+}
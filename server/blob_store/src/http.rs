@@ -3,16 +3,20 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use futures::{stream::BoxStream, StreamExt};
 
-use super::BlobStorageReader;
+use super::{error::classify, BlobStorageReader};
 
 pub struct HttpReader {
     url: String,
+    client: reqwest::Client,
 }
 
 impl HttpReader {
-    pub fn new(url: &str) -> Self {
+    /// Reads `url` with `client`, sharing its connection pool with the other
+    /// readers of the storage.
+    pub fn new(url: &str, client: reqwest::Client) -> Self {
         Self {
             url: url.to_string(),
+            client,
         }
     }
 }
@@ -20,8 +24,7 @@ impl HttpReader {
 #[async_trait]
 impl BlobStorageReader for HttpReader {
     async fn get(&self) -> Result<BoxStream<'static, Result<Bytes>>> {
-        let client = reqwest::Client::new();
-        let response = client.get(&self.url).send().await?;
+        let response = self.client.get(&self.url).send().await.map_err(classify)?;
         let stream = async_stream::stream! {
             let mut stream = response.bytes_stream();
             while let Some(chunk) = stream.next().await {
@@ -31,3 +34,42 @@ impl BlobStorageReader for HttpReader {
         Ok(Box::pin(stream))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::{error::is_transient, BlobClientConfig};
+
+    #[tokio::test]
+    async fn test_request_timeout_is_transient() -> Result<()> {
+        // Accepts connections but never answers them.
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+
+        let client_config = BlobClientConfig {
+            request_timeout_secs: 1,
+            ..Default::default()
+        };
+        let reader = HttpReader::new(
+            &format!("http://{}/blob", addr),
+            client_config.http_client()?,
+        );
+        let res = tokio::time::timeout(Duration::from_secs(10), reader.get()).await?;
+
+        let err = res
+            .err()
+            .expect("request to a stalled endpoint should fail");
+        assert!(is_transient(&err), "unexpected error: {:?}", err);
+        Ok(())
+    }
+}
@@ -1,12 +1,21 @@
-use std::{env, fmt::Debug, sync::Arc};
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
 use bytes::{Bytes, BytesMut};
 use futures::{stream::BoxStream, StreamExt};
+use nanoid::nanoid;
 use object_store::{
-    aws::{AmazonS3, AmazonS3Builder},
+    aws::{AmazonS3, AmazonS3Builder, S3ConditionalPut, S3CopyIfNotExists},
     local,
+    signer::Signer,
+    ClientOptions,
     ObjectStore,
     WriteMultipart,
 };
@@ -14,12 +23,23 @@ use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tokio::io::AsyncWrite;
 
-use self::{disk::DiskFileReader, s3::S3FileReader};
+use self::{
+    disk::DiskFileReader,
+    error::{classify, AlreadyExistsError, HashMismatchError},
+    replicated::ReplicatedReader,
+    s3::{bucket_client, S3FileReader},
+};
 
 pub mod disk;
+pub mod error;
 pub mod http;
+pub mod replicated;
 pub mod s3;
 
+/// Prefix of the blobs create-only puts are staged to. Staging blobs left
+/// behind by a crash are removed by the orphan blob collection.
+pub const STAGING_PREFIX: &str = ".staging/";
+
 type BlobStorageReaderTS = Arc<dyn BlobStorageReader + Sync + Send>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,10 +53,53 @@ pub struct DiskStorageConfig {
     pub path: String,
 }
 
+/// Settings of the HTTP client used to reach remote blob storage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlobClientConfig {
+    pub pool_max_idle_per_host: usize,
+    pub connect_timeout_secs: u64,
+    pub request_timeout_secs: u64,
+}
+
+impl Default for BlobClientConfig {
+    fn default() -> Self {
+        BlobClientConfig {
+            pool_max_idle_per_host: 32,
+            connect_timeout_secs: 5,
+            request_timeout_secs: 30,
+        }
+    }
+}
+
+impl BlobClientConfig {
+    pub fn client_options(&self) -> ClientOptions {
+        ClientOptions::new()
+            .with_pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .with_connect_timeout(Duration::from_secs(self.connect_timeout_secs))
+            .with_timeout(Duration::from_secs(self.request_timeout_secs))
+    }
+
+    pub fn http_client(&self) -> Result<reqwest::Client> {
+        reqwest::Client::builder()
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .connect_timeout(Duration::from_secs(self.connect_timeout_secs))
+            .timeout(Duration::from_secs(self.request_timeout_secs))
+            .build()
+            .context("unable to build blob storage http client")
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlobStorageConfig {
     pub s3: Option<S3Config>,
     pub disk: Option<DiskStorageConfig>,
+    #[serde(default)]
+    pub client: BlobClientConfig,
+    /// Storages every blob is also written to, read when a blob is missing
+    /// from this one.
+    #[serde(default)]
+    pub replicas: Vec<BlobStorageConfig>,
 }
 
 impl BlobStorageConfig {
@@ -46,6 +109,8 @@ impl BlobStorageConfig {
             disk: Some(DiskStorageConfig {
                 path: path.to_string(),
             }),
+            client: Default::default(),
+            replicas: vec![],
         }
     }
 }
@@ -58,10 +123,37 @@ impl Default for BlobStorageConfig {
             disk: Some(DiskStorageConfig {
                 path: blob_store_path.to_str().unwrap().to_string(),
             }),
+            client: Default::default(),
+            replicas: vec![],
         }
     }
 }
 
+/// Maximum number of objects returned by a single `list` call.
+pub const LIST_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlobInfo {
+    /// Path of the object in the store, usable as a listing continuation.
+    pub key: String,
+    /// Url of the object, as returned by `put`.
+    pub url: String,
+    pub size_bytes: u64,
+    /// Milliseconds since the epoch.
+    pub last_modified: u64,
+    /// Content type the object was stored with, only known to `head` on
+    /// stores recording it.
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PutOptions {
+    /// Fail with `AlreadyExistsError` instead of overwriting an existing
+    /// blob. The data is streamed to a staging blob first, which is then
+    /// copied to the key only if it doesn't exist.
+    pub if_not_exists: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct PutResult {
     pub url: String,
@@ -92,7 +184,14 @@ pub trait BlobStorageReader {
 #[derive(Clone)]
 pub struct BlobStorage {
     object_store: Arc<dyn ObjectStore>,
+    /// Set for the backends which can presign urls.
+    signer: Option<Arc<dyn Signer>>,
     config: BlobStorageConfig,
+    /// Clients shared by every reader, so their connections are pooled.
+    http_client: reqwest::Client,
+    s3_clients: Arc<Mutex<HashMap<String, Arc<dyn ObjectStore>>>>,
+    /// Storages blobs are copied to as they're written.
+    replicas: Vec<BlobStorage>,
 }
 
 pub struct StoragePartWriter {
@@ -109,11 +208,14 @@ impl std::fmt::Debug for StoragePartWriter {
     }
 }
 
-fn s3_storage(s3: &S3Config) -> Result<AmazonS3> {
+fn s3_storage(s3: &S3Config, client: &BlobClientConfig) -> Result<AmazonS3> {
     Ok(AmazonS3Builder::from_env()
         .with_region(s3.region.as_str())
         .with_allow_http(true)
         .with_bucket_name(s3.bucket.clone())
+        .with_conditional_put(S3ConditionalPut::ETagMatch)
+        .with_copy_if_not_exists(S3CopyIfNotExists::Multipart)
+        .with_client_options(client.client_options())
         .build()
         .context("unable to build S3 builder")?)
 }
@@ -126,19 +228,36 @@ fn file_storage(disk: DiskStorageConfig) -> Result<local::LocalFileSystem> {
 
 impl BlobStorage {
     pub fn new(config: BlobStorageConfig) -> Result<Self> {
-        let object_store: Arc<dyn ObjectStore> = if let Some(s3) = config.s3.as_ref() {
-            let s = s3_storage(s3)?;
-            Arc::new(s)
-        } else {
-            // If it's not S3, assume it's a file
-            let s = file_storage(config.disk.clone().unwrap_or_else(|| DiskStorageConfig {
-                path: "blobs".to_string(),
-            }))?;
-            Arc::new(s)
-        };
+        let replicas = config
+            .replicas
+            .iter()
+            .map(|replica| {
+                if !replica.replicas.is_empty() {
+                    return Err(anyhow!("blob storage replicas can't have replicas"));
+                }
+                BlobStorage::new(replica.clone())
+            })
+            .collect::<Result<_>>()?;
+        let mut s3_clients: HashMap<String, Arc<dyn ObjectStore>> = HashMap::new();
+        let (object_store, signer): (Arc<dyn ObjectStore>, Option<Arc<dyn Signer>>) =
+            if let Some(s3) = config.s3.as_ref() {
+                let s = Arc::new(s3_storage(s3, &config.client)?);
+                s3_clients.insert(s3.bucket.clone(), s.clone());
+                (s.clone(), Some(s))
+            } else {
+                // If it's not S3, assume it's a file
+                let s = file_storage(config.disk.clone().unwrap_or_else(|| DiskStorageConfig {
+                    path: "blobs".to_string(),
+                }))?;
+                (Arc::new(s), None)
+            };
         Ok(Self {
             object_store,
+            signer,
+            http_client: config.client.http_client()?,
+            s3_clients: Arc::new(Mutex::new(s3_clients)),
             config,
+            replicas,
         })
     }
 
@@ -146,6 +265,30 @@ impl BlobStorage {
         &self,
         key: &str,
         data: impl futures::Stream<Item = Result<Bytes>> + Send + Unpin,
+    ) -> Result<PutResult, anyhow::Error> {
+        self.put_opts(key, data, PutOptions::default()).await
+    }
+
+    /// Writes the blob, copying it to the replicas of the storage as it's
+    /// written.
+    pub async fn put_opts(
+        &self,
+        key: &str,
+        data: impl futures::Stream<Item = Result<Bytes>> + Send + Unpin,
+        options: PutOptions,
+    ) -> Result<PutResult, anyhow::Error> {
+        if self.replicas.is_empty() {
+            return self.put_primary(key, data, options).await;
+        }
+        replicated::put(self, key, data, options).await
+    }
+
+    /// Writes the blob to this storage only.
+    async fn put_primary(
+        &self,
+        key: &str,
+        data: impl futures::Stream<Item = Result<Bytes>> + Send + Unpin,
+        options: PutOptions,
     ) -> Result<PutResult, anyhow::Error> {
         let mut hasher = Sha256::new();
         let mut hashed_stream = data.map(|item| {
@@ -156,16 +299,37 @@ impl BlobStorage {
         });
 
         let path = object_store::path::Path::from(key);
-        let m = self.object_store.put_multipart(&path).await?;
-        let mut w = WriteMultipart::new(m);
-        let mut size_bytes = 0;
-        while let Some(chunk) = hashed_stream.next().await {
-            w.wait_for_capacity(1).await?;
-            let chunk = chunk?;
-            size_bytes += chunk.len() as u64;
-            w.write(&chunk);
-        }
-        w.finish().await?;
+        let size_bytes = if options.if_not_exists {
+            // Multipart uploads can't be conditional, so the data is uploaded
+            // to a staging blob and copied with a create-only copy (a
+            // multipart copy completed with `If-None-Match: *` on S3, a
+            // no-clobber link on local disk).
+            // Random so servers sharing the bucket never stage to the same
+            // blob.
+            let staging =
+                object_store::path::Path::from(format!("{}{}.{}", STAGING_PREFIX, key, nanoid!()));
+            let written = self.write_multipart(&staging, &mut hashed_stream).await;
+            let copied = match written {
+                Ok(_) => self.object_store.copy_if_not_exists(&staging, &path).await,
+                Err(_) => Ok(()),
+            };
+            if let Err(err) = self.object_store.delete(&staging).await {
+                tracing::warn!("failed to delete staging blob {}: {}", staging, err);
+            }
+            match copied {
+                Ok(()) => {}
+                Err(object_store::Error::AlreadyExists { .. }) => {
+                    return Err(AlreadyExistsError {
+                        key: key.to_string(),
+                    }
+                    .into());
+                }
+                Err(e) => return Err(classify(e)),
+            }
+            written?
+        } else {
+            self.write_multipart(&path, &mut hashed_stream).await?
+        };
 
         let hash = format!("{:x}", hasher.finalize());
         Ok(PutResult {
@@ -175,6 +339,35 @@ impl BlobStorage {
         })
     }
 
+    /// Streams the data to `path` with a multipart upload, returning its size.
+    async fn write_multipart(
+        &self,
+        path: &object_store::path::Path,
+        data: &mut (impl futures::Stream<Item = Result<Bytes>> + Send + Unpin),
+    ) -> Result<u64> {
+        let m = self
+            .object_store
+            .put_multipart(path)
+            .await
+            .map_err(classify)?;
+        let mut w = WriteMultipart::new(m);
+        let mut size_bytes = 0;
+        while let Some(chunk) = data.next().await {
+            w.wait_for_capacity(1).await.map_err(classify)?;
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(err) => {
+                    let _ = w.abort().await;
+                    return Err(err);
+                }
+            };
+            size_bytes += chunk.len() as u64;
+            w.write(&chunk);
+        }
+        w.finish().await.map_err(classify)?;
+        Ok(size_bytes)
+    }
+
     pub fn path_url(&self, path: &object_store::path::Path) -> String {
         if let Some(s3) = &self.config.s3 {
             format!("s3://{}/{}", s3.bucket, path)
@@ -188,45 +381,264 @@ impl BlobStorage {
         }
     }
 
-    pub fn get(&self, key: &str) -> BlobStorageReaderTS {
+    /// Returns a reader of the blob, which falls back to the replicas of the
+    /// storage when the blob is missing from it.
+    pub fn get(&self, key: &str) -> Result<BlobStorageReaderTS> {
+        let reader = self.get_primary(key)?;
+        if self.replicas.is_empty() {
+            return Ok(reader);
+        }
+        // Blobs stored elsewhere have no replicas.
+        let Ok(path) = self.object_path(key) else {
+            return Ok(reader);
+        };
+        let replicas = self
+            .replicas
+            .iter()
+            .map(|replica| {
+                let url = replica.path_url(&path);
+                let reader = replica.get_primary(&url)?;
+                Ok((url, reader))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Arc::new(ReplicatedReader::new(key, reader, replicas)))
+    }
+
+    fn get_primary(&self, key: &str) -> Result<BlobStorageReaderTS> {
         if key.starts_with("s3://") {
             let (bucket, key) = parse_s3_url(key)
-                .map_err(|err| anyhow::anyhow!("unable to parse s3 url: {}", err))
-                .unwrap();
-            return Arc::new(S3FileReader::new(bucket, key, &self.config));
+                .map_err(|err| anyhow::anyhow!("unable to parse s3 url: {}", err))?;
+            return Ok(Arc::new(S3FileReader::new(self.s3_client(bucket)?, key)));
         }
 
         if key.starts_with("http") {
-            return Arc::new(http::HttpReader::new(key));
+            return Ok(Arc::new(http::HttpReader::new(
+                key,
+                self.http_client.clone(),
+            )));
         }
 
         // If it's not S3, assume it's a file
-        Arc::new(DiskFileReader::new(key))
+        Ok(Arc::new(DiskFileReader::new(key)))
     }
 
-    pub async fn delete(&self, key: &str) -> Result<()> {
+    /// Returns the client of `bucket`, building it on its first read. The
+    /// configured bucket is read with the client of the storage.
+    fn s3_client(&self, bucket: &str) -> Result<Arc<dyn ObjectStore>> {
+        let mut s3_clients = self.s3_clients.lock().unwrap();
+        if let Some(client) = s3_clients.get(bucket) {
+            return Ok(client.clone());
+        }
+        let client = bucket_client(bucket, &self.config)?;
+        s3_clients.insert(bucket.to_string(), client.clone());
+        Ok(client)
+    }
+
+    /// Maps a blob url produced by `put` to its path in the object store.
+    fn object_path(&self, key: &str) -> Result<object_store::path::Path> {
         if let Some(s3) = &self.config.s3 {
             let (bucket, key) = parse_s3_url(key)
                 .map_err(|err| anyhow::anyhow!("unable to parse s3 url: {}", err))?;
             if bucket != s3.bucket {
                 return Err(anyhow!("invalid bucket {}", bucket));
             }
-            let path = object_store::path::Path::from(key);
-            self.object_store.delete(&path).await?;
-            return Ok(());
+            return Ok(object_store::path::Path::from(key));
+        }
+        let prefix = format!("file://{}/", self.config.disk.as_ref().unwrap().path);
+        match key.strip_prefix(prefix.as_str()) {
+            Some(key) => Ok(object_store::path::Path::from(key)),
+            None => Err(anyhow!("invalid key {}", key)),
+        }
+    }
+
+    /// Deletes the blob and, best effort, its copies on the replicas.
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        self.delete_primary(key).await?;
+        let path = self.object_path(key)?;
+        for replica in &self.replicas {
+            let replica_url = replica.path_url(&path);
+            if let Err(e) = replica.delete_primary(&replica_url).await {
+                tracing::error!("failed to delete replica {}: {:?}", replica_url, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_primary(&self, key: &str) -> Result<()> {
+        let path = self.object_path(key)?;
+        self.object_store.delete(&path).await.map_err(classify)?;
+        Ok(())
+    }
+
+    /// Returns the metadata of a blob without downloading it, or None if it
+    /// doesn't exist.
+    pub async fn head(&self, key: &str) -> Result<Option<BlobInfo>> {
+        let path = self.object_path(key)?;
+        let options = object_store::GetOptions {
+            head: true,
+            ..Default::default()
+        };
+        let result = match self.object_store.get_opts(&path, options).await {
+            Ok(result) => result,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(classify(e)),
+        };
+        Ok(Some(BlobInfo {
+            key: result.meta.location.to_string(),
+            url: self.path_url(&result.meta.location),
+            size_bytes: result.meta.size as u64,
+            last_modified: result.meta.last_modified.timestamp_millis() as u64,
+            content_type: result
+                .attributes
+                .get(&object_store::Attribute::ContentType)
+                .map(|content_type| content_type.to_string()),
+        }))
+    }
+
+    /// Returns a url granting read access to a blob for `expires_in` without
+    /// credentials, or None if the backend can't presign urls.
+    pub async fn presigned_url(&self, key: &str, expires_in: Duration) -> Result<Option<String>> {
+        let Some(signer) = &self.signer else {
+            return Ok(None);
+        };
+        let path = self.object_path(key)?;
+        let url = signer
+            .signed_url(reqwest::Method::GET, &path, expires_in)
+            .await
+            .map_err(classify)?;
+        Ok(Some(url.to_string()))
+    }
+
+    /// Checks whether a blob exists without downloading it.
+    pub async fn exists(&self, key: &str) -> Result<bool> {
+        Ok(self.head(key).await?.is_some())
+    }
+
+    /// Lists the objects whose path starts with `prefix`, in key order,
+    /// resuming after the `continuation` returned by the previous call.
+    pub async fn list(
+        &self,
+        prefix: &str,
+        continuation: Option<&str>,
+    ) -> Result<(Vec<BlobInfo>, Option<String>)> {
+        self.list_page(prefix, continuation, LIST_PAGE_SIZE).await
+    }
+
+    pub async fn list_page(
+        &self,
+        prefix: &str,
+        continuation: Option<&str>,
+        limit: usize,
+    ) -> Result<(Vec<BlobInfo>, Option<String>)> {
+        // One more blob than the page is read to tell whether another page
+        // follows.
+        let mut blobs = match &self.config.s3 {
+            Some(_) => self.list_s3(prefix, continuation, limit + 1).await?,
+            None => {
+                let root = std::path::PathBuf::from(&self.config.disk.as_ref().unwrap().path);
+                let prefix = prefix.to_string();
+                let continuation = continuation.map(str::to_string);
+                let files = tokio::task::spawn_blocking(move || {
+                    disk::list_files(&root, &prefix, continuation.as_deref(), limit + 1)
+                })
+                .await??;
+                files
+                    .into_iter()
+                    .map(|file| BlobInfo {
+                        url: self.path_url(&object_store::path::Path::from(file.key.as_str())),
+                        key: file.key,
+                        size_bytes: file.size_bytes,
+                        last_modified: file.last_modified,
+                        content_type: None,
+                    })
+                    .collect()
+            }
+        };
+
+        let continuation = if blobs.len() > limit {
+            blobs.truncate(limit);
+            blobs.last().map(|blob| blob.key.clone())
         } else {
-            let prefix = format!("file://{}/", self.config.disk.as_ref().unwrap().path);
-            if let Some(key) = key.strip_prefix(prefix.as_str()) {
-                let path = object_store::path::Path::from(key);
-                self.object_store.delete(&path).await?;
-                return Ok(());
+            None
+        };
+        Ok((blobs, continuation))
+    }
+
+    /// Reads up to `limit` blobs from S3, which lists keys in order.
+    async fn list_s3(
+        &self,
+        prefix: &str,
+        continuation: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<BlobInfo>> {
+        // Object store prefixes match whole path segments, so list the
+        // enclosing directory and match the rest of the prefix by hand.
+        let directory = prefix
+            .rsplit_once('/')
+            .map(|(directory, _)| object_store::path::Path::from(directory));
+        let mut objects = match continuation {
+            Some(continuation) => self.object_store.list_with_offset(
+                directory.as_ref(),
+                &object_store::path::Path::from(continuation),
+            ),
+            None => self.object_store.list(directory.as_ref()),
+        };
+        let mut blobs = Vec::new();
+        while blobs.len() < limit {
+            let Some(meta) = objects.next().await else {
+                break;
+            };
+            let meta = meta.map_err(classify)?;
+            let key = meta.location.as_ref();
+            if !key.starts_with(prefix) {
+                if key > prefix {
+                    break;
+                }
+                continue;
             }
+            blobs.push(BlobInfo {
+                key: meta.location.to_string(),
+                url: self.path_url(&meta.location),
+                size_bytes: meta.size as u64,
+                last_modified: meta.last_modified.timestamp_millis() as u64,
+                content_type: None,
+            });
         }
-        Err(anyhow!("invalid key {}", key))
+        Ok(blobs)
+    }
+
+    /// Streams a blob like `get`, hashing it on the way. The stream ends with
+    /// a `HashMismatchError` instead of its end when the blob doesn't hash to
+    /// `sha256`, so corruption is surfaced even after it was partly sent.
+    pub async fn get_verified(
+        &self,
+        key: &str,
+        sha256: &str,
+    ) -> Result<BoxStream<'static, Result<Bytes>>> {
+        let mut data = self.get(key)?.get().await?;
+        let key = key.to_string();
+        let expected = sha256.to_string();
+        let stream = async_stream::try_stream! {
+            let mut hasher = Sha256::new();
+            while let Some(chunk) = data.next().await {
+                let chunk = chunk?;
+                hasher.update(&chunk);
+                yield chunk;
+            }
+            let actual = format!("{:x}", hasher.finalize());
+            if actual != expected {
+                Err(HashMismatchError {
+                    key,
+                    expected,
+                    actual,
+                })?;
+            }
+        };
+        Ok(stream.boxed())
     }
 
     pub async fn read_bytes(&self, key: &str) -> Result<Bytes> {
-        let reader = self.get(key);
+        let reader = self.get(key)?;
         let mut stream = reader.get().await?;
         let mut bytes = BytesMut::new();
         while let Some(chunk) = stream.next().await {
@@ -247,3 +659,155 @@ fn parse_s3_url(s3_url: &str) -> Result<(&str, &str), &str> {
 
     Ok((bucket, key))
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_list_with_prefix_and_paging() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let storage = BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().to_str().unwrap(),
+        ))?;
+        for key in ["ns_b", "ns_a", "other_c", "ns_c"] {
+            let data = stream::iter(vec![Ok(Bytes::from(key.to_string()))]);
+            storage.put(key, data).await?;
+        }
+
+        let (first_page, continuation) = storage.list_page("ns_", None, 2).await?;
+        let keys: Vec<_> = first_page.iter().map(|b| b.key.as_str()).collect();
+        assert_eq!(keys, vec!["ns_a", "ns_b"]);
+        assert_eq!(continuation.as_deref(), Some("ns_b"));
+        assert_eq!(first_page[0].size_bytes, 4);
+        assert!(first_page[0].url.ends_with("/ns_a"));
+
+        let (second_page, continuation) =
+            storage.list_page("ns_", continuation.as_deref(), 2).await?;
+        let keys: Vec<_> = second_page.iter().map(|b| b.key.as_str()).collect();
+        assert_eq!(keys, vec!["ns_c"]);
+        assert_eq!(continuation, None);
+
+        let (all, _) = storage.list("", None).await?;
+        assert_eq!(all.len(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_invalid_s3_url() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let storage = BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().to_str().unwrap(),
+        ))?;
+        assert!(storage.get("s3://bucket-without-key").is_err());
+        assert!(storage.read_bytes("s3://bucket-without-key").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_pages_through_directories_in_key_order() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let storage = BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().to_str().unwrap(),
+        ))?;
+        for key in ["b", "a/c", "a-c", "a/b"] {
+            let data = stream::iter(vec![Ok(Bytes::from(key.to_string()))]);
+            storage.put(key, data).await?;
+        }
+
+        let list_all = |prefix: &'static str| {
+            let storage = storage.clone();
+            async move {
+                let mut keys = Vec::new();
+                let mut continuation = None;
+                loop {
+                    let (page, next) = storage
+                        .list_page(prefix, continuation.as_deref(), 1)
+                        .await?;
+                    keys.extend(page.into_iter().map(|blob| blob.key));
+                    match next {
+                        Some(next) => continuation = Some(next),
+                        None => return Ok::<_, anyhow::Error>(keys),
+                    }
+                }
+            }
+        };
+        assert_eq!(list_all("").await?, vec!["a-c", "a/b", "a/c", "b"]);
+        assert_eq!(list_all("a/").await?, vec!["a/b", "a/c"]);
+        assert_eq!(list_all("a/c").await?, vec!["a/c"]);
+        assert!(list_all("c").await?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_s3_clients_are_reused() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let storage = BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().to_str().unwrap(),
+        ))?;
+        let client = storage.s3_client("bucket")?;
+        assert!(Arc::ptr_eq(&client, &storage.s3_client("bucket")?));
+        assert!(!Arc::ptr_eq(&client, &storage.s3_client("other")?));
+
+        // Clones of the storage share its clients.
+        assert!(Arc::ptr_eq(&client, &storage.clone().s3_client("bucket")?));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_put_if_not_exists() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let storage = BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().to_str().unwrap(),
+        ))?;
+        let data = || stream::iter(vec![Ok(Bytes::from("data"))]);
+        let options = PutOptions {
+            if_not_exists: true,
+        };
+
+        let first = storage.put_opts("key", data(), options.clone()).await?;
+        assert_eq!(first.size_bytes, 4);
+
+        let err = storage
+            .put_opts("key", data(), options.clone())
+            .await
+            .unwrap_err();
+        assert!(error::is_already_exists(&err));
+
+        // Unconditional writes still overwrite.
+        storage.put("key", data()).await?;
+
+        // Staging blobs don't outlive the put.
+        let (blobs, _) = storage.list("", None).await?;
+        let keys: Vec<_> = blobs.into_iter().map(|blob| blob.key).collect();
+        assert_eq!(keys, vec!["key"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_head() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let storage = BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().to_str().unwrap(),
+        ))?;
+        let data = stream::iter(vec![Ok(Bytes::from("data"))]);
+        let put = storage.put("ns/key", data).await?;
+
+        let blob = storage.head(&put.url).await?.unwrap();
+        assert_eq!(blob.key, "ns/key");
+        assert_eq!(blob.url, put.url);
+        assert_eq!(blob.size_bytes, 4);
+        assert!(blob.last_modified > 0);
+        // Local disk doesn't record content types.
+        assert_eq!(blob.content_type, None);
+        assert!(storage.exists(&put.url).await?);
+
+        let missing = put.url.replace("ns/key", "ns/missing");
+        assert_eq!(storage.head(&missing).await?, None);
+        assert!(!storage.exists(&missing).await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{channel::mpsc, future::join_all, stream::BoxStream, SinkExt, Stream, StreamExt};
+use tracing::{error, warn};
+
+use super::{
+    error::is_not_found,
+    BlobStorage,
+    BlobStorageReader,
+    BlobStorageReaderTS,
+    PutOptions,
+    PutResult,
+};
+
+/// Number of chunks buffered for a replica before the upload waits for it.
+const REPLICA_BUFFER_CHUNKS: usize = 16;
+
+/// Writes the blob to the storage and to each of its replicas from a single
+/// pass over `data`, every chunk being handed to the replicas as it's
+/// uploaded to the storage. Only the write to the storage has to succeed,
+/// failed copies are logged and leave that replica without the blob. When
+/// the write to the storage fails, the copies are deleted.
+pub(crate) async fn put(
+    storage: &BlobStorage,
+    key: &str,
+    data: impl Stream<Item = Result<Bytes>> + Send + Unpin,
+    options: PutOptions,
+) -> Result<PutResult> {
+    let (senders, receivers): (Vec<_>, Vec<_>) = storage
+        .replicas
+        .iter()
+        .map(|_| mpsc::channel(REPLICA_BUFFER_CHUNKS))
+        .unzip();
+    let primary = storage.put_primary(key, Box::pin(tee(data, senders)), options.clone());
+    let copies = join_all(
+        storage
+            .replicas
+            .iter()
+            .zip(receivers)
+            .map(|(replica, receiver)| replica.put_primary(key, receiver, options.clone())),
+    );
+    let (result, copies) = futures::join!(primary, copies);
+
+    let path = object_store::path::Path::from(key);
+    for (replica, copy) in storage.replicas.iter().zip(copies) {
+        match (&result, copy) {
+            (Ok(_), Ok(_)) => {}
+            (Ok(written), Err(e)) => error!(
+                "failed to replicate blob {} to {}: {:?}",
+                written.url,
+                replica.path_url(&path),
+                e
+            ),
+            (Err(_), Ok(copy)) => {
+                if let Err(e) = replica.delete_primary(&copy.url).await {
+                    error!("failed to delete replica {}: {:?}", copy.url, e);
+                }
+            }
+            (Err(_), Err(_)) => {}
+        }
+    }
+    result
+}
+
+/// Passes the chunks of `data` through, sending a copy of each to every
+/// sender first. A replica which stopped receiving is skipped.
+fn tee(
+    mut data: impl Stream<Item = Result<Bytes>> + Send + Unpin,
+    mut senders: Vec<mpsc::Sender<Result<Bytes>>>,
+) -> impl Stream<Item = Result<Bytes>> + Send {
+    async_stream::stream! {
+        while let Some(chunk) = data.next().await {
+            for sender in senders.iter_mut() {
+                let copy = match &chunk {
+                    Ok(bytes) => Ok(bytes.clone()),
+                    Err(_) => Err(anyhow!("upload of the blob failed")),
+                };
+                let _ = sender.send(copy).await;
+            }
+            yield chunk;
+        }
+    }
+}
+
+/// Reads a blob from the storage, or from the first replica holding it when
+/// the storage doesn't.
+pub(crate) struct ReplicatedReader {
+    url: String,
+    primary: BlobStorageReaderTS,
+    /// Url of the copy on each replica along with its reader.
+    replicas: Vec<(String, BlobStorageReaderTS)>,
+}
+
+impl ReplicatedReader {
+    pub(crate) fn new(
+        url: &str,
+        primary: BlobStorageReaderTS,
+        replicas: Vec<(String, BlobStorageReaderTS)>,
+    ) -> Self {
+        Self {
+            url: url.to_string(),
+            primary,
+            replicas,
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStorageReader for ReplicatedReader {
+    async fn get(&self) -> Result<BoxStream<'static, Result<Bytes>>> {
+        let err = match self.primary.get().await {
+            Ok(data) => return Ok(data),
+            Err(err) if is_not_found(&err) => err,
+            Err(err) => return Err(err),
+        };
+        for (replica_url, replica) in &self.replicas {
+            match replica.get().await {
+                Ok(data) => {
+                    warn!("blob {} is missing, reading {}", self.url, replica_url);
+                    return Ok(data);
+                }
+                Err(e) if is_not_found(&e) => {}
+                Err(e) => error!("failed to read replica {}: {:?}", replica_url, e),
+            }
+        }
+        Err(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::BlobStorageConfig;
+
+    #[tokio::test]
+    async fn test_replicated_put_and_fallback() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = |name: &str| temp_dir.path().join(name).to_str().unwrap().to_string();
+        let mut config = BlobStorageConfig::new_disk(&path("primary"));
+        config.replicas = vec![BlobStorageConfig::new_disk(&path("secondary"))];
+        let storage = BlobStorage::new(config)?;
+        let primary = BlobStorage::new(BlobStorageConfig::new_disk(&path("primary")))?;
+        let secondary = BlobStorage::new(BlobStorageConfig::new_disk(&path("secondary")))?;
+
+        let data = stream::iter(vec![
+            Ok(Bytes::from_static(b"co")),
+            Ok(Bytes::from_static(b"de")),
+        ]);
+        let result = storage.put("ns/graph", data).await?;
+        assert_eq!(
+            result.url,
+            primary.path_url(&object_store::path::Path::from("ns/graph"))
+        );
+        let replica_url = secondary.path_url(&object_store::path::Path::from("ns/graph"));
+        assert_eq!(secondary.read_bytes(&replica_url).await?.as_ref(), b"code");
+
+        // Reads fall back to the replica once the storage lost the blob.
+        primary.delete(&result.url).await?;
+        assert_eq!(storage.read_bytes(&result.url).await?.as_ref(), b"code");
+
+        secondary.delete(&replica_url).await?;
+        let err = storage.read_bytes(&result.url).await.unwrap_err();
+        assert!(is_not_found(&err), "{:?}", err);
+
+        // A failed upload leaves no copy on the replica.
+        let data = stream::iter(vec![
+            Ok(Bytes::from_static(b"partial")),
+            Err(anyhow!("client went away")),
+        ]);
+        assert!(storage.put("ns/failed", data).await.is_err());
+        let (copies, _) = secondary.list("ns/", None).await?;
+        assert!(copies.is_empty());
+
+        // Deletes remove the copies too.
+        let data = stream::iter(vec![Ok(Bytes::from_static(b"code"))]);
+        let result = storage.put("ns/deleted", data).await?;
+        storage.delete(&result.url).await?;
+        let (copies, _) = secondary.list("ns/", None).await?;
+        assert!(copies.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replicas_of_replicas_are_rejected() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = |name: &str| temp_dir.path().join(name).to_str().unwrap().to_string();
+        let mut replica = BlobStorageConfig::new_disk(&path("replica"));
+        replica.replicas = vec![BlobStorageConfig::new_disk(&path("nested"))];
+        let mut config = BlobStorageConfig::new_disk(&path("primary"));
+        config.replicas = vec![replica];
+        assert!(BlobStorage::new(config).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+use std::fmt::{self, Display};
+
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+
+/// Types a schema can require with the `type` keyword.
+const TYPES: &[&str] = &[
+    "null", "boolean", "object", "array", "number", "integer", "string",
+];
+
+/// Keywords understood by the validator. Schemas using others are rejected
+/// rather than silently accepting everything the keyword would forbid.
+const KEYWORDS: &[&str] = &[
+    "$schema",
+    "title",
+    "description",
+    "type",
+    "enum",
+    "properties",
+    "required",
+    "additionalProperties",
+    "items",
+    "minimum",
+    "maximum",
+    "minLength",
+    "maxLength",
+    "minItems",
+    "maxItems",
+];
+
+/// A value not matching a schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value, empty for the root.
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+/// Checks that a schema only uses the subset of JSON Schema supported by
+/// `validate`, so it can be rejected when a graph declares it.
+pub fn check_schema(schema: &Value) -> Result<()> {
+    check_schema_at(schema, "")
+}
+
+fn check_schema_at(schema: &Value, path: &str) -> Result<()> {
+    let schema = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(schema) => schema,
+        _ => return Err(anyhow!("schema at {} is not an object", display_path(path))),
+    };
+    for keyword in schema.keys() {
+        if !KEYWORDS.contains(&keyword.as_str()) {
+            return Err(anyhow!(
+                "unsupported keyword {} in schema at {}",
+                keyword,
+                display_path(path)
+            ));
+        }
+    }
+    if let Some(types) = schema.get("type") {
+        let types = match types {
+            Value::String(_) => vec![types],
+            Value::Array(types) => types.iter().collect(),
+            _ => vec![],
+        };
+        if types.is_empty() ||
+            !types
+                .iter()
+                .all(|t| t.as_str().is_some_and(|t| TYPES.contains(&t)))
+        {
+            return Err(anyhow!("invalid type in schema at {}", display_path(path)));
+        }
+    }
+    if let Some(properties) = schema.get("properties") {
+        let properties = properties.as_object().ok_or_else(|| {
+            anyhow!(
+                "properties of schema at {} is not an object",
+                display_path(path)
+            )
+        })?;
+        for (name, property) in properties {
+            check_schema_at(property, &format!("{}/properties/{}", path, name))?;
+        }
+    }
+    if let Some(required) = schema.get("required") {
+        if !required
+            .as_array()
+            .is_some_and(|required| required.iter().all(Value::is_string))
+        {
+            return Err(anyhow!(
+                "required of schema at {} is not a list of names",
+                display_path(path)
+            ));
+        }
+    }
+    if let Some(additional) = schema.get("additionalProperties") {
+        check_schema_at(additional, &format!("{}/additionalProperties", path))?;
+    }
+    if let Some(items) = schema.get("items") {
+        check_schema_at(items, &format!("{}/items", path))?;
+    }
+    if schema.get("enum").is_some_and(|values| !values.is_array()) {
+        return Err(anyhow!(
+            "enum of schema at {} is not a list",
+            display_path(path)
+        ));
+    }
+    for keyword in ["minimum", "maximum"] {
+        if schema.get(keyword).is_some_and(|bound| !bound.is_number()) {
+            return Err(anyhow!(
+                "{} of schema at {} is not a number",
+                keyword,
+                display_path(path)
+            ));
+        }
+    }
+    for keyword in ["minLength", "maxLength", "minItems", "maxItems"] {
+        if schema.get(keyword).is_some_and(|bound| !bound.is_u64()) {
+            return Err(anyhow!(
+                "{} of schema at {} is not a non-negative integer",
+                keyword,
+                display_path(path)
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Validates a value against a schema checked by `check_schema`, returning
+/// every violation found.
+pub fn validate(schema: &Value, value: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    validate_at(schema, value, "", &mut violations);
+    violations
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+    let mut violation = |message: String| {
+        violations.push(SchemaViolation {
+            path: path.to_string(),
+            message,
+        })
+    };
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => return violation("no value is allowed".to_string()),
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+    if let Some(types) = schema.get("type") {
+        let types: Vec<&str> = match types {
+            Value::String(t) => vec![t],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        if !types.iter().any(|t| has_type(value, t)) {
+            return violation(format!(
+                "expected {}, got {}",
+                types.join(" or "),
+                type_name(value)
+            ));
+        }
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            violation(format!("{} is not one of the allowed values", value));
+        }
+    }
+    match value {
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+                if number < minimum {
+                    violation(format!("{} is less than the minimum {}", number, minimum));
+                }
+            }
+            if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+                if number > maximum {
+                    violation(format!("{} is more than the maximum {}", number, maximum));
+                }
+            }
+        }
+        Value::String(string) => {
+            let length = string.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if length < min {
+                    violation(format!("length {} is less than {}", length, min));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if length > max {
+                    violation(format!("length {} is more than {}", length, max));
+                }
+            }
+        }
+        Value::Array(items) => {
+            let length = items.len() as u64;
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                if length < min {
+                    violation(format!("{} items are less than {}", length, min));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                if length > max {
+                    violation(format!("{} items are more than {}", length, max));
+                }
+            }
+            if let Some(items_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(items_schema, item, &format!("{}/{}", path, i), violations);
+                }
+            }
+        }
+        Value::Object(object) => validate_object(schema, object, path, violations),
+        _ => {}
+    }
+}
+
+fn validate_object(
+    schema: &Map<String, Value>,
+    object: &Map<String, Value>,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !object.contains_key(name) {
+                violations.push(SchemaViolation {
+                    path: path.to_string(),
+                    message: format!("missing required property {}", name),
+                });
+            }
+        }
+    }
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (name, property) in object {
+        let property_path = format!("{}/{}", path, escape_pointer(name));
+        match properties.and_then(|properties| properties.get(name)) {
+            Some(property_schema) => {
+                validate_at(property_schema, property, &property_path, violations)
+            }
+            None => {
+                if let Some(additional) = schema.get("additionalProperties") {
+                    validate_at(additional, property, &property_path, violations);
+                }
+            }
+        }
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        "string" => value.is_string(),
+        _ => false,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn escape_pointer(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "/"
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "url": {"type": "string", "minLength": 1},
+                "pages": {"type": "integer", "minimum": 1},
+                "tags": {"type": "array", "items": {"enum": ["pdf", "html"]}},
+            },
+            "required": ["url"],
+            "additionalProperties": false,
+        });
+        check_schema(&schema)?;
+
+        assert!(validate(&schema, &json!({"url": "a", "pages": 2, "tags": ["pdf"]})).is_empty());
+        let violations = validate(
+            &schema,
+            &json!({"pages": 0, "tags": ["pdf", "doc"], "extra": 1}),
+        );
+        let mut violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+        violations.sort();
+        assert_eq!(
+            violations,
+            vec![
+                "/: missing required property url",
+                "/extra: no value is allowed",
+                "/pages: 0 is less than the minimum 1",
+                "/tags/1: \"doc\" is not one of the allowed values",
+            ]
+        );
+        assert_eq!(
+            validate(&schema, &json!("a")),
+            vec![SchemaViolation {
+                path: "".to_string(),
+                message: "expected object, got string".to_string(),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_schema() {
+        assert!(check_schema(&json!(true)).is_ok());
+        assert_eq!(
+            check_schema(&json!({"properties": {"a": {"type": "text"}}}))
+                .unwrap_err()
+                .to_string(),
+            "invalid type in schema at /properties/a"
+        );
+        assert_eq!(
+            check_schema(&json!({"oneOf": []})).unwrap_err().to_string(),
+            "unsupported keyword oneOf in schema at /"
+        );
+    }
+}
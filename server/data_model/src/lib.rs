@@ -1,8 +1,13 @@
 pub mod filter;
+pub mod json_schema;
+pub mod lint;
+pub mod plan;
+pub mod schema;
+pub mod stats;
 pub mod test_objects;
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{self, Display},
     hash::{DefaultHasher, Hash, Hasher},
     time::{SystemTime, UNIX_EPOCH},
@@ -11,8 +16,8 @@ use std::{
 use anyhow::{anyhow, Result};
 use derive_builder::Builder;
 use filter::LabelsFilter;
-use indexify_utils::default_creation_time;
-use serde::{Deserialize, Serialize};
+use indexify_utils::{default_creation_time, get_epoch_time_in_ms};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
 
 // Invoke graph for all existing payloads
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -107,6 +112,10 @@ pub struct ComputeFn {
     pub payload_encoder: String,
     pub image_name: String,
     pub image_information: ImageInformation,
+    /// Marks the function as an intended end of the graph, so it isn't
+    /// reported for having no outgoing edges.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub terminal: bool,
 }
 
 impl ComputeFn {
@@ -136,6 +145,22 @@ impl Node {
         }
     }
 
+    pub fn payload_encoder(&self) -> &str {
+        match self {
+            Node::Router(router) => &router.payload_encoder,
+            Node::Compute(compute) => &compute.payload_encoder,
+        }
+    }
+
+    /// Function the node runs, the `fn_name` of a compute function and the
+    /// name of a router.
+    pub fn node_type(&self) -> &str {
+        match self {
+            Node::Router(router) => &router.name,
+            Node::Compute(compute) => &compute.fn_name,
+        }
+    }
+
     pub fn matches_executor(&self, executor: &ExecutorMetadata) -> bool {
         match self {
             Node::Router(_) => true,
@@ -229,6 +254,7 @@ pub struct RuntimeInformation {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(remote = "Self")]
 pub struct ComputeGraph {
     pub namespace: String,
     pub name: String,
@@ -236,16 +262,144 @@ pub struct ComputeGraph {
     pub version: GraphVersion, // Version incremented with code update
     pub code: ComputeGraphCode,
     pub created_at: u64,
+    /// Time the graph was last created or updated.
+    #[serde(default)]
+    pub updated_at: u64,
     pub start_fn: Node,
     pub nodes: HashMap<String, Node>,
     pub edges: HashMap<String, Vec<String>>,
     pub runtime_information: RuntimeInformation,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Frozen graphs can't be updated or deleted until they are unfrozen.
+    #[serde(default)]
+    pub frozen: bool,
+    /// Name the graph was created with when names are normalized, `name`
+    /// then holds its lowercase form.
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Authenticated identity which created the graph, kept across updates.
+    #[serde(default)]
+    pub actor: Option<String>,
+    /// JSON schema the inputs of the graph must match.
+    #[serde(default)]
+    pub input_schema: Option<serde_json::Value>,
+    /// JSON schema the outputs of the last functions of the graph must match.
+    #[serde(default)]
+    pub output_schema: Option<serde_json::Value>,
+    /// Free form key value pairs used to find graphs.
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Schema version the record was written with, records are upgraded to
+    /// `COMPUTE_GRAPH_SCHEMA_VERSION` when they are read.
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Serialize for ComputeGraph {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ComputeGraph::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ComputeGraph {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut record = ComputeGraph::deserialize(deserializer)?;
+        schema::upgrade(&mut record, schema::COMPUTE_GRAPH_UPGRADES).map_err(D::Error::custom)?;
+        Ok(record)
+    }
 }
 
 impl ComputeGraph {
     pub fn key(&self) -> String {
         format!("{}|{}", self.namespace, self.name)
     }
+
+    /// Key of the graph in the index of the compute graphs of a namespace by
+    /// update time. The time is inverted so the most recently updated graphs
+    /// come first.
+    pub fn update_index_key(&self) -> Vec<u8> {
+        let mut key = format!("{}|", self.namespace).into_bytes();
+        key.extend_from_slice(&(u64::MAX - self.updated_at).to_be_bytes());
+        key.extend_from_slice(self.name.as_bytes());
+        key
+    }
+
+    /// Returns the key of the graph an update index key refers to.
+    pub fn key_from_update_index_key(update_index_key: &[u8]) -> Result<Vec<u8>> {
+        let pos = update_index_key
+            .iter()
+            .position(|&x| x == b'|')
+            .ok_or(anyhow!("invalid update index key"))?;
+        let name = update_index_key
+            .get(pos + 1 + 8..)
+            .ok_or(anyhow!("invalid update index key"))?;
+        let mut key = update_index_key[..=pos].to_vec();
+        key.extend_from_slice(name);
+        Ok(key)
+    }
+
+    /// Checks that the input and output schemas only use the keywords the
+    /// validator understands.
+    pub fn check_schemas(&self) -> Result<()> {
+        for (field, schema) in [
+            ("input_schema", &self.input_schema),
+            ("output_schema", &self.output_schema),
+        ] {
+            if let Some(schema) = schema {
+                json_schema::check_schema(schema)
+                    .map_err(|e| anyhow!("invalid {}: {}", field, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Schema the outputs of `compute_fn` must match: the output schema of the
+    /// graph when the function is JSON encoded and nothing runs after it.
+    pub fn output_schema_of(&self, compute_fn: &str) -> Option<&serde_json::Value> {
+        let schema = self.output_schema.as_ref()?;
+        let node = self.nodes.get(compute_fn)?;
+        if node.payload_encoder() != "json" || !self.successors(compute_fn).is_empty() {
+            return None;
+        }
+        Some(schema)
+    }
+
+    /// Names of the nodes that can run after `name`: its edges followed by
+    /// the targets of a router, without duplicates.
+    pub fn successors(&self, name: &str) -> Vec<&str> {
+        let edges = self.edges.get(name).into_iter().flatten();
+        let router_targets = match self.nodes.get(name) {
+            Some(Node::Router(router)) => router.target_functions.iter(),
+            _ => [].iter(),
+        };
+        let mut successors: Vec<&str> = Vec::new();
+        for to in edges.chain(router_targets) {
+            if !successors.contains(&to.as_str()) {
+                successors.push(to);
+            }
+        }
+        successors
+    }
+
+    /// Every edge of the graph as a source and target pair, router targets
+    /// included, ordered by source.
+    pub fn edges(&self) -> Vec<(String, String)> {
+        let mut sources: Vec<&String> = self.nodes.keys().collect();
+        sources.sort();
+        sources
+            .into_iter()
+            .flat_map(|from| {
+                self.successors(from)
+                    .into_iter()
+                    .map(move |to| (from.clone(), to.to_string()))
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -285,6 +439,11 @@ pub struct NodeOutput {
     pub payload: OutputPayload,
     pub errors: Option<DataPayload>,
     pub reduced_state: bool,
+    /// Key of the output consumed by the task which produced this one. None
+    /// for the outputs of the start function, which consume the input of
+    /// the invocation, and for outputs written before it was recorded.
+    #[serde(default)]
+    pub parent_output_key: Option<String>,
 }
 
 impl NodeOutput {
@@ -357,10 +516,39 @@ impl NodeOutputBuilder {
             payload,
             errors,
             reduced_state,
+            parent_output_key: self.parent_output_key.clone().flatten(),
         })
     }
 }
 
+/// Error details of a failed task, recorded when the task is finalized.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FailedOutput {
+    pub namespace: String,
+    pub compute_graph_name: String,
+    pub compute_fn_name: String,
+    pub invocation_id: String,
+    pub task_id: String,
+    pub failed_at: u64,
+    pub errors: Option<DataPayload>,
+    pub diagnostics: Option<TaskDiagnostics>,
+}
+
+impl FailedOutput {
+    /// Keys are ordered by failure time within a namespace.
+    pub fn key(&self) -> String {
+        format!(
+            "{}{}",
+            FailedOutput::key_prefix_from(&self.namespace, self.failed_at),
+            self.task_id
+        )
+    }
+
+    pub fn key_prefix_from(namespace: &str, failed_at: u64) -> String {
+        format!("{}|{:020}|", namespace, failed_at)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder)]
 #[builder(build_fn(skip))]
 pub struct InvocationPayload {
@@ -368,6 +556,14 @@ pub struct InvocationPayload {
     pub namespace: String,
     pub compute_graph_name: String,
     pub payload: DataPayload,
+    /// Metadata supplied by the client with the input. Attributes whose key
+    /// is indexed by the state store can be searched for.
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    /// Time the input was created, 0 for inputs written before it was
+    /// recorded.
+    #[serde(default)]
+    pub created_at: u64,
 }
 
 impl InvocationPayload {
@@ -382,6 +578,19 @@ impl InvocationPayload {
     pub fn invocation_context_key(&self) -> String {
         format!("{}|{}|{}", self.namespace, self.compute_graph_name, self.id)
     }
+
+    /// Key of the entry indexing the input under one of its attributes.
+    pub fn attribute_key(&self, key: &str, value: &str) -> String {
+        format!(
+            "{}{}",
+            Self::attribute_key_prefix(&self.namespace, &self.compute_graph_name, key, value),
+            self.id
+        )
+    }
+
+    pub fn attribute_key_prefix(ns: &str, cg: &str, key: &str, value: &str) -> String {
+        format!("{}|{}|{}|{}|", ns, cg, key, value)
+    }
 }
 
 impl InvocationPayloadBuilder {
@@ -406,10 +615,24 @@ impl InvocationPayloadBuilder {
             namespace: ns,
             compute_graph_name: cg_name,
             payload,
+            attributes: self.attributes.clone().unwrap_or_default(),
+            created_at: self.created_at.unwrap_or_else(get_epoch_time_in_ms),
         })
     }
 }
 
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum InvocationStatus {
+    /// Status of the invocations created before it was tracked, until they
+    /// make progress.
+    #[default]
+    Unknown,
+    Pending,
+    Processing,
+    Completed,
+    Failed,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder)]
 #[builder(build_fn(skip))]
 pub struct GraphInvocationCtx {
@@ -421,6 +644,8 @@ pub struct GraphInvocationCtx {
     pub outstanding_tasks: u64,
     pub fn_task_analytics: HashMap<String, TaskAnalytics>,
     pub is_system_task: bool,
+    #[serde(default)]
+    pub status: InvocationStatus,
 }
 
 impl GraphInvocationCtx {
@@ -434,6 +659,12 @@ impl GraphInvocationCtx {
     pub fn key_from(ns: &str, cg: &str, id: &str) -> String {
         format!("{}|{}|{}", ns, cg, id)
     }
+
+    pub fn has_failed_tasks(&self) -> bool {
+        self.fn_task_analytics
+            .values()
+            .any(|analytics| analytics.failed_tasks > 0)
+    }
 }
 
 impl GraphInvocationCtxBuilder {
@@ -465,6 +696,7 @@ impl GraphInvocationCtxBuilder {
             fn_task_analytics,
             outstanding_tasks: 1, // Starts with 1 for the initial state change event
             is_system_task,
+            status: InvocationStatus::Pending,
         })
     }
 }
@@ -773,8 +1005,121 @@ pub struct StateChange {
     pub processed_at: Option<u64>,
 }
 
+/// A change to a compute graph or its outputs, recorded in the same
+/// transaction as the change and delivered to webhook sinks.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChangeEvent {
+    pub id: u64,
+    pub namespace: String,
+    pub compute_graph: String,
+    #[serde(flatten)]
+    pub kind: ChangeEventKind,
+    pub created_at: u64,
+}
+
+impl ChangeEvent {
+    pub fn key(&self) -> [u8; 8] {
+        self.id.to_be_bytes()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChangeEventKind {
+    GraphCreated,
+    GraphUpdated,
+    GraphDeleted,
+    OutputProduced {
+        invocation_id: String,
+        compute_fn: String,
+        output_id: String,
+    },
+}
+
+/// Change event which couldn't be delivered to a webhook, kept for
+/// inspection instead of blocking the delivery of later events.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeadLetteredChangeEvent {
+    pub event: ChangeEvent,
+    pub url: String,
+    pub error: String,
+    pub attempts: u32,
+    pub failed_at: u64,
+}
+
+impl DeadLetteredChangeEvent {
+    /// Event ids are reused once every event is delivered, the time of the
+    /// failure keeps the keys of dead letters unique.
+    pub fn key(&self) -> String {
+        format!("{:020}|{:020}|{}", self.failed_at, self.event.id, self.url)
+    }
+}
+
+/// Retention of the data of a namespace, data is kept forever by default.
+/// Output ttls and version limits are out of scope, outputs are only deleted
+/// along with their invocation and graphs keep no earlier versions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Inputs created longer than this ago are deleted. Inputs written
+    /// before their creation time was recorded are kept.
+    #[serde(default)]
+    pub input_ttl_secs: Option<u64>,
+}
+
+/// Feature flag rejecting compute graphs with lint warnings.
+pub const STRICT_VALIDATION_FLAG: &str = "strict_validation";
+
+/// Feature flags a namespace can set.
+pub const FEATURE_FLAGS: &[&str] = &[STRICT_VALIDATION_FLAG];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(remote = "Self")]
 pub struct Namespace {
     pub name: String,
     pub created_at: u64,
+    /// Per namespace toggles of server behaviors, flags that aren't set are
+    /// disabled.
+    #[serde(default)]
+    pub feature_flags: BTreeMap<String, bool>,
+    /// How long the data of the namespace is kept.
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+    /// Schema version the record was written with, records are upgraded to
+    /// `NAMESPACE_SCHEMA_VERSION` when they are read.
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+impl Serialize for Namespace {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Namespace::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Namespace {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut record = Namespace::deserialize(deserializer)?;
+        schema::upgrade(&mut record, schema::NAMESPACE_UPGRADES).map_err(D::Error::custom)?;
+        Ok(record)
+    }
+}
+
+impl Namespace {
+    pub fn flag_enabled(&self, flag: &str) -> bool {
+        self.feature_flags.get(flag).copied().unwrap_or(false)
+    }
+
+    /// Key of the namespace in the index of namespaces by creation time.
+    pub fn creation_index_key(&self) -> Vec<u8> {
+        let mut key = self.created_at.to_be_bytes().to_vec();
+        key.extend_from_slice(self.name.as_bytes());
+        key
+    }
+
+    /// Returns the name of the namespace a creation index key refers to.
+    pub fn name_from_creation_index_key(creation_index_key: &[u8]) -> Result<&[u8]> {
+        creation_index_key
+            .get(8..)
+            .ok_or(anyhow!("invalid creation index key"))
+    }
 }
@@ -0,0 +1,214 @@
+use std::{
+    collections::{BTreeSet, HashSet, VecDeque},
+    fmt::{self, Display},
+};
+
+use crate::{ComputeGraph, Node};
+
+/// Advisory findings about the shape of a compute graph. Unlike validation
+/// errors they don't prevent the graph from being created or invoked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintWarning {
+    /// The node can't be reached from the start node.
+    UnreachableNode { node: String },
+    /// The same edge is declared more than once.
+    DuplicateEdge { from: String, to: String },
+    /// An edge points to a node which isn't part of the graph.
+    UnknownEdgeTarget { from: String, to: String },
+    /// A router without any function to route to.
+    RouterWithoutTargets { node: String },
+    /// A function without outgoing edges which isn't marked terminal.
+    UnmarkedTerminalNode { node: String },
+}
+
+impl LintWarning {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LintWarning::UnreachableNode { .. } => "unreachable_node",
+            LintWarning::DuplicateEdge { .. } => "duplicate_edge",
+            LintWarning::UnknownEdgeTarget { .. } => "unknown_edge_target",
+            LintWarning::RouterWithoutTargets { .. } => "router_without_targets",
+            LintWarning::UnmarkedTerminalNode { .. } => "unmarked_terminal_node",
+        }
+    }
+
+    /// Names of the nodes the warning refers to.
+    pub fn nodes(&self) -> Vec<String> {
+        match self {
+            LintWarning::UnreachableNode { node } |
+            LintWarning::RouterWithoutTargets { node } |
+            LintWarning::UnmarkedTerminalNode { node } => vec![node.clone()],
+            LintWarning::DuplicateEdge { from, to } |
+            LintWarning::UnknownEdgeTarget { from, to } => vec![from.clone(), to.clone()],
+        }
+    }
+}
+
+impl Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintWarning::UnreachableNode { node } => {
+                write!(f, "node {} is not reachable from the start node", node)
+            }
+            LintWarning::DuplicateEdge { from, to } => {
+                write!(f, "edge from {} to {} is declared more than once", from, to)
+            }
+            LintWarning::UnknownEdgeTarget { from, to } => {
+                write!(f, "edge from {} points to unknown node {}", from, to)
+            }
+            LintWarning::RouterWithoutTargets { node } => {
+                write!(f, "router {} has no target functions", node)
+            }
+            LintWarning::UnmarkedTerminalNode { node } => {
+                write!(
+                    f,
+                    "function {} has no outgoing edges and isn't marked terminal",
+                    node
+                )
+            }
+        }
+    }
+}
+
+impl ComputeGraph {
+    /// Returns the advisory warnings for the graph, ordered by node name.
+    pub fn lint(&self) -> Vec<LintWarning> {
+        let mut warnings = Vec::new();
+        let mut sources: Vec<&String> = self.edges.keys().collect();
+        sources.sort();
+        for from in sources {
+            let mut seen = HashSet::new();
+            let mut duplicates = BTreeSet::new();
+            for to in &self.edges[from] {
+                if !seen.insert(to) {
+                    duplicates.insert(to);
+                }
+                if !self.nodes.contains_key(to) {
+                    warnings.push(LintWarning::UnknownEdgeTarget {
+                        from: from.clone(),
+                        to: to.clone(),
+                    });
+                }
+            }
+            warnings.extend(duplicates.into_iter().map(|to| LintWarning::DuplicateEdge {
+                from: from.clone(),
+                to: to.clone(),
+            }));
+        }
+
+        let reachable = self.reachable_nodes();
+        let mut names: Vec<&String> = self.nodes.keys().collect();
+        names.sort();
+        for name in names {
+            if !reachable.contains(name.as_str()) {
+                // Unreachable nodes aren't also reported as missing edges.
+                warnings.push(LintWarning::UnreachableNode { node: name.clone() });
+                continue;
+            }
+            match &self.nodes[name] {
+                Node::Router(router) => {
+                    if router.target_functions.is_empty() {
+                        warnings.push(LintWarning::RouterWithoutTargets { node: name.clone() });
+                    }
+                }
+                Node::Compute(compute_fn) => {
+                    if !compute_fn.terminal && self.successors(name).is_empty() {
+                        warnings.push(LintWarning::UnmarkedTerminalNode { node: name.clone() });
+                    }
+                }
+            }
+        }
+        warnings
+    }
+
+    pub(crate) fn reachable_nodes(&self) -> HashSet<&str> {
+        let mut reachable = HashSet::new();
+        let mut queue = VecDeque::from([self.start_fn.name()]);
+        while let Some(name) = queue.pop_front() {
+            if !reachable.insert(name) {
+                continue;
+            }
+            queue.extend(self.successors(name));
+        }
+        reachable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_objects::tests::{mock_graph_a, mock_graph_b},
+        ComputeFn,
+    };
+
+    #[test]
+    fn test_lint_clean_graphs() {
+        assert!(mock_graph_a().lint().is_empty());
+        assert!(mock_graph_b().lint().is_empty());
+    }
+
+    #[test]
+    fn test_lint_unreachable_node() {
+        let mut graph = mock_graph_a();
+        graph.nodes.insert(
+            "fn_orphan".to_string(),
+            Node::Compute(ComputeFn {
+                name: "fn_orphan".to_string(),
+                ..Default::default()
+            }),
+        );
+
+        let warnings = graph.lint();
+        assert_eq!(
+            warnings,
+            vec![LintWarning::UnreachableNode {
+                node: "fn_orphan".to_string()
+            }]
+        );
+        assert_eq!(warnings[0].nodes(), vec!["fn_orphan".to_string()]);
+    }
+
+    #[test]
+    fn test_lint_unmarked_terminal_node() {
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(fn_c)) = graph.nodes.get_mut("fn_c") {
+            fn_c.terminal = false;
+        }
+
+        assert_eq!(
+            graph.lint(),
+            vec![LintWarning::UnmarkedTerminalNode {
+                node: "fn_c".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_lint_edges() {
+        let mut graph = mock_graph_a();
+        graph.edges.insert(
+            "fn_a".to_string(),
+            vec![
+                "fn_b".to_string(),
+                "fn_c".to_string(),
+                "fn_b".to_string(),
+                "fn_x".to_string(),
+            ],
+        );
+
+        assert_eq!(
+            graph.lint(),
+            vec![
+                LintWarning::UnknownEdgeTarget {
+                    from: "fn_a".to_string(),
+                    to: "fn_x".to_string(),
+                },
+                LintWarning::DuplicateEdge {
+                    from: "fn_a".to_string(),
+                    to: "fn_b".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::{anyhow, Result};
+
+use crate::{ComputeGraph, Node};
+
+/// The order in which the nodes of a compute graph run, grouped into stages.
+/// Every node of a stage only depends on nodes of earlier stages, so the
+/// nodes of a stage can run in parallel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionPlan {
+    pub stages: Vec<PlanStage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanStage {
+    /// Nodes of the stage, ordered by name.
+    pub nodes: Vec<PlanNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanNode {
+    pub name: String,
+    pub reducer: bool,
+    /// Nodes that run after this one.
+    pub next: Vec<String>,
+    /// For routers, the functions one of which is picked at runtime.
+    pub branches: Option<Vec<String>>,
+}
+
+impl ComputeGraph {
+    /// Groups the nodes reachable from the start node into topological
+    /// levels. A node is placed one stage after the last of its parents.
+    pub fn execution_plan(&self) -> Result<ExecutionPlan> {
+        let reachable = self.reachable_nodes();
+        let mut in_degree: HashMap<&str, usize> = reachable.iter().map(|n| (*n, 0)).collect();
+        for name in &reachable {
+            for to in self.successors(name) {
+                if let Some(degree) = in_degree.get_mut(to) {
+                    *degree += 1;
+                }
+            }
+        }
+
+        let mut levels: HashMap<&str, usize> = HashMap::new();
+        let mut queue = VecDeque::from([self.start_fn.name()]);
+        levels.insert(self.start_fn.name(), 0);
+        while let Some(name) = queue.pop_front() {
+            let level = levels[name];
+            for to in self.successors(name) {
+                let Some(degree) = in_degree.get_mut(to) else {
+                    continue;
+                };
+                let to_level = levels.entry(to).or_insert(0);
+                *to_level = (*to_level).max(level + 1);
+                *degree -= 1;
+                if *degree == 0 {
+                    queue.push_back(to);
+                }
+            }
+        }
+        if let Some(node) = in_degree.iter().find(|(_, degree)| **degree > 0) {
+            return Err(anyhow!(
+                "compute graph {} has a cycle through node {}",
+                self.name,
+                node.0
+            ));
+        }
+
+        let stage_count = levels.values().max().map_or(0, |max| max + 1);
+        let mut stages = vec![PlanStage { nodes: vec![] }; stage_count];
+        for (name, level) in levels {
+            // Edges may point to unknown nodes, those are reported by `lint`.
+            let Some(node) = self.nodes.get(name) else {
+                continue;
+            };
+            let branches = match node {
+                Node::Router(router) => Some(router.target_functions.clone()),
+                Node::Compute(_) => None,
+            };
+            stages[level].nodes.push(PlanNode {
+                name: name.to_string(),
+                reducer: node.reducer(),
+                next: self
+                    .successors(name)
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect(),
+                branches,
+            });
+        }
+        for stage in &mut stages {
+            stage.nodes.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        Ok(ExecutionPlan { stages })
+    }
+
+    /// Number of nodes on the longest path from the start node, which is the
+    /// number of stages of its execution plan.
+    pub fn depth(&self) -> Result<usize> {
+        Ok(self.execution_plan()?.stages.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_objects::tests::{mock_graph_a, mock_graph_b},
+        ComputeFn,
+    };
+
+    fn stage_names(plan: &ExecutionPlan) -> Vec<Vec<&str>> {
+        plan.stages
+            .iter()
+            .map(|stage| stage.nodes.iter().map(|n| n.name.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_diamond() -> Result<()> {
+        let mut graph = mock_graph_a();
+        graph.nodes.insert(
+            "fn_d".to_string(),
+            Node::Compute(ComputeFn {
+                name: "fn_d".to_string(),
+                ..Default::default()
+            }),
+        );
+        graph
+            .edges
+            .insert("fn_b".to_string(), vec!["fn_d".to_string()]);
+        graph
+            .edges
+            .insert("fn_c".to_string(), vec!["fn_d".to_string()]);
+
+        let plan = graph.execution_plan()?;
+        assert_eq!(
+            stage_names(&plan),
+            vec![vec!["fn_a"], vec!["fn_b", "fn_c"], vec!["fn_d"]]
+        );
+        assert_eq!(plan.stages[0].nodes[0].next, vec!["fn_b", "fn_c"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_router_branches() -> Result<()> {
+        let plan = mock_graph_b().execution_plan()?;
+        let router = plan
+            .stages
+            .iter()
+            .flat_map(|stage| &stage.nodes)
+            .find(|node| node.name == "router_x")
+            .unwrap();
+        assert_eq!(
+            router.branches,
+            Some(vec!["fn_b".to_string(), "fn_c".to_string()])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_cycle() {
+        let mut graph = mock_graph_a();
+        graph
+            .edges
+            .insert("fn_b".to_string(), vec!["fn_c".to_string()]);
+        graph
+            .edges
+            .insert("fn_c".to_string(), vec!["fn_b".to_string()]);
+        assert!(graph.execution_plan().is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+
+use crate::{ComputeGraph, Namespace};
+
+/// Record stored with the schema version it was written with. Records
+/// written before versioning don't have one and are at version 0.
+pub trait Versioned {
+    fn schema_version(&self) -> u32;
+    fn set_schema_version(&mut self, version: u32);
+}
+
+/// Upgrades a record from the version at its index in a list of upgrades to
+/// the next version. Fields added with a serde default are already filled
+/// when the record is decoded, upgrades only fix values which depend on the
+/// version the record was written with.
+pub type Upgrade<T> = fn(&mut T);
+
+pub const COMPUTE_GRAPH_UPGRADES: &[Upgrade<ComputeGraph>] = &[compute_graph_v1, compute_graph_v2];
+pub const NAMESPACE_UPGRADES: &[Upgrade<Namespace>] = &[namespace_v1, namespace_v2];
+
+/// Versions new compute graph and namespace records are written with.
+pub const COMPUTE_GRAPH_SCHEMA_VERSION: u32 = COMPUTE_GRAPH_UPGRADES.len() as u32;
+pub const NAMESPACE_SCHEMA_VERSION: u32 = NAMESPACE_UPGRADES.len() as u32;
+
+/// Brings a decoded record written with an older schema version to the
+/// latest version by applying the upgrades it is missing in order. Records
+/// written by a newer server are rejected rather than losing their unknown
+/// fields.
+pub fn upgrade<T: Versioned>(record: &mut T, upgrades: &[Upgrade<T>]) -> Result<()> {
+    let version = record.schema_version() as usize;
+    if version > upgrades.len() {
+        return Err(anyhow!(
+            "record has schema version {}, the latest supported is {}",
+            version,
+            upgrades.len()
+        ));
+    }
+    for upgrade in &upgrades[version..] {
+        upgrade(record);
+    }
+    record.set_schema_version(upgrades.len() as u32);
+    Ok(())
+}
+
+impl Versioned for ComputeGraph {
+    fn schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    fn set_schema_version(&mut self, version: u32) {
+        self.schema_version = version;
+    }
+}
+
+impl Versioned for Namespace {
+    fn schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    fn set_schema_version(&mut self, version: u32) {
+        self.schema_version = version;
+    }
+}
+
+/// The toggles added to compute graphs before records were versioned are
+/// filled by their serde defaults.
+fn compute_graph_v1(_: &mut ComputeGraph) {}
+
+/// Takes the creation time of graphs written before updates were timed as
+/// their update time.
+fn compute_graph_v2(compute_graph: &mut ComputeGraph) {
+    if compute_graph.updated_at == 0 {
+        compute_graph.updated_at = compute_graph.created_at;
+    }
+}
+
+/// The feature flags added to namespaces before records were versioned are
+/// filled by their serde default.
+fn namespace_v1(_: &mut Namespace) {}
+
+/// Namespaces written before retention policies keep everything, which is
+/// the default policy.
+fn namespace_v2(_: &mut Namespace) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_objects::tests::mock_graph_a;
+
+    #[test]
+    fn test_upgrade_unversioned_record() -> Result<()> {
+        let namespace: Namespace = serde_json::from_str(r#"{"name": "ns", "created_at": 5}"#)?;
+        assert_eq!(namespace.created_at, 5);
+        assert!(namespace.feature_flags.is_empty());
+        assert_eq!(namespace.schema_version, NAMESPACE_SCHEMA_VERSION);
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_compute_graph_update_time() -> Result<()> {
+        let mut record = serde_json::to_value(mock_graph_a())?;
+        let record = record.as_object_mut().unwrap();
+        record.remove("updated_at");
+        record.insert("created_at".to_string(), 5.into());
+        record.insert("schema_version".to_string(), 1.into());
+        let compute_graph: ComputeGraph = serde_json::from_value(record.clone().into())?;
+        assert_eq!(compute_graph.updated_at, 5);
+        assert_eq!(compute_graph.schema_version, COMPUTE_GRAPH_SCHEMA_VERSION);
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_to_next_version() -> Result<()> {
+        fn enable_flag(namespace: &mut Namespace) {
+            namespace
+                .feature_flags
+                .entry("a".to_string())
+                .or_insert(true);
+        }
+        let upgrades: &[Upgrade<Namespace>] = &[namespace_v1, enable_flag];
+
+        // A record written at version 1 only gets the upgrade to version 2.
+        let mut namespace: Namespace = serde_json::from_str(
+            r#"{"name": "ns", "created_at": 5, "feature_flags": {"b": true}}"#,
+        )?;
+        namespace.schema_version = 1;
+        upgrade(&mut namespace, upgrades)?;
+        assert_eq!(namespace.feature_flags.len(), 2);
+        assert!(namespace.flag_enabled("a"));
+        assert_eq!(namespace.schema_version, 2);
+
+        namespace.schema_version = 3;
+        let err = upgrade(&mut namespace, upgrades).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "record has schema version 3, the latest supported is 2"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{ComputeGraph, Node};
+
+/// Structural metrics of a compute graph.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GraphStats {
+    pub nodes: usize,
+    /// Edges between nodes, router targets included.
+    pub edges: usize,
+    /// Largest number of nodes that can run after a single node.
+    pub max_fan_out: usize,
+    pub router_count: usize,
+}
+
+impl ComputeGraph {
+    /// Computes the structural metrics of the graph from its definition.
+    pub fn stats(&self) -> GraphStats {
+        let mut stats = GraphStats {
+            nodes: self.nodes.len(),
+            ..Default::default()
+        };
+        for (name, node) in &self.nodes {
+            let fan_out = self.successors(name).len();
+            stats.edges += fan_out;
+            stats.max_fan_out = stats.max_fan_out.max(fan_out);
+            if matches!(node, Node::Router(_)) {
+                stats.router_count += 1;
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_objects::tests::mock_graph_b, ComputeFn};
+
+    #[test]
+    fn test_graph_stats() {
+        let mut graph = mock_graph_b();
+        let Some(Node::Router(router_x)) = graph.nodes.get("router_x").cloned() else {
+            panic!("router_x isn't a router");
+        };
+        let mut router_y = router_x;
+        router_y.name = "router_y".to_string();
+        router_y.source_fn = "fn_c".to_string();
+        router_y.target_functions = vec!["fn_d".to_string()];
+        graph
+            .nodes
+            .insert("router_y".to_string(), Node::Router(router_y));
+        graph.nodes.insert(
+            "fn_d".to_string(),
+            Node::Compute(ComputeFn {
+                name: "fn_d".to_string(),
+                ..Default::default()
+            }),
+        );
+        graph
+            .edges
+            .insert("fn_c".to_string(), vec!["router_y".to_string()]);
+
+        // fn_a -> router_x -> {fn_b, fn_c}, fn_c -> router_y -> fn_d
+        assert_eq!(
+            graph.stats(),
+            GraphStats {
+                nodes: 6,
+                edges: 5,
+                max_fan_out: 2,
+                router_count: 2,
+            }
+        );
+    }
+}
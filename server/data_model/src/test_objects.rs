@@ -13,6 +13,7 @@ pub mod tests {
         RuntimeInformation,
     };
     use crate::{
+        schema::COMPUTE_GRAPH_SCHEMA_VERSION,
         DataPayload,
         DynamicEdgeRouter,
         ExecutorId,
@@ -57,6 +58,13 @@ pub mod tests {
         }
     }
 
+    fn terminal_fn(name: &str) -> ComputeFn {
+        ComputeFn {
+            terminal: true,
+            ..test_compute_fn(name)
+        }
+    }
+
     fn reducer_fn(name: &str, reduce: bool) -> ComputeFn {
         let mut compute_fn = test_compute_fn(name);
         compute_fn.reducer = reduce;
@@ -143,8 +151,8 @@ pub mod tests {
 
     pub fn mock_graph_a() -> ComputeGraph {
         let fn_a = test_compute_fn("fn_a");
-        let fn_b = test_compute_fn("fn_b");
-        let fn_c = test_compute_fn("fn_c");
+        let fn_b = terminal_fn("fn_b");
+        let fn_c = terminal_fn("fn_c");
         ComputeGraph {
             namespace: TEST_NAMESPACE.to_string(),
             name: "graph_A".to_string(),
@@ -165,11 +173,20 @@ pub mod tests {
                 sha256_hash: "hash123".to_string(),
             },
             created_at: 5,
+            updated_at: 5,
             start_fn: Compute(fn_a),
             runtime_information: RuntimeInformation {
                 major_version: 3,
                 minor_version: 10,
             },
+            enabled: true,
+            frozen: false,
+            display_name: None,
+            actor: None,
+            input_schema: None,
+            output_schema: None,
+            labels: Default::default(),
+            schema_version: COMPUTE_GRAPH_SCHEMA_VERSION,
         }
     }
 
@@ -193,8 +210,8 @@ pub mod tests {
                 ],
             },
         };
-        let fn_b = test_compute_fn("fn_b");
-        let fn_c = test_compute_fn("fn_c");
+        let fn_b = terminal_fn("fn_b");
+        let fn_c = terminal_fn("fn_c");
         ComputeGraph {
             namespace: TEST_NAMESPACE.to_string(),
             name: "graph_B".to_string(),
@@ -213,11 +230,20 @@ pub mod tests {
                 sha256_hash: "hash123".to_string(),
             },
             created_at: 5,
+            updated_at: 5,
             start_fn: Compute(fn_a),
             runtime_information: RuntimeInformation {
                 major_version: 3,
                 minor_version: 10,
             },
+            enabled: true,
+            frozen: false,
+            display_name: None,
+            actor: None,
+            input_schema: None,
+            output_schema: None,
+            labels: Default::default(),
+            schema_version: COMPUTE_GRAPH_SCHEMA_VERSION,
         }
     }
 
@@ -245,11 +271,20 @@ pub mod tests {
             },
             version: crate::GraphVersion(1),
             created_at: 5,
+            updated_at: 5,
             start_fn: Compute(fn_a),
             runtime_information: RuntimeInformation {
                 major_version: 3,
                 minor_version: 10,
             },
+            enabled: true,
+            frozen: false,
+            display_name: None,
+            actor: None,
+            input_schema: None,
+            output_schema: None,
+            labels: Default::default(),
+            schema_version: COMPUTE_GRAPH_SCHEMA_VERSION,
         }
     }
 
@@ -0,0 +1,200 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_stream::try_stream;
+use blob_store::BlobStorage;
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt, TryStreamExt};
+use state_store::IndexifyState;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::io::ReaderStream;
+
+use crate::http_objects::ComputeGraph;
+
+/// Name of the file holding the definition of a graph in an archive.
+pub const DEFINITION_FILE: &str = "compute_graph.json";
+/// Name of the file holding the code of a graph in an archive.
+pub const CODE_FILE: &str = "code";
+
+const BLOCK_SIZE: usize = 512;
+const EXPORT_PAGE_SIZE: usize = 100;
+
+/// Builds the header of a regular file stored at `path`.
+fn header(path: &str, size: u64, mtime_secs: u64) -> Result<Bytes> {
+    let mut header = tar::Header::new_ustar();
+    header
+        .set_path(path)
+        .map_err(|e| anyhow!("invalid archive path {}: {}", path, e))?;
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(mtime_secs);
+    header.set_cksum();
+    Ok(Bytes::copy_from_slice(header.as_bytes()))
+}
+
+/// Number of zeroes completing a file of `size` bytes to a whole number of
+/// blocks.
+fn padding_len(size: u64) -> u64 {
+    (BLOCK_SIZE as u64 - size % BLOCK_SIZE as u64) % BLOCK_SIZE as u64
+}
+
+fn padding(size: u64) -> Bytes {
+    Bytes::from(vec![0u8; padding_len(size) as usize])
+}
+
+/// Streams a tar archive with the definition and code of every compute graph
+/// of the namespace, laid out as `<graph>/compute_graph.json` and
+/// `<graph>/code`. Graphs are read a page at a time and code is streamed
+/// from blob storage, so the archive is never held in memory. The stream
+/// ends with an error if the archive can't be completed, so the response is
+/// aborted instead of looking like a complete archive.
+pub fn export_namespace(
+    indexify_state: Arc<IndexifyState>,
+    blob_storage: Arc<BlobStorage>,
+    namespace: String,
+) -> impl Stream<Item = Result<Bytes>> {
+    let archive = try_stream! {
+        let mut cursor: Option<Vec<u8>> = None;
+        loop {
+            let (compute_graphs, next_cursor) = indexify_state.reader().filter_compute_graphs(
+                &namespace,
+                |_| true,
+                cursor.as_deref(),
+                Some(EXPORT_PAGE_SIZE),
+            )?;
+            for compute_graph in compute_graphs {
+                let name = compute_graph.name.clone();
+                let mtime_secs = compute_graph.created_at / 1000;
+                let code_path = compute_graph.code.path.clone();
+                let code_size = compute_graph.code.size;
+
+                let definition = serde_json::to_vec_pretty(&ComputeGraph::from(compute_graph))?;
+                let size = definition.len() as u64;
+                yield header(&format!("{}/{}", name, DEFINITION_FILE), size, mtime_secs)?;
+                yield Bytes::from(definition);
+                yield padding(size);
+
+                yield header(&format!("{}/{}", name, CODE_FILE), code_size, mtime_secs)?;
+                let mut code = blob_storage.get(&code_path)?.get().await?;
+                let mut written = 0;
+                while let Some(chunk) = code.next().await {
+                    let chunk = chunk?;
+                    written += chunk.len() as u64;
+                    if written > code_size {
+                        break;
+                    }
+                    yield chunk;
+                }
+                // The header already promised the recorded size, a blob of a
+                // different size would corrupt the rest of the archive.
+                if written != code_size {
+                    Err(anyhow!(
+                        "code of compute graph {} is {} bytes, expected {}",
+                        name,
+                        written,
+                        code_size
+                    ))?;
+                }
+                yield padding(code_size);
+            }
+            match next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => break,
+            }
+        }
+        yield Bytes::from(vec![0u8; 2 * BLOCK_SIZE]);
+    };
+    archive.inspect_err(|err| tracing::error!("export of namespace failed: {:?}", err))
+}
+
+/// Regular file of an archive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveFile {
+    pub path: String,
+    pub size: u64,
+}
+
+/// Reads the regular files of a tar archive as it's received, skipping any
+/// other entry.
+pub struct ArchiveReader<R> {
+    reader: R,
+    /// Bytes of the current file, padding included, which weren't read.
+    unread: u64,
+}
+
+impl<R: AsyncRead + Unpin> ArchiveReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, unread: 0 }
+    }
+
+    /// Returns the next regular file, skipping whatever wasn't read of the
+    /// previous one, or None at the end of the archive.
+    pub async fn next_file(&mut self) -> Result<Option<ArchiveFile>> {
+        loop {
+            self.skip(self.unread).await?;
+            self.unread = 0;
+            let mut block = [0u8; BLOCK_SIZE];
+            self.reader
+                .read_exact(&mut block)
+                .await
+                .map_err(|_| anyhow!("tar archive is missing its end marker"))?;
+            if block.iter().all(|b| *b == 0) {
+                return Ok(None);
+            }
+            let header = tar::Header::from_byte_slice(&block);
+            let checksum: u32 = block[..148]
+                .iter()
+                .chain(b"        ")
+                .chain(&block[156..])
+                .map(|b| *b as u32)
+                .sum();
+            if header.cksum()? != checksum {
+                return Err(anyhow!("invalid tar header checksum"));
+            }
+            let size = header.entry_size()?;
+            self.unread = size + padding_len(size);
+            if !header.entry_type().is_file() {
+                continue;
+            }
+            let path = header.path()?;
+            let path = path
+                .to_str()
+                .ok_or_else(|| anyhow!("invalid archive path {}", path.display()))?;
+            return Ok(Some(ArchiveFile {
+                path: path.to_string(),
+                size,
+            }));
+        }
+    }
+
+    /// Reads the whole current file, which is `size` bytes.
+    pub async fn read(&mut self, size: u64) -> Result<Bytes> {
+        let mut data = BytesMut::zeroed(size as usize);
+        self.reader
+            .read_exact(&mut data)
+            .await
+            .map_err(|_| anyhow!("tar archive is truncated"))?;
+        self.unread -= size;
+        Ok(data.freeze())
+    }
+
+    /// Streams the current file, which is `size` bytes. The stream ends early
+    /// if the archive is truncated.
+    pub fn stream(&mut self, size: u64) -> impl Stream<Item = Result<Bytes>> + Send + Unpin + '_
+    where
+        R: Send,
+    {
+        self.unread -= size;
+        ReaderStream::new((&mut self.reader).take(size)).map_err(anyhow::Error::from)
+    }
+
+    async fn skip(&mut self, len: u64) -> Result<()> {
+        let skipped =
+            tokio::io::copy(&mut (&mut self.reader).take(len), &mut tokio::io::sink()).await?;
+        if skipped != len {
+            return Err(anyhow!("tar archive is truncated"));
+        }
+        Ok(())
+    }
+}
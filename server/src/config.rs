@@ -1,18 +1,301 @@
-use std::{env, fmt::Debug, net::SocketAddr};
+use std::{collections::HashMap, env, fmt::Debug, net::SocketAddr, sync::Arc, time::Duration};
 
 use anyhow::Result;
-use blob_store::BlobStorageConfig;
+use blob_store::{BlobStorage, BlobStorageConfig};
 use figment::{
-    providers::{Format, Yaml},
+    providers::{Env, Format, Serialized, Yaml},
     Figment,
 };
 use serde::{Deserialize, Serialize};
+use state_store::{
+    key_prefix::KeyPrefix,
+    CfCompression,
+    CfOverride,
+    IndexifyState,
+    IndexifyStateOptions,
+    PrefixExtractor,
+};
+
+/// Prefix of the environment variables overriding the configuration, e.g.
+/// `INDEXIFY_LISTEN_ADDR`. Nested fields are separated by a double
+/// underscore, as in `INDEXIFY_BLOB_STORAGE__DISK__PATH`.
+const ENV_PREFIX: &str = "INDEXIFY_";
+
+/// How long the startup check waits for the blob storage to answer.
+const BLOB_STORAGE_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub state_store_path: String,
     pub listen_addr: String,
     pub blob_storage: BlobStorageConfig,
+    /// Checks on startup that the code blobs of all compute graphs are
+    /// present in blob storage and logs the ones that are missing.
+    #[serde(default)]
+    pub verify_blob_references_on_startup: bool,
+    /// Largest number of nodes a compute graph can have.
+    #[serde(default = "default_max_nodes")]
+    pub max_nodes: usize,
+    /// Largest size in bytes of the `compute_graph` field of a compute graph
+    /// upload. Larger definitions are rejected with 413 without reading them
+    /// whole. The code field isn't limited by it.
+    #[serde(default = "default_max_compute_graph_definition_bytes")]
+    pub max_compute_graph_definition_bytes: usize,
+    /// Largest size in bytes of the code of a compute graph, once
+    /// decompressed when it's uploaded gzipped. Larger code is rejected with
+    /// 413 as soon as the limit is crossed.
+    #[serde(default = "default_max_compute_graph_code_bytes")]
+    pub max_compute_graph_code_bytes: usize,
+    /// Largest size in bytes of an input or output validated against the
+    /// schemas of its compute graph. Larger inputs are rejected with 413 and
+    /// larger outputs fail their task.
+    #[serde(default = "default_max_schema_validated_bytes")]
+    pub max_schema_validated_bytes: usize,
+    /// When set, state store writes received within this many milliseconds
+    /// are committed together. Disabled by default.
+    #[serde(default)]
+    pub group_commit_window_ms: Option<u64>,
+    /// When set, the state store is flushed to disk every this many
+    /// milliseconds, bounding the writes lost on a crash of the host to
+    /// that window. Disabled by default.
+    #[serde(default)]
+    pub flush_interval_ms: Option<u64>,
+    /// Requests taking longer than this many milliseconds are logged with a
+    /// warning. Disabled by default.
+    #[serde(default)]
+    pub slow_request_threshold_ms: Option<u64>,
+    /// How often the inputs past the retention policy of their namespace
+    /// are deleted.
+    #[serde(default = "default_retention_sweep_interval_secs")]
+    pub retention_sweep_interval_secs: u64,
+    /// Namespace created on startup and used by the routes which don't take
+    /// a namespace, such as `POST /compute_graphs`.
+    #[serde(default)]
+    pub default_namespace: Option<String>,
+    /// Urls the changes to the compute graphs of a namespace are posted to.
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    /// Treat compute graph names differing only by case as the same graph.
+    #[serde(default)]
+    pub normalize_compute_graph_names: bool,
+    /// Largest number of request bodies streamed to blob storage at once.
+    #[serde(default = "default_max_concurrent_uploads")]
+    pub max_concurrent_uploads: usize,
+    /// How long an upload waits for one of the others to finish before
+    /// being rejected with 503.
+    #[serde(default = "default_upload_wait_timeout_secs")]
+    pub upload_wait_timeout_secs: u64,
+    /// Largest number of requests served at once by each of the expensive
+    /// routes, such as the export of a namespace.
+    #[serde(default)]
+    pub route_concurrency_limits: RouteConcurrencyLimits,
+    /// Create the namespace of a compute graph when it doesn't exist instead
+    /// of rejecting the compute graph.
+    #[serde(default)]
+    pub auto_create_namespace: bool,
+    /// Name prepended to every key of the state store, for servers sharing
+    /// a state store directory.
+    #[serde(default)]
+    pub instance_prefix: String,
+    /// How long the pagination cursor returned by a listing can be used to
+    /// fetch the next page.
+    #[serde(default = "default_cursor_ttl_secs")]
+    pub cursor_ttl_secs: u64,
+    /// Number of invocation events buffered for the change notification
+    /// streams. Streams reading slower than events are published past this
+    /// many are sent a `lagged` event and skip ahead.
+    #[serde(default = "default_notify_channel_capacity")]
+    pub notify_channel_capacity: usize,
+    /// Number of times a state store write failing with a transient error,
+    /// such as a lock timeout under contention, is retried before it is
+    /// rejected with 503.
+    #[serde(default = "default_write_retries")]
+    pub write_retries: u32,
+    /// Largest number of namespaces, creating more is rejected with 403.
+    /// Unlimited when unset.
+    #[serde(default)]
+    pub max_namespaces: Option<usize>,
+    /// Percentage of `max_namespaces` from which namespace listings carry a
+    /// warning that the quota is about to be reached.
+    #[serde(default = "default_quota_warning_threshold_percent")]
+    pub quota_warning_threshold_percent: u8,
+    /// Keys of the input attributes which are indexed, inputs can be
+    /// listed by the value of these attributes.
+    #[serde(default)]
+    pub indexed_attributes: Vec<String>,
+    /// How the state store splits the keys of namespaced records into the
+    /// prefix its prefix bloom filters are built on.
+    #[serde(default)]
+    pub prefix_extractor: PrefixExtractorConfig,
+    /// Options of state store column families, by name, overriding the
+    /// defaults they are opened with.
+    #[serde(default)]
+    pub column_families: HashMap<String, ColumnFamilyConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrefixExtractorConfig {
+    None,
+    #[default]
+    Namespace,
+    FixedLength(usize),
+}
+
+impl From<PrefixExtractorConfig> for PrefixExtractor {
+    fn from(config: PrefixExtractorConfig) -> Self {
+        match config {
+            PrefixExtractorConfig::None => PrefixExtractor::None,
+            PrefixExtractorConfig::Namespace => PrefixExtractor::Namespace,
+            PrefixExtractorConfig::FixedLength(len) => PrefixExtractor::FixedLength(len),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnFamilyConfig {
+    #[serde(default)]
+    pub compression: Option<CompressionConfig>,
+    /// Size of a block cache used by this column family only.
+    #[serde(default)]
+    pub block_cache_size_bytes: Option<usize>,
+}
+
+impl From<&ColumnFamilyConfig> for CfOverride {
+    fn from(config: &ColumnFamilyConfig) -> Self {
+        Self {
+            compression: config.compression.map(Into::into),
+            block_cache_size: config.block_cache_size_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionConfig {
+    None,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl From<CompressionConfig> for CfCompression {
+    fn from(config: CompressionConfig) -> Self {
+        match config {
+            CompressionConfig::None => CfCompression::None,
+            CompressionConfig::Snappy => CfCompression::Snappy,
+            CompressionConfig::Lz4 => CfCompression::Lz4,
+            CompressionConfig::Zstd => CfCompression::Zstd,
+        }
+    }
+}
+
+/// State store and blob storage opened by
+/// `ServerConfig::validate_and_connect`.
+pub struct Connections {
+    pub indexify_state: Arc<IndexifyState>,
+    pub blob_storage: Arc<BlobStorage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteConcurrencyLimits {
+    #[serde(default = "default_export_concurrency")]
+    pub export: usize,
+    #[serde(default = "default_import_concurrency")]
+    pub import: usize,
+    #[serde(default = "default_reindex_concurrency")]
+    pub reindex: usize,
+    /// How long a request waits for one of the others to the same route to
+    /// finish before being rejected with 503.
+    #[serde(default = "default_route_wait_timeout_secs")]
+    pub wait_timeout_secs: u64,
+}
+
+impl Default for RouteConcurrencyLimits {
+    fn default() -> Self {
+        Self {
+            export: default_export_concurrency(),
+            import: default_import_concurrency(),
+            reindex: default_reindex_concurrency(),
+            wait_timeout_secs: default_route_wait_timeout_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub namespace: String,
+    pub url: String,
+    /// Key of the HMAC-SHA256 signature of the request body sent in the
+    /// `X-Indexify-Signature` header.
+    pub secret: String,
+    /// Deliveries taking longer than this many seconds fail and are retried.
+    #[serde(default = "default_webhook_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_webhook_timeout_secs() -> u64 {
+    10
+}
+
+fn default_max_nodes() -> usize {
+    1000
+}
+
+fn default_max_compute_graph_definition_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_max_compute_graph_code_bytes() -> usize {
+    1024 * 1024 * 1024
+}
+
+fn default_max_schema_validated_bytes() -> usize {
+    16 * 1024 * 1024
+}
+
+fn default_retention_sweep_interval_secs() -> u64 {
+    60
+}
+
+fn default_max_concurrent_uploads() -> usize {
+    64
+}
+
+fn default_upload_wait_timeout_secs() -> u64 {
+    30
+}
+
+fn default_export_concurrency() -> usize {
+    4
+}
+
+fn default_import_concurrency() -> usize {
+    4
+}
+
+fn default_reindex_concurrency() -> usize {
+    1
+}
+
+fn default_route_wait_timeout_secs() -> u64 {
+    30
+}
+
+fn default_cursor_ttl_secs() -> u64 {
+    600
+}
+
+fn default_notify_channel_capacity() -> usize {
+    100
+}
+
+fn default_write_retries() -> u32 {
+    3
+}
+
+fn default_quota_warning_threshold_percent() -> u8 {
+    80
 }
 
 impl Default for ServerConfig {
@@ -22,6 +305,31 @@ impl Default for ServerConfig {
             state_store_path: state_store_path.to_str().unwrap().to_string(),
             listen_addr: "0.0.0.0:8900".to_string(),
             blob_storage: Default::default(),
+            verify_blob_references_on_startup: false,
+            max_nodes: default_max_nodes(),
+            max_compute_graph_definition_bytes: default_max_compute_graph_definition_bytes(),
+            max_compute_graph_code_bytes: default_max_compute_graph_code_bytes(),
+            max_schema_validated_bytes: default_max_schema_validated_bytes(),
+            group_commit_window_ms: None,
+            flush_interval_ms: None,
+            slow_request_threshold_ms: None,
+            retention_sweep_interval_secs: default_retention_sweep_interval_secs(),
+            default_namespace: None,
+            webhooks: Vec::new(),
+            normalize_compute_graph_names: false,
+            max_concurrent_uploads: default_max_concurrent_uploads(),
+            upload_wait_timeout_secs: default_upload_wait_timeout_secs(),
+            route_concurrency_limits: RouteConcurrencyLimits::default(),
+            auto_create_namespace: false,
+            instance_prefix: String::new(),
+            cursor_ttl_secs: default_cursor_ttl_secs(),
+            notify_channel_capacity: default_notify_channel_capacity(),
+            write_retries: default_write_retries(),
+            max_namespaces: None,
+            quota_warning_threshold_percent: default_quota_warning_threshold_percent(),
+            indexed_attributes: Vec::new(),
+            prefix_extractor: PrefixExtractorConfig::default(),
+            column_families: HashMap::new(),
         }
     }
 }
@@ -29,28 +337,325 @@ impl Default for ServerConfig {
 impl ServerConfig {
     pub fn from_path(path: &str) -> Result<ServerConfig> {
         let config_str = std::fs::read_to_string(path)?;
-        let config: ServerConfig = Figment::new().merge(Yaml::string(&config_str)).extract()?;
+        Self::from_yaml(&config_str)
+    }
+
+    /// Parses a YAML configuration, overridden by the `INDEXIFY_` environment
+    /// variables.
+    pub fn from_yaml(config_str: &str) -> Result<ServerConfig> {
+        Self::extract(Figment::new().merge(Yaml::string(config_str)))
+    }
+
+    /// Builds the configuration from the defaults overridden by the
+    /// `INDEXIFY_` environment variables.
+    pub fn from_env() -> Result<ServerConfig> {
+        Self::extract(Figment::from(Serialized::defaults(ServerConfig::default())))
+    }
+
+    fn extract(figment: Figment) -> Result<ServerConfig> {
+        let config: ServerConfig = figment
+            .merge(Env::prefixed(ENV_PREFIX).split("__"))
+            .extract()?;
         config.validate()?;
         Ok(config)
     }
 
+    /// Options of the state store opened by the server.
+    pub fn indexify_state_options(&self) -> IndexifyStateOptions {
+        IndexifyStateOptions {
+            group_commit_window: self.group_commit_window_ms.map(Duration::from_millis),
+            record_change_events: !self.webhooks.is_empty(),
+            normalize_compute_graph_names: self.normalize_compute_graph_names,
+            auto_create_namespace: self.auto_create_namespace,
+            task_event_capacity: Some(self.notify_channel_capacity),
+            write_retries: self.write_retries,
+            max_namespaces: self.max_namespaces,
+            indexed_attributes: self.indexed_attributes.iter().cloned().collect(),
+            prefix_extractor: self.prefix_extractor.into(),
+            cf_overrides: self
+                .column_families
+                .iter()
+                .map(|(name, config)| (name.clone(), config.into()))
+                .collect(),
+            instance_prefix: self.instance_prefix.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Checks the configuration and the services it points to before the
+    /// server starts: the configuration values, the blob storage answering
+    /// a listing, and the state store opening with a supported schema.
+    /// Every problem found is reported in the returned error, not only the
+    /// first one.
+    pub async fn validate_and_connect(&self) -> Result<Connections> {
+        let mut problems = self.problems();
+
+        let blob_storage = match BlobStorage::new(self.blob_storage.clone()) {
+            Ok(blob_storage) => {
+                let listing = blob_storage.list_page("", None, 1);
+                match tokio::time::timeout(BLOB_STORAGE_CHECK_TIMEOUT, listing).await {
+                    Ok(Ok(_)) => Some(blob_storage),
+                    Ok(Err(err)) => {
+                        problems.push(format!("blob storage is unreachable: {}", err));
+                        None
+                    }
+                    Err(_) => {
+                        problems.push(format!(
+                            "blob storage didn't answer within {:?}",
+                            BLOB_STORAGE_CHECK_TIMEOUT
+                        ));
+                        None
+                    }
+                }
+            }
+            Err(err) => {
+                problems.push(format!("blob storage can't be set up: {}", err));
+                None
+            }
+        };
+
+        let indexify_state = match IndexifyState::new_with_options(
+            self.state_store_path.clone().into(),
+            self.indexify_state_options(),
+        )
+        .await
+        {
+            Ok(indexify_state) => match indexify_state.check_schema_version() {
+                Ok(()) => Some(indexify_state),
+                Err(err) => {
+                    problems.push(err.to_string());
+                    None
+                }
+            },
+            Err(err) => {
+                problems.push(format!(
+                    "state store at {} can't be opened: {}",
+                    self.state_store_path, err
+                ));
+                None
+            }
+        };
+
+        match (blob_storage, indexify_state) {
+            (Some(blob_storage), Some(indexify_state)) if problems.is_empty() => Ok(Connections {
+                indexify_state,
+                blob_storage: Arc::new(blob_storage),
+            }),
+            _ => Err(anyhow::anyhow!(
+                "invalid configuration:\n{}",
+                problems
+                    .iter()
+                    .map(|problem| format!("  - {}", problem))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )),
+        }
+    }
+
     pub fn validate(&self) -> Result<()> {
+        let problems = self.problems();
+        if !problems.is_empty() {
+            return Err(anyhow::anyhow!(problems.join("; ")));
+        }
+        Ok(())
+    }
+
+    /// Every problem of the configuration, checked without connecting to
+    /// anything.
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
         if self.blob_storage.s3.is_some() && self.blob_storage.disk.is_some() {
-            return Err(anyhow::anyhow!(
-                "cannot specify both s3 and disk blob storage"
-            ));
+            problems.push("cannot specify both s3 and disk blob storage".to_string());
         }
         if self.blob_storage.s3.is_none() && self.blob_storage.disk.is_none() {
-            return Err(anyhow::anyhow!(
-                "must specify one of s3 or disk blob storage"
-            ));
+            problems.push("must specify one of s3 or disk blob storage".to_string());
+        }
+        if self.max_nodes == 0 {
+            problems.push("max_nodes must be greater than 0".to_string());
+        }
+        if self.max_compute_graph_definition_bytes == 0 {
+            problems.push("max_compute_graph_definition_bytes must be greater than 0".to_string());
+        }
+        if self.max_compute_graph_code_bytes == 0 {
+            problems.push("max_compute_graph_code_bytes must be greater than 0".to_string());
+        }
+        if self.max_schema_validated_bytes == 0 {
+            problems.push("max_schema_validated_bytes must be greater than 0".to_string());
+        }
+        if self.max_concurrent_uploads == 0 {
+            problems.push("max_concurrent_uploads must be greater than 0".to_string());
+        }
+        let limits = &self.route_concurrency_limits;
+        for (route, limit) in [
+            ("export", limits.export),
+            ("import", limits.import),
+            ("reindex", limits.reindex),
+        ] {
+            if limit == 0 {
+                problems.push(format!(
+                    "route_concurrency_limits.{} must be greater than 0",
+                    route
+                ));
+            }
+        }
+        if self.cursor_ttl_secs == 0 {
+            problems.push("cursor_ttl_secs must be greater than 0".to_string());
+        }
+        if self.notify_channel_capacity == 0 {
+            problems.push("notify_channel_capacity must be greater than 0".to_string());
+        }
+        if self.flush_interval_ms == Some(0) {
+            problems.push("flush_interval_ms must be greater than 0".to_string());
+        }
+        if self.slow_request_threshold_ms == Some(0) {
+            problems.push("slow_request_threshold_ms must be greater than 0".to_string());
+        }
+        if self.retention_sweep_interval_secs == 0 {
+            problems.push("retention_sweep_interval_secs must be greater than 0".to_string());
+        }
+        if let Err(e) = KeyPrefix::new(&self.instance_prefix) {
+            problems.push(e.to_string());
+        }
+        if self.max_namespaces == Some(0) {
+            problems.push("max_namespaces must be greater than 0".to_string());
+        }
+        if !(1..=100).contains(&self.quota_warning_threshold_percent) {
+            problems.push("quota_warning_threshold_percent must be between 1 and 100".to_string());
+        }
+        if self.indexed_attributes.iter().any(|key| key.is_empty()) {
+            problems.push("indexed_attributes must not be empty".to_string());
+        }
+        if self.prefix_extractor == PrefixExtractorConfig::FixedLength(0) {
+            problems.push("prefix_extractor fixed_length must be greater than 0".to_string());
+        }
+        for (name, config) in &self.column_families {
+            if config.block_cache_size_bytes == Some(0) {
+                problems.push(format!(
+                    "column_families.{}.block_cache_size_bytes must be greater than 0",
+                    name
+                ));
+            }
+        }
+        if self.default_namespace.as_deref() == Some("") {
+            problems.push("default_namespace must not be empty".to_string());
+        }
+        for webhook in &self.webhooks {
+            if reqwest::Url::parse(&webhook.url).is_err() {
+                problems.push(format!("invalid webhook url: {}", webhook.url));
+            }
+            if webhook.timeout_secs == 0 {
+                problems.push(format!(
+                    "timeout_secs of webhook {} must be greater than 0",
+                    webhook.url
+                ));
+            }
         }
         if self.listen_addr.parse::<SocketAddr>().is_err() {
-            return Err(anyhow::anyhow!(
-                "invalid listen address: {}",
-                self.listen_addr
-            ));
+            problems.push(format!("invalid listen address: {}", self.listen_addr));
         }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use data_model::test_objects::tests::mock_graph_a;
+    use futures::stream;
+    use state_store::requests::{
+        CreateComputeGraphRequest,
+        RequestPayload,
+        StateMachineUpdateRequest,
+    };
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::routes::RouteState;
+
+    #[tokio::test]
+    async fn test_validate_and_connect() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        // The blob directory can't be created under a regular file.
+        let file = temp_dir.path().join("file");
+        std::fs::write(&file, "")?;
+        let config = ServerConfig {
+            state_store_path: temp_dir.path().join("state").display().to_string(),
+            listen_addr: "not an address".to_string(),
+            blob_storage: BlobStorageConfig::new_disk(&file.join("blob").display().to_string()),
+            ..Default::default()
+        };
+        let err = config.validate_and_connect().await.err().unwrap();
+        let message = err.to_string();
+        assert!(message.contains("invalid listen address: not an address"));
+        assert!(message.contains("blob storage can't be set up"));
+        assert!(!message.contains("state store"));
+
+        let config = ServerConfig {
+            state_store_path: temp_dir.path().join("state").display().to_string(),
+            blob_storage: BlobStorageConfig::new_disk(
+                &temp_dir.path().join("blob").display().to_string(),
+            ),
+            ..Default::default()
+        };
+        assert!(config.validate_and_connect().await.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_route_state_from_config() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let config = ServerConfig::from_yaml(&format!(
+            r#"
+state_store_path: {state}
+listen_addr: 127.0.0.1:8900
+blob_storage:
+  backend: disk
+  disk:
+    path: {blob}
+max_nodes: 10
+max_concurrent_uploads: 2
+auto_create_namespace: true
+"#,
+            state = temp_dir.path().join("state").display(),
+            blob = temp_dir.path().join("blob").display(),
+        ))?;
+        assert_eq!(config.max_nodes, 10);
+        assert!(config.indexify_state_options().auto_create_namespace);
+
+        let state = RouteState::from_config(config).await?;
+        assert_eq!(state.config.max_concurrent_uploads, 2);
+        let code = state
+            .blob_storage
+            .put("code", stream::iter([Ok(Bytes::from("code"))]))
+            .await?;
+        let mut compute_graph = mock_graph_a();
+        compute_graph.namespace = "configured".to_string();
+        compute_graph.code.path = code.url;
+        // The namespace is created with the graph.
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: "configured".to_string(),
+                    compute_graph,
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let compute_graph = state
+            .indexify_state
+            .reader()
+            .get_compute_graph("configured", "graph_A")?
+            .unwrap();
+        let code = state
+            .blob_storage
+            .read_bytes(&compute_graph.code.path)
+            .await?;
+        assert_eq!(code, Bytes::from("code"));
+
         Ok(())
     }
 }
@@ -0,0 +1,87 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use state_store::IndexifyState;
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info};
+
+/// Periodically flushes the state store, bounding the writes lost on a crash
+/// to the flush interval.
+pub struct Flusher {
+    state: Arc<IndexifyState>,
+    interval: Duration,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+}
+
+impl Flusher {
+    pub fn new(
+        state: Arc<IndexifyState>,
+        interval: Duration,
+        shutdown_rx: tokio::sync::watch::Receiver<()>,
+    ) -> Self {
+        Self {
+            state,
+            interval,
+            shutdown_rx,
+        }
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately, nothing was written yet.
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => self.flush().await,
+                _ = self.shutdown_rx.changed() => {
+                    // Flush the writes of the last interval before stopping.
+                    self.flush().await;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    async fn flush(&self) {
+        let state = self.state.clone();
+        let started_at = Instant::now();
+        match tokio::task::spawn_blocking(move || state.flush()).await {
+            Ok(Ok(())) => info!("flushed state store in {:?}", started_at.elapsed()),
+            Ok(Err(e)) => error!("failed to flush state store: {:?}", e),
+            Err(e) => error!("state store flush panicked: {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use state_store::IndexifyState;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_periodic_flush() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        assert!(state.reader().last_flush_at()?.is_none());
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(());
+        let mut flusher = Flusher::new(state.clone(), Duration::from_millis(10), shutdown_rx);
+        let flusher = tokio::spawn(async move { flusher.start().await });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let first_flush = state.reader().last_flush_at()?.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(state.reader().last_flush_at()?.unwrap() > first_flush);
+
+        shutdown_tx.send(())?;
+        flusher.await??;
+
+        Ok(())
+    }
+}
@@ -108,10 +108,12 @@ mod tests {
         let compute_graph = mock_graph_a();
         state
             .write(StateMachineUpdateRequest {
-                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
                     namespace: TEST_NAMESPACE.to_string(),
                     compute_graph: compute_graph.clone(),
-                }),
+                    if_match: None,
+                    if_changed: false,
+                })),
                 state_changes_processed: vec![],
             })
             .await?;
@@ -135,6 +137,7 @@ mod tests {
             }),
             errors: None,
             reduced_state: false,
+            parent_output_key: None,
         };
         let key = output.key(&output.invocation_id);
         let serialized_output = JsonEncoder::encode(&output)?;
@@ -149,6 +152,7 @@ mod tests {
         let request = RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
             namespace: TEST_NAMESPACE.to_string(),
             name: compute_graph.name.clone(),
+            if_match: None,
         });
         state
             .write(StateMachineUpdateRequest {
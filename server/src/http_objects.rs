@@ -1,34 +1,140 @@
-use std::{collections::HashMap, fmt};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
 
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
+    Json,
 };
-use data_model::ComputeGraphCode;
+use data_model::{json_schema, ComputeGraphCode};
 use indexify_utils::get_epoch_time_in_ms;
 use serde::{Deserialize, Serialize};
+use state_store::state_machine::TransientWriteError;
 use utoipa::ToSchema;
 
+/// Machine readable error codes, clients can branch on them instead of
+/// parsing messages. Each code maps to a single HTTP status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ToSchema, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    BadRequest,
+    NotFound,
+    RouteNotFound,
+    NamespaceNotFound,
+    ComputeGraphNotFound,
+    InvocationNotFound,
+    OutputNotFound,
+    Forbidden,
+    NamespaceLimitReached,
+    Conflict,
+    ComputeGraphExists,
+    ComputeGraphDisabled,
+    ComputeGraphFrozen,
+    NamespaceNotEmpty,
+    PreconditionFailed,
+    EtagMismatch,
+    Gone,
+    CursorExpired,
+    PayloadTooLarge,
+    Internal,
+    Unavailable,
+}
+
+impl ErrorCode {
+    /// Generic code of errors raised without a more specific one.
+    fn from_status(status_code: StatusCode) -> Self {
+        match status_code {
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+            StatusCode::CONFLICT => ErrorCode::Conflict,
+            StatusCode::PRECONDITION_FAILED => ErrorCode::PreconditionFailed,
+            StatusCode::GONE => ErrorCode::Gone,
+            StatusCode::PAYLOAD_TOO_LARGE => ErrorCode::PayloadTooLarge,
+            StatusCode::SERVICE_UNAVAILABLE => ErrorCode::Unavailable,
+            status if status.is_client_error() => ErrorCode::BadRequest,
+            _ => ErrorCode::Internal,
+        }
+    }
+
+    pub fn status_code(self) -> StatusCode {
+        match self {
+            ErrorCode::BadRequest => StatusCode::BAD_REQUEST,
+            ErrorCode::NotFound |
+            ErrorCode::RouteNotFound |
+            ErrorCode::NamespaceNotFound |
+            ErrorCode::ComputeGraphNotFound |
+            ErrorCode::InvocationNotFound |
+            ErrorCode::OutputNotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Forbidden | ErrorCode::NamespaceLimitReached => StatusCode::FORBIDDEN,
+            ErrorCode::Conflict |
+            ErrorCode::ComputeGraphExists |
+            ErrorCode::ComputeGraphDisabled |
+            ErrorCode::ComputeGraphFrozen |
+            ErrorCode::NamespaceNotEmpty => StatusCode::CONFLICT,
+            ErrorCode::PreconditionFailed | ErrorCode::EtagMismatch => {
+                StatusCode::PRECONDITION_FAILED
+            }
+            ErrorCode::Gone | ErrorCode::CursorExpired => StatusCode::GONE,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+/// Body of every error response.
 #[derive(Debug, ToSchema, Serialize, Deserialize)]
 pub struct IndexifyAPIError {
     #[serde(skip)]
     status_code: StatusCode,
+    code: ErrorCode,
     message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
 }
 
 impl IndexifyAPIError {
     pub fn new(status_code: StatusCode, message: &str) -> Self {
         Self {
             status_code,
+            code: ErrorCode::from_status(status_code),
+            message: message.to_string(),
+            details: None,
+        }
+    }
+
+    /// Builds an error whose status is the one of its code.
+    pub fn with_code(code: ErrorCode, message: &str) -> Self {
+        Self {
+            status_code: code.status_code(),
+            code,
             message: message.to_string(),
+            details: None,
         }
     }
 
+    /// Attaches structured context about the error to the response.
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        self.status_code
+    }
+
     pub fn _bad_request(e: &str) -> Self {
         Self::new(StatusCode::BAD_REQUEST, e)
     }
 
+    /// Writes which kept failing with a transient state store error are
+    /// reported as unavailable so clients know to retry them.
     pub fn internal_error(e: anyhow::Error) -> Self {
+        if let Some(transient) = e.downcast_ref::<TransientWriteError>() {
+            return Self::new(StatusCode::SERVICE_UNAVAILABLE, &transient.to_string());
+        }
         Self::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string().as_str())
     }
 
@@ -36,25 +142,79 @@ impl IndexifyAPIError {
         Self::new(StatusCode::INTERNAL_SERVER_ERROR, e)
     }
 
-    pub fn not_found(message: &str) -> Self {
-        Self::new(StatusCode::NOT_FOUND, message)
-    }
-
     pub fn bad_request(message: &str) -> Self {
         Self::new(StatusCode::BAD_REQUEST, message)
     }
+
+    pub fn conflict(message: &str) -> Self {
+        Self::new(StatusCode::CONFLICT, message)
+    }
+
+    pub fn route_not_found() -> Self {
+        Self::with_code(ErrorCode::RouteNotFound, "route not found")
+    }
+
+    pub fn namespace_not_found(namespace: &str) -> Self {
+        Self::with_code(
+            ErrorCode::NamespaceNotFound,
+            &format!("namespace {} not found", namespace),
+        )
+    }
+
+    pub fn compute_graph_not_found() -> Self {
+        Self::with_code(ErrorCode::ComputeGraphNotFound, "Compute Graph not found")
+    }
+
+    pub fn invocation_not_found() -> Self {
+        Self::with_code(ErrorCode::InvocationNotFound, "Invocation not found")
+    }
+
+    pub fn output_not_found(message: &str) -> Self {
+        Self::with_code(ErrorCode::OutputNotFound, message)
+    }
+
+    pub fn compute_graph_exists(name: &str) -> Self {
+        Self::with_code(
+            ErrorCode::ComputeGraphExists,
+            &format!("compute graph {} already exists", name),
+        )
+    }
+
+    /// Rejects a value not matching a schema declared by a compute graph,
+    /// listing the violations in the details.
+    pub fn schema_violations(message: &str, violations: &[json_schema::SchemaViolation]) -> Self {
+        let violations: Vec<_> = violations
+            .iter()
+            .map(|violation| {
+                serde_json::json!({"path": violation.path, "message": violation.message})
+            })
+            .collect();
+        Self::bad_request(message).with_details(serde_json::json!({ "violations": violations }))
+    }
 }
 
 impl IntoResponse for IndexifyAPIError {
     fn into_response(self) -> Response {
         tracing::error!("API Error: {} - {}", self.status_code, self.message);
-        (self.status_code, self.message).into_response()
+        (self.status_code, Json(self)).into_response()
     }
 }
 
 impl From<serde_json::Error> for IndexifyAPIError {
     fn from(e: serde_json::Error) -> Self {
-        Self::bad_request(&e.to_string())
+        // Errors raised while converting an already parsed value have no
+        // position.
+        if e.line() == 0 {
+            return Self::bad_request(&format!("invalid json: {}", e));
+        }
+        let position = format!(" at line {} column {}", e.line(), e.column());
+        let message = e.to_string();
+        Self::bad_request(&format!(
+            "invalid json{}: {}",
+            position,
+            message.trim_end_matches(&position)
+        ))
+        .with_details(serde_json::json!({"line": e.line(), "column": e.column()}))
     }
 }
 
@@ -66,8 +226,14 @@ pub struct ListParams {
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Namespace {
-    name: String,
-    created_at: u64,
+    pub name: String,
+    pub created_at: u64,
+    pub feature_flags: BTreeMap<String, bool>,
+    pub retention: RetentionPolicy,
+    /// Number of compute graphs of the namespace, only set by listings
+    /// asked for graph counts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compute_graph_count: Option<usize>,
 }
 
 impl From<data_model::Namespace> for Namespace {
@@ -75,13 +241,484 @@ impl From<data_model::Namespace> for Namespace {
         Self {
             name: namespace.name,
             created_at: namespace.created_at,
+            feature_flags: namespace.feature_flags,
+            retention: namespace.retention.into(),
+            compute_graph_count: None,
+        }
+    }
+}
+
+/// How long the data of a namespace is kept, data is kept forever when
+/// unset. Only inputs expire: outputs can't be deleted apart from their
+/// invocation and graphs keep no earlier versions, so an output ttl or a
+/// maximum number of versions is rejected rather than silently ignored.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionPolicy {
+    /// Inputs created longer than this ago are deleted.
+    #[serde(default)]
+    pub input_ttl_secs: Option<u64>,
+}
+
+impl From<data_model::RetentionPolicy> for RetentionPolicy {
+    fn from(policy: data_model::RetentionPolicy) -> Self {
+        Self {
+            input_ttl_secs: policy.input_ttl_secs,
+        }
+    }
+}
+
+impl From<RetentionPolicy> for data_model::RetentionPolicy {
+    fn from(policy: RetentionPolicy) -> Self {
+        Self {
+            input_ttl_secs: policy.input_ttl_secs,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NamespacePolicy {
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NamespaceFeatureFlags {
+    pub feature_flags: BTreeMap<String, bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NamespaceBlobBytes {
+    pub total: u64,
+    pub compute_graph_code: u64,
+    pub invocation_payloads: u64,
+}
+
+impl From<state_store::scanner::NamespaceBlobUsage> for NamespaceBlobBytes {
+    fn from(usage: state_store::scanner::NamespaceBlobUsage) -> Self {
+        Self {
+            total: usage.total_bytes,
+            compute_graph_code: usage.compute_graph_code_bytes,
+            invocation_payloads: usage.invocation_payload_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GlobalStats {
+    pub namespaces: usize,
+    pub compute_graphs: usize,
+    pub compute_graphs_per_namespace: BTreeMap<String, usize>,
+    pub blob_bytes: NamespaceBlobBytes,
+    pub invocations: usize,
+    /// False when there were too many invocations to read them all, the
+    /// invocation count and payload bytes are then lower bounds.
+    pub invocations_exact: bool,
+}
+
+impl From<state_store::scanner::GlobalStats> for GlobalStats {
+    fn from(stats: state_store::scanner::GlobalStats) -> Self {
+        Self {
+            namespaces: stats.namespaces,
+            compute_graphs: stats.compute_graphs,
+            compute_graphs_per_namespace: stats.compute_graphs_per_namespace,
+            blob_bytes: stats.blob_usage.into(),
+            invocations: stats.invocations,
+            invocations_exact: stats.invocations_exact,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NamespaceUsage {
+    pub namespace: String,
+    pub blob_bytes: NamespaceBlobBytes,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NamespaceDigest {
+    pub namespace: String,
+    pub digest: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NamespaceDiffParams {
+    pub a: String,
+    pub b: String,
+}
+
+/// Compute graphs differing between namespaces `a` and `b`, by name.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NamespaceDiff {
+    pub a: String,
+    pub b: String,
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    /// Graphs in both namespaces which differ in content, such as code or
+    /// nodes. Timestamps and versions aren't compared.
+    pub changed: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DanglingBlobReference {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub url: String,
+}
+
+impl From<state_store::DanglingRef> for DanglingBlobReference {
+    fn from(dangling_ref: state_store::DanglingRef) -> Self {
+        Self {
+            namespace: dangling_ref.namespace,
+            compute_graph: dangling_ref.compute_graph,
+            url: dangling_ref.url,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ComputeGraphReference {
+    pub namespace: String,
+    pub compute_graph: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LiveFile {
+    pub column_family: String,
+    pub name: String,
+    pub size_bytes: u64,
+    pub level: i32,
+}
+
+impl From<state_store::LiveFileInfo> for LiveFile {
+    fn from(file: state_store::LiveFileInfo) -> Self {
+        Self {
+            column_family: file.column_family,
+            name: file.name,
+            size_bytes: file.size_bytes,
+            level: file.level,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LiveFiles {
+    pub files: Vec<LiveFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CfConfig {
+    pub name: String,
+    pub custom: bool,
+    /// One of `none`, `snappy`, `lz4` or `zstd`. Unset when RocksDB picks
+    /// the compression.
+    pub compression: Option<String>,
+    /// Size of the block cache of the column family alone. Unset when it
+    /// uses the default block cache.
+    pub block_cache_size_bytes: Option<usize>,
+    /// `none`, `namespace` or `fixed_length:<bytes>`.
+    pub prefix_extractor: String,
+}
+
+impl From<state_store::CfConfig> for CfConfig {
+    fn from(config: state_store::CfConfig) -> Self {
+        let compression = config.compression.map(|compression| {
+            match compression {
+                state_store::CfCompression::None => "none",
+                state_store::CfCompression::Snappy => "snappy",
+                state_store::CfCompression::Lz4 => "lz4",
+                state_store::CfCompression::Zstd => "zstd",
+            }
+            .to_string()
+        });
+        let prefix_extractor = match config.prefix_extractor {
+            state_store::PrefixExtractor::None => "none".to_string(),
+            state_store::PrefixExtractor::Namespace => "namespace".to_string(),
+            state_store::PrefixExtractor::FixedLength(len) => format!("fixed_length:{}", len),
+        };
+        Self {
+            name: config.name,
+            custom: config.custom,
+            compression,
+            block_cache_size_bytes: config.block_cache_size,
+            prefix_extractor,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CfDiskUsage {
+    pub name: String,
+    /// Bytes of the SST files of the column family.
+    pub disk_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DbStats {
+    pub column_families: Vec<CfDiskUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CfConfigs {
+    pub column_families: Vec<CfConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReindexReport {
+    pub tasks_scanned: usize,
+    pub compute_graphs_scanned: usize,
+    pub namespaces_scanned: usize,
+    pub entries_added: usize,
+    pub entries_removed: usize,
+}
+
+impl From<state_store::ReindexReport> for ReindexReport {
+    fn from(report: state_store::ReindexReport) -> Self {
+        Self {
+            tasks_scanned: report.tasks_scanned,
+            compute_graphs_scanned: report.compute_graphs_scanned,
+            namespaces_scanned: report.namespaces_scanned,
+            entries_added: report.entries_added,
+            entries_removed: report.entries_removed,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ComputeGraphsByCode {
+    pub sha256: String,
+    pub compute_graphs: Vec<ComputeGraphReference>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GraphLabels {
+    /// Values taken by each label key.
+    pub labels: BTreeMap<String, BTreeSet<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MissingCode {
+    /// Names of the compute graphs whose code blob is missing.
+    pub compute_graphs: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CodeUrl {
+    /// Url of the code in blob storage.
+    pub url: String,
+    /// Url granting temporary read access to the code without credentials,
+    /// when requested and supported by the blob storage.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presigned_url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CodeUrlParams {
+    /// Presign the url when the blob storage supports it.
+    pub presign: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SwapCodeParams {
+    /// Delete the previous code blob once the swap is committed.
+    pub delete_previous: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GetCodeParams {
+    /// Hash the code while it is sent and fail the transfer when it doesn't
+    /// match the hash recorded when the graph was created.
+    pub verify: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CodeNamespaces {
+    pub sha256: String,
+    pub namespaces: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BlobIntegrityReport {
+    pub dangling_references: Vec<DanglingBlobReference>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ListBlobsParams {
+    pub prefix: Option<String>,
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BlobInfo {
+    pub key: String,
+    pub url: String,
+    pub size_bytes: u64,
+    pub last_modified: u64,
+}
+
+impl From<blob_store::BlobInfo> for BlobInfo {
+    fn from(blob: blob_store::BlobInfo) -> Self {
+        Self {
+            key: blob.key,
+            url: blob.url,
+            size_bytes: blob.size_bytes,
+            last_modified: blob.last_modified,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BlobList {
+    pub blobs: Vec<BlobInfo>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GcBlobsParams {
+    /// Only report the orphaned blobs without deleting them. Defaults to
+    /// true, deleting requires an explicit `dry_run=false`.
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BlobGcReport {
+    pub dry_run: bool,
+    pub scanned: usize,
+    pub orphans: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LintWarning {
+    pub kind: String,
+    pub nodes: Vec<String>,
+    pub message: String,
+}
+
+impl From<data_model::lint::LintWarning> for LintWarning {
+    fn from(warning: data_model::lint::LintWarning) -> Self {
+        Self {
+            kind: warning.kind().to_string(),
+            nodes: warning.nodes(),
+            message: warning.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ComputeGraphLint {
+    pub warnings: Vec<LintWarning>,
+}
+
+impl From<Vec<data_model::lint::LintWarning>> for ComputeGraphLint {
+    fn from(warnings: Vec<data_model::lint::LintWarning>) -> Self {
+        Self {
+            warnings: warnings.into_iter().map(|w| w.into()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutionPlan {
+    pub stages: Vec<PlanStage>,
+}
+
+impl From<data_model::plan::ExecutionPlan> for ExecutionPlan {
+    fn from(plan: data_model::plan::ExecutionPlan) -> Self {
+        Self {
+            stages: plan.stages.into_iter().map(|s| s.into()).collect(),
+        }
+    }
+}
+
+/// Nodes which can run in parallel once the previous stages are done.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PlanStage {
+    pub nodes: Vec<PlanNode>,
+}
+
+impl From<data_model::plan::PlanStage> for PlanStage {
+    fn from(stage: data_model::plan::PlanStage) -> Self {
+        Self {
+            nodes: stage.nodes.into_iter().map(|n| n.into()).collect(),
         }
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PlanNode {
+    pub name: String,
+    pub reducer: bool,
+    pub next: Vec<String>,
+    /// Set for routers: the functions one of which is picked at runtime.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branches: Option<Vec<String>>,
+}
+
+impl From<data_model::plan::PlanNode> for PlanNode {
+    fn from(node: data_model::plan::PlanNode) -> Self {
+        Self {
+            name: node.name,
+            reducer: node.reducer,
+            next: node.next,
+            branches: node.branches,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NamespaceImport {
+    /// Names of the compute graphs created or updated by the import.
+    pub compute_graphs: Vec<String>,
+}
+
+/// Outcome of a line of a batch import of compute graphs.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchImportResult {
+    /// Line of the request body, starting at 1.
+    pub line: usize,
+    /// Name of the compute graph created or updated from the line.
+    pub compute_graph: Option<String>,
+    pub error: Option<IndexifyAPIError>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct NamespaceList {
     pub namespaces: Vec<Namespace>,
+    pub cursor: Option<Vec<u8>>,
+    /// Advance notice of limits the server is about to reach, such as the
+    /// namespace quota. Warnings don't change the status of the response.
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeleteNamespaceParams {
+    /// Delete the compute graphs of the namespace along with it.
+    pub force: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ListNamespacesParams {
+    /// Only list namespaces whose name starts with this prefix.
+    pub prefix: Option<String>,
+    pub cursor: Option<Vec<u8>>,
+    pub limit: Option<usize>,
+    /// Annotate each namespace with its number of compute graphs.
+    pub graph_counts: Option<bool>,
+    pub order: Option<NamespaceOrder>,
+}
+
+/// Order of a listing of namespaces.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NamespaceOrder {
+    /// By name.
+    Name,
+    /// By creation time, newest first.
+    Created,
 }
 
 #[derive(Clone, Serialize, Deserialize, ToSchema)]
@@ -134,6 +771,8 @@ pub struct ComputeFn {
     pub payload_encoder: String,
     pub image_name: String,
     pub image_information: ImageInformation,
+    #[serde(default)]
+    pub terminal: bool,
 }
 
 impl From<ComputeFn> for data_model::ComputeFn {
@@ -147,6 +786,7 @@ impl From<ComputeFn> for data_model::ComputeFn {
             payload_encoder: val.payload_encoder.clone(),
             image_name: val.image_name.clone(),
             image_information: val.image_information.into(),
+            terminal: val.terminal,
         }
     }
 }
@@ -161,6 +801,7 @@ impl From<data_model::ComputeFn> for ComputeFn {
             payload_encoder: c.payload_encoder,
             image_name: c.image_name,
             image_information: c.image_information.into(),
+            terminal: c.terminal,
         }
     }
 }
@@ -273,9 +914,62 @@ pub struct ComputeGraph {
     pub edges: HashMap<String, Vec<String>>,
     #[serde(default = "get_epoch_time_in_ms")]
     pub created_at: u64,
+    /// Time the graph was last created or updated, ignored when creating a
+    /// graph.
+    #[serde(default)]
+    pub updated_at: u64,
     pub runtime_information: RuntimeInformation,
+    /// Whether the graph accepts invocations. Changed through the
+    /// enable/disable endpoints, ignored when creating a graph.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Whether the graph can't be updated or deleted. Changed through the
+    /// freeze/unfreeze endpoints, ignored when creating a graph.
+    #[serde(default)]
+    pub frozen: bool,
+    /// Name the graph was created with when the server normalizes names,
+    /// ignored when creating a graph.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Who created the graph, from the authenticated identity of the
+    /// creation. Requests aren't authenticated yet, so graphs created through
+    /// the API have none. Ignored when creating a graph.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    /// JSON schema the inputs of the graph must match. Graphs with an input
+    /// schema are only invoked with objects, `invoke_file` rejects them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_schema: Option<serde_json::Value>,
+    /// JSON schema the JSON encoded outputs of the functions without
+    /// successors must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<serde_json::Value>,
+    /// Free form key value pairs used to find graphs.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Number of nodes on the longest path from the start node. Only
+    /// returned when requested with `include=depth`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<usize>,
+}
+
+fn default_enabled() -> bool {
+    true
 }
 
+/// Routes under `/namespaces/{namespace}/compute_graphs/` which aren't
+/// compute graphs. Graphs can't take these names, whatever their case, as
+/// the routes would shadow them.
+pub const RESERVED_COMPUTE_GRAPH_NAMES: &[&str] = &[
+    "histogram",
+    "unused",
+    "missing_code",
+    "by_node",
+    "recent",
+    "batch_import",
+    "lint",
+];
+
 impl ComputeGraph {
     pub fn into_data_model(
         self,
@@ -283,12 +977,20 @@ impl ComputeGraph {
         sha256_hash: &str,
         size: u64,
     ) -> Result<data_model::ComputeGraph, IndexifyAPIError> {
+        if RESERVED_COMPUTE_GRAPH_NAMES
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(&self.name))
+        {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "compute graph name {} is reserved",
+                self.name
+            )));
+        }
         let mut nodes = HashMap::new();
         for (name, node) in self.nodes {
             nodes.insert(name, node.into());
         }
         let start_fn: data_model::Node = self.start_node.into();
-
         let compute_graph = data_model::ComputeGraph {
             name: self.name,
             namespace: self.namespace,
@@ -303,8 +1005,20 @@ impl ComputeGraph {
             nodes,
             edges: self.edges.clone(),
             created_at: 0,
+            updated_at: 0,
             runtime_information: self.runtime_information.into(),
+            enabled: true,
+            frozen: false,
+            display_name: None,
+            actor: None,
+            input_schema: self.input_schema,
+            output_schema: self.output_schema,
+            labels: self.labels,
+            schema_version: data_model::schema::COMPUTE_GRAPH_SCHEMA_VERSION,
         };
+        compute_graph
+            .check_schemas()
+            .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
         Ok(compute_graph)
     }
 }
@@ -327,14 +1041,230 @@ impl From<data_model::ComputeGraph> for ComputeGraph {
             nodes,
             edges: compute_graph.edges,
             created_at: compute_graph.created_at,
+            updated_at: compute_graph.updated_at,
             runtime_information: compute_graph.runtime_information.into(),
+            enabled: compute_graph.enabled,
+            frozen: compute_graph.frozen,
+            display_name: compute_graph.display_name,
+            actor: compute_graph.actor,
+            input_schema: compute_graph.input_schema,
+            output_schema: compute_graph.output_schema,
+            labels: compute_graph.labels,
+            depth: None,
         }
     }
 }
 
+/// Fields replaced in a node of an instantiated compute graph, the others are
+/// kept from the template.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct NodeOverride {
+    pub description: Option<String>,
+    pub image_name: Option<String>,
+    pub payload_encoder: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InstantiateComputeGraph {
+    /// Name of the created compute graph.
+    pub name: String,
+    pub description: Option<String>,
+    /// Overrides by node name.
+    #[serde(default)]
+    pub node_overrides: HashMap<String, NodeOverride>,
+    /// Copy the code of the template to a new blob instead of sharing it.
+    #[serde(default)]
+    pub copy_code: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CreateNamespace {
     pub name: String,
+    /// Per namespace toggles of server behaviors, such as
+    /// `strict_validation` to reject compute graphs with lint warnings.
+    #[serde(default)]
+    pub feature_flags: BTreeMap<String, bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ListComputeGraphsParams {
+    pub limit: Option<usize>,
+    pub cursor: Option<Vec<u8>>,
+    pub enabled: Option<bool>,
+    /// Only list the graphs created by this actor.
+    pub created_by: Option<String>,
+    /// Order the graphs by this key instead of by name. Sorted listings
+    /// aren't paginated.
+    pub sort: Option<ComputeGraphSort>,
+    /// Direction of a sorted listing, defaults to descending.
+    pub order: Option<SortOrder>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ComputeGraphSort {
+    CodeSize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl From<SortOrder> for state_store::scanner::SortOrder {
+    fn from(order: SortOrder) -> Self {
+        match order {
+            SortOrder::Asc => state_store::scanner::SortOrder::Ascending,
+            SortOrder::Desc => state_store::scanner::SortOrder::Descending,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HistogramBucket {
+    Day,
+    Week,
+}
+
+impl From<HistogramBucket> for state_store::scanner::Bucket {
+    fn from(bucket: HistogramBucket) -> Self {
+        match bucket {
+            HistogramBucket::Day => state_store::scanner::Bucket::Day,
+            HistogramBucket::Week => state_store::scanner::Bucket::Week,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct GetComputeGraphParams {
+    /// Comma separated computed fields to add to the compute graph. Only
+    /// `depth` is supported.
+    pub include: Option<String>,
+    /// Return the compute graph as it is stored by the server instead of
+    /// its API representation.
+    pub raw: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RecentComputeGraphsParams {
+    /// Largest number of compute graphs listed.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreationHistogramParams {
+    pub bucket: Option<HistogramBucket>,
+    /// Start of the range in milliseconds since the epoch, inclusive.
+    pub start: Option<u64>,
+    /// End of the range in milliseconds since the epoch, exclusive. Defaults
+    /// to now.
+    pub end: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HistogramBucketCount {
+    /// Start of the bucket in milliseconds since the epoch.
+    pub start: u64,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreationHistogram {
+    pub bucket: HistogramBucket,
+    /// Non-empty buckets, in time order.
+    pub buckets: Vec<HistogramBucketCount>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ListFailuresParams {
+    /// Only return failures recorded at or after this time, in milliseconds
+    /// since the epoch. Defaults to the last hour.
+    pub since: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FailedOutput {
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub invocation_id: String,
+    pub task_id: String,
+    pub failed_at: u64,
+    /// Path of the error payload returned by the function.
+    pub errors_path: Option<String>,
+    pub exception_path: Option<String>,
+    pub stderr_path: Option<String>,
+}
+
+impl From<data_model::FailedOutput> for FailedOutput {
+    fn from(failed_output: data_model::FailedOutput) -> Self {
+        let diagnostics = failed_output.diagnostics;
+        Self {
+            compute_graph: failed_output.compute_graph_name,
+            compute_fn: failed_output.compute_fn_name,
+            invocation_id: failed_output.invocation_id,
+            task_id: failed_output.task_id,
+            failed_at: failed_output.failed_at,
+            errors_path: failed_output.errors.map(|errors| errors.path),
+            exception_path: diagnostics
+                .as_ref()
+                .and_then(|d| d.exception.as_ref())
+                .map(|p| p.path.clone()),
+            stderr_path: diagnostics
+                .as_ref()
+                .and_then(|d| d.stderr.as_ref())
+                .map(|p| p.path.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FailedOutputs {
+    pub failures: Vec<FailedOutput>,
+}
+
+/// An edge of a compute graph, from a node to a node that can run after it.
+#[derive(Debug, Serialize, Deserialize, ToSchema, PartialEq)]
+pub struct ComputeGraphEdge {
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ComputeGraphEdges {
+    pub edges: Vec<ComputeGraphEdge>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ComputeGraphStats {
+    pub nodes: usize,
+    /// Edges between nodes, router targets included.
+    pub edges: usize,
+    /// Largest number of nodes that can run after a single node.
+    pub max_fan_out: usize,
+    pub router_count: usize,
+}
+
+impl From<data_model::stats::GraphStats> for ComputeGraphStats {
+    fn from(stats: data_model::stats::GraphStats) -> Self {
+        Self {
+            nodes: stats.nodes,
+            edges: stats.edges,
+            max_fan_out: stats.max_fan_out,
+            router_count: stats.router_count,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ComputeGraphCreated {
+    pub compute_graph: ComputeGraph,
+    /// False when an idempotent create left the stored graph as it was,
+    /// because its content was identical.
+    pub created: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -343,11 +1273,58 @@ pub struct ComputeGraphsList {
     pub cursor: Option<Vec<u8>>,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum InvocationStatus {
+    Unknown,
+    Pending,
+    Processing,
+    Completed,
+    Failed,
+}
+
+impl From<data_model::InvocationStatus> for InvocationStatus {
+    fn from(status: data_model::InvocationStatus) -> Self {
+        match status {
+            data_model::InvocationStatus::Unknown => InvocationStatus::Unknown,
+            data_model::InvocationStatus::Pending => InvocationStatus::Pending,
+            data_model::InvocationStatus::Processing => InvocationStatus::Processing,
+            data_model::InvocationStatus::Completed => InvocationStatus::Completed,
+            data_model::InvocationStatus::Failed => InvocationStatus::Failed,
+        }
+    }
+}
+
+impl From<InvocationStatus> for data_model::InvocationStatus {
+    fn from(status: InvocationStatus) -> Self {
+        match status {
+            InvocationStatus::Unknown => data_model::InvocationStatus::Unknown,
+            InvocationStatus::Pending => data_model::InvocationStatus::Pending,
+            InvocationStatus::Processing => data_model::InvocationStatus::Processing,
+            InvocationStatus::Completed => data_model::InvocationStatus::Completed,
+            InvocationStatus::Failed => data_model::InvocationStatus::Failed,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct DataObject {
     pub id: String,
     pub payload_size: u64,
     pub payload_sha_256: String,
+    pub status: InvocationStatus,
+    pub attributes: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ListInvocationsParams {
+    pub limit: Option<usize>,
+    pub cursor: Option<Vec<u8>>,
+    pub status: Option<InvocationStatus>,
+    /// Only list the inputs whose attribute `attribute_key` has the value
+    /// `attribute_value`. The key must be indexed.
+    pub attribute_key: Option<String>,
+    pub attribute_value: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -372,6 +1349,10 @@ pub struct CreateNamespaceResponse {
 pub struct GraphInvocations {
     pub invocations: Vec<DataObject>,
     pub cursor: Option<Vec<u8>>,
+    /// Number of invocations of the graph in each status, only returned
+    /// with the first page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_counts: Option<HashMap<InvocationStatus, usize>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -477,12 +1458,58 @@ impl From<data_model::NodeOutput> for FnOutput {
     }
 }
 
+/// Provenance of an output, from the input of its invocation to the output.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OutputLineage {
+    pub invocation_id: String,
+    /// The output of the start function first, the requested output last.
+    pub outputs: Vec<FnOutput>,
+    /// False when the chain stops before reaching the start function, as
+    /// the parents of outputs written by older servers aren't recorded.
+    pub complete: bool,
+}
+
+impl From<state_store::scanner::Lineage> for OutputLineage {
+    fn from(lineage: state_store::scanner::Lineage) -> Self {
+        Self {
+            invocation_id: lineage.input.id,
+            outputs: lineage.outputs.into_iter().map(Into::into).collect(),
+            complete: lineage.complete,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct FnOutputs {
     pub outputs: Vec<FnOutput>,
     pub cursor: Option<Vec<u8>>,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NotifyOnChangeParams {
+    /// Stream every event of this invocation, including the outputs of its
+    /// functions as they are written, until it finishes.
+    pub invocation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AttachOutputParams {
+    /// Function the output is attributed to.
+    pub compute_fn: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LatestOutputsRequest {
+    pub input_ids: Vec<String>,
+}
+
+/// Latest output of each requested input, in request order. Inputs without
+/// outputs are null.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LatestOutputs {
+    pub outputs: Vec<Option<FnOutput>>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct InvocationId {
     pub id: String,
@@ -510,11 +1537,24 @@ impl From<data_model::ExecutorMetadata> for ExecutorMetadata {
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct InvocationQueryParams {
     pub block_until_finish: Option<bool>,
+    /// Attributes of the input, as a JSON object of strings.
+    pub attributes: Option<String>,
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::http_objects::{ComputeFn, DynamicRouter};
+    use crate::http_objects::{ComputeFn, DynamicRouter, IndexifyAPIError};
+
+    #[test]
+    fn test_json_error_has_position() {
+        let err = serde_json::from_str::<serde_json::Value>("{\"name\": \n  tru}").unwrap_err();
+        let api_error: IndexifyAPIError = err.into();
+        assert_eq!(api_error.status_code, axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(
+            api_error.message,
+            "invalid json at line 2 column 6: expected ident"
+        );
+    }
 
     #[test]
     fn test_compute_graph_deserialization() {
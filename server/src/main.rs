@@ -5,21 +5,28 @@ use service::Service;
 use tracing::error;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
+mod archive;
 mod config;
 mod executors;
+mod flusher;
 mod gc;
 mod http_objects;
+mod retention;
 mod routes;
 mod scheduler;
 mod server;
 mod service;
 mod system_tasks;
+mod webhooks;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
     #[arg(short, long, value_name = "config file")]
     config: Option<PathBuf>,
+
+    #[arg(short, long, value_name = "address to listen on, e.g. 0.0.0.0:8900")]
+    listen_addr: Option<String>,
 }
 
 #[tokio::main]
@@ -32,11 +39,26 @@ async fn main() {
 
     let cli = Cli::parse();
     let config = match cli.config {
-        Some(path) => config::ServerConfig::from_path(path.to_str().unwrap()).unwrap(),
-        None => config::ServerConfig::default(),
+        Some(path) => config::ServerConfig::from_path(path.to_str().unwrap()),
+        None => config::ServerConfig::from_env(),
+    };
+    let mut config = match config {
+        Ok(config) => config,
+        Err(err) => {
+            error!("invalid config: {}", err);
+            std::process::exit(1);
+        }
     };
+    if let Some(listen_addr) = cli.listen_addr {
+        config.listen_addr = listen_addr;
+        if let Err(err) = config.validate() {
+            error!("invalid config: {}", err);
+            std::process::exit(1);
+        }
+    }
     let service = Service::new(config);
     if let Err(err) = service.start().await {
         error!("Error starting service: {}", err);
+        std::process::exit(1);
     }
 }
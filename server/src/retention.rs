@@ -0,0 +1,206 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::{
+    requests::{DeleteInvocationRequest, RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info};
+
+/// Number of expired inputs read from the state store at once.
+const SWEEP_BATCH_SIZE: usize = 100;
+
+/// Periodically deletes the inputs of every namespace which are older than
+/// the input ttl of its retention policy.
+pub struct RetentionSweeper {
+    state: Arc<IndexifyState>,
+    interval: Duration,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+}
+
+impl RetentionSweeper {
+    pub fn new(
+        state: Arc<IndexifyState>,
+        interval: Duration,
+        shutdown_rx: tokio::sync::watch::Receiver<()>,
+    ) -> Self {
+        Self {
+            state,
+            interval,
+            shutdown_rx,
+        }
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => match self.sweep().await {
+                    Ok(0) => {}
+                    Ok(deleted) => info!("deleted {} inputs past their retention", deleted),
+                    Err(e) => error!("failed to apply retention policies: {:?}", e),
+                },
+                _ = self.shutdown_rx.changed() => return Ok(()),
+            }
+        }
+    }
+
+    /// Deletes the expired inputs of every namespace with an input ttl and
+    /// returns the number of inputs deleted.
+    pub async fn sweep(&self) -> Result<usize> {
+        self.sweep_in_batches(SWEEP_BATCH_SIZE).await
+    }
+
+    async fn sweep_in_batches(&self, batch_size: usize) -> Result<usize> {
+        let now = get_epoch_time_in_ms();
+        let mut deleted = 0;
+        for namespace in self.state.reader().get_all_namespaces()? {
+            let Some(input_ttl_secs) = namespace.retention.input_ttl_secs else {
+                continue;
+            };
+            let created_before = now.saturating_sub(input_ttl_secs.saturating_mul(1000));
+            let mut cursor: Option<Vec<u8>> = None;
+            loop {
+                // The cursor is the key of the first expired input after the
+                // batch, so the inputs kept by the scan aren't read again.
+                let (inputs, next_cursor) = self.state.reader().inputs_created_before(
+                    &namespace.name,
+                    created_before,
+                    cursor.as_deref(),
+                    Some(batch_size),
+                )?;
+                for input in inputs {
+                    self.state
+                        .write(StateMachineUpdateRequest {
+                            payload: RequestPayload::DeleteInvocation(DeleteInvocationRequest {
+                                namespace: input.namespace,
+                                compute_graph: input.compute_graph_name,
+                                invocation_id: input.id,
+                            }),
+                            state_changes_processed: vec![],
+                        })
+                        .await?;
+                    deleted += 1;
+                }
+                match next_cursor {
+                    Some(next_cursor) => cursor = Some(next_cursor),
+                    None => break,
+                }
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        test_objects::tests::mock_graph_a,
+        DataPayload,
+        InvocationPayloadBuilder,
+        RetentionPolicy,
+    };
+    use state_store::requests::{
+        CreateComputeGraphRequest,
+        InvokeComputeGraphRequest,
+        NamespaceRequest,
+        SetNamespacePolicyRequest,
+    };
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_input_ttl() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let now = get_epoch_time_in_ms();
+        for namespace in ["short_lived", "kept"] {
+            state.upsert_namespace(&NamespaceRequest {
+                name: namespace.to_string(),
+                feature_flags: Default::default(),
+            })?;
+            let mut compute_graph = mock_graph_a();
+            compute_graph.namespace = namespace.to_string();
+            state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: namespace.to_string(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+            for (path, created_at) in [
+                ("old_1", now - 3_600_000),
+                ("new_1", now),
+                ("old_2", now - 3_600_000),
+                ("new_2", now),
+                ("old_3", now - 3_600_000),
+            ] {
+                let invocation_payload = InvocationPayloadBuilder::default()
+                    .namespace(namespace.to_string())
+                    .compute_graph_name("graph_A".to_string())
+                    .payload(DataPayload {
+                        path: path.to_string(),
+                        size: 1,
+                        sha256_hash: path.to_string(),
+                    })
+                    .created_at(created_at)
+                    .build()?;
+                state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                            namespace: namespace.to_string(),
+                            compute_graph_name: "graph_A".to_string(),
+                            invocation_payload,
+                        }),
+                        state_changes_processed: vec![],
+                    })
+                    .await?;
+            }
+        }
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SetNamespacePolicy(SetNamespacePolicyRequest {
+                    namespace: "short_lived".to_string(),
+                    retention: RetentionPolicy {
+                        input_ttl_secs: Some(60),
+                    },
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(());
+        let sweeper = RetentionSweeper::new(state.clone(), Duration::from_secs(60), shutdown_rx);
+        // Batches of one input make the sweep resume after the inputs it
+        // kept and deleted.
+        assert_eq!(sweeper.sweep_in_batches(1).await?, 3);
+        assert_eq!(sweeper.sweep().await?, 0);
+        drop(shutdown_tx);
+
+        let paths = |namespace: &str| -> Result<Vec<String>> {
+            let (inputs, _) = state
+                .reader()
+                .list_invocations(namespace, "graph_A", None, None)?;
+            let mut paths: Vec<_> = inputs.into_iter().map(|input| input.payload.path).collect();
+            paths.sort();
+            Ok(paths)
+        };
+        assert_eq!(paths("short_lived")?, vec!["new_1", "new_2"]);
+        assert_eq!(
+            paths("kept")?,
+            vec!["new_1", "new_2", "old_1", "old_2", "old_3"]
+        );
+
+        Ok(())
+    }
+}
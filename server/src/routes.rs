@@ -4,73 +4,216 @@ use anyhow::Result;
 use axum::{
     body::Body,
     extract::{DefaultBodyLimit, MatchedPath, Multipart, Path, Query, Request, State},
-    http::{Method, Response},
+    handler::Handler,
+    http::{header, HeaderMap, Method, Response, StatusCode},
     response::{sse::Event, IntoResponse},
-    routing::{delete, get, post},
+    routing::{on, MethodFilter, MethodRouter},
     Json,
     Router,
 };
 use blob_store::PutResult;
-use data_model::ExecutorId;
+use data_model::{ExecutorId, InvocationPayload, STRICT_VALIDATION_FLAG};
 use futures::StreamExt;
 use indexify_ui::Assets as UiAssets;
 use indexify_utils::GuardStreamExt;
-use nanoid::nanoid;
 use state_store::{
+    invocation_events::InvocationStateChangeEvent,
     requests::{
         CreateComputeGraphRequest,
         DeleteComputeGraphRequest,
         DeleteInvocationRequest,
+        DeleteNamespaceRequest,
         NamespaceRequest,
         RequestPayload,
         StateMachineUpdateRequest,
+        SwapComputeGraphCodeRequest,
+    },
+    state_machine::{
+        compute_graph_etag,
+        ComputeGraphEtagMismatch,
+        ComputeGraphFrozen,
+        ComputeGraphNameCollision,
+        ComputeGraphUnchanged,
+        NamespaceLimitReached,
+        NamespaceNotEmpty,
+        SchemaVersionMismatch,
     },
     IndexifyState,
 };
+use tokio::sync::{broadcast::error::RecvError, OwnedSemaphorePermit, Semaphore};
 use tower_http::{
     cors::{Any, CorsLayer},
+    normalize_path::NormalizePath,
     trace::TraceLayer,
 };
-use tracing::info;
+use tracing::{error, info};
 use utoipa::{OpenApi, ToSchema};
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::executors::{self, EXECUTOR_TIMEOUT};
+use crate::{
+    config::{Connections, ServerConfig},
+    executors::{self, EXECUTOR_TIMEOUT},
+};
 
+mod admin;
+mod batch_import;
+mod code;
+mod code_upload;
+mod cursor;
+mod default_namespace;
 mod download;
+mod graph_analysis;
+mod graph_queries;
+mod graph_state;
+mod instantiate;
 mod internal_ingest;
 mod invoke;
 mod logs;
+mod namespace_archive;
+mod namespace_reports;
+mod namespace_settings;
+mod outputs;
+mod path_params;
+mod pretty_json;
+mod slow_requests;
+use admin::{
+    cf_configs,
+    db_stats,
+    diff_namespaces,
+    gc_blobs,
+    global_stats,
+    graphs_by_code,
+    list_blobs,
+    live_files,
+    namespaces_by_code,
+    reindex,
+    verify_blob_integrity,
+};
+use batch_import::batch_import_compute_graphs;
+use code::{code_metadata, code_url};
+use code_upload::{put_code_field, read_compute_graph_field};
+use cursor::{decode_cursor, encode_cursor};
+use default_namespace::{create_default_compute_graph, list_default_compute_graphs};
 use download::{
     download_fn_output_by_key,
     download_fn_output_payload,
     download_invocation_payload,
 };
+use graph_analysis::{
+    compute_graph_edges,
+    compute_graph_plan,
+    compute_graph_stats,
+    lint_compute_graph,
+    lint_compute_graph_definition,
+};
+use graph_queries::{
+    compute_graph_creation_histogram,
+    compute_graphs_by_node,
+    compute_graphs_with_missing_code,
+    graph_labels,
+    recent_compute_graphs,
+    unused_compute_graphs,
+};
+use graph_state::{
+    disable_compute_graph,
+    enable_compute_graph,
+    freeze_compute_graph,
+    unfreeze_compute_graph,
+};
+use instantiate::instantiate_compute_graph;
 use internal_ingest::ingest_files_from_executor;
-use invoke::{invoke_with_file, invoke_with_object, rerun_compute_graph};
+use invoke::{invoke_with_file, invoke_with_object, rerun_compute_graph, validate_input};
 use logs::download_task_logs;
+use namespace_archive::{export_namespace, import_namespace};
+use namespace_reports::{list_failures, namespace_digest, namespace_usage};
+use namespace_settings::{check_feature_flags, set_namespace_flags, set_namespace_policy};
+use outputs::{attach_output, latest_outputs, output_lineage};
+use path_params::{normalize_compute_graph_name, reject_empty_path_params};
+use pretty_json::pretty_json;
+use slow_requests::log_slow_requests;
 
 use crate::{
     executors::ExecutorManager,
     http_objects::{
+        AttachOutputParams,
+        BatchImportResult,
+        BlobGcReport,
+        BlobInfo,
+        BlobIntegrityReport,
+        BlobList,
+        CfConfig,
+        CfConfigs,
+        CfDiskUsage,
+        CodeNamespaces,
+        CodeUrl,
         ComputeFn,
         ComputeGraph,
+        ComputeGraphCreated,
+        ComputeGraphEdge,
+        ComputeGraphEdges,
+        ComputeGraphLint,
+        ComputeGraphReference,
+        ComputeGraphSort,
+        ComputeGraphStats,
+        ComputeGraphsByCode,
         ComputeGraphsList,
         CreateNamespace,
+        CreationHistogram,
+        DanglingBlobReference,
         DataObject,
+        DbStats,
+        DeleteNamespaceParams,
         DynamicRouter,
+        ErrorCode,
+        ExecutionPlan,
         ExecutorMetadata,
+        FailedOutput,
+        FailedOutputs,
         FnOutputs,
+        GetCodeParams,
+        GetComputeGraphParams,
+        GlobalStats,
         GraphInvocations,
+        GraphLabels,
         GraphVersion,
+        HistogramBucket,
+        HistogramBucketCount,
         ImageInformation,
         IndexifyAPIError,
+        InstantiateComputeGraph,
         InvocationResult,
+        InvocationStatus,
+        LatestOutputs,
+        LatestOutputsRequest,
+        LintWarning,
+        ListComputeGraphsParams,
+        ListInvocationsParams,
+        ListNamespacesParams,
         ListParams,
+        LiveFile,
+        LiveFiles,
+        MissingCode,
         Namespace,
+        NamespaceBlobBytes,
+        NamespaceDiff,
+        NamespaceDigest,
+        NamespaceFeatureFlags,
+        NamespaceImport,
         NamespaceList,
+        NamespaceOrder,
+        NamespacePolicy,
+        NamespaceUsage,
         Node,
+        NodeOverride,
+        NotifyOnChangeParams,
+        OutputLineage,
+        PlanNode,
+        PlanStage,
+        ReindexReport,
+        RetentionPolicy,
         RuntimeInformation,
+        SortOrder,
+        SwapCodeParams,
         Task,
         TaskOutcome,
         Tasks,
@@ -81,32 +224,106 @@ use crate::{
 #[openapi(
         paths(
             create_namespace,
+            upsert_namespace,
+            delete_namespace,
+            namespace_settings::set_namespace_flags,
+            namespace_settings::set_namespace_policy,
             namespaces,
+            namespace_archive::export_namespace,
+            namespace_archive::import_namespace,
+            batch_import::batch_import_compute_graphs,
+            namespace_reports::namespace_usage,
+            namespace_reports::namespace_digest,
+            admin::diff_namespaces,
+            namespace_reports::list_failures,
             invoke::invoke_with_object,
+            invoke::invoke_with_file,
+            invoke::rerun_compute_graph,
+            invoke::validate_input,
             graph_invocations,
+            notify_on_change,
             create_compute_graph,
+            default_namespace::create_default_compute_graph,
+            default_namespace::list_default_compute_graphs,
+            update_compute_graph,
+            swap_compute_graph_code,
             list_compute_graphs,
             get_compute_graph,
+            graph_analysis::lint_compute_graph,
+            graph_analysis::lint_compute_graph_definition,
+            instantiate::instantiate_compute_graph,
+            graph_queries::compute_graph_creation_histogram,
+            graph_queries::unused_compute_graphs,
+            graph_queries::compute_graphs_by_node,
+            graph_queries::graph_labels,
+            graph_queries::compute_graphs_with_missing_code,
+            graph_queries::recent_compute_graphs,
+            graph_analysis::compute_graph_plan,
+            graph_analysis::compute_graph_edges,
+            graph_analysis::compute_graph_stats,
+            code::code_metadata,
+            code::code_url,
             delete_compute_graph,
+            graph_state::enable_compute_graph,
+            graph_state::disable_compute_graph,
+            graph_state::freeze_compute_graph,
+            graph_state::unfreeze_compute_graph,
             list_tasks,
             list_outputs,
+            outputs::attach_output,
+            outputs::latest_outputs,
+            outputs::output_lineage,
             delete_invocation,
+            get_context,
             logs::download_task_logs,
             list_executors,
             download::download_fn_output_payload,
+            download::download_invocation_payload,
+            admin::verify_blob_integrity,
+            admin::graphs_by_code,
+            admin::namespaces_by_code,
+            admin::global_stats,
+            admin::live_files,
+            admin::db_stats,
+            admin::cf_configs,
+            admin::reindex,
+            admin::list_blobs,
+            admin::gc_blobs,
+            readyz,
         ),
         components(
             schemas(
                 CreateNamespace,
+                NamespaceFeatureFlags,
+                NamespacePolicy,
+                RetentionPolicy,
                 NamespaceList,
+                NamespaceOrder,
+                NamespaceImport,
+                BatchImportResult,
+                NamespaceUsage,
+                GlobalStats,
+                NamespaceDigest,
+                NamespaceDiff,
+                NamespaceBlobBytes,
                 IndexifyAPIError,
+                ErrorCode,
                 Namespace,
                 ComputeGraph,
                 Node,
                 DynamicRouter,
                 ComputeFn,
                 ComputeGraphCreateType,
+                ComputeGraphCodeType,
+                ComputeGraphCreated,
+                InstantiateComputeGraph,
+                NodeOverride,
                 ComputeGraphsList,
+                CreationHistogram,
+                HistogramBucket,
+                HistogramBucketCount,
+                ComputeGraphSort,
+                SortOrder,
                 ImageInformation,
                 InvocationResult,
                 ExecutorMetadata,
@@ -117,6 +334,39 @@ use crate::{
                 GraphInvocations,
                 GraphVersion,
                 DataObject,
+                InvocationStatus,
+                BlobIntegrityReport,
+                DanglingBlobReference,
+                ComputeGraphsByCode,
+                MissingCode,
+                GraphLabels,
+                CodeNamespaces,
+                CodeUrl,
+                ComputeGraphReference,
+                LiveFiles,
+                LiveFile,
+                CfConfigs,
+                CfConfig,
+                DbStats,
+                CfDiskUsage,
+                ReindexReport,
+                BlobInfo,
+                BlobList,
+                BlobGcReport,
+                ComputeGraphLint,
+                ExecutionPlan,
+                PlanStage,
+                PlanNode,
+                ComputeGraphEdges,
+                ComputeGraphEdge,
+                ComputeGraphStats,
+                LintWarning,
+                FailedOutput,
+                FailedOutputs,
+                LatestOutputsRequest,
+                LatestOutputs,
+                OutputLineage,
+                AttachOutputParams,
             )
         ),
         tags(
@@ -131,105 +381,439 @@ pub struct RouteState {
     pub indexify_state: Arc<IndexifyState>,
     pub blob_storage: Arc<blob_store::BlobStorage>,
     pub executor_manager: Arc<ExecutorManager>,
+    pub config: Arc<ServerConfig>,
+    pub upload_limiter: ConcurrencyLimiter,
+    pub route_limiters: RouteLimiters,
+    /// Requests taking longer than this are logged, none when unset.
+    pub slow_request_threshold: Option<Duration>,
 }
 
-pub fn create_routes(route_state: RouteState) -> Router {
-    let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::DELETE])
-        .allow_origin(Any)
-        .allow_headers(Any);
+impl RouteState {
+    /// Opens the state store and the blob storage described by the config.
+    pub async fn from_config(config: ServerConfig) -> anyhow::Result<Self> {
+        let connections = config.validate_and_connect().await?;
+        Ok(Self::from_connections(config, connections).await)
+    }
 
-    Router::new()
-        .merge(SwaggerUi::new("/docs/swagger").url("/docs/openapi.json", ApiDoc::openapi()))
-        .route("/", get(index))
+    pub async fn from_connections(config: ServerConfig, connections: Connections) -> Self {
+        let Connections {
+            indexify_state,
+            blob_storage,
+        } = connections;
+        let executor_manager = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        Self {
+            indexify_state,
+            blob_storage,
+            executor_manager,
+            upload_limiter: ConcurrencyLimiter::uploads(&config),
+            route_limiters: RouteLimiters::from_config(&config),
+            slow_request_threshold: config.slow_request_threshold_ms.map(Duration::from_millis),
+            config: Arc::new(config),
+        }
+    }
+}
+
+/// Bounds the number of operations of a kind, such as the request bodies
+/// streamed to blob storage, running at once.
+#[derive(Clone)]
+pub struct ConcurrencyLimiter {
+    permits: Arc<Semaphore>,
+    timeout: Duration,
+    /// Name of the limited operations in the message of rejections.
+    operations: &'static str,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(operations: &'static str, max_concurrent: usize, timeout: Duration) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            timeout,
+            operations,
+        }
+    }
+
+    pub fn uploads(config: &ServerConfig) -> Self {
+        Self::new(
+            "uploads",
+            config.max_concurrent_uploads,
+            Duration::from_secs(config.upload_wait_timeout_secs),
+        )
+    }
+
+    /// Waits for a free slot, held until the permit is dropped. Fails with
+    /// 503 when none frees up within the timeout.
+    pub async fn acquire(&self) -> Result<OwnedSemaphorePermit, IndexifyAPIError> {
+        match tokio::time::timeout(self.timeout, self.permits.clone().acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(permit),
+            Ok(Err(err)) => Err(IndexifyAPIError::internal_error(anyhow::anyhow!(err))),
+            Err(_) => Err(IndexifyAPIError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                &format!("too many concurrent {}, retry later", self.operations),
+            )),
+        }
+    }
+}
+
+/// Limiters of the expensive routes, each route has its own.
+#[derive(Clone)]
+pub struct RouteLimiters {
+    pub export: ConcurrencyLimiter,
+    pub import: ConcurrencyLimiter,
+    pub reindex: ConcurrencyLimiter,
+}
+
+impl RouteLimiters {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let limits = &config.route_concurrency_limits;
+        let timeout = Duration::from_secs(limits.wait_timeout_secs);
+        Self {
+            export: ConcurrencyLimiter::new("exports", limits.export, timeout),
+            import: ConcurrencyLimiter::new("imports", limits.import, timeout),
+            reindex: ConcurrencyLimiter::new("reindexes", limits.reindex, timeout),
+        }
+    }
+}
+
+/// Serves a request once its route's limiter has a free slot. The slot is
+/// held until the response body is sent, which is when a streamed export
+/// finishes.
+async fn limit_concurrency(
+    State(limiter): State<ConcurrencyLimiter>,
+    request: Request,
+    next: axum::middleware::Next,
+) -> Result<axum::response::Response, IndexifyAPIError> {
+    let permit = limiter.acquire().await?;
+    let response = next.run(request).await;
+    Ok(response.map(|body| Body::from_stream(body.into_data_stream().guard(move || drop(permit)))))
+}
+
+/// Routes of the server along with the method and path of each. The router
+/// is built from the table so the API docs can be checked against the same
+/// routes it serves.
+struct RouteTable {
+    router: Router<RouteState>,
+    routes: Vec<(Method, &'static str)>,
+}
+
+impl RouteTable {
+    fn new() -> Self {
+        Self {
+            router: Router::new(),
+            routes: Vec::new(),
+        }
+    }
+
+    /// Serves the `method` requests to `path` with `handler`.
+    fn route<H, T>(self, method: Method, path: &'static str, handler: H) -> Self
+    where
+        H: Handler<T, RouteState>,
+        T: 'static,
+    {
+        let filter = Self::method_filter(&method);
+        self.push(method, path, on(filter, handler))
+    }
+
+    /// Serves the `method` requests to `path` with `handler` once `limiter`
+    /// has a free slot.
+    fn route_limited<H, T>(
+        self,
+        method: Method,
+        path: &'static str,
+        handler: H,
+        limiter: &ConcurrencyLimiter,
+    ) -> Self
+    where
+        H: Handler<T, RouteState>,
+        T: 'static,
+    {
+        let filter = Self::method_filter(&method);
+        let method_router = on(filter, handler).route_layer(axum::middleware::from_fn_with_state(
+            limiter.clone(),
+            limit_concurrency,
+        ));
+        self.push(method, path, method_router)
+    }
+
+    fn method_filter(method: &Method) -> MethodFilter {
+        MethodFilter::try_from(method.clone()).expect("unsupported route method")
+    }
+
+    fn push(
+        mut self,
+        method: Method,
+        path: &'static str,
+        method_router: MethodRouter<RouteState>,
+    ) -> Self {
+        self.router = self.router.route(path, method_router);
+        self.routes.push((method, path));
+        self
+    }
+}
+
+/// Routes served by `create_routes`, before the layers shared by all of them.
+fn route_table(route_state: &RouteState) -> RouteTable {
+    let limiters = &route_state.route_limiters;
+    RouteTable::new()
+        .route(Method::GET, "/", index)
+        .route(Method::GET, "/readyz", readyz)
+        .route(Method::GET, "/namespaces", namespaces)
+        .route(Method::POST, "/namespaces", create_namespace)
+        .route(Method::PUT, "/namespaces/:namespace", upsert_namespace)
+        .route(Method::DELETE, "/namespaces/:namespace", delete_namespace)
+        .route(Method::PATCH, "/namespaces/:namespace/flags", set_namespace_flags)
+        .route(Method::PUT, "/namespaces/:namespace/policy", set_namespace_policy)
+        .route(Method::GET, "/namespaces/:namespace/usage", namespace_usage)
+        .route(Method::GET, "/namespaces/:namespace/digest", namespace_digest)
+        .route(Method::GET, "/namespaces/:namespace/labels", graph_labels)
+        .route_limited(
+            Method::GET,
+            "/namespaces/:namespace/export",
+            export_namespace,
+            &limiters.export,
+        )
+        .route_limited(
+            Method::POST,
+            "/namespaces/:namespace/import",
+            import_namespace,
+            &limiters.import,
+        )
+        .route(Method::GET, "/namespaces/:namespace/failures", list_failures)
+        .route(Method::POST, "/namespaces/:namespace/compute_graphs", create_compute_graph)
+        .route(Method::GET, "/namespaces/:namespace/compute_graphs", list_compute_graphs)
+        .route(Method::POST, "/compute_graphs", create_default_compute_graph)
+        .route(Method::GET, "/compute_graphs", list_default_compute_graphs)
+        .route(
+            Method::DELETE,
+            "/namespaces/:namespace/compute_graphs/:compute_graph",
+            delete_compute_graph,
+        )
         .route(
-            "/namespaces",
-            get(namespaces).with_state(route_state.clone()),
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/:compute_graph",
+            get_compute_graph,
         )
         .route(
-            "/namespaces",
-            post(create_namespace).with_state(route_state.clone()),
+            Method::PUT,
+            "/namespaces/:namespace/compute_graphs/:compute_graph",
+            update_compute_graph,
         )
         .route(
-            "/namespaces/:namespace/compute_graphs",
-            post(create_compute_graph).with_state(route_state.clone()),
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/lint",
+            lint_compute_graph,
         )
         .route(
-            "/namespaces/:namespace/compute_graphs",
-            get(list_compute_graphs).with_state(route_state.clone()),
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/instantiate",
+            instantiate_compute_graph,
         )
         .route(
-            "/namespaces/:namespace/compute_graphs/:compute_graph",
-            delete(delete_compute_graph).with_state(route_state.clone()),
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/plan",
+            compute_graph_plan,
         )
         .route(
-            "/namespaces/:namespace/compute_graphs/:compute_graph",
-            get(get_compute_graph).with_state(route_state.clone()),
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/edges",
+            compute_graph_edges,
         )
         .route(
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/stats",
+            compute_graph_stats,
+        )
+        .route(
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/histogram",
+            compute_graph_creation_histogram,
+        )
+        .route(Method::GET, "/namespaces/:namespace/compute_graphs/unused", unused_compute_graphs)
+        .route(
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/missing_code",
+            compute_graphs_with_missing_code,
+        )
+        .route(
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/by_node/:node_type",
+            compute_graphs_by_node,
+        )
+        .route(Method::GET, "/namespaces/:namespace/compute_graphs/recent", recent_compute_graphs)
+        .route(
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs/batch_import",
+            batch_import_compute_graphs,
+        )
+        .route(
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs/lint",
+            lint_compute_graph_definition,
+        )
+        .route(
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/enable",
+            enable_compute_graph,
+        )
+        .route(
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/disable",
+            disable_compute_graph,
+        )
+        .route(
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/freeze",
+            freeze_compute_graph,
+        )
+        .route(
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/unfreeze",
+            unfreeze_compute_graph,
+        )
+        .route(
+            Method::GET,
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/tasks",
-            get(list_tasks).with_state(route_state.clone()),
+            list_tasks,
+        )
+        .route(
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/outputs",
+            list_outputs,
         )
         .route(
+            Method::POST,
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/outputs",
-            get(list_outputs).with_state(route_state.clone()),
+            attach_output,
+        )
+        .route(
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/outputs/latest",
+            latest_outputs,
+        )
+        .route(
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/outputs/:output_id/lineage",
+            output_lineage,
         )
         .route(
+            Method::GET,
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/context",
-            get(get_context).with_state(route_state.clone()),
+            get_context,
         )
         .route(
+            Method::GET,
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations",
-            get(graph_invocations).with_state(route_state.clone()),
+            graph_invocations,
         )
         .route(
+            Method::POST,
             "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_file",
-            post(invoke_with_file).with_state(route_state.clone()),
+            invoke_with_file,
         )
         .route(
+            Method::POST,
             "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_object",
-            post(invoke_with_object).with_state(route_state.clone()),
+            invoke_with_object,
         )
         .route(
+            Method::POST,
             "/namespaces/:namespace/compute_graphs/:compute_graph/rerun",
-            post(rerun_compute_graph).with_state(route_state.clone()),
+            rerun_compute_graph,
+        )
+        .route(
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/validate_input",
+            validate_input,
         )
         .route(
+            Method::DELETE,
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id",
-            delete(delete_invocation).with_state(route_state.clone()),
+            delete_invocation,
         )
         .route(
+            Method::GET,
             "/namespaces/:namespace/compute_graphs/:compute_graph/notify",
-            get(notify_on_change).with_state(route_state.clone()),
+            notify_on_change,
         )
         .route(
-            "/internal/namespaces/:namespace/compute_graphs/:compute_graph/code",
-            get(get_code).with_state(route_state.clone()),
+            Method::HEAD,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/code",
+            code_metadata,
         )
         .route(
-            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/payload",
-            get(download_invocation_payload).with_state(route_state.clone()),
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/code",
+            swap_compute_graph_code,
         )
         .route(
-            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/output/:id",
-            get(download_fn_output_payload).with_state(route_state.clone()),
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/code_url",
+            code_url,
         )
-        .route("/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/tasks/:task_id/logs/:file", get(download_task_logs).with_state(route_state.clone()))
         .route(
-            "/internal/ingest_files",
-            post(ingest_files_from_executor).with_state(route_state.clone()),
+            Method::GET,
+            "/internal/namespaces/:namespace/compute_graphs/:compute_graph/code",
+            get_code,
+        )
+        .route(
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/payload",
+            download_invocation_payload,
         )
-        .route("/internal/executors", get(list_executors).with_state(route_state.clone()))
         .route(
-            "/internal/executors/:id/tasks",
-            post(executor_tasks).with_state(route_state.clone()),
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/output/:id",
+            download_fn_output_payload,
         )
         .route(
-            "/internal/fn_outputs/:input_key",
-            get(download_fn_output_by_key).with_state(route_state.clone()),
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/tasks/:task_id/logs/:file",
+            download_task_logs,
         )
-        .route("/ui", get(ui_index_handler))
-        .route("/ui/*rest", get(ui_handler))
+        .route(Method::POST, "/internal/ingest_files", ingest_files_from_executor)
+        .route(Method::GET, "/internal/executors", list_executors)
+        .route(Method::POST, "/internal/executors/:id/tasks", executor_tasks)
+        .route(Method::GET, "/internal/fn_outputs/:input_key", download_fn_output_by_key)
+        .route(Method::GET, "/admin/integrity/blobs", verify_blob_integrity)
+        .route(Method::GET, "/admin/graphs/by_code/:sha256", graphs_by_code)
+        .route(Method::GET, "/admin/code/:sha256/namespaces", namespaces_by_code)
+        .route(Method::GET, "/admin/namespaces/diff", diff_namespaces)
+        .route(Method::GET, "/admin/stats", global_stats)
+        .route(Method::GET, "/admin/live_files", live_files)
+        .route(Method::GET, "/admin/db_stats", db_stats)
+        .route(Method::GET, "/admin/cf_configs", cf_configs)
+        .route_limited(Method::POST, "/admin/reindex", reindex, &limiters.reindex)
+        .route(Method::GET, "/admin/blobs", list_blobs)
+        .route(Method::POST, "/admin/gc/blobs", gc_blobs)
+        .route(Method::GET, "/ui", ui_index_handler)
+        .route(Method::GET, "/ui/*rest", ui_handler)
+}
+
+/// Routes of the server. Trailing slashes are trimmed before routing, so
+/// `/namespaces/ns/compute_graphs/foo/` reaches the same handler as
+/// `/namespaces/ns/compute_graphs/foo`, and compute graph names are
+/// normalized before routing as well.
+pub fn create_routes(route_state: RouteState) -> NormalizePath<Router> {
+    let cors = CorsLayer::new()
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_origin(Any)
+        .allow_headers(Any);
+
+    let router = route_table(&route_state)
+        .router
+        .merge(SwaggerUi::new("/docs/swagger").url("/docs/openapi.json", ApiDoc::openapi()))
+        .route_layer(axum::middleware::from_fn(reject_empty_path_params))
+        .fallback(route_not_found)
+        .layer(axum::middleware::from_fn(pretty_json))
+        .layer(axum::middleware::from_fn_with_state(
+            route_state.slow_request_threshold,
+            log_slow_requests,
+        ))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(|req: &Request| {
@@ -247,12 +831,48 @@ pub fn create_routes(route_state: RouteState) -> Router {
         )
         .layer(cors)
         .layer(DefaultBodyLimit::max(usize::MAX))
+        .with_state(route_state.clone());
+    let router =
+        Router::new()
+            .fallback_service(router)
+            .layer(axum::middleware::from_fn_with_state(
+                route_state.indexify_state.clone(),
+                normalize_compute_graph_name,
+            ));
+    NormalizePath::trim_trailing_slash(router)
 }
 
 async fn index() -> &'static str {
     "Indexify Server"
 }
 
+/// Check whether the server can serve requests
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "operations",
+    responses(
+        (status = 200, description = "The server is ready"),
+        (status = SERVICE_UNAVAILABLE, description = "The state store was written with a newer schema than the server supports"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn readyz(State(state): State<RouteState>) -> Result<&'static str, IndexifyAPIError> {
+    state.indexify_state.check_schema_version().map_err(|e| {
+        match e.downcast_ref::<SchemaVersionMismatch>() {
+            Some(mismatch) => {
+                IndexifyAPIError::new(StatusCode::SERVICE_UNAVAILABLE, &mismatch.to_string())
+            }
+            None => IndexifyAPIError::internal_error(e),
+        }
+    })?;
+    Ok("ready")
+}
+
+async fn route_not_found() -> IndexifyAPIError {
+    IndexifyAPIError::route_not_found()
+}
+
 #[axum::debug_handler]
 #[tracing::instrument(skip_all)]
 async fn ui_index_handler() -> impl IntoResponse {
@@ -284,6 +904,8 @@ async fn ui_handler(Path(url): Path<String>) -> impl IntoResponse {
     tag = "operations",
     responses(
         (status = 200, description = "Namespace created successfully"),
+        (status = BAD_REQUEST, description = "Unknown feature flag"),
+        (status = FORBIDDEN, description = "The server has reached its maximum number of namespaces"),
         (status = INTERNAL_SERVER_ERROR, description = "Unable to create namespace")
     ),
 )]
@@ -291,16 +913,100 @@ async fn create_namespace(
     State(state): State<RouteState>,
     Json(namespace): Json<CreateNamespace>,
 ) -> Result<(), IndexifyAPIError> {
+    check_feature_flags(&namespace.feature_flags)?;
     state
         .indexify_state
         .write(StateMachineUpdateRequest {
             payload: RequestPayload::CreateNameSpace(NamespaceRequest {
                 name: namespace.name,
+                feature_flags: namespace.feature_flags,
             }),
             state_changes_processed: vec![],
         })
         .await
-        .map_err(IndexifyAPIError::internal_error)?;
+        .map_err(compute_graph_write_error)?;
+    Ok(())
+}
+
+/// Create a namespace if it doesn't exist and return it
+#[utoipa::path(
+    put,
+    path = "/namespaces/{namespace}",
+    tag = "operations",
+    responses(
+        (status = 201, description = "Namespace created", body = Namespace),
+        (status = 200, description = "Namespace already exists", body = Namespace),
+        (status = FORBIDDEN, description = "The server has reached its maximum number of namespaces"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to create namespace")
+    ),
+)]
+async fn upsert_namespace(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<(StatusCode, Json<Namespace>), IndexifyAPIError> {
+    let (namespace, created) = state
+        .indexify_state
+        .upsert_namespace(&NamespaceRequest {
+            name: namespace,
+            feature_flags: Default::default(),
+        })
+        .map_err(compute_graph_write_error)?;
+    let status = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(namespace.into())))
+}
+
+/// Delete a namespace. Namespaces with compute graphs are only deleted, along
+/// with their graphs, when forced
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}",
+    tag = "operations",
+    params(
+        ("force" = Option<bool>, Query, description = "Delete the compute graphs of the namespace along with it"),
+    ),
+    responses(
+        (status = 200, description = "Namespace deleted"),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = CONFLICT, description = "Namespace has compute graphs, or one of them is frozen"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to delete namespace")
+    ),
+)]
+async fn delete_namespace(
+    Path(namespace): Path<String>,
+    Query(params): Query<DeleteNamespaceParams>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    let exists = state
+        .indexify_state
+        .reader()
+        .get_namespace(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .is_some();
+    if !exists {
+        return Err(IndexifyAPIError::namespace_not_found(&namespace));
+    }
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::DeleteNamespace(DeleteNamespaceRequest {
+                name: namespace.clone(),
+                force: params.force.unwrap_or_default(),
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(|e| match e.downcast_ref::<NamespaceNotEmpty>() {
+            Some(not_empty) => {
+                IndexifyAPIError::with_code(ErrorCode::NamespaceNotEmpty, &not_empty.to_string())
+                    .with_details(serde_json::json!({ "compute_graphs": not_empty.compute_graphs }))
+            }
+            None => compute_graph_write_error(e),
+        })?;
+    info!("namespace deleted: {}", namespace);
     Ok(())
 }
 
@@ -309,20 +1015,81 @@ async fn create_namespace(
     get,
     path = "/namespaces",
     tag = "operations",
+    params(
+        ("prefix" = Option<String>, Query, description = "Only list namespaces whose name starts with this prefix"),
+        ("graph_counts" = Option<bool>, Query, description = "Annotate each namespace with its number of compute graphs"),
+        ("order" = Option<NamespaceOrder>, Query, description = "Order of the namespaces, by name by default or newest first with created. Namespaces ordered by creation can't be filtered by prefix"),
+    ),
     responses(
         (status = 200, description = "List all namespaces", body = NamespaceList),
+        (status = BAD_REQUEST, description = "Namespaces ordered by creation filtered by prefix"),
         (status = INTERNAL_SERVER_ERROR, description = "Unable to list namespace")
     ),
 )]
 async fn namespaces(
+    Query(params): Query<ListNamespacesParams>,
     State(state): State<RouteState>,
 ) -> Result<Json<NamespaceList>, IndexifyAPIError> {
     let reader = state.indexify_state.reader();
-    let namespaces = reader
-        .get_all_namespaces()
-        .map_err(IndexifyAPIError::internal_error)?;
-    let namespaces: Vec<Namespace> = namespaces.into_iter().map(|n| n.into()).collect();
-    Ok(Json(NamespaceList { namespaces }))
+    let graph_counts = params.graph_counts.unwrap_or_default();
+    let prefix = params.prefix.as_deref().unwrap_or_default();
+    let with_counts =
+        |namespaces: Vec<state_store::scanner::NamespaceWithCount>| -> Vec<Namespace> {
+            namespaces
+                .into_iter()
+                .map(|(n, count)| Namespace {
+                    compute_graph_count: Some(count),
+                    ..n.into()
+                })
+                .collect()
+        };
+    let (namespaces, cursor) = match params.order.unwrap_or(NamespaceOrder::Name) {
+        NamespaceOrder::Name if graph_counts => reader
+            .list_namespaces_with_counts(prefix, params.cursor.as_deref(), params.limit)
+            .map(|(namespaces, cursor)| (with_counts(namespaces), cursor)),
+        NamespaceOrder::Name => reader
+            .list_namespaces(prefix, params.cursor.as_deref(), params.limit)
+            .map(|(namespaces, cursor)| (namespaces.into_iter().map(Into::into).collect(), cursor)),
+        NamespaceOrder::Created => {
+            if params.prefix.is_some() {
+                return Err(IndexifyAPIError::bad_request(
+                    "namespaces ordered by creation can't be filtered by prefix",
+                ));
+            }
+            reader
+                .list_namespaces_by_time(
+                    state_store::scanner::SortOrder::Descending,
+                    params.limit,
+                    params.cursor.as_deref(),
+                )
+                .and_then(|(namespaces, cursor)| {
+                    let namespaces = match graph_counts {
+                        true => with_counts(reader.with_compute_graph_counts(namespaces)?),
+                        false => namespaces.into_iter().map(Into::into).collect(),
+                    };
+                    Ok((namespaces, cursor))
+                })
+        }
+    }
+    .map_err(IndexifyAPIError::internal_error)?;
+    let mut warnings = vec![];
+    if let Some(max_namespaces) = state.config.max_namespaces {
+        let count = reader
+            .count_namespaces()
+            .map_err(IndexifyAPIError::internal_error)?;
+        let threshold = state.config.quota_warning_threshold_percent as usize;
+        if count * 100 >= max_namespaces * threshold {
+            warnings.push(format!(
+                "approaching namespace quota ({}/{})",
+                count, max_namespaces
+            ));
+        }
+    }
+    Ok(Json(NamespaceList {
+        namespaces,
+        cursor,
+        warnings,
+    }))
 }
 
 #[allow(dead_code)]
@@ -333,98 +1100,500 @@ struct ComputeGraphCreateType {
     code: String,
 }
 
-/// Create compute graph
-#[utoipa::path(
-    post,
-    path = "/namespaces/{namespace}/compute_graphs",
-    tag = "operations",
-    request_body(content_type = "multipart/form-data", content = inline(ComputeGraphCreateType)),
-    responses(
-        (status = 200, description = "Create a Compute Graph"),
-        (status = INTERNAL_SERVER_ERROR, description = "Unable to create compute graphs")
-    ),
-)]
-async fn create_compute_graph(
-    Path(namespace): Path<String>,
-    State(state): State<RouteState>,
-    mut compute_graph_code: Multipart,
+#[allow(dead_code)]
+#[derive(ToSchema)]
+struct ComputeGraphCodeType {
+    #[schema(format = "binary")]
+    code: String,
+}
+
+fn is_yaml(content_type: Option<&str>) -> bool {
+    let Some(content_type) = content_type else {
+        return false;
+    };
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    ["application/yaml", "application/x-yaml", "text/yaml"]
+        .iter()
+        .any(|yaml| essence.eq_ignore_ascii_case(yaml))
+}
+
+/// Parses a compute graph definition, placing it in `namespace`. The
+/// definition is json unless `content_type` is a yaml media type.
+fn parse_compute_graph_definition(
+    namespace: &str,
+    text: &str,
+    content_type: Option<&str>,
+) -> Result<ComputeGraph, IndexifyAPIError> {
+    let mut json_value: serde_json::Value = if is_yaml(content_type) {
+        serde_yml::from_str(text)
+            .map_err(|e| IndexifyAPIError::bad_request(&format!("invalid yaml: {}", e)))?
+    } else {
+        serde_json::from_str(text)?
+    };
+    if !json_value.is_object() {
+        return Err(IndexifyAPIError::bad_request(
+            "compute graph definition must be an object",
+        ));
+    }
+    json_value["namespace"] = serde_json::Value::String(namespace.to_string());
+    Ok(serde_json::from_value(json_value)?)
+}
+
+/// Rejects writes to a namespace which doesn't exist, unless the server is
+/// configured to create it along with the compute graph.
+fn check_namespace_exists(state: &RouteState, namespace: &str) -> Result<(), IndexifyAPIError> {
+    if state.config.auto_create_namespace {
+        return Ok(());
+    }
+    let exists = state
+        .indexify_state
+        .reader()
+        .get_namespace(namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .is_some();
+    if !exists {
+        return Err(IndexifyAPIError::namespace_not_found(&namespace));
+    }
+    Ok(())
+}
+
+/// Rejects compute graphs with more nodes than the configured limit.
+fn check_node_count(
+    config: &ServerConfig,
+    compute_graph: &data_model::ComputeGraph,
 ) -> Result<(), IndexifyAPIError> {
-    let mut compute_graph_definition: Option<ComputeGraph> = Option::None;
-    let mut put_result: Option<PutResult> = None;
-    while let Some(field) = compute_graph_code.next_field().await.unwrap() {
-        let name = field.name();
-        if let Some(name) = name {
-            if name == "code" {
-                let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
-                let file_name = format!("{}_{}", namespace, nanoid!());
-                let result = state
-                    .blob_storage
-                    .put(&file_name, stream)
-                    .await
-                    .map_err(|e| IndexifyAPIError::internal_error(e))?;
-                put_result = Some(result);
-            } else if name == "compute_graph" {
-                let text = field
-                    .text()
-                    .await
-                    .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
-                let mut json_value: serde_json::Value = serde_json::from_str(&text)?;
-                json_value["namespace"] = serde_json::Value::String(namespace.clone());
-                compute_graph_definition = Some(serde_json::from_value(json_value)?);
+    let node_count = compute_graph.nodes.len();
+    if node_count > config.max_nodes {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "compute graph {} has {} nodes, the maximum is {}",
+            compute_graph.name, node_count, config.max_nodes
+        )));
+    }
+    Ok(())
+}
+
+/// Rejects compute graphs with lint warnings in namespaces with the
+/// `strict_validation` feature flag.
+fn check_strict_validation(
+    state: &RouteState,
+    compute_graph: &data_model::ComputeGraph,
+) -> Result<(), IndexifyAPIError> {
+    let strict = state
+        .indexify_state
+        .reader()
+        .get_namespace(&compute_graph.namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .is_some_and(|namespace| namespace.flag_enabled(STRICT_VALIDATION_FLAG));
+    let warnings = compute_graph.lint();
+    if !strict || warnings.is_empty() {
+        return Ok(());
+    }
+    let warnings: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+    Err(IndexifyAPIError::bad_request(&format!(
+        "compute graph {} has lint warnings: {}",
+        compute_graph.name,
+        warnings.join("; ")
+    )))
+}
+
+/// Reads the compute graph definition and code of a create or update request
+/// and uploads the code to blob storage.
+async fn compute_graph_from_multipart(
+    state: &RouteState,
+    namespace: &str,
+    mut compute_graph_code: Multipart,
+) -> Result<data_model::ComputeGraph, IndexifyAPIError> {
+    // Collect every field before validating so the errors don't depend on the
+    // order in which the client sent them.
+    let mut compute_graph_definition: Option<Result<ComputeGraph, IndexifyAPIError>> = None;
+    let mut put_result: Option<Result<PutResult, IndexifyAPIError>> = None;
+    let mut multipart_error = None;
+    loop {
+        let field = match compute_graph_code.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(err) => {
+                multipart_error = Some(IndexifyAPIError::bad_request(&format!(
+                    "invalid multipart request: {}",
+                    err
+                )));
+                break;
             }
+        };
+        match field.name() {
+            Some("code") => put_result = Some(put_code_field(state, namespace, field).await),
+            Some("compute_graph") => {
+                let content_type = field.content_type().map(|c| c.to_string());
+                let max_bytes = state.config.max_compute_graph_definition_bytes;
+                let definition = match read_compute_graph_field(field, max_bytes).await {
+                    Ok(text) => {
+                        parse_compute_graph_definition(namespace, &text, content_type.as_deref())
+                    }
+                    Err(err) => {
+                        // Don't read the rest of an oversized request.
+                        let too_large = err.status_code() == StatusCode::PAYLOAD_TOO_LARGE;
+                        compute_graph_definition = Some(Err(err));
+                        if too_large {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+                compute_graph_definition = Some(definition);
+            }
+            _ => {}
         }
     }
 
-    if compute_graph_definition.is_none() {
+    let compute_graph_definition = compute_graph_definition.transpose()?;
+    let put_result = put_result.transpose()?;
+    if let Some(err) = multipart_error {
+        return Err(err);
+    }
+    let Some(compute_graph_definition) = compute_graph_definition else {
         return Err(IndexifyAPIError::bad_request(
             "Compute graph definition is required",
         ));
-    }
-
-    if put_result.is_none() {
+    };
+    let Some(put_result) = put_result else {
         return Err(IndexifyAPIError::bad_request("Code is required"));
-    }
-    let put_result = put_result.unwrap();
-    let compute_graph_definition = compute_graph_definition.unwrap();
+    };
     let compute_graph = compute_graph_definition.into_data_model(
         &put_result.url,
         &put_result.sha256_hash,
         put_result.size_bytes,
     )?;
-    let name = compute_graph.name.clone();
-    let request = RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
-        namespace,
-        compute_graph,
-    });
-    state
-        .indexify_state
-        .write(StateMachineUpdateRequest {
-            payload: request,
-            state_changes_processed: vec![],
-        })
-        .await
-        .map_err(IndexifyAPIError::internal_error)?;
-    info!("compute graph created: {}", name);
-    Ok(())
+    check_node_count(&state.config, &compute_graph)?;
+    check_strict_validation(state, &compute_graph)?;
+    Ok(compute_graph)
 }
 
-/// Delete compute graph
+/// Header making the creation of a compute graph a no-op when the stored
+/// graph has the same content.
+const IDEMPOTENT_HEADER: &str = "x-indexify-idempotent";
+
+/// Create compute graph
 #[utoipa::path(
-    delete,
-    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}",
+    post,
+    path = "/namespaces/{namespace}/compute_graphs",
     tag = "operations",
+    request_body(content_type = "multipart/form-data", content = inline(ComputeGraphCreateType)),
+    params(
+        ("X-Indexify-Idempotent" = Option<bool>, Header, description = "Leave the stored graph as it is when its content is identical"),
+    ),
     responses(
-        (status = 200, description = "Extraction graph deleted successfully"),
-        (status = BAD_REQUEST, description = "Unable to delete extraction graph")
+        (status = 200, description = "Create a Compute Graph", body = ComputeGraphCreated),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = CONFLICT, description = "Compute Graph name collides with an existing graph"),
+        (status = PAYLOAD_TOO_LARGE, description = "Compute Graph definition is too large"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to create compute graphs")
+    ),
+)]
+async fn create_compute_graph(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    headers: HeaderMap,
+    compute_graph_code: Multipart,
+) -> Result<Json<ComputeGraphCreated>, IndexifyAPIError> {
+    check_namespace_exists(&state, &namespace)?;
+    let idempotent = match headers.get(IDEMPOTENT_HEADER) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse::<bool>().ok())
+            .ok_or_else(|| IndexifyAPIError::bad_request("invalid X-Indexify-Idempotent header"))?,
+        None => false,
+    };
+    // Graphs are created without an actor: requests aren't authenticated, and
+    // a name supplied by the client isn't a record of who created the graph.
+    let compute_graph =
+        compute_graph_from_multipart(&state, &namespace, compute_graph_code).await?;
+    let name = state
+        .indexify_state
+        .compute_graph_key_name(&compute_graph.name);
+    let code_path = compute_graph.code.path.clone();
+    // The content is compared in the transaction writing the graph, so a
+    // concurrent update can't be mistaken for the stored graph.
+    let request = RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+        namespace: namespace.clone(),
+        compute_graph,
+        if_match: None,
+        if_changed: idempotent,
+    }));
+    let result = state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: request,
+            state_changes_processed: vec![],
+        })
+        .await;
+    if let Some(unchanged) = result
+        .as_ref()
+        .err()
+        .and_then(|e| e.downcast_ref::<ComputeGraphUnchanged>())
+    {
+        // The stored graph keeps its own copy of the code.
+        delete_unused_code(&state, &code_path).await;
+        return Ok(Json(ComputeGraphCreated {
+            compute_graph: unchanged.compute_graph.clone().into(),
+            created: false,
+        }));
+    }
+    result.map_err(compute_graph_write_error)?;
+    info!("compute graph created: {}", name);
+    let compute_graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    Ok(Json(ComputeGraphCreated {
+        compute_graph: compute_graph.into(),
+        created: true,
+    }))
+}
+
+/// Update compute graph, optionally only if its etag matches If-Match
+#[utoipa::path(
+    put,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}",
+    tag = "operations",
+    request_body(content_type = "multipart/form-data", content = inline(ComputeGraphCreateType)),
+    params(
+        ("If-Match" = Option<String>, Header, description = "Etag the stored compute graph must have"),
+    ),
+    responses(
+        (status = 200, description = "Compute Graph updated"),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = PRECONDITION_FAILED, description = "Compute Graph was modified since it was read"),
+        (status = CONFLICT, description = "Compute Graph is frozen"),
+        (status = PAYLOAD_TOO_LARGE, description = "Compute Graph definition is too large"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn update_compute_graph(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    headers: HeaderMap,
+    compute_graph_code: Multipart,
+) -> Result<(), IndexifyAPIError> {
+    // "*" only requires the graph to exist, which is checked below.
+    let if_match = if_match_etags(&headers)?.filter(|etags| !etags.iter().any(|e| e == "*"));
+    let Some((_, etag)) = state
+        .indexify_state
+        .reader()
+        .get_compute_graph_with_etag(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+    else {
+        return Err(IndexifyAPIError::compute_graph_not_found());
+    };
+    // Don't upload the code of an update which can't succeed. The state
+    // machine checks the etag again in case the graph changes meanwhile.
+    if if_match
+        .as_ref()
+        .is_some_and(|etags| !etags.contains(&etag))
+    {
+        return Err(compute_graph_write_error(
+            ComputeGraphEtagMismatch { namespace, name }.into(),
+        ));
+    }
+    let compute_graph =
+        compute_graph_from_multipart(&state, &namespace, compute_graph_code).await?;
+    let code_url = compute_graph.code.path.clone();
+    if compute_graph.name != name {
+        delete_unused_code(&state, &code_url).await;
+        return Err(IndexifyAPIError::bad_request(
+            "compute graph name does not match the path",
+        ));
+    }
+    let request = RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+        namespace,
+        compute_graph,
+        if_match,
+        if_changed: false,
+    }));
+    let written = state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: request,
+            state_changes_processed: vec![],
+        })
+        .await;
+    if let Err(err) = written {
+        // The graph keeps its previous code.
+        delete_unused_code(&state, &code_url).await;
+        return Err(compute_graph_write_error(err));
+    }
+    info!("compute graph updated: {}", name);
+    Ok(())
+}
+
+/// Deletes a code blob uploaded for a write which didn't happen. Failures are
+/// only logged, the blob is then left behind.
+async fn delete_unused_code(state: &RouteState, url: &str) {
+    if let Err(err) = state.blob_storage.delete(url).await {
+        error!("failed to delete unused code blob {}: {:?}", url, err);
+    }
+}
+
+/// Replace the code of a compute graph, leaving its definition as it is
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/code",
+    tag = "operations",
+    request_body(content_type = "multipart/form-data", content = inline(ComputeGraphCodeType)),
+    params(
+        ("delete_previous" = Option<bool>, Query, description = "Delete the previous code blob once the new code is in place, unless another compute graph uses it"),
+    ),
+    responses(
+        (status = 200, description = "Compute Graph with its new code", body = ComputeGraph),
+        (status = BAD_REQUEST, description = "Code is missing or can't be read"),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = CONFLICT, description = "Compute Graph is frozen"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn swap_compute_graph_code(
+    Path((namespace, name)): Path<(String, String)>,
+    Query(params): Query<SwapCodeParams>,
+    State(state): State<RouteState>,
+    mut code: Multipart,
+) -> Result<Json<ComputeGraph>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let existing = reader
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?;
+    if existing.is_none() {
+        return Err(IndexifyAPIError::compute_graph_not_found());
+    }
+    let mut put_result = None;
+    while let Some(field) = code.next_field().await.map_err(|err| {
+        IndexifyAPIError::bad_request(&format!("invalid multipart request: {}", err))
+    })? {
+        if field.name() == Some("code") {
+            put_result = Some(put_code_field(&state, &namespace, field).await?);
+            break;
+        }
+    }
+    let Some(put_result) = put_result else {
+        return Err(IndexifyAPIError::bad_request("Code is required"));
+    };
+    let request = RequestPayload::SwapComputeGraphCode(SwapComputeGraphCodeRequest {
+        namespace: namespace.clone(),
+        name: name.clone(),
+        code: data_model::ComputeGraphCode {
+            path: put_result.url.clone(),
+            size: put_result.size_bytes,
+            sha256_hash: put_result.sha256_hash,
+        },
+        delete_previous: params.delete_previous.unwrap_or_default(),
+    });
+    let written = state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: request,
+            state_changes_processed: vec![],
+        })
+        .await;
+    if let Err(err) = written {
+        // The graph keeps its previous code.
+        delete_unused_code(&state, &put_result.url).await;
+        return Err(compute_graph_write_error(err));
+    }
+    info!("compute graph code swapped: {}", name);
+    let compute_graph = reader
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    Ok(Json(compute_graph.into()))
+}
+
+/// Maps the errors of compute graph writes rejected by the state machine to
+/// their status code.
+fn compute_graph_write_error(e: anyhow::Error) -> IndexifyAPIError {
+    if let Some(mismatch) = e.downcast_ref::<ComputeGraphEtagMismatch>() {
+        return IndexifyAPIError::with_code(ErrorCode::EtagMismatch, &mismatch.to_string());
+    }
+    if let Some(frozen) = e.downcast_ref::<ComputeGraphFrozen>() {
+        return IndexifyAPIError::with_code(ErrorCode::ComputeGraphFrozen, &frozen.to_string());
+    }
+    if let Some(collision) = e.downcast_ref::<ComputeGraphNameCollision>() {
+        return IndexifyAPIError::with_code(ErrorCode::ComputeGraphExists, &collision.to_string());
+    }
+    if let Some(limit) = e.downcast_ref::<NamespaceLimitReached>() {
+        return IndexifyAPIError::with_code(ErrorCode::NamespaceLimitReached, &limit.to_string());
+    }
+    IndexifyAPIError::internal_error(e)
+}
+
+/// Returns the etags listed by the If-Match header, `*` when any etag
+/// matches.
+fn if_match_etags(headers: &HeaderMap) -> Result<Option<Vec<String>>, IndexifyAPIError> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(None);
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| IndexifyAPIError::bad_request("invalid If-Match header"))?;
+    if value.trim() == "*" {
+        return Ok(Some(vec!["*".to_string()]));
+    }
+    let etags: Vec<String> = value
+        .split(',')
+        .filter(|etag| !etag.trim().is_empty())
+        .map(parse_etag)
+        .collect();
+    if etags.is_empty() {
+        return Err(IndexifyAPIError::bad_request("invalid If-Match header"));
+    }
+    Ok(Some(etags))
+}
+
+/// Strips the quotes and weak validator prefix from an etag header value.
+fn parse_etag(value: &str) -> String {
+    let value = value.trim();
+    let value = value.strip_prefix("W/").unwrap_or(value);
+    value.trim_matches('"').to_string()
+}
+
+/// Delete compute graph, optionally only if its etag matches If-Match
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}",
+    tag = "operations",
+    params(
+        ("If-Match" = Option<String>, Header, description = "Etag the stored compute graph must have"),
+    ),
+    responses(
+        (status = 200, description = "Extraction graph deleted successfully"),
+        (status = BAD_REQUEST, description = "Unable to delete extraction graph"),
+        (status = NOT_FOUND, description = "Compute Graph not found, with If-Match only"),
+        (status = PRECONDITION_FAILED, description = "Compute Graph was modified since it was read"),
+        (status = CONFLICT, description = "Compute Graph is frozen")
     ),
 )]
 async fn delete_compute_graph(
     Path((namespace, compute_graph)): Path<(String, String)>,
     State(state): State<RouteState>,
+    headers: HeaderMap,
 ) -> Result<(), IndexifyAPIError> {
+    let if_match = if_match_etags(&headers)?;
+    if if_match.is_some() {
+        // Unconditional deletes of missing graphs succeed, conditional ones
+        // report them like updates do.
+        let existing = state
+            .indexify_state
+            .reader()
+            .get_compute_graph(&namespace, &compute_graph)
+            .map_err(IndexifyAPIError::internal_error)?;
+        if existing.is_none() {
+            return Err(IndexifyAPIError::compute_graph_not_found());
+        }
+    }
     let request = RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
         namespace,
         name: compute_graph,
+        if_match: if_match.filter(|etags| !etags.iter().any(|e| e == "*")),
     });
     state
         .indexify_state
@@ -433,7 +1602,7 @@ async fn delete_compute_graph(
             state_changes_processed: vec![],
         })
         .await
-        .map_err(IndexifyAPIError::internal_error)?;
+        .map_err(compute_graph_write_error)?;
     Ok(())
 }
 
@@ -442,103 +1611,313 @@ async fn delete_compute_graph(
     get,
     path = "/namespaces/{namespace}/compute_graphs",
     tag = "operations",
+    params(
+        ("limit" = Option<usize>, Query, description = "Maximum number of graphs to return"),
+        ("cursor" = Option<String>, Query, description = "Cursor returned by the previous page"),
+        ("enabled" = Option<bool>, Query, description = "Only list graphs with this enabled state"),
+        ("created_by" = Option<String>, Query, description = "Only list graphs created by this actor"),
+        ("sort" = Option<ComputeGraphSort>, Query, description = "Order the graphs by code size instead of by name, without pagination. Every graph of the namespace is read to sort them"),
+        ("order" = Option<SortOrder>, Query, description = "Direction of a sorted listing, defaults to desc"),
+    ),
     responses(
         (status = 200, description = "Lists Compute Graph", body = ComputeGraphsList),
+        (status = BAD_REQUEST, description = "Sorted listing with a cursor or a filter"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
 async fn list_compute_graphs(
     Path(namespace): Path<String>,
-    Query(params): Query<ListParams>,
+    Query(params): Query<ListComputeGraphsParams>,
     State(state): State<RouteState>,
 ) -> Result<Json<ComputeGraphsList>, IndexifyAPIError> {
-    let (compute_graphs, cursor) = state
-        .indexify_state
-        .reader()
-        .list_compute_graphs(&namespace, params.cursor.as_deref(), params.limit)
-        .map_err(IndexifyAPIError::internal_error)?;
+    if let Some(ComputeGraphSort::CodeSize) = params.sort {
+        if params.cursor.is_some() || params.enabled.is_some() || params.created_by.is_some() {
+            return Err(IndexifyAPIError::bad_request(
+                "sorted listings can't be combined with a cursor or a filter",
+            ));
+        }
+        let compute_graphs = state
+            .indexify_state
+            .reader()
+            .list_compute_graphs_by_code_size(
+                &namespace,
+                params.order.unwrap_or(SortOrder::Desc).into(),
+                params.limit.unwrap_or(usize::MAX),
+            )
+            .map_err(IndexifyAPIError::internal_error)?;
+        return Ok(Json(ComputeGraphsList {
+            compute_graphs: compute_graphs.into_iter().map(|c| c.into()).collect(),
+            cursor: None,
+        }));
+    }
+    let reader = state.indexify_state.reader();
+    let (compute_graphs, cursor) = match (&params.created_by, params.enabled) {
+        (Some(actor), None) => reader.list_compute_graphs_by_actor(
+            &namespace,
+            actor,
+            params.limit,
+            params.cursor.as_deref(),
+        ),
+        (created_by, enabled) => reader.filter_compute_graphs(
+            &namespace,
+            |compute_graph| {
+                (enabled.is_none() || enabled == Some(compute_graph.enabled)) &&
+                    (created_by.is_none() || *created_by == compute_graph.actor)
+            },
+            params.cursor.as_deref(),
+            params.limit,
+        ),
+    }
+    .map_err(IndexifyAPIError::internal_error)?;
     Ok(Json(ComputeGraphsList {
         compute_graphs: compute_graphs.into_iter().map(|c| c.into()).collect(),
         cursor,
     }))
 }
 
+/// Computed field of a compute graph returned with `include=depth`.
+const INCLUDE_DEPTH: &str = "depth";
+
 /// Get a compute graph definition
 #[utoipa::path(
     get,
     path = "/namespaces/{namespace}/compute_graphs/{compute_graph}",
     tag = "operations",
+    params(
+        ("include" = Option<String>, Query, description = "Comma separated computed fields to add, `depth` is the number of nodes on the longest path of the graph"),
+        ("raw" = Option<bool>, Query, description = "Return the graph as it is stored by the server, without converting it to the API representation"),
+    ),
     responses(
-        (status = 200, description = "Compute Graph Definition", body = ComputeGraph),
+        (status = 200, description = "Compute Graph Definition", body = ComputeGraph,
+            headers(("ETag" = String, description = "Etag to pass as If-Match when updating the graph"))),
+        (status = BAD_REQUEST, description = "Unknown computed field, or computed fields requested with raw"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
 async fn get_compute_graph(
     Path((namespace, name)): Path<(String, String)>,
+    Query(params): Query<GetComputeGraphParams>,
     State(state): State<RouteState>,
-) -> Result<Json<ComputeGraph>, IndexifyAPIError> {
+) -> Result<impl IntoResponse, IndexifyAPIError> {
+    let mut include_depth = false;
+    for field in params.include.iter().flat_map(|include| include.split(',')) {
+        match field.trim() {
+            INCLUDE_DEPTH => include_depth = true,
+            "" => {}
+            field => {
+                return Err(IndexifyAPIError::bad_request(&format!(
+                    "unknown field to include: {}",
+                    field
+                )))
+            }
+        }
+    }
+    if params.raw.unwrap_or_default() {
+        if include_depth {
+            return Err(IndexifyAPIError::bad_request(
+                "computed fields can't be included in a raw compute graph",
+            ));
+        }
+        let compute_graph = state
+            .indexify_state
+            .reader()
+            .get_compute_graph_raw(&namespace, &name)
+            .map_err(IndexifyAPIError::internal_error)?
+            .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "application/json".to_string()),
+                (
+                    header::ETAG,
+                    format!("\"{}\"", compute_graph_etag(&compute_graph)),
+                ),
+            ],
+            compute_graph,
+        )
+            .into_response());
+    }
     let compute_graph = state
         .indexify_state
         .reader()
-        .get_compute_graph(&namespace, &name)
+        .get_compute_graph_with_etag(&namespace, &name)
         .map_err(IndexifyAPIError::internal_error)?;
-    if let Some(compute_graph) = compute_graph {
-        return Ok(Json(compute_graph.into()));
+    if let Some((compute_graph, etag)) = compute_graph {
+        // Graphs with a cycle have no depth, which is left out rather than
+        // failing the request.
+        let depth = include_depth.then(|| compute_graph.depth().ok()).flatten();
+        let compute_graph = ComputeGraph {
+            depth,
+            ..compute_graph.into()
+        };
+        return Ok((
+            [(header::ETAG, format!("\"{}\"", etag))],
+            Json(compute_graph),
+        )
+            .into_response());
     }
-    Err(IndexifyAPIError::not_found("Compute Graph not found"))
+    Err(IndexifyAPIError::compute_graph_not_found())
 }
 
-/// List Graph invocations
+/// List Graph invocations, optionally filtered by status
 #[utoipa::path(
     get,
     path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations",
     tag = "ingestion",
+    params(
+        ("status" = Option<InvocationStatus>, Query, description = "Only list invocations in this status"),
+        ("attribute_key" = Option<String>, Query, description = "Only list invocations with this indexed attribute"),
+        ("attribute_value" = Option<String>, Query, description = "Value of the attribute, required with attribute_key"),
+    ),
     responses(
         (status = 200, description = "Compute Graph Definition", body = GraphInvocations),
+        (status = GONE, description = "Cursor expired"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
 async fn graph_invocations(
     Path((namespace, compute_graph)): Path<(String, String)>,
-    Query(params): Query<ListParams>,
+    Query(params): Query<ListInvocationsParams>,
     State(state): State<RouteState>,
 ) -> Result<Json<GraphInvocations>, IndexifyAPIError> {
-    let (data_objects, cursor) = state
-        .indexify_state
-        .reader()
-        .list_invocations(
-            &namespace,
-            &compute_graph,
-            params.cursor.as_deref(),
-            params.limit,
-        )
-        .map_err(IndexifyAPIError::internal_error)?;
+    let reader = state.indexify_state.reader();
+    let (data_objects, cursor) = match (params.attribute_key, params.attribute_value) {
+        (None, None) => {
+            let prefix = format!("{}|{}|", namespace, compute_graph).into_bytes();
+            let start = decode_cursor(&state, &prefix, params.cursor.as_deref())?;
+            let (data_objects, cursor) = reader
+                .list_invocations_with_status(
+                    &namespace,
+                    &compute_graph,
+                    params.status.map(Into::into),
+                    start.as_deref(),
+                    params.limit,
+                )
+                .map_err(IndexifyAPIError::internal_error)?;
+            let last_key = data_objects
+                .last()
+                .map(|(data_object, _)| data_object.key().into_bytes());
+            (
+                data_objects,
+                encode_cursor(cursor.is_some(), last_key.as_deref()),
+            )
+        }
+        (Some(key), Some(value)) => {
+            if !state.config.indexed_attributes.contains(&key) {
+                return Err(IndexifyAPIError::bad_request(&format!(
+                    "attribute {} is not indexed",
+                    key
+                )));
+            }
+            let prefix =
+                InvocationPayload::attribute_key_prefix(&namespace, &compute_graph, &key, &value)
+                    .into_bytes();
+            let start = decode_cursor(&state, &prefix, params.cursor.as_deref())?;
+            let (data_objects, cursor) = reader
+                .find_inputs_by_attribute(
+                    &namespace,
+                    &compute_graph,
+                    (&key, &value),
+                    params.status.map(Into::into),
+                    start.as_deref(),
+                    params.limit,
+                )
+                .map_err(IndexifyAPIError::internal_error)?;
+            let last_key = data_objects
+                .last()
+                .map(|(data_object, _)| data_object.attribute_key(&key, &value).into_bytes());
+            (
+                data_objects,
+                encode_cursor(cursor.is_some(), last_key.as_deref()),
+            )
+        }
+        _ => {
+            return Err(IndexifyAPIError::bad_request(
+                "attribute_key and attribute_value must be set together",
+            ))
+        }
+    };
     let mut invocations = vec![];
-    for data_object in data_objects {
+    for (data_object, status) in data_objects {
         invocations.push(DataObject {
             id: data_object.id,
             payload_size: data_object.payload.size,
             payload_sha_256: data_object.payload.sha256_hash,
+            status: status.into(),
+            attributes: data_object.attributes,
         });
     }
+    // The counts cover every invocation of the graph, they are the same for
+    // every page so they are only counted for the first one.
+    let status_counts = match params.cursor {
+        Some(_) => None,
+        None => Some(
+            reader
+                .invocation_status_counts(&namespace, &compute_graph)
+                .map_err(IndexifyAPIError::internal_error)?
+                .into_iter()
+                .map(|(status, count)| (status.into(), count))
+                .collect(),
+        ),
+    };
     Ok(Json(GraphInvocations {
         invocations,
         cursor,
+        status_counts,
     }))
 }
 
+/// Event sent on a change notification stream in place of the `skipped`
+/// events it missed by reading slower than they were published. Clients
+/// should resync the state of their invocations from the server.
+fn lagged_event(skipped: u64) -> Result<Event, axum::Error> {
+    Event::default()
+        .event("lagged")
+        .json_data(serde_json::json!({ "skipped": skipped }))
+}
+
+/// Stream the changes of the invocations of a compute graph as server sent
+/// events
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/notify",
+    tag = "operations",
+    params(
+        ("invocation_id" = Option<String>, Query, description = "Stream every event of this invocation, including the outputs of its functions, until it finishes"),
+    ),
+    responses(
+        (status = 200, description = "Stream of invocation changes", content_type = "text/event-stream"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
 async fn notify_on_change(
     Path((_namespace, _compute_graph)): Path<(String, String)>,
+    Query(params): Query<NotifyOnChangeParams>,
     State(state): State<RouteState>,
 ) -> Result<impl IntoResponse, IndexifyAPIError> {
     let mut rx = state.indexify_state.task_event_stream();
     let invocation_event_stream = async_stream::stream! {
         loop {
-                if let Ok(ev)  =  rx.recv().await {
-                        yield Event::default().json_data(ev.clone());
-                        return;
-                    }
+            let ev = match rx.recv().await {
+                Ok(ev) => ev,
+                Err(RecvError::Lagged(skipped)) => {
+                    yield lagged_event(skipped);
+                    continue;
                 }
+                Err(RecvError::Closed) => return,
+            };
+            let Some(invocation_id) = &params.invocation_id else {
+                yield Event::default().json_data(ev.clone());
+                return;
+            };
+            if ev.invocation_id() != *invocation_id {
+                continue;
+            }
+            let finished = matches!(ev, InvocationStateChangeEvent::InvocationFinished(_));
+            yield Event::default().json_data(ev.clone());
+            if finished {
+                return;
+            }
+        }
     };
 
     Ok(
@@ -729,6 +2108,7 @@ async fn delete_invocation(
 
 async fn get_code(
     Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<GetCodeParams>,
     State(state): State<RouteState>,
 ) -> Result<impl IntoResponse, IndexifyAPIError> {
     let compute_graph = state
@@ -737,14 +2117,37 @@ async fn get_code(
         .get_compute_graph(&namespace, &compute_graph)
         .map_err(IndexifyAPIError::internal_error)?;
     if compute_graph.is_none() {
-        return Err(IndexifyAPIError::not_found("Compute Graph not found"));
+        return Err(IndexifyAPIError::compute_graph_not_found());
     }
     let compute_graph = compute_graph.unwrap();
-    let storage_reader = state.blob_storage.get(&compute_graph.code.path);
-    let code_stream = storage_reader
-        .get()
-        .await
-        .map_err(|e| IndexifyAPIError::internal_error(e))?;
+    let code_stream = if params.verify.unwrap_or_default() {
+        // The headers are sent by the time the hash is known, so a mismatch
+        // can only abort the transfer.
+        let code_stream = state
+            .blob_storage
+            .get_verified(&compute_graph.code.path, &compute_graph.code.sha256_hash)
+            .await
+            .map_err(IndexifyAPIError::internal_error)?;
+        let graph = compute_graph.key();
+        code_stream
+            .inspect(move |chunk| {
+                if let Err(err) = chunk {
+                    if blob_store::error::is_hash_mismatch(err) {
+                        error!("code of compute graph {} is corrupted: {}", graph, err);
+                    }
+                }
+            })
+            .boxed()
+    } else {
+        let storage_reader = state
+            .blob_storage
+            .get(&compute_graph.code.path)
+            .map_err(IndexifyAPIError::internal_error)?;
+        storage_reader
+            .get()
+            .await
+            .map_err(|e| IndexifyAPIError::internal_error(e))?
+    };
 
     Response::builder()
         .header("Content-Type", "application/octet-stream")
@@ -755,3 +2158,4573 @@ async fn get_code(
         .body(Body::from_stream(code_stream))
         .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
 }
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        io::Write,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    };
+
+    use axum::{extract::FromRequest, routing::get};
+    use blob_store::{BlobStorage, BlobStorageConfig};
+    use bytes::{Bytes, BytesMut};
+    use data_model::{
+        test_objects::tests::{mock_graph_a, mock_graph_b, TEST_NAMESPACE},
+        OutputPayload,
+    };
+    use sha2::{Digest, Sha256};
+    use state_store::{
+        requests::CreateComputeGraphRequest,
+        IndexifyStateOptions,
+        STATE_STORE_SCHEMA_VERSION,
+    };
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        archive,
+        http_objects::{
+            CodeUrlParams,
+            CreationHistogramParams,
+            GcBlobsParams,
+            InvocationQueryParams,
+            NamespaceDiffParams,
+            RecentComputeGraphsParams,
+        },
+    };
+
+    /// Creates a compute graph, for the tests which don't check the graph
+    /// returned by the handler.
+    async fn create_compute_graph(
+        path: Path<String>,
+        state: State<RouteState>,
+        headers: HeaderMap,
+        compute_graph_code: Multipart,
+    ) -> Result<(), IndexifyAPIError> {
+        super::create_compute_graph(path, state, headers, compute_graph_code)
+            .await
+            .map(|_| ())
+    }
+
+    async fn create_default_compute_graph(
+        state: State<RouteState>,
+        headers: HeaderMap,
+        compute_graph_code: Multipart,
+    ) -> Result<(), IndexifyAPIError> {
+        super::create_default_compute_graph(state, headers, compute_graph_code)
+            .await
+            .map(|_| ())
+    }
+
+    async fn multipart(fields: &[(&str, &str)]) -> Multipart {
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|(name, value)| (*name, None, *value))
+            .collect();
+        typed_multipart(&fields).await
+    }
+
+    /// Builds a multipart body whose fields optionally carry a content type.
+    async fn typed_multipart(fields: &[(&str, Option<&str>, &str)]) -> Multipart {
+        let boundary = "indexify-test-boundary";
+        let mut body = String::new();
+        for (name, content_type, value) in fields {
+            body.push_str(&format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n",
+                boundary, name
+            ));
+            if let Some(content_type) = content_type {
+                body.push_str(&format!("Content-Type: {}\r\n", content_type));
+            }
+            body.push_str(&format!("\r\n{}\r\n", value));
+        }
+        body.push_str(&format!("--{}--\r\n", boundary));
+        let request = Request::builder()
+            .method(Method::POST)
+            .header(
+                hyper::header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(Body::from(body))
+            .unwrap();
+        Multipart::from_request(request, &()).await.unwrap()
+    }
+
+    /// Returns the status and message of an error response.
+    async fn error_response(err: IndexifyAPIError) -> (StatusCode, String) {
+        let response = err.into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        (status, body["message"].as_str().unwrap().to_string())
+    }
+
+    /// Returns the path and data of the files of a tar archive.
+    fn archive_files(archive: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut files = Vec::new();
+        for entry in tar::Archive::new(archive).entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_string_lossy().to_string();
+            let mut data = Vec::new();
+            std::io::Read::read_to_end(&mut entry, &mut data)?;
+            files.push((path, data));
+        }
+        Ok(files)
+    }
+
+    async fn test_route_state(temp_dir: &TempDir) -> Result<RouteState> {
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        indexify_state.upsert_namespace(&NamespaceRequest {
+            name: TEST_NAMESPACE.to_string(),
+            feature_flags: Default::default(),
+        })?;
+        let blob_storage = Arc::new(BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().join("blob").to_str().unwrap(),
+        ))?);
+        let executor_manager = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        Ok(RouteState {
+            indexify_state,
+            blob_storage,
+            executor_manager,
+            config: Arc::new(ServerConfig::default()),
+            upload_limiter: ConcurrencyLimiter::uploads(&ServerConfig::default()),
+            route_limiters: RouteLimiters::from_config(&ServerConfig::default()),
+            slow_request_threshold: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_readyz_schema_version() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        assert_eq!(readyz(State(state.clone())).await.unwrap(), "ready");
+
+        // A store written by a newer server, e.g. before a downgrade.
+        state
+            .indexify_state
+            .record_schema_version(STATE_STORE_SCHEMA_VERSION + 1)?;
+        let (status, message) = error_response(readyz(State(state)).await.unwrap_err()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            message,
+            format!(
+                "state store has schema version {}, this server supports up to version {}",
+                STATE_STORE_SCHEMA_VERSION + 1,
+                STATE_STORE_SCHEMA_VERSION
+            )
+        );
+
+        // Reopening the store doesn't overwrite the newer version.
+        let reopened = IndexifyState::new(temp_dir.path().join("reopened")).await?;
+        reopened.record_schema_version(STATE_STORE_SCHEMA_VERSION + 1)?;
+        drop(reopened);
+        let reopened = IndexifyState::new(temp_dir.path().join("reopened")).await?;
+        assert!(reopened.check_schema_version().is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upsert_namespace() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+
+        let (status, Json(created)) =
+            upsert_namespace(Path("namespace1".to_string()), State(state.clone()))
+                .await
+                .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, Json(existing)) =
+            upsert_namespace(Path("namespace1".to_string()), State(state.clone()))
+                .await
+                .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(existing.created_at, created.created_at);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_namespaces() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        let config = ServerConfig {
+            max_namespaces: Some(2),
+            ..Default::default()
+        };
+        state.indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("limited_state"),
+            config.indexify_state_options(),
+        )
+        .await?;
+        let create = |name: &str| {
+            create_namespace(
+                State(state.clone()),
+                Json(CreateNamespace {
+                    name: name.to_string(),
+                    feature_flags: Default::default(),
+                }),
+            )
+        };
+
+        create("ns1").await.unwrap();
+        create("ns2").await.unwrap();
+        let (status, message) = error_response(create("ns3").await.unwrap_err()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(message, "the server is limited to 2 namespaces");
+        let err = upsert_namespace(Path("ns3".to_string()), State(state.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::FORBIDDEN);
+
+        // Existing namespaces can still be recreated.
+        create("ns1").await.unwrap();
+        let (status, _) = upsert_namespace(Path("ns2".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert!(state
+            .indexify_state
+            .reader()
+            .get_namespace("ns3")?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_namespaces_with_prefix() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        for name in ["prod-a", "dev-a", "prod-b"] {
+            let _ = upsert_namespace(Path(name.to_string()), State(state.clone()))
+                .await
+                .unwrap();
+        }
+
+        let Json(list) = namespaces(
+            Query(ListNamespacesParams {
+                prefix: Some("prod".to_string()),
+                cursor: None,
+                limit: None,
+                graph_counts: None,
+                order: None,
+            }),
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        let names: Vec<String> = list.namespaces.into_iter().map(|n| n.name).collect();
+        assert_eq!(names, vec!["prod-a", "prod-b"]);
+        assert!(list.cursor.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_namespaces_by_creation() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        // Namespaces created in the same millisecond would be ordered by name.
+        for name in ["beta", "gamma", "alpha"] {
+            tokio::time::sleep(Duration::from_millis(2)).await;
+            let _ = upsert_namespace(Path(name.to_string()), State(state.clone()))
+                .await
+                .unwrap();
+        }
+        let list = |order, cursor, limit| {
+            namespaces(
+                Query(ListNamespacesParams {
+                    prefix: None,
+                    cursor,
+                    limit,
+                    graph_counts: None,
+                    order,
+                }),
+                State(state.clone()),
+            )
+        };
+        let names = |list: &NamespaceList| -> Vec<String> {
+            list.namespaces.iter().map(|n| n.name.clone()).collect()
+        };
+
+        let Json(by_name) = list(None, None, None).await.unwrap();
+        assert_eq!(
+            names(&by_name),
+            vec!["alpha", "beta", "gamma", TEST_NAMESPACE]
+        );
+        let Json(by_creation) = list(Some(NamespaceOrder::Created), None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            names(&by_creation),
+            vec!["alpha", "gamma", "beta", TEST_NAMESPACE]
+        );
+        assert!(by_creation.cursor.is_none());
+
+        let Json(first) = list(Some(NamespaceOrder::Created), None, Some(2))
+            .await
+            .unwrap();
+        assert_eq!(names(&first), vec!["alpha", "gamma"]);
+        let Json(second) = list(Some(NamespaceOrder::Created), first.cursor, Some(2))
+            .await
+            .unwrap();
+        assert_eq!(names(&second), vec!["beta", TEST_NAMESPACE]);
+        assert!(second.cursor.is_none());
+
+        let reader = state.indexify_state.reader();
+        let (oldest, cursor) = reader.list_namespaces_by_time(
+            state_store::scanner::SortOrder::Ascending,
+            Some(1),
+            None,
+        )?;
+        assert_eq!(oldest[0].name, TEST_NAMESPACE);
+        let (rest, _) = reader.list_namespaces_by_time(
+            state_store::scanner::SortOrder::Ascending,
+            None,
+            cursor.as_deref(),
+        )?;
+        let rest: Vec<String> = rest.into_iter().map(|n| n.name).collect();
+        assert_eq!(rest, vec!["beta", "gamma", "alpha"]);
+
+        // Deleted namespaces leave the index.
+        delete_namespace(
+            Path("gamma".to_string()),
+            Query(DeleteNamespaceParams { force: None }),
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        let Json(by_creation) = list(Some(NamespaceOrder::Created), None, None)
+            .await
+            .unwrap();
+        assert_eq!(names(&by_creation), vec!["alpha", "beta", TEST_NAMESPACE]);
+
+        let err = namespaces(
+            Query(ListNamespacesParams {
+                prefix: Some("a".to_string()),
+                cursor: None,
+                limit: None,
+                graph_counts: None,
+                order: Some(NamespaceOrder::Created),
+            }),
+            State(state.clone()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_namespaces_with_graph_counts() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let definition = serde_json::to_string(&ComputeGraph::from(mock_graph_a()))?;
+        create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&[("compute_graph", &definition), ("code", "code")]).await,
+        )
+        .await
+        .unwrap();
+        let _ = upsert_namespace(Path("empty".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        let list = |order, graph_counts| {
+            namespaces(
+                Query(ListNamespacesParams {
+                    prefix: None,
+                    cursor: None,
+                    limit: None,
+                    graph_counts,
+                    order,
+                }),
+                State(state.clone()),
+            )
+        };
+        let counts = |list: NamespaceList| -> Vec<(String, Option<usize>)> {
+            list.namespaces
+                .into_iter()
+                .map(|n| (n.name, n.compute_graph_count))
+                .collect()
+        };
+
+        let Json(by_name) = list(None, Some(true)).await.unwrap();
+        assert_eq!(
+            counts(by_name),
+            vec![
+                ("empty".to_string(), Some(0)),
+                (TEST_NAMESPACE.to_string(), Some(1))
+            ]
+        );
+        let Json(by_creation) = list(Some(NamespaceOrder::Created), Some(true))
+            .await
+            .unwrap();
+        assert_eq!(
+            counts(by_creation),
+            vec![
+                ("empty".to_string(), Some(0)),
+                (TEST_NAMESPACE.to_string(), Some(1))
+            ]
+        );
+        let Json(uncounted) = list(None, None).await.unwrap();
+        assert!(uncounted
+            .namespaces
+            .iter()
+            .all(|n| n.compute_graph_count.is_none()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_namespaces_quota_warning() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        let config = ServerConfig {
+            max_namespaces: Some(5),
+            quota_warning_threshold_percent: 60,
+            ..Default::default()
+        };
+        state.indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("limited_state"),
+            config.indexify_state_options(),
+        )
+        .await?;
+        state.config = Arc::new(config);
+        let list = || {
+            namespaces(
+                Query(ListNamespacesParams {
+                    prefix: Some("ns2".to_string()),
+                    cursor: None,
+                    limit: None,
+                    graph_counts: None,
+                    order: None,
+                }),
+                State(state.clone()),
+            )
+        };
+
+        for name in ["ns1", "ns2"] {
+            let _ = upsert_namespace(Path(name.to_string()), State(state.clone()))
+                .await
+                .unwrap();
+        }
+        let Json(below) = list().await.unwrap();
+        assert!(below.warnings.is_empty());
+
+        // The warning is about every namespace, not only the listed ones.
+        let _ = upsert_namespace(Path("ns3".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        let Json(approaching) = list().await.unwrap();
+        assert_eq!(approaching.namespaces.len(), 1);
+        assert_eq!(
+            approaching.warnings,
+            vec!["approaching namespace quota (3/5)"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_global_stats() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let _ = upsert_namespace(Path("other".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        let _ = upsert_namespace(Path("empty".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        let mut graphs = vec![];
+        for (namespace, graph) in [
+            (TEST_NAMESPACE, mock_graph_a()),
+            (TEST_NAMESPACE, mock_graph_b()),
+            ("other", mock_graph_a()),
+        ] {
+            let mut graph = graph;
+            graph.namespace = namespace.to_string();
+            state
+                .indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: namespace.to_string(),
+                            compute_graph: graph.clone(),
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+            graphs.push(graph);
+        }
+        for _ in 0..2 {
+            invoke_with_object(
+                Path(("other".to_string(), graphs[2].name.clone())),
+                Query(InvocationQueryParams {
+                    block_until_finish: None,
+                    attributes: None,
+                }),
+                State(state.clone()),
+                Body::from("payload"),
+            )
+            .await
+            .unwrap();
+        }
+
+        let Json(stats) = global_stats(State(state)).await.unwrap();
+        assert_eq!(stats.namespaces, 3);
+        assert_eq!(stats.compute_graphs, 3);
+        assert_eq!(
+            stats.compute_graphs_per_namespace,
+            BTreeMap::from([
+                ("empty".to_string(), 0),
+                ("other".to_string(), 1),
+                (TEST_NAMESPACE.to_string(), 2),
+            ])
+        );
+        assert_eq!(stats.invocations, 2);
+        assert!(stats.invocations_exact);
+        let code_bytes = graphs.iter().map(|graph| graph.code.size).sum::<u64>();
+        assert_eq!(stats.blob_bytes.compute_graph_code, code_bytes);
+        assert_eq!(
+            stats.blob_bytes.invocation_payloads,
+            2 * "payload".len() as u64
+        );
+        assert_eq!(
+            stats.blob_bytes.total,
+            code_bytes + 2 * "payload".len() as u64
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_graphs_by_code() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let graphs = [
+            ("ns1", "graph_A", "hash1"),
+            ("ns2", "graph_B", "hash1"),
+            ("ns1", "graph_C", "hash2"),
+        ];
+        for (namespace, name, sha256) in graphs {
+            let mut graph = mock_graph_a();
+            graph.namespace = namespace.to_string();
+            graph.name = name.to_string();
+            graph.code.sha256_hash = sha256.to_string();
+            state
+                .indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: namespace.to_string(),
+                            compute_graph: graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let Json(found) = graphs_by_code(Path("hash1".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        let found: Vec<(String, String)> = found
+            .compute_graphs
+            .into_iter()
+            .map(|r| (r.namespace, r.compute_graph))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("ns1".to_string(), "graph_A".to_string()),
+                ("ns2".to_string(), "graph_B".to_string()),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_by_code() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let graphs = [
+            ("ns2", "graph_A", "hash1"),
+            ("ns1", "graph_A", "hash1"),
+            ("ns1", "graph_B", "hash1"),
+            ("ns3", "graph_A", "hash2"),
+        ];
+        for (namespace, name, sha256) in graphs {
+            state.indexify_state.upsert_namespace(&NamespaceRequest {
+                name: namespace.to_string(),
+                feature_flags: Default::default(),
+            })?;
+            let mut graph = mock_graph_a();
+            graph.namespace = namespace.to_string();
+            graph.name = name.to_string();
+            graph.code.sha256_hash = sha256.to_string();
+            state
+                .indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: namespace.to_string(),
+                            compute_graph: graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let Json(found) = namespaces_by_code(Path("hash1".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(found.sha256, "hash1");
+        assert_eq!(found.namespaces, vec!["ns1", "ns2"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_diff_namespaces() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        state.indexify_state.upsert_namespace(&NamespaceRequest {
+            name: "prod".to_string(),
+            feature_flags: Default::default(),
+        })?;
+        for (namespace, mut graph) in [
+            (TEST_NAMESPACE, mock_graph_a()),
+            (TEST_NAMESPACE, mock_graph_b()),
+            ("prod", mock_graph_a()),
+        ] {
+            graph.namespace = namespace.to_string();
+            state
+                .indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: namespace.to_string(),
+                            compute_graph: graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let params = |b: &str| NamespaceDiffParams {
+            a: TEST_NAMESPACE.to_string(),
+            b: b.to_string(),
+        };
+        let Json(diff) = diff_namespaces(Query(params("prod")), State(state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(diff.only_in_a, vec!["graph_B"]);
+        assert!(diff.only_in_b.is_empty());
+        assert!(diff.changed.is_empty());
+
+        let err = diff_namespaces(Query(params("missing")), State(state.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trailing_slash_routes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let app = axum::ServiceExt::<Request>::into_make_service(create_routes(state));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        for path in ["graph_A", "graph_A/"] {
+            let response = reqwest::get(format!(
+                "http://{}/namespaces/{}/compute_graphs/{}",
+                addr, TEST_NAMESPACE, path
+            ))
+            .await?;
+            assert_eq!(response.status(), StatusCode::OK, "path {}", path);
+            let compute_graph: ComputeGraph = response.json().await?;
+            assert_eq!(compute_graph.name, "graph_A");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_route_concurrency_limits() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        let limiter = ConcurrencyLimiter::new("exports", 2, Duration::from_millis(200));
+        state.route_limiters.export = limiter.clone();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let app = axum::ServiceExt::<Request>::into_make_service(create_routes(state.clone()));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let export_url = format!("http://{}/namespaces/{}/export", addr, TEST_NAMESPACE);
+
+        // Exports beyond the limit are shed once the timeout passes.
+        let in_progress = [
+            limiter.acquire().await.unwrap(),
+            limiter.acquire().await.unwrap(),
+        ];
+        let responses =
+            futures::future::join_all((0..4).map(|_| reqwest::get(export_url.clone()))).await;
+        for response in responses {
+            assert_eq!(response?.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+
+        // Other routes aren't limited by the export limiter.
+        let response = reqwest::get(format!("http://{}/namespaces", addr)).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Exports waiting within the timeout are served once a slot frees up.
+        drop(in_progress);
+        let responses =
+            futures::future::join_all((0..4).map(|_| reqwest::get(export_url.clone()))).await;
+        for response in responses {
+            let response = response?;
+            assert_eq!(response.status(), StatusCode::OK);
+            response.bytes().await?;
+        }
+        assert_eq!(limiter.permits.available_permits(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_route_concurrency_limits_queue() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        let limiter = ConcurrencyLimiter::new("exports", 1, Duration::from_secs(10));
+        state.route_limiters.export = limiter.clone();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let app = axum::ServiceExt::<Request>::into_make_service(create_routes(state.clone()));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let export_url = format!("http://{}/namespaces/{}/export", addr, TEST_NAMESPACE);
+
+        // Exports queue behind the one in progress until it finishes.
+        let in_progress = limiter.acquire().await.unwrap();
+        let exports = tokio::spawn(futures::future::join_all(
+            (0..3).map(|_| reqwest::get(export_url.clone())),
+        ));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!exports.is_finished());
+        drop(in_progress);
+        for response in exports.await? {
+            assert_eq!(response?.status(), StatusCode::OK);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_empty_path_params() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let app = axum::ServiceExt::<Request>::into_make_service(create_routes(state));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        for (method, path, param) in [
+            (Method::GET, "/namespaces//compute_graphs", "namespace"),
+            (
+                Method::GET,
+                "/namespaces//compute_graphs/recent",
+                "namespace",
+            ),
+            (
+                Method::GET,
+                "/namespaces/test/compute_graphs//invocations",
+                "compute_graph",
+            ),
+            (
+                Method::GET,
+                "/namespaces/test/compute_graphs//plan",
+                "compute_graph",
+            ),
+            (
+                Method::DELETE,
+                "/namespaces//compute_graphs/graph_A",
+                "namespace",
+            ),
+        ] {
+            let response = client
+                .request(method, format!("http://{}{}", addr, path))
+                .send()
+                .await?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "path {}", path);
+            let body: serde_json::Value = response.json().await?;
+            assert_eq!(body["code"], "BAD_REQUEST");
+            assert_eq!(body["message"], format!("{} must not be empty", param));
+        }
+
+        // Listing the compute graphs of a namespace is unaffected.
+        let response = reqwest::get(format!(
+            "http://{}/namespaces/{}/compute_graphs",
+            addr, TEST_NAMESPACE
+        ))
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_slow_request_log() -> Result<()> {
+        #[derive(Clone, Default)]
+        struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+        impl std::io::Write for CapturedLogs {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        // The test runtime is single threaded, so the server task logs to it.
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        state.slow_request_threshold = Some(Duration::from_millis(20));
+        let app = Router::new()
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    StatusCode::ACCEPTED
+                }),
+            )
+            .route("/fast", get(|| async { "fast" }))
+            .layer(axum::middleware::from_fn_with_state(
+                state.slow_request_threshold,
+                log_slow_requests,
+            ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        client.get(format!("http://{}/fast", addr)).send().await?;
+        let response = client
+            .get(format!("http://{}/slow", addr))
+            .header("X-Request-Id", "req-1")
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone())?;
+        let warnings: Vec<_> = logs
+            .lines()
+            .filter(|line| line.contains("slow request"))
+            .collect();
+        assert_eq!(warnings.len(), 1, "{}", logs);
+        let warning = warnings[0];
+        assert!(warning.contains("WARN"));
+        for field in [
+            "method=GET",
+            "path=\"/slow\"",
+            "status=202",
+            "request_id=\"req-1\"",
+        ] {
+            assert!(
+                warning.contains(field),
+                "{} missing from {}",
+                field,
+                warning
+            );
+        }
+        let duration_ms: u64 = warning
+            .split("duration_ms=")
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+            .unwrap()
+            .parse()?;
+        assert!(duration_ms >= 50);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pretty_json() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let app = axum::ServiceExt::<Request>::into_make_service(create_routes(state));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        let url = format!(
+            "http://{}/namespaces/{}/compute_graphs/graph_A",
+            addr, TEST_NAMESPACE
+        );
+        let compact = client.get(&url).send().await?.bytes().await?;
+        let pretty_query = client
+            .get(format!("{}?pretty=true", url))
+            .send()
+            .await?
+            .bytes()
+            .await?;
+        let pretty_header = client
+            .get(&url)
+            .header("X-Pretty", "true")
+            .send()
+            .await?
+            .bytes()
+            .await?;
+
+        assert!(!compact.contains(&b'\n'));
+        assert!(pretty_query.len() > compact.len());
+        assert_eq!(pretty_header, pretty_query);
+        let compact: serde_json::Value = serde_json::from_slice(&compact)?;
+        let pretty: serde_json::Value = serde_json::from_slice(&pretty_query)?;
+        assert_eq!(compact, pretty);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_disabled_compute_graph_rejects_invocations() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let graph = mock_graph_a();
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let path = || Path((TEST_NAMESPACE.to_string(), graph.name.clone()));
+        let invoke = |state: RouteState| {
+            invoke_with_object(
+                path(),
+                Query(InvocationQueryParams {
+                    block_until_finish: None,
+                    attributes: None,
+                }),
+                State(state),
+                Body::from("payload"),
+            )
+        };
+
+        disable_compute_graph(path(), State(state.clone()))
+            .await
+            .unwrap();
+        let err = invoke(state.clone()).await.err().unwrap();
+        assert_eq!(err.into_response().status(), StatusCode::CONFLICT);
+
+        enable_compute_graph(path(), State(state.clone()))
+            .await
+            .unwrap();
+        assert!(invoke(state.clone()).await.is_ok());
+
+        // Frozen graphs keep accepting inputs.
+        freeze_compute_graph(path(), State(state.clone()))
+            .await
+            .unwrap();
+        assert!(invoke(state.clone()).await.is_ok());
+
+        let (invocations, _) = state.indexify_state.reader().list_invocations(
+            TEST_NAMESPACE,
+            &graph.name,
+            None,
+            None,
+        )?;
+        assert_eq!(invocations.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_frozen_compute_graph_rejects_updates() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let graph = mock_graph_a();
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let path = || Path((TEST_NAMESPACE.to_string(), graph.name.clone()));
+        let mut changed_graph = ComputeGraph::from(graph.clone());
+        changed_graph.description = "changed".to_string();
+        let definition = serde_json::to_string(&changed_graph)?;
+        let update = || async {
+            update_compute_graph(
+                path(),
+                State(state.clone()),
+                HeaderMap::new(),
+                multipart(&[("compute_graph", &definition), ("code", "code")]).await,
+            )
+            .await
+        };
+
+        freeze_compute_graph(path(), State(state.clone()))
+            .await
+            .unwrap();
+        let Json(listed) = list_compute_graphs(
+            Path(TEST_NAMESPACE.to_string()),
+            Query(ListComputeGraphsParams {
+                cursor: None,
+                limit: None,
+                enabled: None,
+                created_by: None,
+                sort: None,
+                order: None,
+            }),
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        assert!(listed.compute_graphs[0].frozen);
+
+        let err = update().await.unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::CONFLICT);
+        let err = delete_compute_graph(path(), State(state.clone()), HeaderMap::new())
+            .await
+            .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::CONFLICT);
+
+        unfreeze_compute_graph(path(), State(state.clone()))
+            .await
+            .unwrap();
+        update().await.unwrap();
+        let stored = state
+            .indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, &graph.name)?
+            .unwrap();
+        assert_eq!(stored.description, "changed");
+        assert!(!stored.frozen);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_error_response_code() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let envelope = |err: IndexifyAPIError| async move {
+            let response = err.into_response();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+            anyhow::Ok((status, serde_json::from_slice::<serde_json::Value>(&body)?))
+        };
+
+        let err = get_compute_graph(
+            Path((TEST_NAMESPACE.to_string(), "missing".to_string())),
+            Query(Default::default()),
+            State(state.clone()),
+        )
+        .await
+        .err()
+        .unwrap();
+        let (status, body) = envelope(err).await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "COMPUTE_GRAPH_NOT_FOUND");
+        assert_eq!(body["message"], "Compute Graph not found");
+
+        let err = delete_namespace(
+            Path("missing".to_string()),
+            Query(DeleteNamespaceParams { force: None }),
+            State(state.clone()),
+        )
+        .await
+        .unwrap_err();
+        let (status, body) = envelope(err).await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "NAMESPACE_NOT_FOUND");
+
+        let (status, body) = envelope(route_not_found().await).await?;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "ROUTE_NOT_FOUND");
+
+        let err = IndexifyAPIError::from(serde_json::from_str::<ComputeGraph>("{").unwrap_err());
+        let (status, body) = envelope(err).await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "BAD_REQUEST");
+        assert_eq!(body["details"]["line"], 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_attach_output() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let graph = mock_graph_a();
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        invoke_with_object(
+            Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+            Query(InvocationQueryParams {
+                block_until_finish: None,
+                attributes: None,
+            }),
+            State(state.clone()),
+            Body::from("payload"),
+        )
+        .await
+        .unwrap();
+        let (invocations, _) = state.indexify_state.reader().list_invocations(
+            TEST_NAMESPACE,
+            &graph.name,
+            None,
+            None,
+        )?;
+        let invocation_id = invocations[0].id.clone();
+        let attach = |invocation_id: &str| {
+            attach_output(
+                Path((
+                    TEST_NAMESPACE.to_string(),
+                    graph.name.clone(),
+                    invocation_id.to_string(),
+                )),
+                Query(AttachOutputParams {
+                    compute_fn: "fn_b".to_string(),
+                }),
+                State(state.clone()),
+                Body::from("legacy result"),
+            )
+        };
+
+        let Json(attached) = attach(&invocation_id).await.unwrap();
+        assert_eq!(attached.compute_fn, "fn_b");
+        let output = state
+            .indexify_state
+            .reader()
+            .fn_output_payload(
+                TEST_NAMESPACE,
+                &graph.name,
+                &invocation_id,
+                "fn_b",
+                &attached.id,
+            )?
+            .unwrap();
+        let OutputPayload::Fn(payload) = output.payload else {
+            panic!("unexpected payload {:?}", output.payload);
+        };
+        assert_eq!(
+            state.blob_storage.read_bytes(&payload.path).await?,
+            "legacy result"
+        );
+
+        let err = attach("missing").await.unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_input_and_output_schemas() -> Result<()> {
+        async fn violations(err: IndexifyAPIError) -> Result<serde_json::Value> {
+            let body = axum::body::to_bytes(err.into_response().into_body(), usize::MAX).await?;
+            let body: serde_json::Value = serde_json::from_slice(&body)?;
+            Ok(body["details"]["violations"].clone())
+        }
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let mut graph = mock_graph_a();
+        graph.input_schema = Some(serde_json::json!({
+            "type": "object",
+            "properties": {"url": {"type": "string"}},
+            "required": ["url"],
+        }));
+        graph.output_schema =
+            Some(serde_json::json!({"type": "array", "items": {"type": "string"}}));
+        if let Some(data_model::Node::Compute(fn_c)) = graph.nodes.get_mut("fn_c") {
+            fn_c.payload_encoder = "json".to_string();
+        }
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invoke = |input: Vec<u8>| {
+            invoke_with_object(
+                Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+                Query(InvocationQueryParams {
+                    block_until_finish: None,
+                    attributes: None,
+                }),
+                State(state.clone()),
+                Body::from(input),
+            )
+        };
+
+        assert!(invoke(br#"{"url": "https://example.com"}"#.to_vec())
+            .await
+            .is_ok());
+        let cbor_input =
+            indexify_utils::json_to_cbor(serde_json::json!({"url": "https://example.com"}))?;
+        assert!(invoke(cbor_input).await.is_ok());
+        let Err(err) = invoke(br#"{"link": "https://example.com"}"#.to_vec()).await else {
+            panic!("input without url was accepted");
+        };
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            violations(err).await?,
+            serde_json::json!([{"path": "", "message": "missing required property url"}])
+        );
+        // Sample inputs are validated without creating invocations.
+        let validate = |input: &'static str| {
+            validate_input(
+                Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+                State(state.clone()),
+                Body::from(input),
+            )
+        };
+        assert!(validate(r#"{"url": "https://example.com"}"#).await.is_ok());
+        let err = validate(r#"{"url": 1}"#).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            violations(err).await?,
+            serde_json::json!([{"path": "/url", "message": "expected string, got number"}])
+        );
+        let (invocations, _) = state.indexify_state.reader().list_invocations(
+            TEST_NAMESPACE,
+            &graph.name,
+            None,
+            None,
+        )?;
+        assert_eq!(invocations.len(), 2);
+
+        let attach = |compute_fn: &str, output: &'static str| {
+            attach_output(
+                Path((
+                    TEST_NAMESPACE.to_string(),
+                    graph.name.clone(),
+                    invocations[0].id.clone(),
+                )),
+                Query(AttachOutputParams {
+                    compute_fn: compute_fn.to_string(),
+                }),
+                State(state.clone()),
+                Body::from(output),
+            )
+        };
+        assert!(attach("fn_c", r#"["a", "b"]"#).await.is_ok());
+        let Err(err) = attach("fn_c", r#"["a", 1]"#).await else {
+            panic!("output with a number was accepted");
+        };
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            violations(err).await?,
+            serde_json::json!([{"path": "/1", "message": "expected string, got number"}])
+        );
+        // Only the outputs of JSON encoded functions are validated.
+        assert!(attach("fn_b", "not json").await.is_ok());
+
+        // Files can't be checked against the input schema.
+        let err = invoke_with_file(
+            Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+            State(state.clone()),
+            Query(InvocationQueryParams {
+                block_until_finish: None,
+                attributes: None,
+            }),
+            multipart(&[("file", "https://example.com")]).await,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+        // Bodies larger than the validated size are rejected.
+        let mut small_state = state.clone();
+        small_state.config = Arc::new(ServerConfig {
+            max_schema_validated_bytes: 8,
+            ..Default::default()
+        });
+        let err = invoke_with_object(
+            Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+            Query(InvocationQueryParams {
+                block_until_finish: None,
+                attributes: None,
+            }),
+            State(small_state.clone()),
+            Body::from(r#"{"url": "https://example.com"}"#),
+        )
+        .await
+        .err()
+        .unwrap();
+        assert_eq!(err.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+        let err = validate_input(
+            Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+            State(small_state.clone()),
+            Body::from(r#"{"url": "https://example.com"}"#),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+        let err = attach_output(
+            Path((
+                TEST_NAMESPACE.to_string(),
+                graph.name.clone(),
+                invocations[0].id.clone(),
+            )),
+            Query(AttachOutputParams {
+                compute_fn: "fn_c".to_string(),
+            }),
+            State(small_state),
+            Body::from(r#"["a", "b", "c"]"#),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Schemas with keywords the validator doesn't understand are rejected
+        // by the state machine too.
+        graph.input_schema = Some(serde_json::json!({"oneOf": [{"type": "string"}]}));
+        let result = state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_notify_on_change_streams_outputs() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let graph = mock_graph_a();
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        invoke_with_object(
+            Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+            Query(InvocationQueryParams {
+                block_until_finish: None,
+                attributes: None,
+            }),
+            State(state.clone()),
+            Body::from("payload"),
+        )
+        .await
+        .unwrap();
+        let (invocations, _) = state.indexify_state.reader().list_invocations(
+            TEST_NAMESPACE,
+            &graph.name,
+            None,
+            None,
+        )?;
+        let invocation_id = invocations[0].id.clone();
+
+        let response = notify_on_change(
+            Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+            Query(NotifyOnChangeParams {
+                invocation_id: Some(invocation_id.clone()),
+            }),
+            State(state.clone()),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        let mut body = response.into_body().into_data_stream();
+        let mut output_ids = vec![];
+        for compute_fn in ["fn_b", "fn_c"] {
+            let Json(attached) = attach_output(
+                Path((
+                    TEST_NAMESPACE.to_string(),
+                    graph.name.clone(),
+                    invocation_id.clone(),
+                )),
+                Query(AttachOutputParams {
+                    compute_fn: compute_fn.to_string(),
+                }),
+                State(state.clone()),
+                Body::from("partial result"),
+            )
+            .await
+            .unwrap();
+            output_ids.push(attached.id);
+        }
+
+        let mut events = vec![];
+        while events.len() < 2 {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+                .await?
+                .unwrap()?;
+            let chunk = String::from_utf8(chunk.to_vec())?;
+            for data in chunk.lines().filter_map(|line| line.strip_prefix("data: ")) {
+                events.push(serde_json::from_str::<serde_json::Value>(data)?);
+            }
+        }
+        for ((event, compute_fn), output_id) in events.iter().zip(["fn_b", "fn_c"]).zip(output_ids)
+        {
+            let output = &event["OutputProduced"];
+            assert_eq!(output["invocation_id"], invocation_id.as_str());
+            assert_eq!(output["fn_name"], compute_fn);
+            assert_eq!(output["output_id"], output_id.as_str());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_notify_on_change_lagged() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        state.indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("small_channel_state"),
+            IndexifyStateOptions {
+                task_event_capacity: Some(2),
+                ..Default::default()
+            },
+        )
+        .await?;
+        state.indexify_state.upsert_namespace(&NamespaceRequest {
+            name: TEST_NAMESPACE.to_string(),
+            feature_flags: Default::default(),
+        })?;
+        let graph = mock_graph_a();
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        invoke_with_object(
+            Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+            Query(InvocationQueryParams {
+                block_until_finish: None,
+                attributes: None,
+            }),
+            State(state.clone()),
+            Body::from("payload"),
+        )
+        .await
+        .unwrap();
+        let (invocations, _) = state.indexify_state.reader().list_invocations(
+            TEST_NAMESPACE,
+            &graph.name,
+            None,
+            None,
+        )?;
+        let invocation_id = invocations[0].id.clone();
+
+        let response = notify_on_change(
+            Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+            Query(NotifyOnChangeParams {
+                invocation_id: Some(invocation_id.clone()),
+            }),
+            State(state.clone()),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        // Publish more events than the channel holds before the stream is
+        // read.
+        for _ in 0..5 {
+            let _ = attach_output(
+                Path((
+                    TEST_NAMESPACE.to_string(),
+                    graph.name.clone(),
+                    invocation_id.clone(),
+                )),
+                Query(AttachOutputParams {
+                    compute_fn: "fn_b".to_string(),
+                }),
+                State(state.clone()),
+                Body::from("partial result"),
+            )
+            .await
+            .unwrap();
+        }
+
+        let mut body = response.into_body().into_data_stream();
+        let mut events = vec![];
+        while events.len() < 3 {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+                .await?
+                .unwrap()?;
+            let chunk = String::from_utf8(chunk.to_vec())?;
+            let mut name = None;
+            for line in chunk.lines() {
+                if let Some(event) = line.strip_prefix("event: ") {
+                    name = Some(event.to_string());
+                } else if let Some(data) = line.strip_prefix("data: ") {
+                    let data = serde_json::from_str::<serde_json::Value>(data)?;
+                    events.push((name.take(), data));
+                }
+            }
+        }
+        assert_eq!(events[0].0.as_deref(), Some("lagged"));
+        assert_eq!(events[0].1["skipped"], 3);
+        for (name, data) in &events[1..] {
+            assert!(name.is_none());
+            assert_eq!(data["OutputProduced"]["fn_name"], "fn_b");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_graph_invocations_cursor_expiry() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        let graph = mock_graph_a();
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        for _ in 0..3 {
+            invoke_with_object(
+                Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+                Query(InvocationQueryParams {
+                    block_until_finish: None,
+                    attributes: None,
+                }),
+                State(state.clone()),
+                Body::from("payload"),
+            )
+            .await
+            .unwrap();
+        }
+        let list = |state: RouteState, cursor: Option<Vec<u8>>| {
+            graph_invocations(
+                Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+                Query(ListInvocationsParams {
+                    limit: Some(1),
+                    cursor,
+                    status: None,
+                    attribute_key: None,
+                    attribute_value: None,
+                }),
+                State(state),
+            )
+        };
+
+        let Json(first) = list(state.clone(), None).await.unwrap();
+        let Json(second) = list(state.clone(), first.cursor.clone()).await.unwrap();
+        assert_eq!(second.invocations.len(), 1);
+        assert_ne!(second.invocations[0].id, first.invocations[0].id);
+
+        // Deleting the rows already listed, or the next one, neither repeats
+        // nor skips the remaining rows.
+        for id in [&first.invocations[0].id, &second.invocations[0].id] {
+            delete_invocation(
+                Path((TEST_NAMESPACE.to_string(), graph.name.clone(), id.clone())),
+                State(state.clone()),
+            )
+            .await
+            .unwrap();
+        }
+        let Json(third) = list(state.clone(), second.cursor.clone()).await.unwrap();
+        assert_eq!(third.invocations.len(), 1);
+        assert!(![&first.invocations[0].id, &second.invocations[0].id]
+            .contains(&&third.invocations[0].id));
+        assert!(third.cursor.is_none());
+
+        // A cursor can only resume the listing it was issued by.
+        let err = graph_invocations(
+            Path((TEST_NAMESPACE.to_string(), "other".to_string())),
+            Query(ListInvocationsParams {
+                limit: Some(1),
+                cursor: first.cursor.clone(),
+                status: None,
+                attribute_key: None,
+                attribute_value: None,
+            }),
+            State(state.clone()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::BAD_REQUEST);
+
+        // Cursors are only usable until they outlive the TTL.
+        state.config = Arc::new(ServerConfig {
+            cursor_ttl_secs: 0,
+            ..Default::default()
+        });
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let err = list(state.clone(), first.cursor).await.unwrap_err();
+        let (status, message) = error_response(err).await;
+        assert_eq!(status, StatusCode::GONE);
+        assert_eq!(
+            message,
+            "cursor expired, restart the listing from the first page"
+        );
+
+        let err = list(state, Some(vec![1, 2])).await.unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_graph_invocations_by_attribute() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        let config = ServerConfig {
+            indexed_attributes: vec!["customer".to_string()],
+            ..Default::default()
+        };
+        state.indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("indexed_state"),
+            config.indexify_state_options(),
+        )
+        .await?;
+        state.config = Arc::new(config);
+        state.indexify_state.upsert_namespace(&NamespaceRequest {
+            name: TEST_NAMESPACE.to_string(),
+            feature_flags: Default::default(),
+        })?;
+        let graph = mock_graph_a();
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invoke = |attributes: Option<&str>| {
+            invoke_with_object(
+                Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+                Query(InvocationQueryParams {
+                    block_until_finish: None,
+                    attributes: attributes.map(str::to_string),
+                }),
+                State(state.clone()),
+                Body::from("payload"),
+            )
+        };
+        let mut ids = vec![];
+        for attributes in [
+            Some(r#"{"customer": "acme", "source": "web"}"#),
+            Some(r#"{"customer": "acme|x"}"#),
+            Some(r#"{"customer": "globex"}"#),
+            Some(r#"{"customer": "acme"}"#),
+            None,
+        ] {
+            let response = invoke(attributes).await.unwrap().into_response();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+            let body = String::from_utf8(body.to_vec())?;
+            let data = body.trim().strip_prefix("data: ").unwrap();
+            let id = serde_json::from_str::<serde_json::Value>(data)?["id"].clone();
+            ids.push(id.as_str().unwrap().to_string());
+        }
+        let err = invoke(Some(r#"{"customer": 1}"#)).await.err().unwrap();
+        assert_eq!(error_response(err).await.0, StatusCode::BAD_REQUEST);
+
+        let list = |key: Option<&str>, value: Option<&str>| {
+            graph_invocations(
+                Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+                Query(ListInvocationsParams {
+                    limit: None,
+                    cursor: None,
+                    status: None,
+                    attribute_key: key.map(str::to_string),
+                    attribute_value: value.map(str::to_string),
+                }),
+                State(state.clone()),
+            )
+        };
+        let Json(acme) = list(Some("customer"), Some("acme")).await.unwrap();
+        let mut found = acme
+            .invocations
+            .iter()
+            .map(|invocation| invocation.id.clone())
+            .collect::<Vec<_>>();
+        found.sort();
+        let mut expected = vec![ids[0].clone(), ids[3].clone()];
+        expected.sort();
+        assert_eq!(found, expected);
+        let first = acme
+            .invocations
+            .iter()
+            .find(|invocation| invocation.id == ids[0])
+            .unwrap();
+        assert_eq!(first.attributes["source"], "web");
+
+        // The attribute query is paged like the listing of every invocation.
+        let page = |cursor: Option<Vec<u8>>| {
+            graph_invocations(
+                Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+                Query(ListInvocationsParams {
+                    limit: Some(1),
+                    cursor,
+                    status: None,
+                    attribute_key: Some("customer".to_string()),
+                    attribute_value: Some("acme".to_string()),
+                }),
+                State(state.clone()),
+            )
+        };
+        let Json(first_page) = page(None).await.unwrap();
+        let Json(second_page) = page(first_page.cursor.clone()).await.unwrap();
+        assert!(second_page.cursor.is_none());
+        let mut paged = [&first_page, &second_page]
+            .iter()
+            .flat_map(|page| {
+                page.invocations
+                    .iter()
+                    .map(|invocation| invocation.id.clone())
+            })
+            .collect::<Vec<_>>();
+        paged.sort();
+        assert_eq!(paged, expected);
+        // Cursors of another listing are rejected.
+        let Json(all_first_page) = graph_invocations(
+            Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+            Query(ListInvocationsParams {
+                limit: Some(1),
+                cursor: None,
+                status: None,
+                attribute_key: None,
+                attribute_value: None,
+            }),
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        let err = page(all_first_page.cursor).await.unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::BAD_REQUEST);
+
+        let Json(globex) = list(Some("customer"), Some("globex")).await.unwrap();
+        assert_eq!(globex.invocations.len(), 1);
+        assert_eq!(globex.invocations[0].id, ids[2]);
+        let Json(none) = list(Some("customer"), Some("initech")).await.unwrap();
+        assert!(none.invocations.is_empty());
+        let Json(all) = list(None, None).await.unwrap();
+        assert_eq!(all.invocations.len(), 5);
+
+        let (status, message) =
+            error_response(list(Some("source"), Some("web")).await.unwrap_err()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(message, "attribute source is not indexed");
+        let err = list(Some("customer"), None).await.unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::BAD_REQUEST);
+
+        // Deleted inputs are removed from the index.
+        delete_invocation(
+            Path((
+                TEST_NAMESPACE.to_string(),
+                graph.name.clone(),
+                ids[3].clone(),
+            )),
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        let Json(acme) = list(Some("customer"), Some("acme")).await.unwrap();
+        assert_eq!(acme.invocations.len(), 1);
+        assert_eq!(acme.invocations[0].id, ids[0]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_compute_graph_invalid_json() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+
+        let err = create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state),
+            HeaderMap::new(),
+            multipart(&[("compute_graph", "{\"name\": \"graph\",\n \"edges\": }")]).await,
+        )
+        .await
+        .unwrap_err();
+        let (status, message) = error_response(err).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(message, "invalid json at line 2 column 11: expected value");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_compute_graph_if_match() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let graph = mock_graph_a();
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let path = || Path((TEST_NAMESPACE.to_string(), graph.name.clone()));
+
+        let response = get_compute_graph(path(), Query(Default::default()), State(state.clone()))
+            .await
+            .unwrap()
+            .into_response();
+        let etag = response.headers()[header::ETAG].clone();
+
+        // A second writer changes the graph after it was read.
+        let mut changed_graph = graph.clone();
+        changed_graph.description = "changed by another writer".to_string();
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: changed_graph,
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let definition = serde_json::to_string(&ComputeGraph::from(graph.clone()))?;
+        let (blobs, _) = state.blob_storage.list("", None).await?;
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_MATCH, etag);
+        let err = update_compute_graph(
+            path(),
+            State(state.clone()),
+            headers,
+            multipart(&[("compute_graph", &definition), ("code", "code")]).await,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.into_response().status(),
+            StatusCode::PRECONDITION_FAILED
+        );
+
+        let stored = state
+            .indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, &graph.name)?
+            .unwrap();
+        assert_eq!(stored.description, "changed by another writer");
+        // The code of the rejected update wasn't uploaded.
+        assert_eq!(state.blob_storage.list("", None).await?.0, blobs);
+
+        // The update goes through when the current etag is one of those listed.
+        let response = get_compute_graph(path(), Query(Default::default()), State(state.clone()))
+            .await
+            .unwrap()
+            .into_response();
+        let etag = response.headers()[header::ETAG].to_str()?;
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_MATCH, format!("\"stale\", {}", etag).parse()?);
+        update_compute_graph(
+            path(),
+            State(state.clone()),
+            headers,
+            multipart(&[("compute_graph", &definition), ("code", "code")]).await,
+        )
+        .await
+        .unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_compute_graph_if_match() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let graph = mock_graph_a();
+        let write = |compute_graph: data_model::ComputeGraph| {
+            state.indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph,
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+        };
+        write(graph.clone()).await?;
+        let path = || Path((TEST_NAMESPACE.to_string(), graph.name.clone()));
+        let if_match = |etag: header::HeaderValue| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_MATCH, etag);
+            headers
+        };
+
+        let response = get_compute_graph(path(), Query(Default::default()), State(state.clone()))
+            .await
+            .unwrap()
+            .into_response();
+        let etag = response.headers()[header::ETAG].clone();
+
+        // A second writer changes the graph after it was read.
+        let mut changed_graph = graph.clone();
+        changed_graph.description = "changed by another writer".to_string();
+        write(changed_graph).await?;
+
+        let err = delete_compute_graph(path(), State(state.clone()), if_match(etag))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.into_response().status(),
+            StatusCode::PRECONDITION_FAILED
+        );
+        assert!(state
+            .indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, &graph.name)?
+            .is_some());
+
+        // With the current etag the delete goes through.
+        let response = get_compute_graph(path(), Query(Default::default()), State(state.clone()))
+            .await
+            .unwrap()
+            .into_response();
+        let etag = response.headers()[header::ETAG].clone();
+        delete_compute_graph(path(), State(state.clone()), if_match(etag.clone()))
+            .await
+            .unwrap();
+        assert!(state
+            .indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, &graph.name)?
+            .is_none());
+
+        let err = delete_compute_graph(path(), State(state.clone()), if_match(etag))
+            .await
+            .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reserved_compute_graph_names() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        for name in ["recent", "Lint"] {
+            let mut graph = mock_graph_a();
+            graph.name = name.to_string();
+            let definition = serde_json::to_string(&ComputeGraph::from(graph))?;
+            let fields = [("compute_graph", definition.as_str()), ("code", "code")];
+            let err = create_compute_graph(
+                Path(TEST_NAMESPACE.to_string()),
+                State(state.clone()),
+                HeaderMap::new(),
+                multipart(&fields).await,
+            )
+            .await
+            .unwrap_err();
+            let (status, message) = error_response(err).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(message, format!("compute graph name {} is reserved", name));
+        }
+        assert!(state
+            .indexify_state
+            .reader()
+            .list_compute_graphs(TEST_NAMESPACE, None, None)?
+            .0
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_namespace_strict_validation_flag() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        create_namespace(
+            State(state.clone()),
+            Json(CreateNamespace {
+                name: "strict".to_string(),
+                feature_flags: BTreeMap::from([(STRICT_VALIDATION_FLAG.to_string(), true)]),
+            }),
+        )
+        .await
+        .unwrap();
+        let mut graph = mock_graph_a();
+        graph.nodes.insert(
+            "fn_orphan".to_string(),
+            data_model::Node::Compute(data_model::ComputeFn {
+                name: "fn_orphan".to_string(),
+                ..Default::default()
+            }),
+        );
+        let definition = serde_json::to_string(&ComputeGraph::from(graph))?;
+        let fields = [("compute_graph", definition.as_str()), ("code", "code")];
+
+        let err = create_compute_graph(
+            Path("strict".to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&fields).await,
+        )
+        .await
+        .unwrap_err();
+        let (status, message) = error_response(err).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("fn_orphan"), "{}", message);
+
+        create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&fields).await,
+        )
+        .await
+        .unwrap();
+
+        // Once the flag is turned off the graph is accepted.
+        let Json(namespace) = set_namespace_flags(
+            Path("strict".to_string()),
+            State(state.clone()),
+            Json(NamespaceFeatureFlags {
+                feature_flags: BTreeMap::from([(STRICT_VALIDATION_FLAG.to_string(), false)]),
+            }),
+        )
+        .await
+        .unwrap();
+        assert!(!namespace.feature_flags[STRICT_VALIDATION_FLAG]);
+        let set_flags = |namespace: &str, flag: &str| {
+            set_namespace_flags(
+                Path(namespace.to_string()),
+                State(state.clone()),
+                Json(NamespaceFeatureFlags {
+                    feature_flags: BTreeMap::from([(flag.to_string(), true)]),
+                }),
+            )
+        };
+        let err = set_flags("missing", STRICT_VALIDATION_FLAG)
+            .await
+            .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+        assert!(state
+            .indexify_state
+            .reader()
+            .get_namespace("missing")?
+            .is_none());
+        let err = set_flags("strict", "no_such_flag").await.unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::BAD_REQUEST);
+        let err = create_namespace(
+            State(state.clone()),
+            Json(CreateNamespace {
+                name: "other".to_string(),
+                feature_flags: BTreeMap::from([("no_such_flag".to_string(), true)]),
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::BAD_REQUEST);
+        create_compute_graph(
+            Path("strict".to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&fields).await,
+        )
+        .await
+        .unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_namespace_policy() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let set_policy = |namespace: &str, input_ttl_secs| {
+            set_namespace_policy(
+                Path(namespace.to_string()),
+                State(state.clone()),
+                Json(NamespacePolicy {
+                    retention: RetentionPolicy { input_ttl_secs },
+                }),
+            )
+        };
+
+        let Json(namespace) = set_policy(TEST_NAMESPACE, Some(60)).await.unwrap();
+        assert_eq!(namespace.retention.input_ttl_secs, Some(60));
+        let stored = state
+            .indexify_state
+            .reader()
+            .get_namespace(TEST_NAMESPACE)?
+            .unwrap();
+        assert_eq!(stored.retention.input_ttl_secs, Some(60));
+
+        let Json(namespace) = set_policy(TEST_NAMESPACE, None).await.unwrap();
+        assert_eq!(namespace.retention.input_ttl_secs, None);
+
+        let err = set_policy("missing", Some(60)).await.unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+
+        // Outputs and versions aren't subject to retention.
+        for body in [
+            r#"{"retention": {"output_ttl_secs": 60}}"#,
+            r#"{"retention": {"max_versions": 3}}"#,
+        ] {
+            assert!(serde_json::from_str::<NamespacePolicy>(body).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unused_compute_graphs() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        // "graph" is a prefix of "graph_A", whose invocations must not count.
+        let mut graph = mock_graph_a();
+        graph.name = "graph".to_string();
+        for compute_graph in [mock_graph_a(), mock_graph_b(), graph] {
+            state
+                .indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        invoke_with_object(
+            Path((TEST_NAMESPACE.to_string(), "graph_A".to_string())),
+            Query(InvocationQueryParams {
+                block_until_finish: None,
+                attributes: None,
+            }),
+            State(state.clone()),
+            Body::from("payload"),
+        )
+        .await
+        .unwrap();
+
+        let Json(unused) = unused_compute_graphs(Path(TEST_NAMESPACE.to_string()), State(state))
+            .await
+            .unwrap();
+        let names: Vec<String> = unused.compute_graphs.into_iter().map(|g| g.name).collect();
+        assert_eq!(names, vec!["graph".to_string(), "graph_B".to_string()]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_graph_labels() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let labeled = |mut compute_graph: data_model::ComputeGraph, labels: &[(&str, &str)]| {
+            compute_graph.labels = labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            compute_graph
+        };
+        let mut graph_c = mock_graph_a();
+        graph_c.name = "graph_C".to_string();
+        for compute_graph in [
+            labeled(mock_graph_a(), &[("team", "search"), ("env", "prod")]),
+            labeled(mock_graph_b(), &[("team", "ads"), ("env", "prod")]),
+            labeled(graph_c, &[("team", "search"), ("tier", "gold")]),
+        ] {
+            state
+                .indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let Json(labels) = graph_labels(Path(TEST_NAMESPACE.to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        let values = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            labels.labels,
+            BTreeMap::from([
+                ("env".to_string(), values(&["prod"])),
+                ("team".to_string(), values(&["ads", "search"])),
+                ("tier".to_string(), values(&["gold"])),
+            ])
+        );
+
+        let _ = upsert_namespace(Path("empty".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        let Json(labels) = graph_labels(Path("empty".to_string()), State(state.clone()))
+            .await
+            .unwrap();
+        assert!(labels.labels.is_empty());
+        let err = graph_labels(Path("missing".to_string()), State(state.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compute_graphs_by_node() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        // graph_C keeps the node name fn_b but runs another function.
+        let mut graph_c = mock_graph_a();
+        graph_c.name = "graph_C".to_string();
+        if let Some(data_model::Node::Compute(fn_b)) = graph_c.nodes.get_mut("fn_b") {
+            fn_b.fn_name = "fn_b_v2".to_string();
+        }
+        for compute_graph in [mock_graph_a(), mock_graph_b(), graph_c] {
+            state
+                .indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let by_node = |node_type: &str| {
+            compute_graphs_by_node(
+                Path((TEST_NAMESPACE.to_string(), node_type.to_string())),
+                State(state.clone()),
+            )
+        };
+        let names = |graphs: ComputeGraphsList| -> Vec<String> {
+            graphs.compute_graphs.into_iter().map(|g| g.name).collect()
+        };
+        let Json(graphs) = by_node("fn_b").await.unwrap();
+        assert_eq!(names(graphs), vec!["graph_A", "graph_B"]);
+        let Json(graphs) = by_node("fn_b_v2").await.unwrap();
+        assert_eq!(names(graphs), vec!["graph_C"]);
+        let Json(graphs) = by_node("router_x").await.unwrap();
+        assert_eq!(names(graphs), vec!["graph_B"]);
+        let Json(graphs) = by_node("fn_d").await.unwrap();
+        assert!(graphs.compute_graphs.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recent_compute_graphs() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let write = |compute_graph: data_model::ComputeGraph| {
+            state.indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph,
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+        };
+        let mut graph_c = mock_graph_a();
+        graph_c.name = "graph_C".to_string();
+        for compute_graph in [mock_graph_a(), mock_graph_b(), graph_c] {
+            write(compute_graph).await?;
+            // Update times are in milliseconds.
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        let mut updated = mock_graph_a();
+        updated.description = "updated".to_string();
+        write(updated).await?;
+
+        let recent = |limit| {
+            recent_compute_graphs(
+                Path(TEST_NAMESPACE.to_string()),
+                Query(RecentComputeGraphsParams { limit }),
+                State(state.clone()),
+            )
+        };
+        let Json(recent_graphs) = recent(None).await.unwrap();
+        let names: Vec<String> = recent_graphs
+            .compute_graphs
+            .iter()
+            .map(|g| g.name.clone())
+            .collect();
+        assert_eq!(names, vec!["graph_A", "graph_C", "graph_B"]);
+        assert_eq!(recent_graphs.compute_graphs[0].description, "updated");
+        assert!(
+            recent_graphs.compute_graphs[0].updated_at > recent_graphs.compute_graphs[0].created_at
+        );
+        let Json(recent_graphs) = recent(Some(1)).await.unwrap();
+        assert_eq!(recent_graphs.compute_graphs.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_compute_graphs_by_actor() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        // The actor comes from the authenticated identity of the creation,
+        // which requests don't carry yet.
+        let create = |name: &str, actor: Option<&str>| {
+            let mut compute_graph = mock_graph_a();
+            compute_graph.name = name.to_string();
+            compute_graph.actor = actor.map(str::to_string);
+            let state = state.clone();
+            async move {
+                state
+                    .indexify_state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::CreateComputeGraph(Box::new(
+                            CreateComputeGraphRequest {
+                                namespace: TEST_NAMESPACE.to_string(),
+                                compute_graph,
+                                if_match: None,
+                                if_changed: false,
+                            },
+                        )),
+                        state_changes_processed: vec![],
+                    })
+                    .await
+                    .unwrap();
+            }
+        };
+        create("graph_a", Some("alice")).await;
+        create("graph_b", Some("bob")).await;
+        create("graph_c", Some("alice")).await;
+        create("graph_d", None).await;
+        // Updates keep the actor which created the graph.
+        create("graph_b", Some("alice")).await;
+
+        let list = |created_by: &str, enabled: Option<bool>, limit, cursor| {
+            list_compute_graphs(
+                Path(TEST_NAMESPACE.to_string()),
+                Query(ListComputeGraphsParams {
+                    limit,
+                    cursor,
+                    enabled,
+                    created_by: Some(created_by.to_string()),
+                    sort: None,
+                    order: None,
+                }),
+                State(state.clone()),
+            )
+        };
+        let names = |list: &ComputeGraphsList| {
+            list.compute_graphs
+                .iter()
+                .map(|compute_graph| compute_graph.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let Json(alice) = list("alice", None, None, None).await.unwrap();
+        assert_eq!(names(&alice), vec!["graph_a", "graph_c"]);
+        assert_eq!(alice.compute_graphs[0].actor.as_deref(), Some("alice"));
+        let Json(bob) = list("bob", None, None, None).await.unwrap();
+        assert_eq!(names(&bob), vec!["graph_b"]);
+        let Json(bob) = list("bob", Some(true), None, None).await.unwrap();
+        assert_eq!(names(&bob), vec!["graph_b"]);
+        let Json(nobody) = list("carol", None, None, None).await.unwrap();
+        assert!(nobody.compute_graphs.is_empty());
+
+        let Json(first) = list("alice", None, Some(1), None).await.unwrap();
+        assert_eq!(names(&first), vec!["graph_a"]);
+        let Json(second) = list("alice", None, Some(1), first.cursor).await.unwrap();
+        assert_eq!(names(&second), vec!["graph_c"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_compute_graphs_by_code_size() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        for (name, size) in [
+            ("graph_small", 10),
+            ("graph_large", 300),
+            ("graph_medium", 50),
+        ] {
+            let mut compute_graph = mock_graph_a();
+            compute_graph.name = name.to_string();
+            compute_graph.code.size = size;
+            state
+                .indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let list = |order, limit, cursor| {
+            list_compute_graphs(
+                Path(TEST_NAMESPACE.to_string()),
+                Query(ListComputeGraphsParams {
+                    limit,
+                    cursor,
+                    enabled: None,
+                    created_by: None,
+                    sort: Some(ComputeGraphSort::CodeSize),
+                    order,
+                }),
+                State(state.clone()),
+            )
+        };
+        let names = |list: ComputeGraphsList| -> Vec<String> {
+            list.compute_graphs.into_iter().map(|g| g.name).collect()
+        };
+
+        let Json(listed) = list(Some(SortOrder::Desc), None, None).await.unwrap();
+        assert_eq!(
+            names(listed),
+            vec!["graph_large", "graph_medium", "graph_small"]
+        );
+        // Descending is the default.
+        let Json(listed) = list(None, Some(2), None).await.unwrap();
+        assert_eq!(listed.cursor, None);
+        assert_eq!(names(listed), vec!["graph_large", "graph_medium"]);
+        let Json(listed) = list(Some(SortOrder::Asc), Some(1), None).await.unwrap();
+        assert_eq!(names(listed), vec!["graph_small"]);
+
+        let err = list(None, None, Some(vec![1])).await.unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_namespace() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        for compute_graph in [mock_graph_a(), mock_graph_b()] {
+            state
+                .indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        let delete = |force| {
+            delete_namespace(
+                Path(TEST_NAMESPACE.to_string()),
+                Query(DeleteNamespaceParams { force }),
+                State(state.clone()),
+            )
+        };
+
+        let (status, message) = error_response(delete(None).await.unwrap_err()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(
+            message,
+            format!(
+                "namespace {} has 2 compute graphs, delete them first or force the deletion",
+                TEST_NAMESPACE
+            )
+        );
+        let reader = state.indexify_state.reader();
+        assert!(reader.get_namespace(TEST_NAMESPACE)?.is_some());
+        assert!(reader
+            .get_compute_graph(TEST_NAMESPACE, "graph_A")?
+            .is_some());
+
+        delete(Some(true)).await.unwrap();
+        assert!(reader.get_namespace(TEST_NAMESPACE)?.is_none());
+        let (compute_graphs, _) =
+            reader.filter_compute_graphs(TEST_NAMESPACE, |_| true, None, None)?;
+        assert!(compute_graphs.is_empty());
+
+        let (status, _) = error_response(delete(Some(true)).await.unwrap_err()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compute_graph_creation_histogram() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+        let day = 20_000 * DAY_MS;
+        let graphs = [
+            ("graph_1", day + 1000),
+            ("graph_2", day + DAY_MS - 1),
+            ("graph_3", day + DAY_MS + 5000),
+        ];
+        for (name, created_at) in graphs {
+            let mut graph = mock_graph_a();
+            graph.name = name.to_string();
+            graph.created_at = created_at;
+            state
+                .indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph: graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let Json(histogram) = compute_graph_creation_histogram(
+            Path(TEST_NAMESPACE.to_string()),
+            Query(CreationHistogramParams {
+                bucket: None,
+                start: None,
+                end: None,
+            }),
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        let buckets: Vec<(u64, usize)> = histogram
+            .buckets
+            .iter()
+            .map(|bucket| (bucket.start, bucket.count))
+            .collect();
+        assert_eq!(buckets, vec![(day, 2), (day + DAY_MS, 1)]);
+
+        // The end of the range is exclusive.
+        let Json(histogram) = compute_graph_creation_histogram(
+            Path(TEST_NAMESPACE.to_string()),
+            Query(CreationHistogramParams {
+                bucket: Some(HistogramBucket::Day),
+                start: Some(day),
+                end: Some(day + DAY_MS),
+            }),
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(histogram.buckets.len(), 1);
+        assert_eq!(histogram.buckets[0].count, 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_limiter() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        state.upload_limiter = ConcurrencyLimiter::new("uploads", 1, Duration::from_millis(200));
+        let definition = |name: &str| {
+            let mut graph = mock_graph_a();
+            graph.name = name.to_string();
+            serde_json::to_string(&ComputeGraph::from(graph)).unwrap()
+        };
+        let create = |state: RouteState, definition: String| async move {
+            let fields = [("compute_graph", definition.as_str()), ("code", "code")];
+            create_compute_graph(
+                Path(TEST_NAMESPACE.to_string()),
+                State(state),
+                HeaderMap::new(),
+                multipart(&fields).await,
+            )
+            .await
+        };
+
+        // Uploads wait for the slot and give up after the timeout.
+        let permit = state.upload_limiter.acquire().await.unwrap();
+        let err = create(state.clone(), definition("graph_1"))
+            .await
+            .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::SERVICE_UNAVAILABLE);
+        drop(permit);
+
+        // More uploads than permits run one after the other.
+        state.upload_limiter = ConcurrencyLimiter::new("uploads", 1, Duration::from_secs(10));
+        let results = futures::future::join_all(
+            (0..4).map(|i| create(state.clone(), definition(&format!("graph_{}", i)))),
+        )
+        .await;
+        assert!(results.iter().all(|result| result.is_ok()));
+
+        // A failed upload releases its permit.
+        let boundary = "indexify-test-boundary";
+        let body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"compute_graph\"\r\n\r\n{}\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"code\"\r\n\
+             Content-Encoding: gzip\r\n\r\nnot gzip\r\n--{b}--\r\n",
+            definition("graph_invalid"),
+            b = boundary
+        );
+        let request = Request::builder()
+            .method(Method::POST)
+            .header(
+                hyper::header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(Body::from(body))
+            .unwrap();
+        let err = create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            Multipart::from_request(request, &()).await.unwrap(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(state.upload_limiter.permits.available_permits(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_instantiate_compute_graph() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let definition = serde_json::to_string(&ComputeGraph::from(mock_graph_a()))?;
+        create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&[("compute_graph", &definition), ("code", "some code")]).await,
+        )
+        .await
+        .unwrap();
+        let instantiate = |name: &str, copy_code: bool| {
+            instantiate_compute_graph(
+                Path((TEST_NAMESPACE.to_string(), "graph_A".to_string())),
+                State(state.clone()),
+                Json(InstantiateComputeGraph {
+                    name: name.to_string(),
+                    description: None,
+                    node_overrides: std::collections::HashMap::from([(
+                        "fn_b".to_string(),
+                        NodeOverride {
+                            image_name: Some("custom_image".to_string()),
+                            ..Default::default()
+                        },
+                    )]),
+                    copy_code,
+                }),
+            )
+        };
+
+        let Json(created) = instantiate("graph_shared", false).await.unwrap();
+        assert_eq!(created.name, "graph_shared");
+        let reader = state.indexify_state.reader();
+        let template = reader
+            .get_compute_graph(TEST_NAMESPACE, "graph_A")?
+            .unwrap();
+        let shared = reader
+            .get_compute_graph(TEST_NAMESPACE, "graph_shared")?
+            .unwrap();
+        assert_eq!(shared.nodes["fn_b"].image_name(), "custom_image");
+        for (name, node) in &template.nodes {
+            if name != "fn_b" {
+                assert_eq!(&shared.nodes[name], node);
+            }
+        }
+        assert_eq!(shared.start_fn, template.start_fn);
+        assert_eq!(shared.edges, template.edges);
+        assert_eq!(shared.code, template.code);
+
+        let _ = instantiate("graph_copied", true).await.unwrap();
+        let copied = reader
+            .get_compute_graph(TEST_NAMESPACE, "graph_copied")?
+            .unwrap();
+        assert_ne!(copied.code.path, template.code.path);
+        assert_eq!(copied.code.sha256_hash, template.code.sha256_hash);
+
+        let err = instantiate("graph_shared", false).await.unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::CONFLICT);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lint_compute_graph() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let mut graph = mock_graph_a();
+        graph.nodes.insert(
+            "fn_orphan".to_string(),
+            data_model::Node::Compute(data_model::ComputeFn {
+                name: "fn_orphan".to_string(),
+                ..Default::default()
+            }),
+        );
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let Json(lint) = lint_compute_graph(
+            Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(lint.warnings.len(), 1);
+        assert_eq!(lint.warnings[0].kind, "unreachable_node");
+        assert_eq!(lint.warnings[0].nodes, vec!["fn_orphan".to_string()]);
+
+        let definition = serde_json::to_string(&ComputeGraph::from(graph))?;
+        let Json(lint) = lint_compute_graph_definition(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            definition,
+        )
+        .await
+        .unwrap();
+        assert_eq!(lint.warnings.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compute_graph_stats() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_b(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let Json(stats) = compute_graph_stats(
+            Path((TEST_NAMESPACE.to_string(), "graph_B".to_string())),
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            (
+                stats.nodes,
+                stats.edges,
+                stats.max_fan_out,
+                stats.router_count
+            ),
+            (4, 3, 2, 1)
+        );
+
+        let err = compute_graph_stats(
+            Path((TEST_NAMESPACE.to_string(), "missing".to_string())),
+            State(state),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compute_graph_edges() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        // fn_a feeds router_x, which branches to fn_b and fn_c. fn_b also has
+        // a regular edge to fn_c.
+        let mut graph = mock_graph_b();
+        graph
+            .edges
+            .insert("fn_b".to_string(), vec!["fn_c".to_string()]);
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let Json(edges) = compute_graph_edges(
+            Path((TEST_NAMESPACE.to_string(), graph.name.clone())),
+            State(state.clone()),
+        )
+        .await
+        .unwrap();
+        let edge = |source: &str, target: &str| ComputeGraphEdge {
+            source: source.to_string(),
+            target: target.to_string(),
+        };
+        assert_eq!(
+            edges.edges,
+            vec![
+                edge("fn_a", "router_x"),
+                edge("fn_b", "fn_c"),
+                edge("router_x", "fn_b"),
+                edge("router_x", "fn_c"),
+            ]
+        );
+
+        let err = compute_graph_edges(
+            Path((TEST_NAMESPACE.to_string(), "missing".to_string())),
+            State(state.clone()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_compute_graph_depth() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        // fn_a -> router_x -> fn_b -> fn_d, the longest path has 4 nodes.
+        let mut graph = mock_graph_b();
+        graph.nodes.insert(
+            "fn_d".to_string(),
+            data_model::Node::Compute(data_model::ComputeFn {
+                name: "fn_d".to_string(),
+                ..Default::default()
+            }),
+        );
+        graph
+            .edges
+            .insert("fn_b".to_string(), vec!["fn_d".to_string()]);
+        // Cycles are rejected by the execution plan but not when writing.
+        let mut cyclic = mock_graph_a();
+        cyclic.name = "graph_cyclic".to_string();
+        cyclic
+            .edges
+            .insert("fn_b".to_string(), vec!["fn_c".to_string()]);
+        cyclic
+            .edges
+            .insert("fn_c".to_string(), vec!["fn_b".to_string()]);
+        for compute_graph in [graph.clone(), cyclic.clone()] {
+            state
+                .indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let get = |name: &str, include: Option<&str>| {
+            get_compute_graph(
+                Path((TEST_NAMESPACE.to_string(), name.to_string())),
+                Query(GetComputeGraphParams {
+                    include: include.map(|include| include.to_string()),
+                    raw: None,
+                }),
+                State(state.clone()),
+            )
+        };
+        let depth = |response: axum::response::Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+            let compute_graph: serde_json::Value = serde_json::from_slice(&body)?;
+            anyhow::Ok(compute_graph.get("depth").cloned())
+        };
+
+        // The depth is left out unless requested.
+        let response = get(&graph.name, None).await.unwrap().into_response();
+        assert_eq!(depth(response).await?, None);
+        let response = get(&graph.name, Some("depth"))
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(depth(response).await?, Some(serde_json::json!(4)));
+
+        let response = get(&cyclic.name, Some("depth"))
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(depth(response).await?, None);
+
+        let err = get(&graph.name, Some("depth,size")).await.err().unwrap();
+        assert_eq!(error_response(err).await.0, StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_compute_graph_raw() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let reader = state.indexify_state.reader();
+        let stored = reader
+            .get_compute_graph_raw(TEST_NAMESPACE, "graph_A")?
+            .unwrap();
+
+        let get = |raw: bool| {
+            get_compute_graph(
+                Path((TEST_NAMESPACE.to_string(), "graph_A".to_string())),
+                Query(GetComputeGraphParams {
+                    include: None,
+                    raw: Some(raw),
+                }),
+                State(state.clone()),
+            )
+        };
+        let response = get(true).await.unwrap().into_response();
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        let etag = response.headers().get(header::ETAG).cloned();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        assert_eq!(body, stored);
+        let compute_graph: data_model::ComputeGraph = serde_json::from_slice(&body)?;
+        assert_eq!(
+            Some(compute_graph),
+            reader.get_compute_graph(TEST_NAMESPACE, "graph_A")?
+        );
+
+        // Both representations share the etag of the stored graph.
+        let response = get(false).await.unwrap().into_response();
+        assert_eq!(response.headers().get(header::ETAG).cloned(), etag);
+
+        let err = get_compute_graph(
+            Path((TEST_NAMESPACE.to_string(), "missing".to_string())),
+            Query(GetComputeGraphParams {
+                include: None,
+                raw: Some(true),
+            }),
+            State(state.clone()),
+        )
+        .await
+        .err()
+        .unwrap();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_compute_graph_from_yaml() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let graph = mock_graph_b();
+        let definition = serde_json::to_value(ComputeGraph::from(graph.clone()))?;
+        let json_definition = serde_json::to_string(&definition)?;
+        let yaml_definition = serde_yml::to_string(&definition)?;
+        for namespace in ["json", "yaml"] {
+            state.indexify_state.upsert_namespace(&NamespaceRequest {
+                name: namespace.to_string(),
+                feature_flags: Default::default(),
+            })?;
+        }
+
+        create_compute_graph(
+            Path("json".to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            typed_multipart(&[
+                ("compute_graph", Some("application/json"), &json_definition),
+                ("code", None, "code"),
+            ])
+            .await,
+        )
+        .await
+        .unwrap();
+        create_compute_graph(
+            Path("yaml".to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            typed_multipart(&[
+                ("compute_graph", Some("application/yaml"), &yaml_definition),
+                ("code", None, "code"),
+            ])
+            .await,
+        )
+        .await
+        .unwrap();
+
+        let reader = state.indexify_state.reader();
+        let from_json = reader.get_compute_graph("json", &graph.name)?.unwrap();
+        let mut from_yaml = reader.get_compute_graph("yaml", &graph.name)?.unwrap();
+        assert_ne!(from_yaml.code.path, from_json.code.path);
+        from_yaml.namespace = from_json.namespace.clone();
+        from_yaml.code.path = from_json.code.path.clone();
+        from_yaml.created_at = from_json.created_at;
+        from_yaml.updated_at = from_json.updated_at;
+        assert_eq!(from_yaml, from_json);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_compute_graph_field_order() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let definition = serde_json::to_string(&ComputeGraph::from(mock_graph_a()))?;
+        for namespace in ["graph_first", "code_first"] {
+            state.indexify_state.upsert_namespace(&NamespaceRequest {
+                name: namespace.to_string(),
+                feature_flags: Default::default(),
+            })?;
+        }
+        let create = |namespace: &str, multipart: Multipart| {
+            create_compute_graph(
+                Path(namespace.to_string()),
+                State(state.clone()),
+                HeaderMap::new(),
+                multipart,
+            )
+        };
+
+        create(
+            "graph_first",
+            multipart(&[("compute_graph", &definition), ("code", "code")]).await,
+        )
+        .await
+        .unwrap();
+        create(
+            "code_first",
+            multipart(&[("code", "code"), ("compute_graph", &definition)]).await,
+        )
+        .await
+        .unwrap();
+
+        let cases = [
+            (
+                vec![("compute_graph", definition.as_str())],
+                "Code is required",
+            ),
+            (
+                vec![("code", "code")],
+                "Compute graph definition is required",
+            ),
+            (vec![], "Compute graph definition is required"),
+        ];
+        for (fields, expected) in cases {
+            let err = create(TEST_NAMESPACE, multipart(&fields).await)
+                .await
+                .unwrap_err();
+            assert_eq!(
+                error_response(err).await,
+                (StatusCode::BAD_REQUEST, expected.to_string())
+            );
+        }
+
+        // An invalid definition is reported whatever the position of the code.
+        let invalid_orders = [
+            [("compute_graph", "{"), ("code", "code")],
+            [("code", "code"), ("compute_graph", "{")],
+        ];
+        let mut messages = vec![];
+        for fields in invalid_orders {
+            let err = create(TEST_NAMESPACE, multipart(&fields).await)
+                .await
+                .unwrap_err();
+            let (status, message) = error_response(err).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            messages.push(message);
+        }
+        assert_eq!(messages[0], messages[1]);
+        assert_ne!(messages[0], "Code is required");
+
+        // A code field cut short is reported as such rather than as missing.
+        let boundary = "indexify-test-boundary";
+        let body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"compute_graph\"\r\n\r\n{}\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"code\"\r\n\r\ncode",
+            definition,
+            b = boundary
+        );
+        let request = Request::builder()
+            .method(Method::POST)
+            .header(
+                hyper::header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(Body::from(body))?;
+        let truncated = Multipart::from_request(request, &()).await.unwrap();
+        let err = create(TEST_NAMESPACE, truncated).await.unwrap_err();
+        let (status, message) = error_response(err).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(
+            message.starts_with("failed to read code field"),
+            "{}",
+            message
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_code_metadata() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let definition = serde_json::to_string(&ComputeGraph::from(mock_graph_a()))?;
+        create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&[("compute_graph", &definition), ("code", "some code")]).await,
+        )
+        .await
+        .unwrap();
+        let code = state
+            .indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, "graph_A")?
+            .unwrap()
+            .code;
+
+        let response = code_metadata(
+            Path((TEST_NAMESPACE.to_string(), "graph_A".to_string())),
+            State(state.clone()),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[header::CONTENT_LENGTH], code.size.to_string());
+        assert_eq!(code.size, 9);
+        assert_eq!(headers[header::ETAG], format!("\"{}\"", code.sha256_hash));
+        assert_eq!(headers[header::CONTENT_TYPE], "application/octet-stream");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        assert!(body.is_empty());
+
+        let err = code_metadata(
+            Path((TEST_NAMESPACE.to_string(), "unknown".to_string())),
+            State(state.clone()),
+        )
+        .await
+        .err()
+        .unwrap();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compute_graphs_with_missing_code() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        for graph in [mock_graph_a(), mock_graph_b()] {
+            let definition = serde_json::to_string(&ComputeGraph::from(graph))?;
+            create_compute_graph(
+                Path(TEST_NAMESPACE.to_string()),
+                State(state.clone()),
+                HeaderMap::new(),
+                multipart(&[("compute_graph", &definition), ("code", "some code")]).await,
+            )
+            .await
+            .unwrap();
+        }
+        let missing_code = |namespace: &str| {
+            compute_graphs_with_missing_code(Path(namespace.to_string()), State(state.clone()))
+        };
+        let Json(missing) = missing_code(TEST_NAMESPACE).await.unwrap();
+        assert!(missing.compute_graphs.is_empty());
+
+        let code_url = state
+            .indexify_state
+            .reader()
+            .get_code_url(TEST_NAMESPACE, "graph_B")?
+            .unwrap();
+        std::fs::remove_file(code_url.strip_prefix("file://").unwrap())?;
+        let Json(missing) = missing_code(TEST_NAMESPACE).await.unwrap();
+        assert_eq!(missing.compute_graphs, vec!["graph_B"]);
+
+        let err = missing_code("missing").await.unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_compute_graph_idempotent() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let definition = serde_json::to_string(&ComputeGraph::from(mock_graph_a()))?;
+        let create = |idempotent: &str, code: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(IDEMPOTENT_HEADER, idempotent.parse().unwrap());
+            let state = state.clone();
+            let definition = definition.clone();
+            async move {
+                super::create_compute_graph(
+                    Path(TEST_NAMESPACE.to_string()),
+                    State(state),
+                    headers,
+                    multipart(&[("compute_graph", &definition), ("code", code)]).await,
+                )
+                .await
+            }
+        };
+        let stored = || {
+            state
+                .indexify_state
+                .reader()
+                .get_compute_graph(TEST_NAMESPACE, "graph_A")
+                .unwrap()
+                .unwrap()
+        };
+
+        let Json(created) = create("true", "code").await.unwrap();
+        assert!(created.created);
+        let first = stored();
+
+        // Identical content leaves the stored graph and its code untouched.
+        let Json(created) = create("true", "code").await.unwrap();
+        assert!(!created.created);
+        assert_eq!(created.compute_graph.updated_at, first.updated_at);
+        assert_eq!(stored(), first);
+        let (blobs, _) = state.blob_storage.list("", None).await?;
+        assert_eq!(blobs.len(), 1);
+
+        // Different content writes a new version.
+        let Json(created) = create("true", "new code").await.unwrap();
+        assert!(created.created);
+        assert_eq!(stored().version, first.version.next());
+
+        let err = create("yes", "code").await.unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_swap_compute_graph_code() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let definition = serde_json::to_string(&ComputeGraph::from(mock_graph_a()))?;
+        create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&[("compute_graph", &definition), ("code", "old code")]).await,
+        )
+        .await
+        .unwrap();
+        let stored = || {
+            state
+                .indexify_state
+                .reader()
+                .get_compute_graph(TEST_NAMESPACE, "graph_A")
+                .unwrap()
+                .unwrap()
+        };
+        let before = stored();
+        let swap = |name: &str, code: &'static str| {
+            let state = state.clone();
+            let name = name.to_string();
+            async move {
+                swap_compute_graph_code(
+                    Path((TEST_NAMESPACE.to_string(), name)),
+                    Query(SwapCodeParams {
+                        delete_previous: Some(true),
+                    }),
+                    State(state),
+                    multipart(&[("code", code)]).await,
+                )
+                .await
+            }
+        };
+
+        let Json(swapped) = swap("graph_A", "new code").await.unwrap();
+        let after = stored();
+        assert_ne!(after.code.path, before.code.path);
+        assert_eq!(after.code.size, "new code".len() as u64);
+        assert_ne!(after.code.sha256_hash, before.code.sha256_hash);
+        assert_eq!(swapped.updated_at, after.updated_at);
+        assert_eq!(after.version, before.version.next());
+        assert_eq!(after.nodes, before.nodes);
+        assert_eq!(after.edges, before.edges);
+        assert_eq!(
+            state.blob_storage.read_bytes(&after.code.path).await?,
+            "new code"
+        );
+
+        // The previous code is deleted by the garbage collector.
+        assert_eq!(
+            state.indexify_state.reader().get_gc_urls(None)?,
+            vec![before.code.path.clone()]
+        );
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(());
+        let mut gc = crate::gc::Gc::new(
+            state.indexify_state.clone(),
+            state.blob_storage.clone(),
+            shutdown_rx,
+        );
+        tokio::spawn(async move { gc.start().await });
+        let started_at = std::time::Instant::now();
+        while !state.indexify_state.reader().get_gc_urls(None)?.is_empty() {
+            assert!(started_at.elapsed() < Duration::from_secs(10));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        shutdown_tx.send(()).unwrap();
+        assert!(state
+            .blob_storage
+            .read_bytes(&before.code.path)
+            .await
+            .is_err());
+
+        let err = swap("missing", "code").await.unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_code_url() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let definition = serde_json::to_string(&ComputeGraph::from(mock_graph_a()))?;
+        create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&[("compute_graph", &definition), ("code", "some code")]).await,
+        )
+        .await
+        .unwrap();
+        let stored = state
+            .indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, "graph_A")?
+            .unwrap()
+            .code
+            .path;
+
+        let code_url_of = |name: &str, presign| {
+            code_url(
+                Path((TEST_NAMESPACE.to_string(), name.to_string())),
+                Query(CodeUrlParams { presign }),
+                State(state.clone()),
+            )
+        };
+        let Json(code) = code_url_of("graph_A", None).await.unwrap();
+        assert_eq!(code.url, stored);
+        assert_eq!(state.blob_storage.read_bytes(&code.url).await?, "some code");
+        assert_eq!(code.presigned_url, None);
+
+        // Local disk storage can't presign urls.
+        let Json(code) = code_url_of("graph_A", Some(true)).await.unwrap();
+        assert_eq!(code.url, stored);
+        assert_eq!(code.presigned_url, None);
+
+        let err = code_url_of("unknown", None).await.unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gc_blobs_defaults_to_dry_run() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let Json(report) = gc_blobs(Query(GcBlobsParams { dry_run: None }), State(state.clone()))
+            .await
+            .unwrap();
+        assert!(report.dry_run);
+        assert!(report.deleted.is_empty());
+
+        let Json(report) = gc_blobs(
+            Query(GcBlobsParams {
+                dry_run: Some(false),
+            }),
+            State(state),
+        )
+        .await
+        .unwrap();
+        assert!(!report.dry_run);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_code_verify() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let definition = serde_json::to_string(&ComputeGraph::from(mock_graph_a()))?;
+        create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&[("compute_graph", &definition), ("code", "some code")]).await,
+        )
+        .await
+        .unwrap();
+        let get_code_body = |verify| {
+            let state = state.clone();
+            async move {
+                let response = get_code(
+                    Path((TEST_NAMESPACE.to_string(), "graph_A".to_string())),
+                    Query(GetCodeParams { verify }),
+                    State(state),
+                )
+                .await
+                .unwrap()
+                .into_response();
+                assert_eq!(response.status(), StatusCode::OK);
+                axum::body::to_bytes(response.into_body(), usize::MAX).await
+            }
+        };
+        assert_eq!(get_code_body(Some(true)).await.unwrap(), "some code");
+
+        // Corrupt the code in blob storage, keeping its size.
+        let path = state
+            .indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, "graph_A")?
+            .unwrap()
+            .code
+            .path;
+        std::fs::write(path.strip_prefix("file://").unwrap(), "some c0de")?;
+
+        let err = get_code_body(Some(true)).await.unwrap_err();
+        assert!(err.to_string().contains("has sha256"), "{}", err);
+        // Without verification the corrupted code is served as is.
+        assert_eq!(get_code_body(None).await.unwrap(), "some c0de");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_compute_graph_gzipped_code() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let definition = serde_json::to_string(&ComputeGraph::from(mock_graph_a()))?;
+        let code = "some code ".repeat(1000);
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(code.as_bytes())?;
+        let gzipped = encoder.finish()?;
+        let gzip_multipart = |code: &[u8]| {
+            let boundary = "indexify-test-boundary";
+            let mut body = format!(
+                "--{b}\r\nContent-Disposition: form-data; name=\"compute_graph\"\r\n\r\n{}\r\n\
+                 --{b}\r\nContent-Disposition: form-data; name=\"code\"\r\n\
+                 Content-Encoding: gzip\r\n\r\n",
+                definition,
+                b = boundary
+            )
+            .into_bytes();
+            body.extend_from_slice(code);
+            body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+            let request = Request::builder()
+                .method(Method::POST)
+                .header(
+                    hyper::header::CONTENT_TYPE,
+                    format!("multipart/form-data; boundary={}", boundary),
+                )
+                .body(Body::from(body))
+                .unwrap();
+            Multipart::from_request(request, &())
+        };
+
+        create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            gzip_multipart(&gzipped).await.unwrap(),
+        )
+        .await
+        .unwrap();
+        let stored = state
+            .indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, "graph_A")?
+            .unwrap()
+            .code;
+        assert_eq!(stored.size, code.len() as u64);
+        assert_eq!(
+            stored.sha256_hash,
+            format!("{:x}", Sha256::digest(code.as_bytes()))
+        );
+        let mut blob = state.blob_storage.get(&stored.path)?.get().await?;
+        let mut blob_bytes = Vec::new();
+        while let Some(chunk) = blob.next().await {
+            blob_bytes.extend_from_slice(&chunk?);
+        }
+        assert_eq!(blob_bytes, code.as_bytes());
+
+        let err = create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            gzip_multipart(b"not gzip").await.unwrap(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::BAD_REQUEST);
+        // Nothing was stored for the rejected upload.
+        assert_eq!(state.blob_storage.list("", None).await?.0.len(), 1);
+
+        // Code is limited by its decompressed size, whether it's gzipped or
+        // not.
+        let mut state = state;
+        state.config = Arc::new(ServerConfig {
+            max_compute_graph_code_bytes: code.len() - 1,
+            ..Default::default()
+        });
+        let err = create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            gzip_multipart(&gzipped).await.unwrap(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::PAYLOAD_TOO_LARGE);
+        let fields = [
+            ("compute_graph", definition.as_str()),
+            ("code", code.as_str()),
+        ];
+        let err = create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&fields).await,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(state.blob_storage.list("", None).await?.0.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_namespace() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        for (graph, code) in [(mock_graph_a(), "code of a"), (mock_graph_b(), "code of b")] {
+            let definition = serde_json::to_string(&ComputeGraph::from(graph))?;
+            create_compute_graph(
+                Path(TEST_NAMESPACE.to_string()),
+                State(state.clone()),
+                HeaderMap::new(),
+                multipart(&[("compute_graph", &definition), ("code", code)]).await,
+            )
+            .await
+            .unwrap();
+        }
+
+        let response = export_namespace(Path(TEST_NAMESPACE.to_string()), State(state.clone()))
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-tar"
+        );
+        let archive = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let entries = archive_files(&archive)?;
+        let paths: Vec<&str> = entries.iter().map(|e| e.0.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "graph_A/compute_graph.json",
+                "graph_A/code",
+                "graph_B/compute_graph.json",
+                "graph_B/code",
+            ]
+        );
+        assert_eq!(entries[1].1, b"code of a");
+        assert_eq!(entries[3].1, b"code of b");
+        let definition: ComputeGraph = serde_json::from_slice(&entries[2].1)?;
+        assert_eq!(definition.name, "graph_B");
+
+        state.indexify_state.upsert_namespace(&NamespaceRequest {
+            name: "restored".to_string(),
+            feature_flags: Default::default(),
+        })?;
+        let Json(imported) = import_namespace(
+            Path("restored".to_string()),
+            State(state.clone()),
+            Body::from(archive),
+        )
+        .await
+        .unwrap();
+        assert_eq!(imported.compute_graphs, vec!["graph_A", "graph_B"]);
+        let reader = state.indexify_state.reader();
+        let original = reader
+            .get_compute_graph(TEST_NAMESPACE, "graph_B")?
+            .unwrap();
+        let restored = reader.get_compute_graph("restored", "graph_B")?.unwrap();
+        assert_eq!(restored.code.sha256_hash, original.code.sha256_hash);
+        assert_eq!(restored.nodes, original.nodes);
+
+        // A graph moved to the directory of another name is rejected.
+        let mut renamed = tar::Builder::new(Vec::new());
+        for (path, data) in &entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            let path = path.replace("graph_B/", "graph_C/");
+            renamed.append_data(&mut header, path, data.as_slice())?;
+        }
+        let renamed = renamed.into_inner()?;
+        let (status, message) = error_response(
+            import_namespace(
+                Path("restored".to_string()),
+                State(state.clone()),
+                Body::from(renamed),
+            )
+            .await
+            .unwrap_err(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            message,
+            "archive has the definition of graph_B under graph_C"
+        );
+
+        let err = export_namespace(Path("missing".to_string()), State(state.clone()))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_namespace_failures_leave_nothing_behind() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        for (graph, code) in [(mock_graph_a(), "code of a"), (mock_graph_b(), "code of b")] {
+            let definition = serde_json::to_string(&ComputeGraph::from(graph))?;
+            create_compute_graph(
+                Path(TEST_NAMESPACE.to_string()),
+                State(state.clone()),
+                HeaderMap::new(),
+                multipart(&[("compute_graph", &definition), ("code", code)]).await,
+            )
+            .await
+            .unwrap();
+        }
+        let response = export_namespace(Path(TEST_NAMESPACE.to_string()), State(state.clone()))
+            .await
+            .unwrap()
+            .into_response();
+        let archive = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let blob_count = |state: RouteState| async move {
+            anyhow::Ok(state.blob_storage.list("", None).await?.0.len())
+        };
+        assert_eq!(blob_count(state.clone()).await?, 2);
+
+        state.indexify_state.upsert_namespace(&NamespaceRequest {
+            name: "restored".to_string(),
+            feature_flags: Default::default(),
+        })?;
+        // The code of graph_A is uploaded before the definition of graph_B
+        // is rejected.
+        state.config = Arc::new(ServerConfig {
+            max_nodes: 3,
+            ..Default::default()
+        });
+        let err = import_namespace(
+            Path("restored".to_string()),
+            State(state.clone()),
+            Body::from(archive.clone()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+        state.config = Arc::new(ServerConfig::default());
+        let truncated = archive.slice(..archive.len() - 2048);
+        let (status, message) = error_response(
+            import_namespace(
+                Path("restored".to_string()),
+                State(state.clone()),
+                Body::from(truncated),
+            )
+            .await
+            .unwrap_err(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(message, "tar archive is missing its end marker");
+
+        let (compute_graphs, _) = state
+            .indexify_state
+            .reader()
+            .list_compute_graphs("restored", None, None)?;
+        assert!(compute_graphs.is_empty());
+        assert_eq!(blob_count(state).await?, 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_namespace_fails_on_missing_code() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        let mut compute_graph = mock_graph_a();
+        compute_graph.code.path = format!("file://{}/missing", temp_dir.path().display());
+        compute_graph.code.size = 4;
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph,
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        // The body fails instead of ending like a complete archive.
+        let response = export_namespace(Path(TEST_NAMESPACE.to_string()), State(state))
+            .await
+            .unwrap()
+            .into_response();
+        assert!(axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_namespace_streams_incrementally() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+
+        // The code of the graph is served over http, and only once the test
+        // releases it.
+        let release = Arc::new(tokio::sync::Notify::new());
+        let served = Arc::new(AtomicBool::new(false));
+        let code_router = Router::new().route(
+            "/code",
+            get({
+                let release = release.clone();
+                let served = served.clone();
+                move || async move {
+                    release.notified().await;
+                    served.store(true, Ordering::SeqCst);
+                    "code"
+                }
+            }),
+        );
+        let code_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let code_addr = code_listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(code_listener, code_router).await });
+
+        let mut compute_graph = mock_graph_a();
+        compute_graph.code.path = format!("http://{}/code", code_addr);
+        compute_graph.code.size = 4;
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph,
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let app = axum::ServiceExt::<Request>::into_make_service(create_routes(state));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut response = reqwest::get(format!(
+            "http://{}/namespaces/{}/export",
+            addr, TEST_NAMESPACE
+        ))
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::TRANSFER_ENCODING], "chunked");
+
+        // The definition arrives while the export is still waiting for the
+        // code, it isn't held back until the archive is complete.
+        let first = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .expect("export was buffered")?
+            .unwrap();
+        assert!(!first.is_empty());
+        assert!(!served.load(Ordering::SeqCst));
+
+        release.notify_one();
+        let mut archive = BytesMut::from(&first[..]);
+        while let Some(chunk) = response.chunk().await? {
+            archive.extend_from_slice(&chunk);
+        }
+        let entries = archive_files(&archive)?;
+        let code = entries
+            .iter()
+            .find(|entry| entry.0 == format!("graph_A/{}", archive::CODE_FILE))
+            .unwrap();
+        assert_eq!(code.1, b"code");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_import_compute_graphs() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+        // graph_B already exists and keeps its code, graph_A's code is
+        // uploaded ahead of the import.
+        let definition_b = serde_json::to_string(&ComputeGraph::from(mock_graph_b()))?;
+        create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&[("compute_graph", &definition_b), ("code", "code of b")]).await,
+        )
+        .await
+        .unwrap();
+        let code_a = state
+            .blob_storage
+            .put(
+                "uploaded_code_a",
+                futures::stream::iter([Ok(Bytes::from("code of a"))]),
+            )
+            .await?;
+        let mut definition_a = serde_json::to_value(ComputeGraph::from(mock_graph_a()))?;
+        definition_a["code_url"] = serde_json::Value::String(code_a.url.clone());
+
+        let body = format!(
+            "{}\n{}\n{}\n",
+            definition_a,
+            serde_json::json!({"name": "graph_broken"}),
+            definition_b
+        );
+        let response = batch_import_compute_graphs(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            Body::from(body),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/x-ndjson"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let results: Vec<serde_json::Value> = body
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(serde_json::from_slice)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["compute_graph"], "graph_A");
+        assert!(results[0]["error"].is_null());
+        assert_eq!(results[1]["line"], 2);
+        assert!(results[1]["compute_graph"].is_null());
+        assert_eq!(results[1]["error"]["code"], "BAD_REQUEST");
+        assert_eq!(results[2]["compute_graph"], "graph_B");
+        assert!(results[2]["error"].is_null());
+
+        let reader = state.indexify_state.reader();
+        let graph_a = reader
+            .get_compute_graph(TEST_NAMESPACE, "graph_A")?
+            .unwrap();
+        // The code is copied to a blob of the namespace.
+        assert_ne!(graph_a.code.path, code_a.url);
+        assert_eq!(graph_a.code.sha256_hash, code_a.sha256_hash);
+        assert_eq!(
+            state.blob_storage.read_bytes(&graph_a.code.path).await?,
+            "code of a"
+        );
+        let graph_b = reader
+            .get_compute_graph(TEST_NAMESPACE, "graph_B")?
+            .unwrap();
+        assert_eq!(graph_b.code.size, "code of b".len() as u64);
+        assert!(reader
+            .get_compute_graph(TEST_NAMESPACE, "graph_broken")?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_import_limits() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        let code_url = |name: &str| {
+            let state = state.clone();
+            let name = name.to_string();
+            async move {
+                let code = if name == "large" {
+                    "x".repeat(64)
+                } else {
+                    "code".to_string()
+                };
+                let put = state
+                    .blob_storage
+                    .put(&name, futures::stream::iter([Ok(Bytes::from(code))]))
+                    .await?;
+                anyhow::Ok(put.url)
+            }
+        };
+        let definition = |code_url: &str| {
+            let mut definition = serde_json::to_value(ComputeGraph::from(mock_graph_a()))?;
+            definition["code_url"] = serde_json::Value::String(code_url.to_string());
+            anyhow::Ok(definition.to_string())
+        };
+        let large = definition(&code_url("large").await?)?;
+        let outside = definition("file:///elsewhere/code")?;
+        let valid = definition(&code_url("small").await?)?;
+        state.config = Arc::new(ServerConfig {
+            max_compute_graph_definition_bytes: valid.len() + 64,
+            max_compute_graph_code_bytes: 16,
+            ..(*state.config).clone()
+        });
+        let too_long = "x".repeat(valid.len() * 3);
+
+        // Sent in small chunks so the long line spans many of them.
+        let body = format!("{}\n{}\n{}\n{}\n", large, outside, too_long, valid);
+        let chunks: Vec<Result<Bytes, std::io::Error>> = body
+            .as_bytes()
+            .chunks(100)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+        let response = batch_import_compute_graphs(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            Body::from_stream(futures::stream::iter(chunks)),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let results: Vec<serde_json::Value> = body
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(serde_json::from_slice)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0]["error"]["code"], "PAYLOAD_TOO_LARGE");
+        assert_eq!(results[1]["error"]["code"], "BAD_REQUEST");
+        assert_eq!(results[2]["line"], 3);
+        assert_eq!(results[2]["error"]["code"], "PAYLOAD_TOO_LARGE");
+        assert_eq!(results[3]["line"], 4);
+        assert_eq!(results[3]["compute_graph"], "graph_A");
+        assert!(results[3]["error"].is_null());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_default_namespace_routes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        let definition = serde_json::to_string(&ComputeGraph::from(mock_graph_a()))?;
+        let fields = [("compute_graph", definition.as_str()), ("code", "code")];
+        let list_params = || {
+            Query(ListComputeGraphsParams {
+                limit: None,
+                cursor: None,
+                enabled: None,
+                created_by: None,
+                sort: None,
+                order: None,
+            })
+        };
+
+        let err = create_default_compute_graph(
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&fields).await,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+
+        state.config = Arc::new(ServerConfig {
+            default_namespace: Some("default".to_string()),
+            ..Default::default()
+        });
+        state.indexify_state.upsert_namespace(&NamespaceRequest {
+            name: "default".to_string(),
+            feature_flags: Default::default(),
+        })?;
+        create_default_compute_graph(
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&fields).await,
+        )
+        .await
+        .unwrap();
+        let Json(list) = list_default_compute_graphs(list_params(), State(state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(list.compute_graphs.len(), 1);
+        assert!(state
+            .indexify_state
+            .reader()
+            .get_compute_graph("default", "graph_A")?
+            .is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_normalized_compute_graph_names() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        state.indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("normalized_state"),
+            IndexifyStateOptions {
+                normalize_compute_graph_names: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+        state.indexify_state.upsert_namespace(&NamespaceRequest {
+            name: TEST_NAMESPACE.to_string(),
+            feature_flags: Default::default(),
+        })?;
+        let create = |name: &'static str| {
+            let state = state.clone();
+            async move {
+                let mut graph = mock_graph_a();
+                graph.name = name.to_string();
+                let definition = serde_json::to_string(&ComputeGraph::from(graph))?;
+                let fields = [("compute_graph", definition.as_str()), ("code", "code")];
+                let multipart = multipart(&fields).await;
+                anyhow::Ok(
+                    create_compute_graph(
+                        Path(TEST_NAMESPACE.to_string()),
+                        State(state),
+                        HeaderMap::new(),
+                        multipart,
+                    )
+                    .await,
+                )
+            }
+        };
+
+        create("MyGraph").await?.unwrap();
+        let compute_graph = state
+            .indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, "mygraph")?
+            .unwrap();
+        assert_eq!(compute_graph.display_name.as_deref(), Some("MyGraph"));
+
+        let err = create("mygraph").await?.unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::CONFLICT);
+
+        // Creating the graph again with the same name updates it.
+        create("MyGraph").await?.unwrap();
+
+        // Idempotent creations of the same graph leave it untouched.
+        let mut graph = mock_graph_a();
+        graph.name = "MyGraph".to_string();
+        let definition = serde_json::to_string(&ComputeGraph::from(graph))?;
+        let mut headers = HeaderMap::new();
+        headers.insert(IDEMPOTENT_HEADER, "true".parse().unwrap());
+        let stored = state
+            .indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, "mygraph")?
+            .unwrap();
+        let Json(created) = super::create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            headers,
+            multipart(&[("compute_graph", &definition), ("code", "code")]).await,
+        )
+        .await
+        .unwrap();
+        assert!(!created.created);
+        assert_eq!(
+            state
+                .indexify_state
+                .reader()
+                .get_compute_graph(TEST_NAMESPACE, "mygraph")?
+                .unwrap(),
+            stored
+        );
+
+        // Every route finds the graph by the name it was created with.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let app = axum::ServiceExt::<Request>::into_make_service(create_routes(state.clone()));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let url = format!(
+            "http://{}/namespaces/{}/compute_graphs/MyGraph",
+            addr, TEST_NAMESPACE
+        );
+        let client = reqwest::Client::new();
+
+        let response = client.get(&url).send().await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let compute_graph: ComputeGraph = response.json().await?;
+        assert_eq!(compute_graph.name, "mygraph");
+        assert_eq!(compute_graph.display_name.as_deref(), Some("MyGraph"));
+
+        let response = client
+            .post(format!("{}/invoke_object", url))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body("{}")
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let invocations = state
+            .indexify_state
+            .reader()
+            .list_invocations(TEST_NAMESPACE, "mygraph", None, None)?
+            .0;
+        assert_eq!(invocations.len(), 1);
+
+        let response = client.delete(&url).send().await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = client.get(&url).send().await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_compute_graph_max_nodes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        state.config = Arc::new(ServerConfig {
+            max_nodes: 3,
+            ..Default::default()
+        });
+        let create = |graph: data_model::ComputeGraph| {
+            let state = state.clone();
+            async move {
+                let definition = serde_json::to_string(&ComputeGraph::from(graph))?;
+                let multipart =
+                    multipart(&[("compute_graph", &definition), ("code", "code")]).await;
+                anyhow::Ok(
+                    create_compute_graph(
+                        Path(TEST_NAMESPACE.to_string()),
+                        State(state),
+                        HeaderMap::new(),
+                        multipart,
+                    )
+                    .await,
+                )
+            }
+        };
+
+        // graph_A has 3 nodes, graph_B has a router in addition.
+        assert!(create(mock_graph_a()).await?.is_ok());
+        let err = create(mock_graph_b()).await?.unwrap_err();
+        let (status, message) = error_response(err).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            message,
+            "compute graph graph_B has 4 nodes, the maximum is 3"
+        );
+        assert!(state
+            .indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, "graph_B")?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_compute_graph_definition_too_large() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        state.config = Arc::new(ServerConfig {
+            max_compute_graph_definition_bytes: 1024 * 1024,
+            ..Default::default()
+        });
+
+        // A 16MiB compute_graph field, streamed in 64KiB chunks.
+        let boundary = "indexify-test-boundary";
+        let chunk = bytes::Bytes::from(vec![b' '; 64 * 1024]);
+        let chunk_count = 256;
+        let total_bytes = chunk.len() * chunk_count;
+        let pulled = Arc::new(AtomicUsize::new(0));
+        let head = bytes::Bytes::from(format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"compute_graph\"\r\n\r\n",
+            boundary
+        ));
+        let tail = bytes::Bytes::from(format!("\r\n--{}--\r\n", boundary));
+        let body = futures::stream::iter(
+            std::iter::once(head)
+                .chain(std::iter::repeat_n(chunk, chunk_count))
+                .chain(std::iter::once(tail)),
+        )
+        .then({
+            let pulled = pulled.clone();
+            move |bytes| {
+                let pulled = pulled.clone();
+                async move {
+                    // Chunks arrive one at a time like over a socket, the
+                    // multipart parser buffers everything that is ready.
+                    tokio::task::yield_now().await;
+                    pulled.fetch_add(bytes.len(), Ordering::SeqCst);
+                    Ok::<_, std::io::Error>(bytes)
+                }
+            }
+        });
+        let request = Request::builder()
+            .method(Method::POST)
+            .header(
+                hyper::header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .body(Body::from_stream(body))
+            .unwrap();
+        let multipart = Multipart::from_request(request, &()).await.unwrap();
+
+        let err = create_compute_graph(
+            Path(TEST_NAMESPACE.to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart,
+        )
+        .await
+        .unwrap_err();
+        let (status, message) = error_response(err).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(message, "compute_graph field is larger than 1048576 bytes");
+        // The request was abandoned shortly after crossing the limit.
+        assert!(pulled.load(Ordering::SeqCst) < 2 * 1024 * 1024);
+        assert!(pulled.load(Ordering::SeqCst) < total_bytes);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auto_create_namespace() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut state = test_route_state(&temp_dir).await?;
+        let definition = serde_json::to_string(&ComputeGraph::from(mock_graph_a()))?;
+        let fields = [("compute_graph", definition.as_str()), ("code", "code")];
+
+        let err = create_compute_graph(
+            Path("missing".to_string()),
+            State(state.clone()),
+            HeaderMap::new(),
+            multipart(&fields).await,
+        )
+        .await
+        .unwrap_err();
+        let (status, message) = error_response(err).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(message, "namespace missing not found");
+        assert!(state
+            .indexify_state
+            .reader()
+            .get_compute_graph("missing", "graph_A")?
+            .is_none());
+        let err = import_namespace(
+            Path("missing".to_string()),
+            State(state.clone()),
+            Body::empty(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error_response(err).await.0, StatusCode::NOT_FOUND);
+        let err = instantiate_compute_graph(
+            Path(("missing".to_string(), "graph_A".to_string())),
+            State(state.clone()),
+            Json(InstantiateComputeGraph {
+                name: "graph_copy".to_string(),
+                description: None,
+                node_overrides: Default::default(),
+                copy_code: false,
+            }),
+        )
+        .await
+        .unwrap_err();
+        let (status, message) = error_response(err).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(message, "namespace missing not found");
+
+        state.config = Arc::new(ServerConfig {
+            auto_create_namespace: true,
+            ..Default::default()
+        });
+        state.indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("auto_create_state"),
+            IndexifyStateOptions {
+                auto_create_namespace: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+        for _ in 0..2 {
+            create_compute_graph(
+                Path("created".to_string()),
+                State(state.clone()),
+                HeaderMap::new(),
+                multipart(&fields).await,
+            )
+            .await
+            .unwrap();
+        }
+        let reader = state.indexify_state.reader();
+        assert!(reader.get_namespace("created")?.is_some());
+        assert!(reader.get_compute_graph("created", "graph_A")?.is_some());
+
+        Ok(())
+    }
+
+    /// Routes left out of the API docs on purpose: the UI and the routes only
+    /// executors call.
+    const UNDOCUMENTED_ROUTES: &[(&str, &str)] = &[
+        ("get", "/"),
+        ("get", "/ui"),
+        ("get", "/ui/*rest"),
+        ("post", "/internal/ingest_files"),
+        ("post", "/internal/executors/{id}/tasks"),
+        ("get", "/internal/fn_outputs/{input_key}"),
+        (
+            "get",
+            "/internal/namespaces/{namespace}/compute_graphs/{compute_graph}/code",
+        ),
+    ];
+
+    /// Returns the method and OpenAPI path of every route served by
+    /// `create_routes`.
+    fn registered_routes(state: &RouteState) -> BTreeSet<(String, String)> {
+        route_table(state)
+            .routes
+            .into_iter()
+            .map(|(method, path)| {
+                let path = path
+                    .split('/')
+                    .map(|segment| match segment.strip_prefix(':') {
+                        Some(param) => format!("{{{}}}", param),
+                        None => segment.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/");
+                (method.as_str().to_lowercase(), path)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_routes_are_documented() -> Result<()> {
+        use utoipa::openapi::PathItemType;
+
+        let temp_dir = TempDir::new()?;
+        let state = test_route_state(&temp_dir).await?;
+
+        let mut documented = BTreeSet::new();
+        for (path, item) in ApiDoc::openapi().paths.paths {
+            for method in item.operations.keys() {
+                let method = match method {
+                    PathItemType::Get => "get",
+                    PathItemType::Post => "post",
+                    PathItemType::Put => "put",
+                    PathItemType::Patch => "patch",
+                    PathItemType::Delete => "delete",
+                    PathItemType::Head => "head",
+                    _ => continue,
+                };
+                documented.insert((method.to_string(), path.clone()));
+            }
+        }
+        let routes = registered_routes(&state);
+        assert!(routes.contains(&("get".to_string(), "/namespaces".to_string())));
+
+        let undocumented: Vec<_> = routes
+            .iter()
+            .filter(|(method, path)| {
+                !documented.contains(&(method.clone(), path.clone())) &&
+                    !UNDOCUMENTED_ROUTES.contains(&(method.as_str(), path.as_str()))
+            })
+            .collect();
+        assert!(
+            undocumented.is_empty(),
+            "routes missing from ApiDoc: {:?}",
+            undocumented
+        );
+        let unrouted: Vec<_> = documented.difference(&routes).collect();
+        assert!(
+            unrouted.is_empty(),
+            "documented paths without a route: {:?}",
+            unrouted
+        );
+
+        Ok(())
+    }
+}
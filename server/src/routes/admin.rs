@@ -0,0 +1,323 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use state_store::ORPHAN_BLOB_MIN_AGE;
+
+use super::{check_namespace_exists, RouteState};
+use crate::http_objects::{
+    BlobGcReport,
+    BlobIntegrityReport,
+    BlobList,
+    CfConfigs,
+    CfDiskUsage,
+    CodeNamespaces,
+    ComputeGraphReference,
+    ComputeGraphsByCode,
+    DbStats,
+    GcBlobsParams,
+    GlobalStats,
+    IndexifyAPIError,
+    ListBlobsParams,
+    LiveFiles,
+    NamespaceDiff,
+    NamespaceDiffParams,
+    ReindexReport,
+};
+
+/// Report compute graphs whose code blob is missing from blob storage
+#[utoipa::path(
+    get,
+    path = "/admin/integrity/blobs",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Dangling blob references", body = BlobIntegrityReport),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn verify_blob_integrity(
+    State(state): State<RouteState>,
+) -> Result<Json<BlobIntegrityReport>, IndexifyAPIError> {
+    let dangling_refs = state
+        .indexify_state
+        .verify_blob_references(&state.blob_storage)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(BlobIntegrityReport {
+        dangling_references: dangling_refs.into_iter().map(|r| r.into()).collect(),
+    }))
+}
+
+/// List the SST files of the state store, for backup tooling
+#[utoipa::path(
+    get,
+    path = "/admin/live_files",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Live files of the state store", body = LiveFiles),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn live_files(
+    State(state): State<RouteState>,
+) -> Result<Json<LiveFiles>, IndexifyAPIError> {
+    let files = state
+        .indexify_state
+        .live_files()
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(LiveFiles {
+        files: files.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Report the space taken on disk by every column family of the state store
+#[utoipa::path(
+    get,
+    path = "/admin/db_stats",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Disk usage of the column families", body = DbStats),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn db_stats(State(state): State<RouteState>) -> Result<Json<DbStats>, IndexifyAPIError> {
+    let mut disk_sizes = state
+        .indexify_state
+        .reader()
+        .cf_disk_sizes()
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(DbStats {
+        column_families: state
+            .indexify_state
+            .cf_configs()
+            .into_iter()
+            .map(|config| CfDiskUsage {
+                disk_bytes: disk_sizes.remove(&config.name).unwrap_or_default(),
+                name: config.name,
+            })
+            .collect(),
+    }))
+}
+
+/// List the column families of the state store with the options they were
+/// opened with
+#[utoipa::path(
+    get,
+    path = "/admin/cf_configs",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Options of the column families", body = CfConfigs),
+    ),
+)]
+pub async fn cf_configs(State(state): State<RouteState>) -> Json<CfConfigs> {
+    Json(CfConfigs {
+        column_families: state
+            .indexify_state
+            .cf_configs()
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+    })
+}
+
+/// Rebuild the secondary indexes of the state store from its primary rows
+#[utoipa::path(
+    post,
+    path = "/admin/reindex",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Secondary indexes rebuilt", body = ReindexReport),
+        (status = SERVICE_UNAVAILABLE, description = "A reindex is already in progress"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn reindex(
+    State(state): State<RouteState>,
+) -> Result<Json<ReindexReport>, IndexifyAPIError> {
+    let report = state
+        .indexify_state
+        .reindex()
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(report.into()))
+}
+
+/// List the compute graphs of every namespace built from the code with the
+/// given sha256 hash
+#[utoipa::path(
+    get,
+    path = "/admin/graphs/by_code/{sha256}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Compute graphs using the code", body = ComputeGraphsByCode),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn graphs_by_code(
+    Path(sha256): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<ComputeGraphsByCode>, IndexifyAPIError> {
+    let compute_graphs = state
+        .indexify_state
+        .reader()
+        .find_graphs_by_code_sha256(&sha256)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(ComputeGraphsByCode {
+        sha256,
+        compute_graphs: compute_graphs
+            .into_iter()
+            .map(|(namespace, compute_graph)| ComputeGraphReference {
+                namespace,
+                compute_graph,
+            })
+            .collect(),
+    }))
+}
+
+/// List the namespaces with a compute graph built from the code with the
+/// given sha256 hash
+#[utoipa::path(
+    get,
+    path = "/admin/code/{sha256}/namespaces",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Namespaces using the code", body = CodeNamespaces),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn namespaces_by_code(
+    Path(sha256): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<CodeNamespaces>, IndexifyAPIError> {
+    let namespaces = state
+        .indexify_state
+        .reader()
+        .namespaces_with_code_sha256(&sha256)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(CodeNamespaces { sha256, namespaces }))
+}
+
+/// Compare the compute graphs of two namespaces
+#[utoipa::path(
+    get,
+    path = "/admin/namespaces/diff",
+    tag = "operations",
+    params(
+        ("a" = String, Query, description = "Namespace compared"),
+        ("b" = String, Query, description = "Namespace compared against"),
+    ),
+    responses(
+        (status = 200, description = "Compute graphs differing between the namespaces", body = NamespaceDiff),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn diff_namespaces(
+    Query(params): Query<NamespaceDiffParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<NamespaceDiff>, IndexifyAPIError> {
+    check_namespace_exists(&state, &params.a)?;
+    check_namespace_exists(&state, &params.b)?;
+    let diff = state
+        .indexify_state
+        .reader()
+        .diff_namespaces(&params.a, &params.b)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(NamespaceDiff {
+        a: params.a,
+        b: params.b,
+        only_in_a: diff.only_in_a,
+        only_in_b: diff.only_in_b,
+        changed: diff.changed,
+    }))
+}
+
+/// Summary of the namespaces, compute graphs and invocations of the server
+#[utoipa::path(
+    get,
+    path = "/admin/stats",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Counts and blob usage across every namespace", body = GlobalStats),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn global_stats(
+    State(state): State<RouteState>,
+) -> Result<Json<GlobalStats>, IndexifyAPIError> {
+    let stats = state
+        .indexify_state
+        .reader()
+        .global_stats()
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(stats.into()))
+}
+
+/// List the objects in blob storage
+#[utoipa::path(
+    get,
+    path = "/admin/blobs",
+    tag = "operations",
+    params(
+        ("prefix" = Option<String>, Query, description = "Only list objects whose key starts with the prefix"),
+        ("cursor" = Option<String>, Query, description = "Cursor returned by the previous page"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of objects to return, at most 1000"),
+    ),
+    responses(
+        (status = 200, description = "Objects in blob storage, in key order", body = BlobList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_blobs(
+    Query(params): Query<ListBlobsParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<BlobList>, IndexifyAPIError> {
+    let limit = params
+        .limit
+        .unwrap_or(blob_store::LIST_PAGE_SIZE)
+        .clamp(1, blob_store::LIST_PAGE_SIZE);
+    let (blobs, cursor) = state
+        .blob_storage
+        .list_page(
+            params.prefix.as_deref().unwrap_or_default(),
+            params.cursor.as_deref(),
+            limit,
+        )
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(BlobList {
+        blobs: blobs.into_iter().map(Into::into).collect(),
+        cursor,
+    }))
+}
+
+/// Delete the blobs no longer referenced by any compute graph, invocation or
+/// output
+#[utoipa::path(
+    post,
+    path = "/admin/gc/blobs",
+    tag = "operations",
+    params(
+        ("dry_run" = Option<bool>, Query, description = "Only report the orphaned blobs, defaults to true. Pass false to delete them"),
+    ),
+    responses(
+        (status = 200, description = "Orphaned blobs", body = BlobGcReport),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn gc_blobs(
+    Query(params): Query<GcBlobsParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<BlobGcReport>, IndexifyAPIError> {
+    let dry_run = params.dry_run.unwrap_or(true);
+    let report = state
+        .indexify_state
+        .gc_orphan_blobs(&state.blob_storage, ORPHAN_BLOB_MIN_AGE, dry_run)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(BlobGcReport {
+        dry_run,
+        scanned: report.scanned,
+        orphans: report.orphans,
+        deleted: report.deleted,
+    }))
+}
@@ -0,0 +1,299 @@
+use std::convert::Infallible;
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use blob_store::{PutOptions, PutResult};
+use bytes::{Bytes, BytesMut};
+use data_model::ComputeGraphCode;
+use futures::{Stream, StreamExt};
+use nanoid::nanoid;
+use state_store::requests::{CreateComputeGraphRequest, RequestPayload, StateMachineUpdateRequest};
+use tracing::{error, info};
+
+use super::{
+    check_namespace_exists,
+    check_node_count,
+    check_strict_validation,
+    code_upload::{limit_code_size, CodeTooLarge},
+    compute_graph_write_error,
+    parse_compute_graph_definition,
+    RouteState,
+};
+use crate::http_objects::{BatchImportResult, IndexifyAPIError};
+
+/// Field of a batch import line with the URL of the pre-uploaded code.
+const CODE_URL_FIELD: &str = "code_url";
+
+/// Create or update compute graphs from a newline-delimited JSON stream of
+/// definitions
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/batch_import",
+    tag = "operations",
+    request_body(content = String, content_type = "application/x-ndjson",
+        description = "A compute graph definition per line, with the URL of its pre-uploaded code in `code_url`, which is copied for the graph. Graphs which already exist keep their code when it is omitted. Lines larger than `max_compute_graph_definition_bytes` fail with 413."),
+    responses(
+        (status = 200, description = "A result per imported line, streamed as the graphs are written",
+            content_type = "application/x-ndjson", body = BatchImportResult),
+        (status = NOT_FOUND, description = "Namespace not found"),
+    ),
+)]
+pub async fn batch_import_compute_graphs(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    body: Body,
+) -> Result<impl IntoResponse, IndexifyAPIError> {
+    check_namespace_exists(&state, &namespace)?;
+    let max_line_bytes = state.config.max_compute_graph_definition_bytes;
+    let results = async_stream::stream! {
+        let lines = ndjson_lines(body.into_data_stream(), max_line_bytes);
+        futures::pin_mut!(lines);
+        let mut line = 0;
+        while let Some(text) = lines.next().await {
+            line += 1;
+            let (result, body_error) = match text {
+                Ok(Line::Complete(text)) if text.trim_ascii().is_empty() => continue,
+                Ok(Line::Complete(text)) => {
+                    (import_compute_graph(&state, &namespace, &text).await, false)
+                }
+                Ok(Line::TooLong) => (
+                    Err(IndexifyAPIError::new(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        &format!("line is larger than {} bytes", max_line_bytes),
+                    )),
+                    false,
+                ),
+                Err(err) => (
+                    Err(IndexifyAPIError::bad_request(&format!(
+                        "failed to read request body: {}",
+                        err
+                    ))),
+                    true,
+                ),
+            };
+            let result = match result {
+                Ok(compute_graph) => BatchImportResult {
+                    line,
+                    compute_graph: Some(compute_graph),
+                    error: None,
+                },
+                Err(err) => BatchImportResult {
+                    line,
+                    compute_graph: None,
+                    error: Some(err),
+                },
+            };
+            let mut json = serde_json::to_vec(&result).unwrap_or_default();
+            json.push(b'\n');
+            yield Ok::<_, Infallible>(Bytes::from(json));
+            // Nothing after an unreadable chunk can be imported.
+            if body_error {
+                break;
+            }
+        }
+    };
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(results),
+    ))
+}
+
+/// Creates or updates the compute graph defined by a line of a batch import
+/// in its own transaction and returns its name.
+async fn import_compute_graph(
+    state: &RouteState,
+    namespace: &str,
+    text: &[u8],
+) -> Result<String, IndexifyAPIError> {
+    let mut definition: serde_json::Value = serde_json::from_slice(text)?;
+    let code_url = match definition
+        .as_object_mut()
+        .and_then(|definition| definition.remove(CODE_URL_FIELD))
+    {
+        Some(serde_json::Value::String(url)) => Some(url),
+        Some(_) => {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "{} must be a string",
+                CODE_URL_FIELD
+            )))
+        }
+        None => None,
+    };
+    let definition = parse_compute_graph_definition(
+        namespace,
+        &definition.to_string(),
+        Some("application/json"),
+    )?;
+
+    let (code, copied) = match code_url {
+        Some(url) => {
+            let copy = copy_code(state, namespace, &url).await?;
+            let code = ComputeGraphCode {
+                size: copy.size_bytes,
+                sha256_hash: copy.sha256_hash,
+                path: copy.url.clone(),
+            };
+            (code, Some(copy.url))
+        }
+        None => {
+            let code = state
+                .indexify_state
+                .reader()
+                .get_compute_graph(namespace, &definition.name)
+                .map_err(IndexifyAPIError::internal_error)?
+                .map(|compute_graph| compute_graph.code)
+                .ok_or_else(|| {
+                    IndexifyAPIError::bad_request(&format!(
+                        "{} is required to create compute graph {}",
+                        CODE_URL_FIELD, definition.name
+                    ))
+                })?;
+            (code, None)
+        }
+    };
+
+    let result = async {
+        let compute_graph = definition.into_data_model(&code.path, &code.sha256_hash, code.size)?;
+        check_node_count(&state.config, &compute_graph)?;
+        check_strict_validation(state, &compute_graph)?;
+        let name = compute_graph.name.clone();
+        state
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: namespace.to_string(),
+                    compute_graph,
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await
+            .map_err(compute_graph_write_error)?;
+        Ok(name)
+    }
+    .await;
+    match (&result, copied) {
+        (Ok(name), _) => info!("compute graph imported: {}", name),
+        (Err(_), Some(copied)) => {
+            if let Err(err) = state.blob_storage.delete(&copied).await {
+                error!(
+                    "failed to delete code {} of failed import: {:?}",
+                    copied, err
+                );
+            }
+        }
+        (Err(_), None) => {}
+    }
+    result
+}
+
+/// Copies the code at `url` in the blob storage to a blob of the namespace,
+/// so the graph doesn't share it with the graphs of other namespaces. The
+/// code is checked against `max_compute_graph_code_bytes` before it's read
+/// and hashed as it's copied.
+async fn copy_code(
+    state: &RouteState,
+    namespace: &str,
+    url: &str,
+) -> Result<PutResult, IndexifyAPIError> {
+    let info = state.blob_storage.head(url).await.map_err(|_| {
+        IndexifyAPIError::bad_request(&format!("{} isn't in the blob storage", url))
+    })?;
+    let Some(info) = info else {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "code not found at {}",
+            url
+        )));
+    };
+    let max_bytes = state.config.max_compute_graph_code_bytes;
+    if info.size_bytes > max_bytes as u64 {
+        return Err(IndexifyAPIError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &CodeTooLarge(max_bytes).to_string(),
+        ));
+    }
+    let code = state
+        .blob_storage
+        .get(url)
+        .map_err(IndexifyAPIError::internal_error)?
+        .get()
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    let _permit = state.upload_limiter.acquire().await?;
+    state
+        .blob_storage
+        .put_opts(
+            &format!("{}_{}", namespace, nanoid!()),
+            limit_code_size(code, max_bytes).boxed(),
+            PutOptions {
+                if_not_exists: true,
+            },
+        )
+        .await
+        .map_err(|err| match err.downcast_ref::<CodeTooLarge>() {
+            Some(too_large) => {
+                IndexifyAPIError::new(StatusCode::PAYLOAD_TOO_LARGE, &too_large.to_string())
+            }
+            None => IndexifyAPIError::internal_error(err),
+        })
+}
+
+/// Line of a batch import.
+enum Line {
+    Complete(Bytes),
+    /// A line longer than the limit, which was skipped without buffering it.
+    TooLong,
+}
+
+/// Splits a stream of chunks into lines without their newline. The last line
+/// doesn't need to end with one. Lines longer than `max_bytes` are skipped as
+/// they are received.
+fn ndjson_lines<E>(
+    chunks: impl Stream<Item = Result<Bytes, E>>,
+    max_bytes: usize,
+) -> impl Stream<Item = Result<Line, E>> {
+    async_stream::stream! {
+        futures::pin_mut!(chunks);
+        let mut buffer = BytesMut::new();
+        // Whether the rest of a line too long to import is being skipped.
+        let mut skipping = false;
+        while let Some(chunk) = chunks.next().await {
+            let mut chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(err) => {
+                    yield Err(err);
+                    return;
+                }
+            };
+            if skipping {
+                let Some(end) = chunk.iter().position(|&b| b == b'\n') else {
+                    continue;
+                };
+                skipping = false;
+                chunk = chunk.slice(end + 1..);
+            }
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                let line = buffer.split_to(end + 1);
+                if end > max_bytes {
+                    yield Ok(Line::TooLong);
+                } else {
+                    yield Ok(Line::Complete(line.freeze().slice(..end)));
+                }
+            }
+            if buffer.len() > max_bytes {
+                buffer.clear();
+                skipping = true;
+                yield Ok(Line::TooLong);
+            }
+        }
+        if !buffer.is_empty() {
+            yield Ok(Line::Complete(buffer.freeze()));
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+
+use super::RouteState;
+use crate::http_objects::{CodeUrl, CodeUrlParams, IndexifyAPIError};
+
+/// Get the size, hash and content type of the code of a compute graph
+#[utoipa::path(
+    head,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/code",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Code metadata, without a body",
+            headers(
+                ("Content-Length" = u64, description = "Size of the code in bytes"),
+                ("ETag" = String, description = "Sha256 hash of the code"),
+                ("Content-Type" = String, description = "Content type of the code"),
+            )),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn code_metadata(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<impl IntoResponse, IndexifyAPIError> {
+    let metadata = state
+        .indexify_state
+        .reader()
+        .get_code_metadata(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    Ok([
+        (header::CONTENT_LENGTH, metadata.size_bytes.to_string()),
+        (header::ETAG, format!("\"{}\"", metadata.sha256)),
+        (header::CONTENT_TYPE, metadata.content_type),
+    ])
+}
+
+/// How long a presigned code url grants access to the code.
+const PRESIGNED_CODE_URL_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Get the url of the code of a compute graph, so clients can fetch it from
+/// blob storage without going through the server
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/code_url",
+    tag = "operations",
+    params(
+        ("presign" = Option<bool>, Query, description = "Also return a url valid for 15 minutes without credentials, when the blob storage supports it"),
+    ),
+    responses(
+        (status = 200, description = "Url of the code", body = CodeUrl),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn code_url(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<CodeUrlParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<CodeUrl>, IndexifyAPIError> {
+    let url = state
+        .indexify_state
+        .reader()
+        .get_code_url(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    let presigned_url = if params.presign.unwrap_or_default() {
+        state
+            .blob_storage
+            .presigned_url(&url, PRESIGNED_CODE_URL_TTL)
+            .await
+            .map_err(IndexifyAPIError::internal_error)?
+    } else {
+        None
+    };
+    Ok(Json(CodeUrl { url, presigned_url }))
+}
@@ -0,0 +1,193 @@
+use std::io::Write;
+
+use anyhow::Result;
+use async_stream::try_stream;
+use axum::{
+    extract::multipart::{Field, MultipartError},
+    http::{header, StatusCode},
+};
+use blob_store::{PutOptions, PutResult};
+use bytes::Bytes;
+use flate2::write::GzDecoder;
+use futures::{Stream, StreamExt};
+use nanoid::nanoid;
+
+use super::RouteState;
+use crate::http_objects::IndexifyAPIError;
+
+/// Returned when a field sent with `Content-Encoding: gzip` isn't valid gzip.
+#[derive(Debug)]
+struct InvalidGzip(std::io::Error);
+
+impl std::fmt::Display for InvalidGzip {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid gzip stream: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidGzip {}
+
+/// Returned when the code of a compute graph is larger than
+/// `max_compute_graph_code_bytes`.
+#[derive(Debug)]
+pub(super) struct CodeTooLarge(pub(super) usize);
+
+impl std::fmt::Display for CodeTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "code is larger than {} bytes", self.0)
+    }
+}
+
+impl std::error::Error for CodeTooLarge {}
+
+/// Buffer of decompressed bytes failing writes past `max_bytes` in total, so a
+/// small gzip stream can't expand to more than the limit in memory.
+struct BoundedBuf {
+    buf: Vec<u8>,
+    written: usize,
+    max_bytes: usize,
+}
+
+impl Write for BoundedBuf {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        if self.written + data.len() > self.max_bytes {
+            return Err(std::io::Error::other(CodeTooLarge(self.max_bytes)));
+        }
+        self.written += data.len();
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn gzip_error(err: std::io::Error) -> anyhow::Error {
+    if err
+        .get_ref()
+        .is_some_and(|inner| inner.downcast_ref::<CodeTooLarge>().is_some())
+    {
+        let inner = err.into_inner().unwrap();
+        return anyhow::Error::from(*inner.downcast::<CodeTooLarge>().unwrap());
+    }
+    InvalidGzip(err).into()
+}
+
+/// Decompresses a gzip stream as it is read, failing once more than
+/// `max_bytes` are decompressed.
+fn gunzip<'a>(
+    mut stream: impl Stream<Item = Result<Bytes>> + Send + Unpin + 'a,
+    max_bytes: usize,
+) -> impl Stream<Item = Result<Bytes>> + Send + 'a {
+    try_stream! {
+        let mut decoder = GzDecoder::new(BoundedBuf {
+            buf: Vec::new(),
+            written: 0,
+            max_bytes,
+        });
+        while let Some(chunk) = stream.next().await {
+            decoder.write_all(&chunk?).map_err(gzip_error)?;
+            let decompressed = std::mem::take(&mut decoder.get_mut().buf);
+            if !decompressed.is_empty() {
+                yield Bytes::from(decompressed);
+            }
+        }
+        let rest = decoder.finish().map_err(gzip_error)?.buf;
+        if !rest.is_empty() {
+            yield Bytes::from(rest);
+        }
+    }
+}
+
+/// Fails a stream once it has yielded more than `max_bytes`.
+pub(super) fn limit_code_size<'a>(
+    mut stream: impl Stream<Item = Result<Bytes>> + Send + Unpin + 'a,
+    max_bytes: usize,
+) -> impl Stream<Item = Result<Bytes>> + Send + 'a {
+    try_stream! {
+        let mut read = 0;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            read += chunk.len();
+            if read > max_bytes {
+                Err(CodeTooLarge(max_bytes))?;
+            }
+            yield chunk;
+        }
+    }
+}
+
+/// Reads the `compute_graph` field as text, failing with 413 as soon as it
+/// grows past `max_bytes` instead of buffering all of it.
+pub(super) async fn read_compute_graph_field(
+    mut field: axum::extract::multipart::Field<'_>,
+    max_bytes: usize,
+) -> Result<String, IndexifyAPIError> {
+    let mut buf = Vec::new();
+    loop {
+        let chunk = field.chunk().await.map_err(|err| {
+            IndexifyAPIError::bad_request(&format!("failed to read compute_graph field: {}", err))
+        })?;
+        let Some(chunk) = chunk else {
+            break;
+        };
+        if buf.len() + chunk.len() > max_bytes {
+            return Err(IndexifyAPIError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                &format!("compute_graph field is larger than {} bytes", max_bytes),
+            ));
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    String::from_utf8(buf)
+        .map_err(|_| IndexifyAPIError::bad_request("compute_graph field is not valid UTF-8"))
+}
+
+/// Streams the code field of a multipart request to blob storage under a new
+/// name.
+pub(super) async fn put_code_field(
+    state: &RouteState,
+    namespace: &str,
+    field: Field<'_>,
+) -> Result<PutResult, IndexifyAPIError> {
+    let gzipped = field
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .is_some_and(|encoding| encoding.as_bytes().eq_ignore_ascii_case(b"gzip"));
+    let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
+    // The code is stored and hashed decompressed. Nothing is left behind when
+    // the stream fails, the create-only put removes what it wrote.
+    let max_bytes = state.config.max_compute_graph_code_bytes;
+    let stream = if gzipped {
+        gunzip(stream, max_bytes).boxed()
+    } else {
+        limit_code_size(stream, max_bytes).boxed()
+    };
+    let file_name = format!("{}_{}", namespace, nanoid!());
+    let _permit = state.upload_limiter.acquire().await?;
+    // Never silently replace the code of another graph that drew the same file
+    // name.
+    let options = PutOptions {
+        if_not_exists: true,
+    };
+    let result = state
+        .blob_storage
+        .put_opts(&file_name, stream, options)
+        .await;
+    result.map_err(|err| {
+        // Errors of the field stream are caused by the request, the rest by
+        // the blob store.
+        if let Some(too_large) = err.downcast_ref::<CodeTooLarge>() {
+            IndexifyAPIError::new(StatusCode::PAYLOAD_TOO_LARGE, &too_large.to_string())
+        } else if err.downcast_ref::<MultipartError>().is_some() ||
+            err.downcast_ref::<InvalidGzip>().is_some()
+        {
+            IndexifyAPIError::bad_request(&format!("failed to read code field: {}", err))
+        } else if blob_store::error::is_already_exists(&err) {
+            IndexifyAPIError::conflict(&err.to_string())
+        } else {
+            IndexifyAPIError::internal_error(err)
+        }
+    })
+}
@@ -0,0 +1,52 @@
+use indexify_utils::get_epoch_time_in_ms;
+
+use super::RouteState;
+use crate::http_objects::{ErrorCode, IndexifyAPIError};
+
+/// Length of the issue time prefixed to the key a listing resumes after.
+const HEADER_LEN: usize = 8;
+
+/// Wraps the key of the last row of a page into the cursor returned to the
+/// client, stamped with the time it was issued. Returns no cursor when the
+/// listing has no more rows.
+pub fn encode_cursor(has_more: bool, last_key: Option<&[u8]>) -> Option<Vec<u8>> {
+    let last_key = last_key.filter(|_| has_more)?;
+    let mut cursor = Vec::with_capacity(HEADER_LEN + last_key.len());
+    cursor.extend_from_slice(&get_epoch_time_in_ms().to_be_bytes());
+    cursor.extend_from_slice(last_key);
+    Some(cursor)
+}
+
+/// Returns the key a listing of the rows under `prefix` resumes from, which
+/// is the first key sorting after the last row of the previous page. Rows
+/// deleted or added since the previous page are neither repeated nor make
+/// the listing skip the remaining rows. Fails with 400 for a cursor issued
+/// by the listing of another prefix and with 410 when it's older than the
+/// configured TTL.
+pub fn decode_cursor(
+    state: &RouteState,
+    prefix: &[u8],
+    cursor: Option<&[u8]>,
+) -> Result<Option<Vec<u8>>, IndexifyAPIError> {
+    let Some(cursor) = cursor else {
+        return Ok(None);
+    };
+    if cursor.len() < HEADER_LEN {
+        return Err(IndexifyAPIError::bad_request("invalid cursor"));
+    }
+    let (issued_at, last_key) = cursor.split_at(HEADER_LEN);
+    if !last_key.starts_with(prefix) || last_key.len() == prefix.len() {
+        return Err(IndexifyAPIError::bad_request("invalid cursor"));
+    }
+    let issued_at = u64::from_be_bytes(issued_at.try_into().unwrap());
+    let age_ms = get_epoch_time_in_ms().saturating_sub(issued_at);
+    if age_ms > state.config.cursor_ttl_secs * 1000 {
+        return Err(IndexifyAPIError::with_code(
+            ErrorCode::CursorExpired,
+            "cursor expired, restart the listing from the first page",
+        ));
+    }
+    let mut start = last_key.to_vec();
+    start.push(0);
+    Ok(Some(start))
+}
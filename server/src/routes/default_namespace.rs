@@ -0,0 +1,74 @@
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::HeaderMap,
+    Json,
+};
+
+use super::{create_compute_graph, list_compute_graphs, ComputeGraphCreateType, RouteState};
+use crate::http_objects::{
+    ComputeGraphCreated,
+    ComputeGraphsList,
+    ErrorCode,
+    IndexifyAPIError,
+    ListComputeGraphsParams,
+};
+
+/// Returns the namespace used by the routes which don't take one.
+pub(super) fn default_namespace(state: &RouteState) -> Result<String, IndexifyAPIError> {
+    state.config.default_namespace.clone().ok_or_else(|| {
+        IndexifyAPIError::with_code(
+            ErrorCode::NamespaceNotFound,
+            "no default namespace is configured",
+        )
+    })
+}
+
+/// Create compute graph in the default namespace
+#[utoipa::path(
+    post,
+    path = "/compute_graphs",
+    tag = "operations",
+    request_body(content_type = "multipart/form-data", content = inline(ComputeGraphCreateType)),
+    responses(
+        (status = 200, description = "Create a Compute Graph", body = ComputeGraphCreated),
+        (status = NOT_FOUND, description = "No default namespace is configured"),
+        (status = PAYLOAD_TOO_LARGE, description = "Compute Graph definition is too large"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to create compute graphs")
+    ),
+)]
+pub async fn create_default_compute_graph(
+    State(state): State<RouteState>,
+    headers: HeaderMap,
+    compute_graph_code: Multipart,
+) -> Result<Json<ComputeGraphCreated>, IndexifyAPIError> {
+    let namespace = default_namespace(&state)?;
+    create_compute_graph(Path(namespace), State(state), headers, compute_graph_code).await
+}
+
+/// List compute graphs of the default namespace
+#[utoipa::path(
+    get,
+    path = "/compute_graphs",
+    tag = "operations",
+    params(
+        ("limit" = Option<usize>, Query, description = "Maximum number of graphs to return"),
+        ("cursor" = Option<String>, Query, description = "Cursor returned by the previous page"),
+        ("enabled" = Option<bool>, Query, description = "Only list graphs with this enabled state"),
+        ("created_by" = Option<String>, Query, description = "Only list graphs created by this actor"),
+        ("sort" = Option<ComputeGraphSort>, Query, description = "Order the graphs by code size instead of by name, without pagination. Every graph of the namespace is read to sort them"),
+        ("order" = Option<SortOrder>, Query, description = "Direction of a sorted listing, defaults to desc"),
+    ),
+    responses(
+        (status = 200, description = "Lists Compute Graph", body = ComputeGraphsList),
+        (status = BAD_REQUEST, description = "Sorted listing with a cursor or a filter"),
+        (status = NOT_FOUND, description = "No default namespace is configured"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_default_compute_graphs(
+    params: Query<ListComputeGraphsParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<ComputeGraphsList>, IndexifyAPIError> {
+    let namespace = default_namespace(&state)?;
+    list_compute_graphs(Path(namespace), params, State(state)).await
+}
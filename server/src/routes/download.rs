@@ -8,6 +8,16 @@ use axum::{
 use super::RouteState;
 use crate::http_objects::IndexifyAPIError;
 
+/// Get the input payload of an invocation
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/payload",
+    tag = "retrieve",
+    responses(
+        (status = 200, description = "Input payload of the invocation"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
 pub async fn download_invocation_payload(
     Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
     State(state): State<RouteState>,
@@ -22,7 +32,10 @@ pub async fn download_invocation_payload(
                 e
             ))
         })?;
-    let storage_reader = state.blob_storage.get(&output.payload.path);
+    let storage_reader = state
+        .blob_storage
+        .get(&output.payload.path)
+        .map_err(IndexifyAPIError::internal_error)?;
     let payload_stream = storage_reader
         .get()
         .await
@@ -65,7 +78,7 @@ pub async fn download_fn_output_payload(
                 e
             ))
         })?
-        .ok_or(IndexifyAPIError::not_found(
+        .ok_or(IndexifyAPIError::output_not_found(
             format!(
                 "fn output not found: {}/{}/{}/{}/{}",
                 namespace, compute_graph, invocation_id, fn_name, id
@@ -81,7 +94,10 @@ pub async fn download_fn_output_payload(
             )))
         }
     };
-    let storage_reader = state.blob_storage.get(&payload.path);
+    let storage_reader = state
+        .blob_storage
+        .get(&payload.path)
+        .map_err(IndexifyAPIError::internal_error)?;
     let payload_stream = storage_reader
         .get()
         .await
@@ -117,7 +133,10 @@ pub async fn download_fn_output_by_key(
             )))
         }
     };
-    let storage_reader = state.blob_storage.get(&payload.path);
+    let storage_reader = state
+        .blob_storage
+        .get(&payload.path)
+        .map_err(IndexifyAPIError::internal_error)?;
     let payload_stream = storage_reader
         .get()
         .await
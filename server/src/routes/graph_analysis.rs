@@ -0,0 +1,149 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap},
+    Json,
+};
+
+use super::{check_node_count, parse_compute_graph_definition, RouteState};
+use crate::http_objects::{
+    ComputeGraphEdge,
+    ComputeGraphEdges,
+    ComputeGraphLint,
+    ComputeGraphStats,
+    ExecutionPlan,
+    IndexifyAPIError,
+};
+
+/// Report advisory warnings about the shape of a compute graph
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/lint",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Lint warnings", body = ComputeGraphLint),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn lint_compute_graph(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<ComputeGraphLint>, IndexifyAPIError> {
+    let compute_graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    Ok(Json(compute_graph.lint().into()))
+}
+
+/// Preview the stages in which the nodes of a compute graph run
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/plan",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Execution plan", body = ExecutionPlan),
+        (status = BAD_REQUEST, description = "Compute Graph has a cycle"),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn compute_graph_plan(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<ExecutionPlan>, IndexifyAPIError> {
+    let compute_graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    let plan = compute_graph
+        .execution_plan()
+        .map_err(|err| IndexifyAPIError::bad_request(&err.to_string()))?;
+    Ok(Json(plan.into()))
+}
+
+/// List the edges of a compute graph as source and target pairs, including
+/// the branches of its routers
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/edges",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Edges of the compute graph", body = ComputeGraphEdges),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn compute_graph_edges(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<ComputeGraphEdges>, IndexifyAPIError> {
+    let compute_graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    let edges = compute_graph
+        .edges()
+        .into_iter()
+        .map(|(source, target)| ComputeGraphEdge { source, target })
+        .collect();
+    Ok(Json(ComputeGraphEdges { edges }))
+}
+
+/// Get structural metrics of a compute graph, such as its number of nodes
+/// and its largest fan-out
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/stats",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Statistics of the compute graph", body = ComputeGraphStats),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn compute_graph_stats(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<ComputeGraphStats>, IndexifyAPIError> {
+    let compute_graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    Ok(Json(compute_graph.stats().into()))
+}
+
+/// Report advisory warnings about a compute graph definition without creating
+/// it
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/lint",
+    tag = "operations",
+    request_body(content = ComputeGraph, content_type = "application/json"),
+    responses(
+        (status = 200, description = "Lint warnings", body = ComputeGraphLint),
+        (status = BAD_REQUEST, description = "Invalid compute graph definition")
+    ),
+)]
+pub async fn lint_compute_graph_definition(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    headers: HeaderMap,
+    definition: String,
+) -> Result<Json<ComputeGraphLint>, IndexifyAPIError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let compute_graph = parse_compute_graph_definition(&namespace, &definition, content_type)?
+        .into_data_model("", "", 0)?;
+    check_node_count(&state.config, &compute_graph)?;
+    Ok(Json(compute_graph.lint().into()))
+}
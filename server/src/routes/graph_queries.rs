@@ -0,0 +1,196 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use indexify_utils::get_epoch_time_in_ms;
+
+use super::{check_namespace_exists, RouteState};
+use crate::http_objects::{
+    ComputeGraphsList,
+    CreationHistogram,
+    CreationHistogramParams,
+    GraphLabels,
+    HistogramBucket,
+    HistogramBucketCount,
+    IndexifyAPIError,
+    MissingCode,
+    RecentComputeGraphsParams,
+};
+
+/// Count the compute graphs of a namespace by creation day or week
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/histogram",
+    tag = "operations",
+    params(
+        ("bucket" = Option<HistogramBucket>, Query, description = "Width of the buckets, defaults to day"),
+        ("start" = Option<u64>, Query, description = "Only count graphs created at or after this time in ms"),
+        ("end" = Option<u64>, Query, description = "Only count graphs created before this time in ms, defaults to now"),
+    ),
+    responses(
+        (status = 200, description = "Compute graphs created by bucket", body = CreationHistogram),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn compute_graph_creation_histogram(
+    Path(namespace): Path<String>,
+    Query(params): Query<CreationHistogramParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<CreationHistogram>, IndexifyAPIError> {
+    let bucket = params.bucket.unwrap_or(HistogramBucket::Day);
+    let counts = state
+        .indexify_state
+        .reader()
+        .compute_graph_creation_histogram(
+            &namespace,
+            bucket.into(),
+            params.start.unwrap_or(0),
+            params.end.unwrap_or_else(get_epoch_time_in_ms),
+        )
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(CreationHistogram {
+        bucket,
+        buckets: counts
+            .into_iter()
+            .map(|(start, count)| HistogramBucketCount { start, count })
+            .collect(),
+    }))
+}
+
+/// List the compute graphs of a namespace which were never invoked
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/unused",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Compute graphs without invocations", body = ComputeGraphsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn unused_compute_graphs(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<ComputeGraphsList>, IndexifyAPIError> {
+    let compute_graphs = state
+        .indexify_state
+        .reader()
+        .unused_compute_graphs(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(ComputeGraphsList {
+        compute_graphs: compute_graphs.into_iter().map(|c| c.into()).collect(),
+        cursor: None,
+    }))
+}
+
+/// List the compute graphs of a namespace whose code is missing from blob
+/// storage
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/missing_code",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Compute graphs whose code is missing", body = MissingCode),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn compute_graphs_with_missing_code(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<MissingCode>, IndexifyAPIError> {
+    check_namespace_exists(&state, &namespace)?;
+    let compute_graphs = state
+        .indexify_state
+        .list_graphs_with_missing_code(&namespace, &state.blob_storage)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(MissingCode { compute_graphs }))
+}
+
+/// List the compute graphs of a namespace with a node of the given type,
+/// the `fn_name` of a compute function or the name of a router
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/by_node/{node_type}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Compute graphs using the node type", body = ComputeGraphsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn compute_graphs_by_node(
+    Path((namespace, node_type)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<ComputeGraphsList>, IndexifyAPIError> {
+    let compute_graphs = state
+        .indexify_state
+        .reader()
+        .find_graphs_using_node(&namespace, &node_type)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(ComputeGraphsList {
+        compute_graphs: compute_graphs.into_iter().map(|c| c.into()).collect(),
+        cursor: None,
+    }))
+}
+
+/// List the label keys used by the compute graphs of a namespace with their
+/// values
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/labels",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Values of each label key", body = GraphLabels),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn graph_labels(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<GraphLabels>, IndexifyAPIError> {
+    check_namespace_exists(&state, &namespace)?;
+    let labels = state
+        .indexify_state
+        .reader()
+        .distinct_labels(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(GraphLabels { labels }))
+}
+
+/// Number of compute graphs listed by the recent compute graphs route when
+/// no limit is given.
+const DEFAULT_RECENT_COMPUTE_GRAPHS: usize = 20;
+
+/// List the compute graphs of a namespace, the most recently created or
+/// updated first
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/recent",
+    tag = "operations",
+    params(
+        ("limit" = Option<usize>, Query, description = "Largest number of compute graphs listed, defaults to 20"),
+    ),
+    responses(
+        (status = 200, description = "Recently updated compute graphs", body = ComputeGraphsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn recent_compute_graphs(
+    Path(namespace): Path<String>,
+    Query(params): Query<RecentComputeGraphsParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<ComputeGraphsList>, IndexifyAPIError> {
+    let compute_graphs = state
+        .indexify_state
+        .reader()
+        .list_recently_updated(
+            &namespace,
+            params.limit.unwrap_or(DEFAULT_RECENT_COMPUTE_GRAPHS),
+        )
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(ComputeGraphsList {
+        compute_graphs: compute_graphs.into_iter().map(|c| c.into()).collect(),
+        cursor: None,
+    }))
+}
@@ -0,0 +1,143 @@
+use axum::extract::{Path, State};
+use state_store::requests::{
+    RequestPayload,
+    SetComputeGraphEnabledRequest,
+    SetComputeGraphFrozenRequest,
+    StateMachineUpdateRequest,
+};
+
+use super::RouteState;
+use crate::http_objects::IndexifyAPIError;
+
+async fn set_compute_graph_enabled(
+    state: &RouteState,
+    namespace: String,
+    name: String,
+    enabled: bool,
+) -> Result<(), IndexifyAPIError> {
+    let compute_graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?;
+    if compute_graph.is_none() {
+        return Err(IndexifyAPIError::compute_graph_not_found());
+    }
+    let request = RequestPayload::SetComputeGraphEnabled(SetComputeGraphEnabledRequest {
+        namespace,
+        name,
+        enabled,
+    });
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: request,
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(())
+}
+
+/// Enable a compute graph so it accepts invocations again
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/enable",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Compute graph enabled"),
+        (status = NOT_FOUND, description = "Compute graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn enable_compute_graph(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    set_compute_graph_enabled(&state, namespace, compute_graph, true).await
+}
+
+/// Disable a compute graph, new invocations are rejected until it's enabled
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/disable",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Compute graph disabled"),
+        (status = NOT_FOUND, description = "Compute graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn disable_compute_graph(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    set_compute_graph_enabled(&state, namespace, compute_graph, false).await
+}
+
+async fn set_compute_graph_frozen(
+    state: &RouteState,
+    namespace: String,
+    name: String,
+    frozen: bool,
+) -> Result<(), IndexifyAPIError> {
+    let compute_graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?;
+    if compute_graph.is_none() {
+        return Err(IndexifyAPIError::compute_graph_not_found());
+    }
+    let request = RequestPayload::SetComputeGraphFrozen(SetComputeGraphFrozenRequest {
+        namespace,
+        name,
+        frozen,
+    });
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: request,
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(())
+}
+
+/// Freeze a compute graph, updates and deletes are rejected until it's
+/// unfrozen
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/freeze",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Compute graph frozen"),
+        (status = NOT_FOUND, description = "Compute graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn freeze_compute_graph(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    set_compute_graph_frozen(&state, namespace, compute_graph, true).await
+}
+
+/// Unfreeze a compute graph so it can be updated and deleted again
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/unfreeze",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Compute graph unfrozen"),
+        (status = NOT_FOUND, description = "Compute graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn unfreeze_compute_graph(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    set_compute_graph_frozen(&state, namespace, compute_graph, false).await
+}
@@ -0,0 +1,144 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use blob_store::PutOptions;
+use nanoid::nanoid;
+use state_store::requests::{CreateComputeGraphRequest, RequestPayload, StateMachineUpdateRequest};
+use tracing::info;
+
+use super::{
+    check_namespace_exists,
+    check_node_count,
+    check_strict_validation,
+    compute_graph_write_error,
+    RouteState,
+};
+use crate::http_objects::{ComputeGraph, IndexifyAPIError, InstantiateComputeGraph, NodeOverride};
+
+fn apply_node_override(node: &mut data_model::Node, node_override: &NodeOverride) {
+    let (description, image_name, payload_encoder) = match node {
+        data_model::Node::Router(router) => (
+            &mut router.description,
+            &mut router.image_name,
+            &mut router.payload_encoder,
+        ),
+        data_model::Node::Compute(compute_fn) => (
+            &mut compute_fn.description,
+            &mut compute_fn.image_name,
+            &mut compute_fn.payload_encoder,
+        ),
+    };
+    if let Some(value) = &node_override.description {
+        description.clone_from(value);
+    }
+    if let Some(value) = &node_override.image_name {
+        image_name.clone_from(value);
+    }
+    if let Some(value) = &node_override.payload_encoder {
+        payload_encoder.clone_from(value);
+    }
+}
+
+/// Create a compute graph from another one used as a template, replacing
+/// fields of some of its nodes
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/instantiate",
+    request_body = InstantiateComputeGraph,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Created compute graph", body = ComputeGraph),
+        (status = BAD_REQUEST, description = "An override names an unknown node, or the created graph is invalid"),
+        (status = NOT_FOUND, description = "Namespace or template compute graph not found"),
+        (status = CONFLICT, description = "A compute graph with the new name already exists"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn instantiate_compute_graph(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    Json(request): Json<InstantiateComputeGraph>,
+) -> Result<Json<ComputeGraph>, IndexifyAPIError> {
+    check_namespace_exists(&state, &namespace)?;
+    let reader = state.indexify_state.reader();
+    let mut compute_graph = reader
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    if reader
+        .get_compute_graph(&namespace, &request.name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .is_some()
+    {
+        return Err(IndexifyAPIError::compute_graph_exists(&request.name));
+    }
+
+    for (node_name, node_override) in &request.node_overrides {
+        let Some(node) = compute_graph.nodes.get_mut(node_name) else {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "compute graph {} has no node {}",
+                name, node_name
+            )));
+        };
+        apply_node_override(node, node_override);
+        if compute_graph.start_fn.name() == node_name {
+            apply_node_override(&mut compute_graph.start_fn, node_override);
+        }
+    }
+    compute_graph.name = request.name;
+    if let Some(description) = request.description {
+        compute_graph.description = description;
+    }
+    compute_graph.version = Default::default();
+    compute_graph.created_at = 0;
+    compute_graph.enabled = true;
+    compute_graph.frozen = false;
+    compute_graph.display_name = None;
+    compute_graph.actor = None;
+    check_node_count(&state.config, &compute_graph)?;
+    check_strict_validation(&state, &compute_graph)?;
+
+    if request.copy_code {
+        let code = state
+            .blob_storage
+            .read_bytes(&compute_graph.code.path)
+            .await
+            .map_err(IndexifyAPIError::internal_error)?;
+        let file_name = format!("{}_{}", namespace, nanoid!());
+        let options = PutOptions {
+            if_not_exists: true,
+        };
+        let put_result = state
+            .blob_storage
+            .put_opts(&file_name, futures::stream::iter([Ok(code)]), options)
+            .await
+            .map_err(IndexifyAPIError::internal_error)?;
+        compute_graph.code.path = put_result.url;
+    }
+
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                namespace: namespace.clone(),
+                compute_graph: compute_graph.clone(),
+                if_match: None,
+                if_changed: false,
+            })),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(compute_graph_write_error)?;
+    let compute_graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &compute_graph.name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    info!(
+        "compute graph {} instantiated from {}",
+        compute_graph.name, name
+    );
+    Ok(Json(compute_graph.into()))
+}
@@ -1,9 +1,11 @@
-use std::{collections::HashMap, sync::Arc, vec};
+use std::{sync::Arc, vec};
 
 use anyhow::{anyhow, Result};
 use axum::extract::{multipart::Field, Multipart, State};
 use blob_store::{BlobStorage, PutResult};
+use bytes::Bytes;
 use data_model::{
+    json_schema::{self, SchemaViolation},
     DataPayload,
     ExecutorId,
     NodeOutput,
@@ -12,11 +14,10 @@ use data_model::{
     TaskDiagnostics,
     TaskId,
 };
-use futures::StreamExt;
+use futures::{stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use state_store::requests::{FinalizeTaskRequest, RequestPayload, StateMachineUpdateRequest};
 use tracing::{error, info};
-use utoipa::ToSchema;
 
 use super::RouteState;
 use crate::http_objects::IndexifyAPIError;
@@ -69,27 +70,7 @@ pub struct RouterOutput {
     pub edges: Vec<String>,
 }
 
-#[allow(dead_code)]
-#[derive(ToSchema)]
-pub struct InvokeWithFile {
-    /// Extra metadata for file
-    metadata: Option<HashMap<String, serde_json::Value>>,
-    #[schema(format = "binary")]
-    /// File to upload
-    file: Option<String>,
-}
-/// Upload data to a compute graph
-#[utoipa::path(
-    post,
-    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invoke_file",
-    request_body(content_type = "multipart/form-data", content = inline(InvokeWithFile)),
-    tag = "ingestion",
-    responses(
-        (status = 200, description = "upload successful"),
-        (status = 400, description = "bad request"),
-        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
-    ),
-)]
+/// Stores the outputs and diagnostics of a task uploaded by an executor
 pub async fn ingest_files_from_executor(
     State(state): State<RouteState>,
     mut files: Multipart,
@@ -160,8 +141,17 @@ pub async fn ingest_files_from_executor(
     }
 
     // Save metadata in rocksdb for the objects in the blob store.
-    let task_result =
+    let mut task_result =
         task_result.ok_or(IndexifyAPIError::bad_request("task_result is required"))?;
+    if let TaskOutcome::Success = task_result.outcome {
+        let violations = check_output_schema(&state, &task_result, &output_objects).await?;
+        if !violations.is_empty() {
+            exception_msg = Some(
+                fail_task_output(&state, &mut task_result, &mut output_objects, &violations)
+                    .await?,
+            );
+        }
+    }
     let mut node_outputs: Vec<NodeOutput> = vec![];
 
     for put_result in output_objects {
@@ -236,6 +226,111 @@ pub async fn ingest_files_from_executor(
     Ok(())
 }
 
+/// Checks the outputs of a task against the output schema of its graph.
+/// Outputs larger than `max_schema_validated_bytes` aren't read and are
+/// reported as violations.
+async fn check_output_schema(
+    state: &RouteState,
+    task_result: &TaskResult,
+    outputs: &[PutResult],
+) -> Result<Vec<SchemaViolation>, IndexifyAPIError> {
+    let Some(compute_graph) = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&task_result.namespace, &task_result.compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+    else {
+        return Ok(vec![]);
+    };
+    let Some(schema) = compute_graph.output_schema_of(&task_result.compute_fn) else {
+        return Ok(vec![]);
+    };
+    let max_bytes = state.config.max_schema_validated_bytes;
+    let mut violations = vec![];
+    for (i, output) in outputs.iter().enumerate() {
+        if output.size_bytes > max_bytes as u64 {
+            violations.push(SchemaViolation {
+                path: format!("/{}", i),
+                message: format!(
+                    "output is larger than the {} bytes validated against the output schema",
+                    max_bytes
+                ),
+            });
+            continue;
+        }
+        let data = state
+            .blob_storage
+            .read_bytes(&output.url)
+            .await
+            .map_err(IndexifyAPIError::internal_error)?;
+        violations.extend(output_schema_violations(schema, &data, &format!("/{}", i)));
+    }
+    Ok(violations)
+}
+
+/// Turns a task whose outputs don't match the output schema into a failed
+/// task. The outputs are removed from the blob store and the violations are
+/// stored as the exception of the task, replacing the one it was sent with.
+async fn fail_task_output(
+    state: &RouteState,
+    task_result: &mut TaskResult,
+    outputs: &mut Vec<PutResult>,
+    violations: &[SchemaViolation],
+) -> Result<PutResult, IndexifyAPIError> {
+    for output in outputs.drain(..) {
+        if let Err(e) = state.blob_storage.delete(&output.url).await {
+            error!("failed to delete rejected output {}: {:?}", output.url, e);
+        }
+    }
+    task_result.router_output = None;
+    task_result.outcome = TaskOutcome::Failure;
+    let mut message = format!(
+        "outputs of {} don't match the output schema of the compute graph",
+        task_result.compute_fn
+    );
+    for violation in violations {
+        message.push_str(&format!("\n{}", violation));
+    }
+    let file_name = format!(
+        "{}.{}.{}.{}.exception_msg",
+        task_result.namespace,
+        task_result.compute_graph,
+        task_result.compute_fn,
+        task_result.invocation_id,
+    );
+    let data = stream::once(async move { Ok(Bytes::from(message)) });
+    state
+        .blob_storage
+        .put(&file_name, Box::pin(data))
+        .await
+        .map_err(|e| {
+            error!("failed to write to blob store: {}", e);
+            IndexifyAPIError::internal_error(anyhow!("failed to write to blob store: {}", e))
+        })
+}
+
+/// Validates a JSON encoded output, prefixing the paths of the violations
+/// with `path`.
+pub(crate) fn output_schema_violations(
+    schema: &serde_json::Value,
+    data: &[u8],
+    path: &str,
+) -> Vec<SchemaViolation> {
+    match serde_json::from_slice(data) {
+        Ok(value) => json_schema::validate(schema, &value)
+            .into_iter()
+            .map(|violation| SchemaViolation {
+                path: format!("{}{}", path, violation.path),
+                ..violation
+            })
+            .collect(),
+        Err(e) => vec![SchemaViolation {
+            path: path.to_string(),
+            message: format!("invalid json: {}", e),
+        }],
+    }
+}
+
 async fn write_to_disk<'a>(
     blob_storage: Arc<BlobStorage>,
     field: &'a mut Field<'a>,
@@ -1,14 +1,19 @@
-use std::time::Duration;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
 
 use anyhow::anyhow;
 use axum::{
     body::Body,
     extract::{Multipart, Path, Query, State},
+    http::StatusCode,
     response::{sse::Event, IntoResponse},
     Json,
 };
 use blob_store::PutResult;
-use data_model::InvocationPayloadBuilder;
+use bytes::{Bytes, BytesMut};
+use data_model::{json_schema, ComputeGraph, InvocationPayloadBuilder};
 use futures::{stream, StreamExt};
 use state_store::{
     invocation_events::{InvocationFinishedEvent, InvocationStateChangeEvent},
@@ -18,44 +23,153 @@ use state_store::{
         RerunComputeGraphRequest,
         StateMachineUpdateRequest,
     },
+    state_machine::ComputeGraphDisabled,
 };
-use tokio::sync::broadcast::Receiver;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
 use tracing::{error, info};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-use super::RouteState;
-use crate::http_objects::{GraphInputFile, IndexifyAPIError, InvocationId, InvocationQueryParams};
+use super::{lagged_event, RouteState};
+use crate::http_objects::{
+    ErrorCode,
+    GraphInputFile,
+    IndexifyAPIError,
+    InvocationId,
+    InvocationQueryParams,
+};
+
+/// Rejects invocations of graphs which don't exist or are disabled before any
+/// payload is uploaded. Frozen graphs still accept invocations, freezing only
+/// keeps their definition from changing.
+fn check_graph_accepts_invocations(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+) -> Result<ComputeGraph, IndexifyAPIError> {
+    let graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(namespace, compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    if !graph.enabled {
+        return Err(IndexifyAPIError::with_code(
+            ErrorCode::ComputeGraphDisabled,
+            &format!("compute graph {} is disabled", compute_graph),
+        ));
+    }
+    Ok(graph)
+}
 
-// #[allow(dead_code)]
-// #[derive(ToSchema)]
-// pub struct InvokeWithFile {
-//     /// Extra metadata for file
-//     metadata: Option<HashMap<String, serde_json::Value>>,
-//     #[schema(format = "binary")]
-//     /// Mime type of file
-//     mime_type: Option<String>,
-//     /// File to upload
-//     file: Option<String>,
-// }
-// /// Upload data to a compute graph
-// #[utoipa::path(
-//     post,
-//     path =
-// "/namespaces/{namespace}/compute_graphs/{compute_graph}/invoke_file",
-//     request_body(content_type = "multipart/form-data", content =
-// inline(InvokeWithFile)),     tag = "ingestion",
-//     responses(
-//         (status = 200, description = "upload successful"),
-//         (status = 400, description = "bad request"),
-//         (status = INTERNAL_SERVER_ERROR, description = "Internal Server
-// Error")     ),
-// )]
+/// Maps the errors of invocation writes to their status code. Graphs disabled
+/// since they were checked are rejected by the state machine.
+fn invocation_write_error(e: anyhow::Error) -> IndexifyAPIError {
+    if let Some(disabled) = e.downcast_ref::<ComputeGraphDisabled>() {
+        return IndexifyAPIError::with_code(ErrorCode::ComputeGraphDisabled, &disabled.to_string());
+    }
+    IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
+}
+
+/// Reads the whole body of a request validated against a schema of its
+/// graph. Bodies larger than `max_bytes` are rejected with 413 as soon as the
+/// limit is crossed.
+pub(super) async fn read_schema_validated_body(
+    body: Body,
+    max_bytes: usize,
+) -> Result<Bytes, IndexifyAPIError> {
+    let mut data = BytesMut::new();
+    let mut chunks = body.into_data_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+        if data.len() + chunk.len() > max_bytes {
+            return Err(IndexifyAPIError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                &format!(
+                    "body is larger than the {} bytes validated against a schema",
+                    max_bytes
+                ),
+            ));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data.freeze())
+}
+
+/// Checks an object input against the input schema of its graph. Inputs are
+/// decoded as JSON, or as CBOR when they aren't JSON.
+fn check_input_schema(schema: &serde_json::Value, input: &[u8]) -> Result<(), IndexifyAPIError> {
+    let input: serde_json::Value = match serde_json::from_slice(input) {
+        Ok(input) => input,
+        Err(_) => ciborium::from_reader(input).map_err(|_| {
+            IndexifyAPIError::bad_request("input must be JSON or CBOR to match the input schema")
+        })?,
+    };
+    let violations = json_schema::validate(schema, &input);
+    if !violations.is_empty() {
+        return Err(IndexifyAPIError::schema_violations(
+            "input doesn't match the input schema of the compute graph",
+            &violations,
+        ));
+    }
+    Ok(())
+}
+
+/// Parses the attributes of an input sent in the `attributes` query
+/// parameter.
+fn parse_input_attributes(
+    params: &InvocationQueryParams,
+) -> Result<BTreeMap<String, String>, IndexifyAPIError> {
+    let Some(attributes) = &params.attributes else {
+        return Ok(BTreeMap::new());
+    };
+    serde_json::from_str(attributes).map_err(|e| {
+        IndexifyAPIError::bad_request(&format!(
+            "attributes must be a JSON object of strings: {}",
+            e
+        ))
+    })
+}
+
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct InvokeWithFile {
+    /// Extra metadata for file
+    metadata: Option<HashMap<String, serde_json::Value>>,
+    #[schema(format = "binary")]
+    /// File to upload
+    file: Option<String>,
+}
+
+/// Upload data to a compute graph
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invoke_file",
+    request_body(content_type = "multipart/form-data", content = inline(InvokeWithFile)),
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "upload successful", body = InvocationId),
+        (status = 400, description = "bad request, or the compute graph has an input schema"),
+        (status = CONFLICT, description = "Compute Graph is disabled"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
 pub async fn invoke_with_file(
     Path((namespace, compute_graph)): Path<(String, String)>,
     State(state): State<RouteState>,
-    Query(_params): Query<InvocationQueryParams>,
+    Query(params): Query<InvocationQueryParams>,
     mut files: Multipart,
 ) -> Result<Json<InvocationId>, IndexifyAPIError> {
+    let graph = check_graph_accepts_invocations(&state, &namespace, &compute_graph)?;
+    // Files are stored as they're received, they can't be checked against the
+    // objects an input schema describes.
+    if graph.input_schema.is_some() {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "compute graph {} has an input schema, invoke it with an object",
+            compute_graph
+        )));
+    }
+    let attributes = parse_input_attributes(&params)?;
     let mut metadata: Option<serde_json::Value> = None;
     let mut put_result: Option<PutResult> = None;
 
@@ -65,6 +179,7 @@ pub async fn invoke_with_file(
                 let name = Uuid::new_v4().to_string();
                 info!("writing to blob store, file name = {:?}", name);
                 let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
+                let _permit = state.upload_limiter.acquire().await?;
                 let res = state.blob_storage.put(&name, stream).await.map_err(|e| {
                     error!("failed to write to blob store: {}", e);
                     IndexifyAPIError::internal_error(anyhow!(
@@ -115,6 +230,7 @@ pub async fn invoke_with_file(
         .namespace(namespace.clone())
         .compute_graph_name(compute_graph.clone())
         .payload(data_payload)
+        .attributes(attributes)
         .build()
         .map_err(|e| {
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
@@ -133,9 +249,7 @@ pub async fn invoke_with_file(
             state_changes_processed: vec![],
         })
         .await
-        .map_err(|e| {
-            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
-        })?;
+        .map_err(invocation_write_error)?;
     Ok(Json(InvocationId { id }))
 }
 
@@ -148,6 +262,8 @@ pub async fn invoke_with_file(
     responses(
         (status = 200, description = "invocation successful"),
         (status = 400, description = "bad request"),
+        (status = CONFLICT, description = "Compute Graph is disabled"),
+        (status = PAYLOAD_TOO_LARGE, description = "Input is too large to be validated against the input schema"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
@@ -157,11 +273,23 @@ pub async fn invoke_with_object(
     State(state): State<RouteState>,
     body: Body,
 ) -> Result<impl IntoResponse, IndexifyAPIError> {
+    let graph = check_graph_accepts_invocations(&state, &namespace, &compute_graph)?;
+    let attributes = parse_input_attributes(&params)?;
+    let body = match &graph.input_schema {
+        Some(schema) => {
+            let input =
+                read_schema_validated_body(body, state.config.max_schema_validated_bytes).await?;
+            check_input_schema(schema, &input)?;
+            Body::from(input)
+        }
+        None => body,
+    };
     let should_block = params.block_until_finish.unwrap_or(false);
     let payload_key = Uuid::new_v4().to_string();
     let payload_stream = body
         .into_data_stream()
         .map(|res| res.map_err(|err| anyhow::anyhow!(err)));
+    let permit = state.upload_limiter.acquire().await?;
     let put_result = state
         .blob_storage
         .put(&payload_key, Box::pin(payload_stream))
@@ -170,6 +298,8 @@ pub async fn invoke_with_object(
             error!("failed to write to blob store: {}", e);
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
         })?;
+    // Blocking invocations can wait for a long time, don't hold the slot.
+    drop(permit);
     let data_payload = data_model::DataPayload {
         path: put_result.url,
         size: put_result.size_bytes,
@@ -179,6 +309,7 @@ pub async fn invoke_with_object(
         .namespace(namespace.clone())
         .compute_graph_name(compute_graph.clone())
         .payload(data_payload)
+        .attributes(attributes)
         .build()
         .map_err(|e| {
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
@@ -200,9 +331,7 @@ pub async fn invoke_with_object(
             state_changes_processed: vec![],
         })
         .await
-        .map_err(|e| {
-            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
-        })?;
+        .map_err(invocation_write_error)?;
 
     let invocation_event_stream = async_stream::stream! {
         if !should_block {
@@ -211,14 +340,20 @@ pub async fn invoke_with_object(
         }
         if let Some(rx) = rx.as_mut() {
             loop {
-                if let Ok(ev)  =  rx.recv().await {
-                    if ev.invocation_id() == id  || ev.invocation_id() == "" {
-                        yield Event::default().json_data(ev.clone());
+                let ev = match rx.recv().await {
+                    Ok(ev) => ev,
+                    Err(RecvError::Lagged(skipped)) => {
+                        yield lagged_event(skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return,
+                };
+                if ev.invocation_id() == id  || ev.invocation_id() == "" {
+                    yield Event::default().json_data(ev.clone());
 
-                        if let InvocationStateChangeEvent::InvocationFinished(InvocationFinishedEvent{ id }) = ev {
-                            yield Event::default().json_data(InvocationId { id: id.clone() });
-                            return;
-                        }
+                    if let InvocationStateChangeEvent::InvocationFinished(InvocationFinishedEvent{ id }) = ev {
+                        yield Event::default().json_data(InvocationId { id: id.clone() });
+                        return;
                     }
                 }
             }
@@ -234,6 +369,38 @@ pub async fn invoke_with_object(
     )
 }
 
+/// Validate a sample input against the input schema of a compute graph
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/validate_input",
+    request_body(content_type = "application/json", content = inline(serde_json::Value)),
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "The input matches the input schema, or the graph has none"),
+        (status = 400, description = "The input doesn't match the input schema"),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = PAYLOAD_TOO_LARGE, description = "Input is too large to be validated against the input schema"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn validate_input(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    body: Body,
+) -> Result<(), IndexifyAPIError> {
+    let graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    let Some(schema) = &graph.input_schema else {
+        return Ok(());
+    };
+    let input = read_schema_validated_body(body, state.config.max_schema_validated_bytes).await?;
+    check_input_schema(schema, &input)
+}
+
 /// Rerun compute graph with all existing payloads
 #[utoipa::path(
     post,
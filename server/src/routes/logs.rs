@@ -50,7 +50,10 @@ pub async fn download_task_logs(
             "diagnostic payload not found for task"
         )))?;
 
-    let storage_reader = state.blob_storage.get(&payload.path);
+    let storage_reader = state
+        .blob_storage
+        .get(&payload.path)
+        .map_err(IndexifyAPIError::internal_error)?;
     let payload_stream = storage_reader
         .get()
         .await
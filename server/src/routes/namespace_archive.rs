@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use blob_store::PutOptions;
+use futures::TryStreamExt;
+use nanoid::nanoid;
+use state_store::requests::{CreateComputeGraphRequest, RequestPayload, StateMachineUpdateRequest};
+
+use super::{
+    check_namespace_exists,
+    check_node_count,
+    check_strict_validation,
+    compute_graph_write_error,
+    delete_unused_code,
+    parse_compute_graph_definition,
+    RouteState,
+};
+use crate::{
+    archive,
+    http_objects::{IndexifyAPIError, NamespaceImport},
+};
+
+/// Export the compute graphs of a namespace and their code as a tar archive
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/export",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Tar archive with a directory per compute graph",
+            content_type = "application/x-tar"),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = SERVICE_UNAVAILABLE, description = "Too many exports in progress"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn export_namespace(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<impl IntoResponse, IndexifyAPIError> {
+    check_namespace_exists(&state, &namespace)?;
+    let disposition = format!("attachment; filename=\"{}.tar\"", namespace);
+    let archive = archive::export_namespace(
+        state.indexify_state.clone(),
+        state.blob_storage.clone(),
+        namespace,
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-tar".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(archive),
+    ))
+}
+
+/// Create or update compute graphs from an archive produced by the export of
+/// a namespace
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/import",
+    tag = "operations",
+    request_body(content = Vec<u8>, content_type = "application/x-tar"),
+    responses(
+        (status = 200, description = "Imported compute graphs", body = NamespaceImport),
+        (status = BAD_REQUEST, description = "Invalid archive"),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = SERVICE_UNAVAILABLE, description = "Too many imports in progress"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn import_namespace(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    body: Body,
+) -> Result<Json<NamespaceImport>, IndexifyAPIError> {
+    check_namespace_exists(&state, &namespace)?;
+    let _permit = state.upload_limiter.acquire().await?;
+    let mut uploaded = Vec::new();
+    let result = import_archive(&state, &namespace, body, &mut uploaded).await;
+    if result.is_err() {
+        for url in uploaded {
+            delete_unused_code(&state, &url).await;
+        }
+    }
+    result
+}
+
+/// Reads the graphs of an archive as it's received. Each definition is
+/// validated before the code following it is uploaded, and the graphs are
+/// written in a single transaction once the whole archive is read. The urls
+/// of the uploaded code are added to `uploaded` so they can be deleted on
+/// failure.
+async fn import_archive(
+    state: &RouteState,
+    namespace: &str,
+    body: Body,
+    uploaded: &mut Vec<String>,
+) -> Result<Json<NamespaceImport>, IndexifyAPIError> {
+    let body = body.into_data_stream().map_err(std::io::Error::other);
+    let mut archive = archive::ArchiveReader::new(tokio_util::io::StreamReader::new(body));
+    let invalid = |err: anyhow::Error| IndexifyAPIError::bad_request(&err.to_string());
+    let mut definitions: BTreeMap<String, data_model::ComputeGraph> = BTreeMap::new();
+    let mut compute_graphs = Vec::new();
+    while let Some(file) = archive.next_file().await.map_err(invalid)? {
+        let Some((graph, name)) = file.path.rsplit_once('/') else {
+            continue;
+        };
+        match name {
+            archive::DEFINITION_FILE => {
+                let max_bytes = state.config.max_compute_graph_definition_bytes;
+                if file.size > max_bytes as u64 {
+                    return Err(IndexifyAPIError::new(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        &format!("definition of {} is larger than {} bytes", graph, max_bytes),
+                    ));
+                }
+                let definition = archive.read(file.size).await.map_err(invalid)?;
+                let definition = std::str::from_utf8(&definition)
+                    .map_err(|err| IndexifyAPIError::bad_request(&err.to_string()))?;
+                let definition = parse_compute_graph_definition(
+                    namespace,
+                    definition,
+                    Some("application/json"),
+                )?;
+                // The code is filled in once it's uploaded.
+                let compute_graph = definition.into_data_model("", "", 0)?;
+                // Each graph's code is matched to its definition by the
+                // directory, so it has to be named after the graph.
+                if compute_graph.name != graph {
+                    return Err(IndexifyAPIError::bad_request(&format!(
+                        "archive has the definition of {} under {}",
+                        compute_graph.name, graph
+                    )));
+                }
+                check_node_count(&state.config, &compute_graph)?;
+                check_strict_validation(state, &compute_graph)?;
+                definitions.insert(graph.to_string(), compute_graph);
+            }
+            archive::CODE_FILE => {
+                // Exports write the definition of a graph before its code.
+                let Some(mut compute_graph) = definitions.remove(graph) else {
+                    return Err(IndexifyAPIError::bad_request(&format!(
+                        "archive has the code of {} before its definition",
+                        graph
+                    )));
+                };
+                let max_bytes = state.config.max_compute_graph_code_bytes;
+                if file.size > max_bytes as u64 {
+                    return Err(IndexifyAPIError::new(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        &format!("code of {} is larger than {} bytes", graph, max_bytes),
+                    ));
+                }
+                let file_name = format!("{}_{}", namespace, nanoid!());
+                let options = PutOptions {
+                    if_not_exists: true,
+                };
+                let put_result = state
+                    .blob_storage
+                    .put_opts(&file_name, archive.stream(file.size), options)
+                    .await
+                    .map_err(IndexifyAPIError::internal_error)?;
+                uploaded.push(put_result.url.clone());
+                if put_result.size_bytes != file.size {
+                    return Err(IndexifyAPIError::bad_request("tar archive is truncated"));
+                }
+                compute_graph.code = data_model::ComputeGraphCode {
+                    path: put_result.url,
+                    size: put_result.size_bytes,
+                    sha256_hash: put_result.sha256_hash,
+                };
+                compute_graphs.push(compute_graph);
+            }
+            _ => {}
+        }
+    }
+    if let Some(graph) = definitions.keys().next() {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "archive is missing the code of {}",
+            graph
+        )));
+    }
+
+    let names = compute_graphs.iter().map(|g| g.name.clone()).collect();
+    let requests = compute_graphs
+        .into_iter()
+        .map(|compute_graph| StateMachineUpdateRequest {
+            payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                namespace: namespace.to_string(),
+                compute_graph,
+                if_match: None,
+                if_changed: false,
+            })),
+            state_changes_processed: vec![],
+        })
+        .collect();
+    state
+        .indexify_state
+        .write_all(requests)
+        .await
+        .map_err(compute_graph_write_error)?;
+    Ok(Json(NamespaceImport {
+        compute_graphs: names,
+    }))
+}
@@ -0,0 +1,101 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use indexify_utils::get_epoch_time_in_ms;
+
+use super::RouteState;
+use crate::http_objects::{
+    FailedOutputs,
+    IndexifyAPIError,
+    ListFailuresParams,
+    NamespaceDigest,
+    NamespaceUsage,
+};
+
+/// Get the storage used by a namespace
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/usage",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Storage used by the namespace", body = NamespaceUsage),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn namespace_usage(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<NamespaceUsage>, IndexifyAPIError> {
+    let blob_usage = state
+        .indexify_state
+        .reader()
+        .namespace_blob_usage(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(NamespaceUsage {
+        namespace,
+        blob_bytes: blob_usage.into(),
+    }))
+}
+
+/// Get a hash of all the compute graphs of a namespace
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/digest",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Digest of the namespace's compute graphs", body = NamespaceDigest),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn namespace_digest(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<NamespaceDigest>, IndexifyAPIError> {
+    let digest = state
+        .indexify_state
+        .reader()
+        .namespace_graphs_digest(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(NamespaceDigest { namespace, digest }))
+}
+
+const DEFAULT_FAILURES_WINDOW_MS: u64 = 60 * 60 * 1000;
+const DEFAULT_FAILURES_LIMIT: usize = 100;
+const MAX_FAILURES_LIMIT: usize = 1000;
+
+/// List recent task failures in a namespace, oldest first
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/failures",
+    tag = "operations",
+    params(
+        ("since" = Option<u64>, Query, description = "Only list failures recorded at or after this time in ms, defaults to the last hour"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of failures to return, at most 1000"),
+    ),
+    responses(
+        (status = 200, description = "Recent failures", body = FailedOutputs),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_failures(
+    Path(namespace): Path<String>,
+    Query(params): Query<ListFailuresParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<FailedOutputs>, IndexifyAPIError> {
+    let since = params
+        .since
+        .unwrap_or_else(|| get_epoch_time_in_ms().saturating_sub(DEFAULT_FAILURES_WINDOW_MS));
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_FAILURES_LIMIT)
+        .min(MAX_FAILURES_LIMIT);
+    let failures = state
+        .indexify_state
+        .reader()
+        .list_failed_outputs(&namespace, since, limit)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(FailedOutputs {
+        failures: failures.into_iter().map(|f| f.into()).collect(),
+    }))
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use data_model::FEATURE_FLAGS;
+use state_store::requests::{
+    RequestPayload,
+    SetNamespaceFlagsRequest,
+    SetNamespacePolicyRequest,
+    StateMachineUpdateRequest,
+};
+
+use super::RouteState;
+use crate::http_objects::{IndexifyAPIError, Namespace, NamespaceFeatureFlags, NamespacePolicy};
+
+/// Rejects feature flags which no handler consults.
+pub(super) fn check_feature_flags(flags: &BTreeMap<String, bool>) -> Result<(), IndexifyAPIError> {
+    match flags
+        .keys()
+        .find(|flag| !FEATURE_FLAGS.contains(&flag.as_str()))
+    {
+        Some(flag) => Err(IndexifyAPIError::bad_request(&format!(
+            "unknown feature flag {}, known flags are: {}",
+            flag,
+            FEATURE_FLAGS.join(", ")
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Set feature flags of a namespace, leaving the flags not in the request
+/// unchanged
+#[utoipa::path(
+    patch,
+    path = "/namespaces/{namespace}/flags",
+    request_body = NamespaceFeatureFlags,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Namespace with its updated flags", body = Namespace),
+        (status = BAD_REQUEST, description = "Unknown feature flag"),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to update namespace")
+    ),
+)]
+pub async fn set_namespace_flags(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    Json(flags): Json<NamespaceFeatureFlags>,
+) -> Result<Json<Namespace>, IndexifyAPIError> {
+    check_feature_flags(&flags.feature_flags)?;
+    let exists = state
+        .indexify_state
+        .reader()
+        .get_namespace(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .is_some();
+    if !exists {
+        return Err(IndexifyAPIError::namespace_not_found(&namespace));
+    }
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::SetNamespaceFlags(SetNamespaceFlagsRequest {
+                name: namespace.clone(),
+                feature_flags: flags.feature_flags,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    let namespace = state
+        .indexify_state
+        .reader()
+        .get_namespace(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(|| IndexifyAPIError::namespace_not_found(&namespace))?;
+    Ok(Json(namespace.into()))
+}
+
+/// Set the retention policy of a namespace
+#[utoipa::path(
+    put,
+    path = "/namespaces/{namespace}/policy",
+    request_body = NamespacePolicy,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Namespace with its updated policy", body = Namespace),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to update namespace")
+    ),
+)]
+pub async fn set_namespace_policy(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    Json(policy): Json<NamespacePolicy>,
+) -> Result<Json<Namespace>, IndexifyAPIError> {
+    // Namespaces aren't created along with their policy, even when they are
+    // created along with compute graphs.
+    let exists = state
+        .indexify_state
+        .reader()
+        .get_namespace(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .is_some();
+    if !exists {
+        return Err(IndexifyAPIError::namespace_not_found(&namespace));
+    }
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::SetNamespacePolicy(SetNamespacePolicyRequest {
+                namespace: namespace.clone(),
+                retention: policy.retention.into(),
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    let namespace = state
+        .indexify_state
+        .reader()
+        .get_namespace(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(|| IndexifyAPIError::namespace_not_found(&namespace))?;
+    Ok(Json(namespace.into()))
+}
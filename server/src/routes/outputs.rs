@@ -0,0 +1,166 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    Json,
+};
+use futures::StreamExt;
+use nanoid::nanoid;
+use state_store::requests::{AttachOutputRequest, RequestPayload, StateMachineUpdateRequest};
+
+use super::{internal_ingest, invoke, RouteState};
+use crate::http_objects::{
+    AttachOutputParams,
+    FnOutput,
+    IndexifyAPIError,
+    LatestOutputs,
+    LatestOutputsRequest,
+    OutputLineage,
+};
+
+/// Attach an output computed outside of Indexify to an invocation
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/outputs",
+    tag = "ingestion",
+    params(
+        ("compute_fn" = String, Query, description = "Function the output is attributed to"),
+    ),
+    request_body(content_type = "application/octet-stream", content = inline(serde_json::Value)),
+    responses(
+        (status = 200, description = "Output attached", body = FnOutput),
+        (status = 400, description = "Output doesn't match the output schema of the compute graph"),
+        (status = NOT_FOUND, description = "Invocation not found"),
+        (status = PAYLOAD_TOO_LARGE, description = "Output is too large to be validated against the output schema"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn attach_output(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    Query(params): Query<AttachOutputParams>,
+    State(state): State<RouteState>,
+    body: Body,
+) -> Result<Json<FnOutput>, IndexifyAPIError> {
+    let graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(IndexifyAPIError::compute_graph_not_found)?;
+    if !graph.nodes.contains_key(&params.compute_fn) {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "compute graph {} has no function {}",
+            compute_graph, params.compute_fn
+        )));
+    }
+    state
+        .indexify_state
+        .reader()
+        .invocation_payload(&namespace, &compute_graph, &invocation_id)
+        .map_err(|_| IndexifyAPIError::invocation_not_found())?;
+    let body = match graph.output_schema_of(&params.compute_fn) {
+        Some(schema) => {
+            let output =
+                invoke::read_schema_validated_body(body, state.config.max_schema_validated_bytes)
+                    .await?;
+            let violations = internal_ingest::output_schema_violations(schema, &output, "");
+            if !violations.is_empty() {
+                return Err(IndexifyAPIError::schema_violations(
+                    "output doesn't match the output schema of the compute graph",
+                    &violations,
+                ));
+            }
+            Body::from(output)
+        }
+        None => body,
+    };
+
+    let payload_stream = body
+        .into_data_stream()
+        .map(|res| res.map_err(|err| anyhow::anyhow!(err)));
+    let _permit = state.upload_limiter.acquire().await?;
+    let put_result = state
+        .blob_storage
+        .put(&format!("{}_{}", namespace, nanoid!()), payload_stream)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    let output = data_model::NodeOutputBuilder::default()
+        .namespace(namespace.clone())
+        .compute_graph_name(compute_graph.clone())
+        .compute_fn_name(params.compute_fn)
+        .invocation_id(invocation_id.clone())
+        .payload(data_model::OutputPayload::Fn(data_model::DataPayload {
+            path: put_result.url,
+            size: put_result.size_bytes,
+            sha256_hash: put_result.sha256_hash,
+        }))
+        .build()
+        .map_err(IndexifyAPIError::internal_error)?;
+    let request = RequestPayload::AttachOutput(AttachOutputRequest {
+        namespace,
+        compute_graph,
+        invocation_id,
+        output: output.clone(),
+    });
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: request,
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(output.into()))
+}
+
+/// Get the latest output of a batch of invocations
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/outputs/latest",
+    tag = "retrieve",
+    request_body = LatestOutputsRequest,
+    responses(
+        (status = 200, description = "Latest output of each input, in request order", body = LatestOutputs),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn latest_outputs(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    Json(request): Json<LatestOutputsRequest>,
+) -> Result<Json<LatestOutputs>, IndexifyAPIError> {
+    let outputs = state
+        .indexify_state
+        .reader()
+        .latest_outputs_for_inputs(&namespace, &compute_graph, &request.input_ids)
+        .map_err(IndexifyAPIError::internal_error)?;
+    let outputs = outputs
+        .into_iter()
+        .map(|output| output.map(Into::into))
+        .collect();
+    Ok(Json(LatestOutputs { outputs }))
+}
+
+/// Get the chain of outputs leading from the input of an invocation to an
+/// output
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/outputs/{output_id}/lineage",
+    tag = "retrieve",
+    responses(
+        (status = 200, description = "Lineage of the output", body = OutputLineage),
+        (status = NOT_FOUND, description = "Output not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn output_lineage(
+    Path((namespace, compute_graph, output_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<OutputLineage>, IndexifyAPIError> {
+    let lineage = state
+        .indexify_state
+        .reader()
+        .output_lineage(&namespace, &compute_graph, &output_id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(|| IndexifyAPIError::output_not_found("Output not found"))?;
+    Ok(Json(lineage.into()))
+}
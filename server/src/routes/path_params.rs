@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{RawPathParams, Request, State},
+    http::{uri::PathAndQuery, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use state_store::IndexifyState;
+
+use crate::http_objects::IndexifyAPIError;
+
+/// Rejects requests with an empty path parameter, such as the namespace of
+/// `/namespaces//compute_graphs`, before they reach a handler and the state
+/// store.
+pub async fn reject_empty_path_params(
+    params: RawPathParams,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some((name, _)) = params.iter().find(|(_, value)| value.is_empty()) {
+        return IndexifyAPIError::bad_request(&format!("{} must not be empty", name))
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// Rewrites the compute graph name of the path to the name the graph is
+/// stored under, so every route finds graphs whatever the case of their name
+/// when names are normalized. Must run before routing, the parameters of a
+/// matched route can't be changed.
+pub async fn normalize_compute_graph_name(
+    State(indexify_state): State<Arc<IndexifyState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let Some(uri) = normalized_uri(&indexify_state, request.uri()) {
+        *request.uri_mut() = uri;
+    }
+    next.run(request).await
+}
+
+fn normalized_uri(indexify_state: &IndexifyState, uri: &Uri) -> Option<Uri> {
+    let mut segments: Vec<String> = uri.path().split('/').map(String::from).collect();
+    let index = match segments.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["", "namespaces", _, "compute_graphs", _, ..] => 4,
+        ["", "internal", "namespaces", _, "compute_graphs", _, ..] => 5,
+        _ => return None,
+    };
+    let name = percent_decode_str(&segments[index]).decode_utf8().ok()?;
+    let key_name = indexify_state.compute_graph_key_name(&name);
+    if key_name == name {
+        return None;
+    }
+    segments[index] = utf8_percent_encode(&key_name, NON_ALPHANUMERIC).to_string();
+    let mut path_and_query = segments.join("/");
+    if let Some(query) = uri.query() {
+        path_and_query = format!("{}?{}", path_and_query, query);
+    }
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).ok()?);
+    Uri::from_parts(parts).ok()
+}
@@ -0,0 +1,61 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Query, Request},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::http_objects::IndexifyAPIError;
+
+/// Header asking for an indented JSON response, like the `pretty` query
+/// parameter.
+const PRETTY_HEADER: &str = "x-pretty";
+
+#[derive(Deserialize)]
+struct PrettyParams {
+    pretty: Option<bool>,
+}
+
+/// Re-serializes the JSON responses of requests with `?pretty=true` or an
+/// `X-Pretty: true` header with indentation. Every other response, and
+/// streamed formats such as NDJSON and SSE, are passed through unchanged.
+pub async fn pretty_json(request: Request, next: Next) -> Response {
+    let pretty = wants_pretty_json(&request);
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| content_type == "application/json");
+    if !pretty || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(err) => return IndexifyAPIError::internal_error(anyhow::anyhow!(err)).into_response(),
+    };
+    let body = match serde_json::from_slice::<serde_json::Value>(&bytes)
+        .and_then(|value| serde_json::to_vec_pretty(&value))
+    {
+        Ok(pretty) => Body::from(pretty),
+        Err(_) => Body::from(bytes),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, body)
+}
+
+fn wants_pretty_json(request: &Request) -> bool {
+    let header = request.headers().get(PRETTY_HEADER).is_some_and(is_true);
+    let query = Query::<PrettyParams>::try_from_uri(request.uri())
+        .is_ok_and(|Query(params)| params.pretty.unwrap_or_default());
+    header || query
+}
+
+fn is_true(value: &HeaderValue) -> bool {
+    value
+        .to_str()
+        .is_ok_and(|value| value.eq_ignore_ascii_case("true") || value == "1")
+}
@@ -0,0 +1,46 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use tracing::warn;
+
+/// Header carrying the id a client or proxy assigned to the request.
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Logs a warning for every request whose response took longer than the
+/// threshold to produce, from the request reaching the server to the
+/// response headers. Streamed bodies are not included.
+pub async fn log_slow_requests(
+    State(threshold): State<Option<Duration>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(threshold) = threshold else {
+        return next.run(request).await;
+    };
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let started_at = Instant::now();
+    let response = next.run(request).await;
+    let duration = started_at.elapsed();
+    if duration > threshold {
+        warn!(
+            %method,
+            path,
+            status = response.status().as_u16(),
+            duration_ms = duration.as_millis() as u64,
+            request_id,
+            "slow request"
+        );
+    }
+    response
+}
@@ -1,19 +1,25 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    io,
+    net::{SocketAddr, TcpListener},
+    time::Duration,
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use axum::{extract::Request, ServiceExt};
 use axum_server::Handle;
-use blob_store::BlobStorage;
-use state_store::IndexifyState;
+use state_store::requests::NamespaceRequest;
 use tokio::{self, signal, sync::watch};
-use tracing::info;
+use tracing::{error, info, warn};
 
 use super::{routes::RouteState, scheduler::Scheduler};
 use crate::{
     config::ServerConfig,
-    executors::ExecutorManager,
+    flusher::Flusher,
     gc::Gc,
+    retention::RetentionSweeper,
     routes::create_routes,
     system_tasks::SystemTasksExecutor,
+    webhooks::WebhookDispatcher,
 };
 
 pub struct Service {
@@ -26,15 +32,33 @@ impl Service {
     }
 
     pub async fn start(&self) -> Result<()> {
+        // Reports every problem of the configuration at once, before
+        // anything is started.
+        let route_state = RouteState::from_config(self.config.clone()).await?;
+        let addr: SocketAddr = self.config.listen_addr.parse()?;
+        let listener = bind_listener(addr)?;
         let (shutdown_tx, shutdown_rx) = watch::channel(());
-        let indexify_state = IndexifyState::new(self.config.state_store_path.parse()?).await?;
-        let blob_storage = Arc::new(BlobStorage::new(self.config.blob_storage.clone())?);
-        let executor_manager = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
-        let route_state = RouteState {
-            indexify_state: indexify_state.clone(),
-            blob_storage: blob_storage.clone(),
-            executor_manager,
-        };
+        let indexify_state = route_state.indexify_state.clone();
+        let blob_storage = route_state.blob_storage.clone();
+        if let Some(namespace) = &self.config.default_namespace {
+            let (_, created) = indexify_state.upsert_namespace(&NamespaceRequest {
+                name: namespace.clone(),
+                feature_flags: Default::default(),
+            })?;
+            if created {
+                info!("created default namespace {}", namespace);
+            }
+        }
+        if self.config.verify_blob_references_on_startup {
+            info!("verifying blob references");
+            let dangling_refs = indexify_state.verify_blob_references(&blob_storage).await?;
+            for dangling_ref in &dangling_refs {
+                warn!(
+                    "compute graph {}/{} references missing code blob {}",
+                    dangling_ref.namespace, dangling_ref.compute_graph, dangling_ref.url
+                );
+            }
+        }
         let app = create_routes(route_state);
         let handle = Handle::new();
         let handle_sh = handle.clone();
@@ -43,6 +67,29 @@ impl Service {
         let mut gc = Gc::new(indexify_state.clone(), blob_storage, shutdown_rx.clone());
         let mut system_tasks_executor =
             SystemTasksExecutor::new(indexify_state.clone(), shutdown_rx.clone());
+        let webhook_dispatcher = (!self.config.webhooks.is_empty())
+            .then(|| {
+                WebhookDispatcher::new(
+                    indexify_state.clone(),
+                    self.config.webhooks.clone(),
+                    shutdown_rx.clone(),
+                )
+            })
+            .transpose()?;
+
+        let flusher = self.config.flush_interval_ms.map(|interval_ms| {
+            Flusher::new(
+                indexify_state.clone(),
+                Duration::from_millis(interval_ms),
+                shutdown_rx.clone(),
+            )
+        });
+
+        let mut retention_sweeper = RetentionSweeper::new(
+            indexify_state.clone(),
+            Duration::from_secs(self.config.retention_sweep_interval_secs),
+            shutdown_rx.clone(),
+        );
 
         let state_watcher_rx = indexify_state.get_state_change_watcher();
         tokio::spawn(async move {
@@ -55,27 +102,63 @@ impl Service {
             let _ = gc.start().await;
             info!("garbage collector shutdown");
         });
+        tokio::spawn(async move {
+            info!("starting retention sweeper");
+            let _ = retention_sweeper.start().await;
+            info!("retention sweeper shutdown");
+        });
         tokio::spawn(async move {
             info!("starting system tasks executor");
             let _ = system_tasks_executor.start().await;
             info!("system tasks executor shutdown");
         });
 
+        if let Some(mut webhook_dispatcher) = webhook_dispatcher {
+            tokio::spawn(async move {
+                info!("starting webhook dispatcher");
+                while let Err(err) = webhook_dispatcher.start().await {
+                    error!("webhook dispatcher failed, restarting: {:?}", err);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                info!("webhook dispatcher shutdown");
+            });
+        }
+
+        if let Some(mut flusher) = flusher {
+            tokio::spawn(async move {
+                info!("starting state store flusher");
+                let _ = flusher.start().await;
+                info!("state store flusher shutdown");
+            });
+        }
+
         tokio::spawn(async move {
             shutdown_signal(handle_sh, shutdown_tx).await;
             info!("received graceful shutdown signal. Telling tasks to shutdown");
         });
-        let addr: SocketAddr = self.config.listen_addr.parse()?;
         info!("server api listening on {}", self.config.listen_addr);
-        axum_server::bind(addr)
+        axum_server::from_tcp(listener)
             .handle(handle)
-            .serve(app.into_make_service())
+            .serve(ServiceExt::<Request>::into_make_service(app))
             .await
-            .unwrap();
+            .map_err(|e| anyhow!("server api on {} failed: {}", addr, e))?;
         Ok(())
     }
 }
 
+/// Binds the api listener up front so that an address which is already in
+/// use fails startup with an error naming it instead of a panic.
+fn bind_listener(addr: SocketAddr) -> Result<TcpListener> {
+    TcpListener::bind(addr).map_err(|e| match e.kind() {
+        io::ErrorKind::AddrInUse => anyhow!(
+            "unable to listen on {}: address is already in use, \
+             set a different listen_addr in the config or pass --listen-addr",
+            addr
+        ),
+        _ => anyhow!("unable to listen on {}: {}", addr, e),
+    })
+}
+
 async fn shutdown_signal(handle: Handle, shutdown_tx: watch::Sender<()>) {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -104,3 +187,25 @@ async fn shutdown_signal(handle: Handle, shutdown_tx: watch::Sender<()>) {
     shutdown_tx.send(()).unwrap();
     info!("signal received, shutting down server gracefully");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_listener_address_in_use() -> Result<()> {
+        let existing = TcpListener::bind("127.0.0.1:0")?;
+        let addr = existing.local_addr()?;
+
+        let err = bind_listener(addr).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "unable to listen on {}: address is already in use, \
+                 set a different listen_addr in the config or pass --listen-addr",
+                addr
+            )
+        );
+        Ok(())
+    }
+}
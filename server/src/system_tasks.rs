@@ -222,10 +222,12 @@ mod tests {
         let cg_request = CreateComputeGraphRequest {
             namespace: graph.namespace.clone(),
             compute_graph: graph.clone(),
+            if_match: None,
+            if_changed: false,
         };
         state
             .write(StateMachineUpdateRequest {
-                payload: RequestPayload::CreateComputeGraph(cg_request),
+                payload: RequestPayload::CreateComputeGraph(Box::new(cg_request)),
                 state_changes_processed: vec![],
             })
             .await
@@ -365,10 +367,12 @@ mod tests {
         let cg_request = CreateComputeGraphRequest {
             namespace: graph.namespace.clone(),
             compute_graph: graph.clone(),
+            if_match: None,
+            if_changed: false,
         };
         state
             .write(StateMachineUpdateRequest {
-                payload: RequestPayload::CreateComputeGraph(cg_request),
+                payload: RequestPayload::CreateComputeGraph(Box::new(cg_request)),
                 state_changes_processed: vec![],
             })
             .await
@@ -570,10 +574,12 @@ mod tests {
         let cg_request = CreateComputeGraphRequest {
             namespace: graph.namespace.clone(),
             compute_graph: graph.clone(),
+            if_match: None,
+            if_changed: false,
         };
         state
             .write(StateMachineUpdateRequest {
-                payload: RequestPayload::CreateComputeGraph(cg_request),
+                payload: RequestPayload::CreateComputeGraph(Box::new(cg_request)),
                 state_changes_processed: vec![],
             })
             .await
@@ -620,10 +626,12 @@ mod tests {
         let cg_request = CreateComputeGraphRequest {
             namespace: graph.namespace.clone(),
             compute_graph: graph.clone(),
+            if_match: None,
+            if_changed: false,
         };
         state
             .write(StateMachineUpdateRequest {
-                payload: RequestPayload::CreateComputeGraph(cg_request),
+                payload: RequestPayload::CreateComputeGraph(Box::new(cg_request)),
                 state_changes_processed: vec![],
             })
             .await
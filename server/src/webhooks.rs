@@ -0,0 +1,618 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use data_model::{ChangeEvent, DeadLetteredChangeEvent};
+use hmac::{Hmac, Mac};
+use indexify_utils::get_epoch_time_in_ms;
+use sha2::Sha256;
+use state_store::{
+    requests::{RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tokio::{sync::mpsc, time::Instant};
+
+use crate::config::WebhookConfig;
+
+pub const SIGNATURE_HEADER: &str = "X-Indexify-Signature";
+pub const EVENT_ID_HEADER: &str = "X-Indexify-Event-Id";
+
+/// Attempts made to deliver an event to a webhook before dead lettering it.
+const MAX_DELIVERY_ATTEMPTS: u32 = 5;
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+const EVENTS_BATCH_SIZE: usize = 100;
+
+/// Returns the hex encoded HMAC-SHA256 of `body` keyed with `secret`.
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Delivery progress of a single webhook, owned by the task delivering to it.
+struct Endpoint {
+    /// Position of the webhook in the configuration.
+    index: usize,
+    webhook: WebhookConfig,
+    client: reqwest::Client,
+    /// Id of the last event which was delivered, dead lettered or isn't for
+    /// the namespace of the webhook.
+    resolved_up_to: Option<u64>,
+    /// Failed attempts to deliver the event after `resolved_up_to`.
+    attempts: u32,
+    retry_at: Option<Instant>,
+    /// Events which exhausted their attempts and weren't reported yet.
+    dead_letters: Vec<DeadLetteredChangeEvent>,
+}
+
+/// Events resolved by a webhook since its previous report.
+struct Progress {
+    index: usize,
+    resolved_up_to: u64,
+    dead_letters: Vec<DeadLetteredChangeEvent>,
+}
+
+/// Posts the change events recorded by the state store to the webhooks of
+/// their namespace. Every webhook is delivered to by its own task, which
+/// progresses through the events and backs off on failures without holding
+/// up the others, and requests time out after the `timeout_secs` of their
+/// webhook. Events are removed from the state store once resolved for every
+/// webhook, so the ones pending when the server stops are delivered after a
+/// restart. Events failing `MAX_DELIVERY_ATTEMPTS` times are moved to the
+/// dead letters.
+pub struct WebhookDispatcher {
+    state: Arc<IndexifyState>,
+    /// Webhooks whose tasks weren't started yet.
+    endpoints: Vec<Endpoint>,
+    retry_delay: Duration,
+    /// Last event resolved by each webhook.
+    resolved_up_to: Vec<Option<u64>>,
+    /// Dead letters reported by the webhooks which weren't saved yet.
+    dead_letters: Vec<DeadLetteredChangeEvent>,
+    /// Id of the last event removed from the state store.
+    removed_up_to: Option<u64>,
+    progress_tx: mpsc::UnboundedSender<Progress>,
+    progress_rx: mpsc::UnboundedReceiver<Progress>,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+}
+
+impl WebhookDispatcher {
+    pub fn new(
+        state: Arc<IndexifyState>,
+        webhooks: Vec<WebhookConfig>,
+        shutdown_rx: tokio::sync::watch::Receiver<()>,
+    ) -> Result<Self> {
+        let endpoints = webhooks
+            .into_iter()
+            .enumerate()
+            .map(|(index, webhook)| {
+                let client = reqwest::Client::builder()
+                    .timeout(Duration::from_secs(webhook.timeout_secs))
+                    .build()?;
+                Ok(Endpoint {
+                    index,
+                    webhook,
+                    client,
+                    resolved_up_to: None,
+                    attempts: 0,
+                    retry_at: None,
+                    dead_letters: vec![],
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+        Ok(Self {
+            state,
+            resolved_up_to: vec![None; endpoints.len()],
+            endpoints,
+            retry_delay: INITIAL_RETRY_DELAY,
+            dead_letters: vec![],
+            removed_up_to: None,
+            progress_tx,
+            progress_rx,
+            shutdown_rx,
+        })
+    }
+
+    /// Starts the task of every webhook, then removes the events resolved by
+    /// all of them as they report their progress.
+    pub async fn start(&mut self) -> Result<()> {
+        for endpoint in self.endpoints.drain(..) {
+            tokio::spawn(endpoint.run(
+                self.state.clone(),
+                self.retry_delay,
+                self.progress_tx.clone(),
+                self.shutdown_rx.clone(),
+            ));
+        }
+        loop {
+            // Progress left over from a failed removal is saved first.
+            self.remove_resolved().await?;
+            tokio::select! {
+                progress = self.progress_rx.recv() => {
+                    let Some(progress) = progress else {
+                        return Ok(());
+                    };
+                    self.record(progress);
+                    while let Ok(progress) = self.progress_rx.try_recv() {
+                        self.record(progress);
+                    }
+                }
+                _ = self.shutdown_rx.changed() => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn record(&mut self, progress: Progress) {
+        self.resolved_up_to[progress.index] = Some(progress.resolved_up_to);
+        self.dead_letters.extend(progress.dead_letters);
+    }
+
+    /// Saves the reported dead letters and removes the events resolved by
+    /// every webhook. Dead letters are saved with the removal of their
+    /// events, the events are retried if the server stops before.
+    async fn remove_resolved(&mut self) -> Result<()> {
+        let resolved_up_to = self.resolved_up_to.iter().min().copied().flatten();
+        loop {
+            let removed: Vec<u64> = match resolved_up_to {
+                Some(resolved_up_to) if Some(resolved_up_to) > self.removed_up_to => self
+                    .state
+                    .reader()
+                    .get_change_events(self.removed_up_to, Some(EVENTS_BATCH_SIZE))?
+                    .into_iter()
+                    .map(|event| event.id)
+                    .take_while(|id| *id <= resolved_up_to)
+                    .collect(),
+                _ => vec![],
+            };
+            let mut requests = vec![];
+            if !self.dead_letters.is_empty() {
+                requests.push(StateMachineUpdateRequest {
+                    payload: RequestPayload::DeadLetterChangeEvents(self.dead_letters.clone()),
+                    state_changes_processed: vec![],
+                });
+            }
+            let last_removed = removed.last().copied();
+            if !removed.is_empty() {
+                requests.push(StateMachineUpdateRequest {
+                    payload: RequestPayload::RemoveChangeEvents(removed),
+                    state_changes_processed: vec![],
+                });
+            }
+            if requests.is_empty() {
+                return Ok(());
+            }
+            self.state.write_all(requests).await?;
+            self.dead_letters.clear();
+            match last_removed {
+                Some(last_removed) => self.removed_up_to = Some(last_removed),
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+impl Endpoint {
+    /// Delivers the events to the webhook until the server shuts down,
+    /// reporting the events it resolves.
+    async fn run(
+        mut self,
+        state: Arc<IndexifyState>,
+        retry_delay: Duration,
+        progress_tx: mpsc::UnboundedSender<Progress>,
+        mut shutdown_rx: tokio::sync::watch::Receiver<()>,
+    ) {
+        let mut rx = state.get_change_events_watcher();
+        loop {
+            if shutdown_rx.has_changed().unwrap_or(false) {
+                return;
+            }
+            match self.dispatch(&state, retry_delay).await {
+                Ok(0) => {}
+                Ok(_) => {
+                    let progress = Progress {
+                        index: self.index,
+                        resolved_up_to: self.resolved_up_to.unwrap_or_default(),
+                        dead_letters: std::mem::take(&mut self.dead_letters),
+                    };
+                    if progress_tx.send(progress).is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                Err(err) => {
+                    tracing::error!(
+                        "failed to read change events for {}: {:?}",
+                        self.webhook.url,
+                        err
+                    );
+                    self.retry_at = Some(Instant::now() + retry_delay);
+                }
+            }
+            let retry_at = self.retry_at;
+            tokio::select! {
+                _ = rx.changed() => { rx.borrow_and_update(); }
+                _ = async {
+                    match retry_at {
+                        Some(retry_at) => tokio::time::sleep_until(retry_at).await,
+                        None => std::future::pending().await,
+                    }
+                } => {}
+                _ = shutdown_rx.changed() => {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Delivers the pending events in order until one fails, and returns how
+    /// many events were resolved.
+    async fn dispatch(&mut self, state: &IndexifyState, retry_delay: Duration) -> Result<usize> {
+        if self
+            .retry_at
+            .is_some_and(|retry_at| retry_at > Instant::now())
+        {
+            return Ok(0);
+        }
+        self.retry_at = None;
+        let events = state
+            .reader()
+            .get_change_events(self.resolved_up_to, Some(EVENTS_BATCH_SIZE))?;
+        let mut resolved = 0;
+        for event in events {
+            if event.namespace == self.webhook.namespace {
+                if let Err(err) = deliver(&self.client, &self.webhook, &event).await {
+                    self.attempts += 1;
+                    if self.attempts < MAX_DELIVERY_ATTEMPTS {
+                        tracing::warn!(
+                            "failed to deliver change event {} to {}, attempt {}: {:?}",
+                            event.id,
+                            self.webhook.url,
+                            self.attempts,
+                            err
+                        );
+                        self.retry_at =
+                            Some(Instant::now() + retry_delay * 2u32.pow(self.attempts - 1));
+                        break;
+                    }
+                    tracing::error!(
+                        "giving up on delivering change event {} to {}: {:?}",
+                        event.id,
+                        self.webhook.url,
+                        err
+                    );
+                    self.dead_letters.push(DeadLetteredChangeEvent {
+                        url: self.webhook.url.clone(),
+                        error: format!("{:?}", err),
+                        attempts: self.attempts,
+                        failed_at: get_epoch_time_in_ms(),
+                        event: event.clone(),
+                    });
+                }
+            }
+            self.attempts = 0;
+            self.resolved_up_to = Some(event.id);
+            resolved += 1;
+        }
+        Ok(resolved)
+    }
+}
+
+async fn deliver(
+    client: &reqwest::Client,
+    webhook: &WebhookConfig,
+    event: &ChangeEvent,
+) -> Result<()> {
+    let body = serde_json::to_vec(event)?;
+    let signature = format!("sha256={}", sign(webhook.secret.as_bytes(), &body));
+    client
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .header(EVENT_ID_HEADER, event.id.to_string())
+        .body(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{extract::State, http::HeaderMap, routing::post, Router};
+    use bytes::Bytes;
+    use data_model::{
+        test_objects::tests::{mock_graph_a, mock_graph_b, TEST_NAMESPACE},
+        ChangeEventKind,
+    };
+    use state_store::{requests::CreateComputeGraphRequest, IndexifyStateOptions};
+    use tokio::sync::{mpsc, watch};
+
+    use super::*;
+
+    type SinkState = (
+        mpsc::UnboundedSender<(HeaderMap, Bytes)>,
+        Arc<std::sync::atomic::AtomicU32>,
+    );
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deliver_signed_events() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new_with_options(
+            temp_dir.path().join("state"),
+            IndexifyStateOptions {
+                record_change_events: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        // A sink which fails the first request to exercise the retries.
+        let (requests_tx, mut requests_rx) = mpsc::unbounded_channel();
+        let attempts = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let sink = Router::new()
+            .route(
+                "/hook",
+                post(
+                    |State((requests_tx, attempts)): State<SinkState>,
+                     headers: HeaderMap,
+                     body: Bytes| async move {
+                        if attempts.fetch_add(1, std::sync::atomic::Ordering::Relaxed) == 0 {
+                            return axum::http::StatusCode::SERVICE_UNAVAILABLE;
+                        }
+                        requests_tx.send((headers, body)).unwrap();
+                        axum::http::StatusCode::OK
+                    },
+                ),
+            )
+            .with_state((requests_tx, attempts));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, sink).await });
+
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let secret = "shared secret";
+        let (_shutdown_tx, shutdown_rx) = watch::channel(());
+        let mut dispatcher = WebhookDispatcher::new(
+            state.clone(),
+            vec![
+                WebhookConfig {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    url: format!("http://{}/hook", addr),
+                    secret: secret.to_string(),
+                    timeout_secs: 10,
+                },
+                WebhookConfig {
+                    namespace: "other".to_string(),
+                    url: format!("http://{}/other", addr),
+                    secret: secret.to_string(),
+                    timeout_secs: 10,
+                },
+            ],
+            shutdown_rx,
+        )?;
+        tokio::spawn(async move { dispatcher.start().await });
+
+        let (headers, body) = requests_rx.recv().await.unwrap();
+        assert_eq!(
+            headers[SIGNATURE_HEADER],
+            format!("sha256={}", sign(secret.as_bytes(), &body))
+        );
+        let event: ChangeEvent = serde_json::from_slice(&body)?;
+        assert_eq!(headers[EVENT_ID_HEADER], event.id.to_string());
+        assert_eq!(event.namespace, TEST_NAMESPACE);
+        assert_eq!(event.compute_graph, "graph_A");
+        assert_eq!(event.kind, ChangeEventKind::GraphCreated);
+
+        // Delivered events are removed.
+        wait_for_delivery(&state).await?;
+        assert!(requests_rx.try_recv().is_err());
+        assert!(state
+            .reader()
+            .get_dead_lettered_change_events(None)?
+            .is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failing_webhook_doesnt_block_others() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new_with_options(
+            temp_dir.path().join("state"),
+            IndexifyStateOptions {
+                record_change_events: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        let (requests_tx, mut requests_rx) = mpsc::unbounded_channel();
+        let sink =
+            Router::new()
+                .route(
+                    "/hook",
+                    post(
+                        |State((requests_tx, _)): State<SinkState>,
+                         headers: HeaderMap,
+                         body: Bytes| async move {
+                            requests_tx.send((headers, body)).unwrap();
+                            axum::http::StatusCode::OK
+                        },
+                    ),
+                )
+                .route(
+                    "/broken",
+                    post(|| async { axum::http::StatusCode::INTERNAL_SERVER_ERROR }),
+                )
+                .with_state((requests_tx, Arc::new(std::sync::atomic::AtomicU32::new(0))));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, sink).await });
+
+        for compute_graph in [mock_graph_a(), mock_graph_b()] {
+            state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let (_shutdown_tx, shutdown_rx) = watch::channel(());
+        let broken_url = format!("http://{}/broken", addr);
+        let mut dispatcher = WebhookDispatcher::new(
+            state.clone(),
+            vec![
+                WebhookConfig {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    url: broken_url.clone(),
+                    secret: "secret".to_string(),
+                    timeout_secs: 10,
+                },
+                WebhookConfig {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    url: format!("http://{}/hook", addr),
+                    secret: "secret".to_string(),
+                    timeout_secs: 10,
+                },
+            ],
+            shutdown_rx,
+        )?;
+        dispatcher.retry_delay = Duration::from_millis(100);
+        tokio::spawn(async move { dispatcher.start().await });
+
+        // The healthy webhook gets every event while the broken one backs
+        // off, the events are kept for it.
+        for _ in 0..2 {
+            tokio::time::timeout(Duration::from_secs(1), requests_rx.recv())
+                .await?
+                .unwrap();
+        }
+        assert_eq!(state.reader().get_change_events(None, None)?.len(), 2);
+
+        // Events are removed once the broken webhook gives up on them.
+        wait_for_delivery(&state).await?;
+        let dead_letters = state.reader().get_dead_lettered_change_events(None)?;
+        assert_eq!(dead_letters.len(), 2);
+        for dead_letter in &dead_letters {
+            assert_eq!(dead_letter.url, broken_url);
+            assert_eq!(dead_letter.attempts, MAX_DELIVERY_ATTEMPTS);
+        }
+        assert_eq!(dead_letters[0].event.compute_graph, "graph_A");
+        assert_eq!(dead_letters[1].event.compute_graph, "graph_B");
+        assert!(requests_rx.try_recv().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hanging_webhook_doesnt_block_others() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new_with_options(
+            temp_dir.path().join("state"),
+            IndexifyStateOptions {
+                record_change_events: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        let (requests_tx, mut requests_rx) = mpsc::unbounded_channel();
+        let sink =
+            Router::new()
+                .route(
+                    "/hook",
+                    post(
+                        |State((requests_tx, _)): State<SinkState>,
+                         headers: HeaderMap,
+                         body: Bytes| async move {
+                            requests_tx.send((headers, body)).unwrap();
+                            axum::http::StatusCode::OK
+                        },
+                    ),
+                )
+                .route("/hang", post(|| std::future::pending::<()>()))
+                .with_state((requests_tx, Arc::new(std::sync::atomic::AtomicU32::new(0))));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, sink).await });
+
+        let (_shutdown_tx, shutdown_rx) = watch::channel(());
+        let mut dispatcher = WebhookDispatcher::new(
+            state.clone(),
+            ["hang", "hook"]
+                .into_iter()
+                .map(|path| WebhookConfig {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    url: format!("http://{}/{}", addr, path),
+                    secret: "secret".to_string(),
+                    timeout_secs: 60,
+                })
+                .collect(),
+            shutdown_rx,
+        )?;
+        tokio::spawn(async move { dispatcher.start().await });
+
+        // Events keep reaching the healthy webhook while the delivery of the
+        // first one to the other webhook hangs.
+        for compute_graph in [mock_graph_a(), mock_graph_b()] {
+            state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+            let (_, body) = tokio::time::timeout(Duration::from_secs(5), requests_rx.recv())
+                .await?
+                .unwrap();
+            let event: ChangeEvent = serde_json::from_slice(&body)?;
+            assert_eq!(event.kind, ChangeEventKind::GraphCreated);
+        }
+        Ok(())
+    }
+
+    /// Waits for every change event to be removed from the state store.
+    async fn wait_for_delivery(state: &IndexifyState) -> Result<()> {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while !state.reader().get_change_events(None, None)?.is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok(())
+        })
+        .await?
+    }
+}
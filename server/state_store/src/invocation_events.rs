@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use data_model::{TaskAnalytics, TaskOutcome};
+use data_model::{NodeOutput, TaskAnalytics, TaskOutcome};
 use serde::{Deserialize, Serialize};
 
 use crate::requests;
@@ -11,6 +11,7 @@ pub enum InvocationStateChangeEvent {
     TaskCreated(TaskCreated),
     TaskAssigned(TaskAssigned),
     TaskCompleted(TaskCompleted),
+    OutputProduced(OutputProduced),
     InvocationFinished(InvocationFinishedEvent),
     DiagnosticMessage(DiagnosticMessage),
 }
@@ -25,6 +26,14 @@ impl InvocationStateChangeEvent {
         })
     }
 
+    pub fn from_node_output(output: &NodeOutput) -> Self {
+        Self::OutputProduced(OutputProduced {
+            invocation_id: output.invocation_id.clone(),
+            fn_name: output.compute_fn_name.clone(),
+            output_id: output.id.clone(),
+        })
+    }
+
     pub fn invocation_id(&self) -> String {
         match self {
             InvocationStateChangeEvent::AsyncInvocation(InvocationStarted { id }) => id.clone(),
@@ -40,6 +49,9 @@ impl InvocationStateChangeEvent {
             InvocationStateChangeEvent::TaskCompleted(TaskCompleted { invocation_id, .. }) => {
                 invocation_id.clone()
             }
+            InvocationStateChangeEvent::OutputProduced(OutputProduced {
+                invocation_id, ..
+            }) => invocation_id.clone(),
             InvocationStateChangeEvent::DiagnosticMessage(_) => "".to_string(),
         }
     }
@@ -83,6 +95,15 @@ pub struct TaskCompleted {
     pub outcome: TaskOutcome,
 }
 
+/// An output written by a function of an invocation, published as soon as it
+/// is written rather than when the invocation finishes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutputProduced {
+    pub invocation_id: String,
+    pub fn_name: String,
+    pub output_id: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct InvocationFinished {
     pub namespace: String,
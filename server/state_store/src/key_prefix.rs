@@ -0,0 +1,229 @@
+use std::{borrow::Cow, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use rocksdb::{
+    AsColumnFamilyRef,
+    Direction,
+    Error,
+    IteratorMode,
+    ReadOptions,
+    Transaction,
+    TransactionDB,
+};
+
+use crate::{scanner::prefix_upper_bound, state_machine::IndexifyObjectsColumns};
+
+/// Separates the instance prefix from the keys of the instance.
+const INSTANCE_SEPARATOR: char = '/';
+
+/// Key and value of a row read by an iterator.
+pub type Row = (Box<[u8]>, Box<[u8]>);
+
+/// Prefix prepended to every key of the state store, so several instances
+/// can share a RocksDB directory without seeing each other's rows. Keys are
+/// prefixed when they are written or looked up and stripped of the prefix
+/// when they are read back, so the rest of the state store only deals with
+/// the keys of its own instance. The empty prefix leaves keys unchanged, so
+/// an instance without a prefix can't share its directory, see
+/// `check_shared_directory`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyPrefix(Arc<[u8]>);
+
+impl KeyPrefix {
+    /// Prefix of the keys of the instance named `instance_prefix`. The name
+    /// can't contain the separator, otherwise the keys of an instance named
+    /// `a` in a namespace `b/c` would be those of an instance named `a/b` in
+    /// a namespace `c`.
+    pub fn new(instance_prefix: &str) -> Result<Self> {
+        if instance_prefix.is_empty() {
+            return Ok(Self::default());
+        }
+        if instance_prefix.contains(INSTANCE_SEPARATOR) {
+            return Err(anyhow!(
+                "instance prefix {} can't contain {}",
+                instance_prefix,
+                INSTANCE_SEPARATOR
+            ));
+        }
+        let prefix = format!("{}{}", instance_prefix, INSTANCE_SEPARATOR);
+        Ok(Self(prefix.into_bytes().into()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Fails when the directory already holds instances with a prefix and
+    /// this one has none, or the other way around. An instance without a
+    /// prefix reads every key of the directory and would take the rows of
+    /// the others for its own. Instances are recognized by the metadata
+    /// every instance records when it's opened, stored under its prefix.
+    pub(crate) fn check_shared_directory(&self, db: &TransactionDB) -> Result<()> {
+        let cf = IndexifyObjectsColumns::StateMachineMetadata.cf_db(db);
+        for row in db.iterator_cf(&cf, IteratorMode::Start) {
+            let (key, _) = row?;
+            let prefixed = key.contains(&(INSTANCE_SEPARATOR as u8));
+            if self.is_empty() && prefixed {
+                return Err(anyhow!(
+                    "state store directory holds instances with a prefix, \
+                     an instance without one would read their rows"
+                ));
+            }
+            if !self.is_empty() && !prefixed {
+                return Err(anyhow!(
+                    "state store directory holds an instance without a prefix, \
+                     it would read the rows of this one"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the stored form of `key`.
+    pub fn key<'a>(&self, key: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.is_empty() {
+            return Cow::Borrowed(key);
+        }
+        let mut prefixed = Vec::with_capacity(self.0.len() + key.len());
+        prefixed.extend_from_slice(&self.0);
+        prefixed.extend_from_slice(key);
+        Cow::Owned(prefixed)
+    }
+
+    /// Bounds the iterations with `read_options` to the keys starting with
+    /// `key_prefix` in the instance, all its keys when `key_prefix` is empty.
+    /// Replaces any bounds already set.
+    pub fn bound(&self, read_options: &mut ReadOptions, key_prefix: &[u8]) {
+        let prefix = self.key(key_prefix);
+        if prefix.is_empty() {
+            return;
+        }
+        read_options.set_iterate_lower_bound(prefix.to_vec());
+        if let Some(upper_bound) = prefix_upper_bound(&prefix) {
+            read_options.set_iterate_upper_bound(upper_bound);
+        }
+    }
+
+    /// Returns the stored key to seek to for `mode`. Iterations from the
+    /// start or the end seek in the bounds set by `bound`.
+    pub fn seek_key(&self, mode: &IteratorMode) -> Option<(Vec<u8>, Direction)> {
+        match mode {
+            IteratorMode::Start | IteratorMode::End => None,
+            IteratorMode::From(key, direction) => Some((self.key(key).into_owned(), *direction)),
+        }
+    }
+
+    /// Strips the prefix from the keys of the rows read by `iter`.
+    pub fn strip<'a>(
+        &self,
+        iter: impl Iterator<Item = Result<Row, Error>> + 'a,
+    ) -> impl Iterator<Item = Result<Row, Error>> + 'a {
+        let len = self.0.len();
+        iter.map(move |row| {
+            row.map(|(key, value)| {
+                if len == 0 {
+                    (key, value)
+                } else {
+                    (key[len..].into(), value)
+                }
+            })
+        })
+    }
+}
+
+/// Applies `mode` to the stored keys, bounding the iteration to the instance.
+pub fn iterator_mode<'a>(
+    mode: IteratorMode,
+    seek_key: &'a Option<(Vec<u8>, Direction)>,
+) -> IteratorMode<'a> {
+    match (mode, seek_key) {
+        (_, Some((key, direction))) => IteratorMode::From(key, *direction),
+        (IteratorMode::End, None) => IteratorMode::End,
+        _ => IteratorMode::Start,
+    }
+}
+
+/// A transaction reading and writing the keys of a single instance.
+pub struct PrefixedTransaction<'db> {
+    txn: Transaction<'db, TransactionDB>,
+    prefix: KeyPrefix,
+}
+
+impl<'db> PrefixedTransaction<'db> {
+    pub(crate) fn new(txn: Transaction<'db, TransactionDB>, prefix: KeyPrefix) -> Self {
+        Self { txn, prefix }
+    }
+
+    pub fn put_cf<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        &self,
+        cf: &impl AsColumnFamilyRef,
+        key: K,
+        value: V,
+    ) -> Result<(), Error> {
+        self.txn.put_cf(cf, self.prefix.key(key.as_ref()), value)
+    }
+
+    pub fn get_cf<K: AsRef<[u8]>>(
+        &self,
+        cf: &impl AsColumnFamilyRef,
+        key: K,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.txn.get_cf(cf, self.prefix.key(key.as_ref()))
+    }
+
+    pub fn get_for_update_cf<K: AsRef<[u8]>>(
+        &self,
+        cf: &impl AsColumnFamilyRef,
+        key: K,
+        exclusive: bool,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.txn
+            .get_for_update_cf(cf, self.prefix.key(key.as_ref()), exclusive)
+    }
+
+    pub fn delete_cf<K: AsRef<[u8]>>(
+        &self,
+        cf: &impl AsColumnFamilyRef,
+        key: K,
+    ) -> Result<(), Error> {
+        self.txn.delete_cf(cf, self.prefix.key(key.as_ref()))
+    }
+
+    pub fn iterator_cf<'a>(
+        &'a self,
+        cf: &impl AsColumnFamilyRef,
+        mode: IteratorMode,
+    ) -> impl Iterator<Item = Result<Row, Error>> + 'a {
+        self.iterator_cf_opt(cf, ReadOptions::default(), mode)
+    }
+
+    pub fn iterator_cf_opt<'a>(
+        &'a self,
+        cf: &impl AsColumnFamilyRef,
+        mut read_options: ReadOptions,
+        mode: IteratorMode,
+    ) -> impl Iterator<Item = Result<Row, Error>> + 'a {
+        self.prefix.bound(&mut read_options, &[]);
+        let seek_key = self.prefix.seek_key(&mode);
+        let iter = self
+            .txn
+            .iterator_cf_opt(cf, read_options, iterator_mode(mode, &seek_key));
+        self.prefix.strip(iter)
+    }
+
+    pub fn set_savepoint(&self) {
+        self.txn.set_savepoint()
+    }
+
+    pub fn rollback_to_savepoint(&self) -> Result<(), Error> {
+        self.txn.rollback_to_savepoint()
+    }
+
+    pub fn commit(self) -> Result<(), Error> {
+        self.txn.commit()
+    }
+
+    pub fn rollback(&self) -> Result<(), Error> {
+        self.txn.rollback()
+    }
+}
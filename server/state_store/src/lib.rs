@@ -1,20 +1,30 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Debug,
     fs,
     path::PathBuf,
     pin::Pin,
     sync::{
         atomic::{self, AtomicU64},
         Arc,
+        Mutex,
+        Weak,
     },
+    time::Duration,
     vec,
 };
 
 use anyhow::{anyhow, Result};
+use blob_store::BlobStorage;
 use data_model::{
+    ChangeEvent,
+    ChangeEventKind,
     ChangeType,
+    ComputeGraph,
     ExecutorId,
     InvokeComputeGraphEvent,
+    Namespace,
+    NodeOutput,
     StateChange,
     StateChangeBuilder,
     StateChangeId,
@@ -25,17 +35,44 @@ use data_model::{
 use futures::Stream;
 use indexify_utils::get_epoch_time_in_ms;
 use invocation_events::{InvocationFinishedEvent, InvocationStateChangeEvent};
-use requests::StateMachineUpdateRequest;
-use rocksdb::{ColumnFamilyDescriptor, Options, TransactionDB, TransactionDBOptions};
-use state_machine::{IndexifyObjectsColumns, InvocationCompletion};
+use key_prefix::{KeyPrefix, PrefixedTransaction};
+use requests::{NamespaceRequest, StateMachineUpdateRequest};
+use rocksdb::{
+    BlockBasedOptions,
+    BoundColumnFamily,
+    Cache,
+    ColumnFamilyDescriptor,
+    DBCompressionType,
+    Direction,
+    IteratorMode,
+    Options,
+    ReadOptions,
+    SliceTransform,
+    TransactionDB,
+    TransactionDBOptions,
+    TransactionOptions,
+    WriteOptions,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serializer::{JsonEncode, JsonEncoder};
+use state_machine::{
+    IndexifyObjectsColumns,
+    InvocationCompletion,
+    SchemaVersionMismatch,
+    TransientWriteError,
+};
 use strum::IntoEnumIterator;
 use tokio::sync::{
     broadcast,
+    mpsc,
+    oneshot,
     watch::{Receiver, Sender},
     RwLock,
 };
 
 pub mod invocation_events;
+pub mod key_prefix;
+pub mod namespace_transfer;
 pub mod requests;
 pub mod scanner;
 pub mod serializer;
@@ -92,6 +129,278 @@ pub type StateChangeStream =
 
 pub struct InvocationChangeSubscriber {}
 
+/// A compute graph whose code blob is missing from blob storage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DanglingRef {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub url: String,
+}
+
+/// An SST file of the state store.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveFileInfo {
+    pub column_family: String,
+    pub name: String,
+    pub size_bytes: u64,
+    pub level: i32,
+}
+
+/// Counts of a rebuild of the secondary indexes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReindexReport {
+    pub tasks_scanned: usize,
+    pub compute_graphs_scanned: usize,
+    pub namespaces_scanned: usize,
+    pub entries_added: usize,
+    pub entries_removed: usize,
+}
+
+/// Number of tasks whose index entries are rebuilt in one transaction.
+const REINDEX_BATCH_SIZE: usize = 100;
+
+/// Blobs which aren't older than this are never collected, since the rows
+/// referencing them may not be written yet.
+pub const ORPHAN_BLOB_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Outcome of a collection of the blobs no row references.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GcReport {
+    pub scanned: usize,
+    /// Urls of the unreferenced blobs older than the safety window.
+    pub orphans: Vec<String>,
+    /// Urls of the orphans which were deleted. Empty on dry runs.
+    pub deleted: Vec<String>,
+}
+
+/// Options used when opening the state store.
+#[derive(Debug, Clone, Default)]
+pub struct IndexifyStateOptions {
+    /// Column families created and opened in addition to the core
+    /// `IndexifyObjectsColumns`. Extensions read and write them through
+    /// `put_cf`, `get_cf` and `scan_cf`.
+    pub custom_column_families: Vec<String>,
+    /// How long a transaction waits for a lock held by another transaction
+    /// before failing with a timeout. Uses the RocksDB default when unset.
+    pub transaction_lock_timeout: Option<Duration>,
+    /// Detect deadlocks between transactions and fail one of them instead of
+    /// waiting for the lock timeout.
+    pub deadlock_detect: bool,
+    /// When set, writes received within this window are committed together
+    /// in a single transaction, trading latency for throughput.
+    pub group_commit_window: Option<Duration>,
+    /// Record changes to compute graphs and their outputs in the
+    /// `ChangeEvents` column family, for delivery to webhooks.
+    pub record_change_events: bool,
+    /// Key compute graphs by the lowercase form of their name, keeping the
+    /// name they were created with as their display name.
+    pub normalize_compute_graph_names: bool,
+    /// Create the namespace of a compute graph in the same transaction as
+    /// the graph when it doesn't exist yet.
+    pub auto_create_namespace: bool,
+    /// Number of invocation events buffered for the subscribers of
+    /// `task_event_stream`. Defaults to `DEFAULT_TASK_EVENT_CAPACITY`.
+    pub task_event_capacity: Option<usize>,
+    /// Number of times a write failing with a transient RocksDB error, such
+    /// as a lock timeout, is retried in a new transaction before failing
+    /// with `TransientWriteError`.
+    pub write_retries: u32,
+    /// Largest number of namespaces, creating more fails with
+    /// `NamespaceLimitReached`. Unlimited when unset.
+    pub max_namespaces: Option<usize>,
+    /// Keys of the input attributes maintained in the `InputAttributes`
+    /// index, searched by `StateReader::find_inputs_by_attribute`.
+    pub indexed_attributes: HashSet<String>,
+    /// Prefix extractor of the column families keyed by namespace, used by
+    /// the prefix bloom filters of the scans within a namespace.
+    pub prefix_extractor: PrefixExtractor,
+    /// Options of core or custom column families, by name, overriding the
+    /// defaults they are opened with.
+    pub cf_overrides: HashMap<String, CfOverride>,
+    /// Name of the instance prepended to every key of the state store, so
+    /// instances sharing a RocksDB directory don't see each other's rows.
+    /// The keys are left unchanged when it is empty, a directory holding
+    /// instances with a prefix then can't be opened.
+    pub instance_prefix: String,
+}
+
+/// Compression of the blocks of a column family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CfCompression {
+    None,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl From<CfCompression> for DBCompressionType {
+    fn from(compression: CfCompression) -> Self {
+        match compression {
+            CfCompression::None => DBCompressionType::None,
+            CfCompression::Snappy => DBCompressionType::Snappy,
+            CfCompression::Lz4 => DBCompressionType::Lz4,
+            CfCompression::Zstd => DBCompressionType::Zstd,
+        }
+    }
+}
+
+/// Options of a column family which replace the defaults when set.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CfOverride {
+    pub compression: Option<CfCompression>,
+    /// Size in bytes of a block cache used by this column family only.
+    pub block_cache_size: Option<usize>,
+}
+
+/// Options a column family was opened with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CfConfig {
+    pub name: String,
+    /// Whether it is one of `IndexifyStateOptions::custom_column_families`.
+    pub custom: bool,
+    /// Compression of its blocks, or `None` for the RocksDB default, which
+    /// is snappy when RocksDB is built with it.
+    pub compression: Option<CfCompression>,
+    /// Size of its own block cache, or `None` for the RocksDB default cache.
+    pub block_cache_size: Option<usize>,
+    /// Prefix extractor of its prefix bloom filters, `None` for the column
+    /// families which use whole key filters only.
+    pub prefix_extractor: PrefixExtractor,
+}
+
+impl CfConfig {
+    fn new(
+        name: &str,
+        custom: bool,
+        prefix_extractor: PrefixExtractor,
+        cf_override: Option<&CfOverride>,
+    ) -> Self {
+        let cf_override = cf_override.cloned().unwrap_or_default();
+        Self {
+            name: name.to_string(),
+            custom,
+            compression: cf_override.compression,
+            block_cache_size: cf_override.block_cache_size,
+            prefix_extractor,
+        }
+    }
+
+    /// RocksDB options of the column family. The column families keyed by
+    /// namespace get the prefix extractor and prefix bloom filters, point
+    /// lookups keep using whole key filters.
+    fn options(&self) -> Options {
+        let mut options = Options::default();
+        if let Some(compression) = self.compression {
+            options.set_compression_type(compression.into());
+        }
+        let mut block_options = BlockBasedOptions::default();
+        if let Some(size) = self.block_cache_size {
+            block_options.set_block_cache(&Cache::new_lru_cache(size));
+        }
+        if let Some(slice_transform) = self.prefix_extractor.slice_transform() {
+            options.set_prefix_extractor(slice_transform);
+            options.set_memtable_prefix_bloom_ratio(0.1);
+            block_options.set_bloom_filter(10.0, false);
+            block_options.set_whole_key_filtering(true);
+        }
+        options.set_block_based_table_factory(&block_options);
+        options
+    }
+}
+
+/// How the keys of the column families keyed by namespace are split into
+/// the prefix RocksDB builds its prefix bloom filters on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrefixExtractor {
+    /// No prefix extractor, every scan seeks in total order.
+    None,
+    /// The namespace and the `|` which follows it.
+    #[default]
+    Namespace,
+    /// The first bytes of the key.
+    FixedLength(usize),
+}
+
+impl PrefixExtractor {
+    fn slice_transform(&self) -> Option<SliceTransform> {
+        match self {
+            PrefixExtractor::None => None,
+            PrefixExtractor::Namespace => Some(SliceTransform::create(
+                "indexify.namespace_prefix",
+                namespace_prefix,
+                Some(has_namespace_prefix),
+            )),
+            PrefixExtractor::FixedLength(len) => Some(SliceTransform::create_fixed_prefix(*len)),
+        }
+    }
+
+    /// Whether all the keys starting with `key_prefix` have the same
+    /// extracted prefix, in which case a scan of `key_prefix` can seek by
+    /// prefix.
+    pub fn covers(&self, key_prefix: &[u8]) -> bool {
+        match self {
+            PrefixExtractor::None => false,
+            PrefixExtractor::Namespace => has_namespace_prefix(key_prefix),
+            PrefixExtractor::FixedLength(len) => key_prefix.len() >= *len,
+        }
+    }
+}
+
+fn namespace_prefix(key: &[u8]) -> &[u8] {
+    match key.iter().position(|b| *b == b'|') {
+        Some(separator) => &key[..=separator],
+        None => key,
+    }
+}
+
+fn has_namespace_prefix(key: &[u8]) -> bool {
+    key.contains(&b'|')
+}
+
+pub const DEFAULT_TASK_EVENT_CAPACITY: usize = 100;
+
+/// Version of the layout of the state store written by this server. Older
+/// stores are upgraded to it when they are opened, stores with a newer
+/// version are left untouched and fail `check_schema_version`.
+pub const STATE_STORE_SCHEMA_VERSION: u32 = 1;
+
+/// Wait before the first retry of a write, doubled for every further one.
+const WRITE_RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Upper bound on the writes committed by a single group commit.
+const MAX_GROUP_COMMIT_WRITES: usize = 256;
+
+type PendingWrite = (StateMachineUpdateRequest, oneshot::Sender<Result<()>>);
+
+/// Effects of a write applied once its transaction is committed. Writes
+/// retried after a transient error are applied again from scratch, so
+/// nothing outside the transaction may change before the commit.
+#[derive(Default)]
+struct WriteEffects {
+    allocated_tasks_by_executor: Vec<ExecutorId>,
+    tasks_finalized: HashMap<ExecutorId, Vec<TaskId>>,
+    /// Change events recorded by the write, numbered when the transaction
+    /// is committed.
+    change_events: Vec<ChangeEvent>,
+    invocation_events: Vec<InvocationStateChangeEvent>,
+    notify_system_tasks: bool,
+    notify_gc: bool,
+    executor_registered: Option<ExecutorId>,
+    executor_deregistered: Option<ExecutorId>,
+}
+
+fn output_produced(output: &NodeOutput) -> (String, String, ChangeEventKind) {
+    (
+        output.namespace.clone(),
+        output.compute_graph_name.clone(),
+        ChangeEventKind::OutputProduced {
+            invocation_id: output.invocation_id.clone(),
+            compute_fn: output.compute_fn_name.clone(),
+            output_id: output.id.clone(),
+        },
+    )
+}
+
 pub struct IndexifyState {
     pub db: Arc<TransactionDB>,
     pub executor_states: RwLock<HashMap<ExecutorId, ExecutorState>>,
@@ -103,27 +412,119 @@ pub struct IndexifyState {
     pub gc_rx: tokio::sync::watch::Receiver<()>,
     pub system_tasks_tx: tokio::sync::watch::Sender<()>,
     pub system_tasks_rx: tokio::sync::watch::Receiver<()>,
+    pub change_events_tx: tokio::sync::watch::Sender<()>,
+    pub change_events_rx: tokio::sync::watch::Receiver<()>,
+    record_change_events: bool,
+    normalize_compute_graph_names: bool,
+    auto_create_namespace: bool,
+    write_retries: u32,
+    max_namespaces: Option<usize>,
+    indexed_attributes: HashSet<String>,
+    prefix_extractor: PrefixExtractor,
+    last_change_event_id: AtomicU64,
+    /// Held while change event ids are handed out and their transaction is
+    /// committed.
+    change_event_ids: Mutex<()>,
+    custom_column_families: HashSet<String>,
+    cf_configs: Vec<CfConfig>,
+    transaction_options: TransactionOptions,
+    key_prefix: KeyPrefix,
+    group_commit_tx: Option<mpsc::UnboundedSender<PendingWrite>>,
+    /// Serializes executor registrations, whose writes depend on the
+    /// registration counts only updated once they are committed.
+    executor_registrations: tokio::sync::Mutex<()>,
 }
 
 impl IndexifyState {
     pub async fn new(path: PathBuf) -> Result<Arc<Self>> {
+        Self::new_with_options(path, IndexifyStateOptions::default()).await
+    }
+
+    pub async fn new_with_options(
+        path: PathBuf,
+        options: IndexifyStateOptions,
+    ) -> Result<Arc<Self>> {
         let (tx, rx) = tokio::sync::watch::channel(StateChangeId::new(std::u64::MAX));
         fs::create_dir_all(path.clone())?;
-        let sm_column_families = IndexifyObjectsColumns::iter()
-            .map(|cf| ColumnFamilyDescriptor::new(cf.to_string(), Options::default()));
+        let mut custom_column_families = HashSet::new();
+        for name in options.custom_column_families {
+            if IndexifyObjectsColumns::iter().any(|cf| cf.as_ref() == name) {
+                return Err(anyhow!(
+                    "custom column family {} conflicts with a core column family",
+                    name
+                ));
+            }
+            custom_column_families.insert(name);
+        }
+        let key_prefix = KeyPrefix::new(&options.instance_prefix)?;
+        if options.prefix_extractor == PrefixExtractor::FixedLength(0) {
+            return Err(anyhow!(
+                "fixed length prefix extractor must be longer than 0"
+            ));
+        }
+        for name in options.cf_overrides.keys() {
+            if !custom_column_families.contains(name) &&
+                !IndexifyObjectsColumns::iter().any(|cf| cf.as_ref() == name)
+            {
+                return Err(anyhow!("options override unknown column family {}", name));
+            }
+        }
+        let mut custom_names: Vec<_> = custom_column_families.iter().collect();
+        custom_names.sort();
+        let cf_configs: Vec<_> = IndexifyObjectsColumns::iter()
+            .map(|cf| {
+                let prefix_extractor = if cf.keyed_by_namespace() {
+                    options.prefix_extractor
+                } else {
+                    PrefixExtractor::None
+                };
+                CfConfig::new(
+                    cf.as_ref(),
+                    false,
+                    prefix_extractor,
+                    options.cf_overrides.get(cf.as_ref()),
+                )
+            })
+            .chain(custom_names.into_iter().map(|cf| {
+                CfConfig::new(
+                    cf,
+                    true,
+                    PrefixExtractor::None,
+                    options.cf_overrides.get(cf),
+                )
+            }))
+            .collect();
+        let sm_column_families = cf_configs
+            .iter()
+            .map(|cf| ColumnFamilyDescriptor::new(&cf.name, cf.options()));
         let mut db_opts = Options::default();
         db_opts.create_missing_column_families(true);
         db_opts.create_if_missing(true);
-        let db: TransactionDB = TransactionDB::open_cf_descriptors(
-            &db_opts,
-            &TransactionDBOptions::default(),
-            path,
-            sm_column_families,
-        )
-        .map_err(|e| anyhow!("failed to open db: {}", e))?;
+        let mut txn_db_opts = TransactionDBOptions::default();
+        if let Some(timeout) = options.transaction_lock_timeout {
+            txn_db_opts.set_txn_lock_timeout(timeout.as_millis() as i64);
+        }
+        let mut transaction_options = TransactionOptions::default();
+        transaction_options.set_deadlock_detect(options.deadlock_detect);
+        let db: TransactionDB =
+            TransactionDB::open_cf_descriptors(&db_opts, &txn_db_opts, path, sm_column_families)
+                .map_err(|e| anyhow!("failed to open db: {}", e))?;
+        key_prefix.check_shared_directory(&db)?;
         let (gc_tx, gc_rx) = tokio::sync::watch::channel(());
-        let (task_event_tx, _) = tokio::sync::broadcast::channel(100);
+        let (task_event_tx, _) = tokio::sync::broadcast::channel(
+            options
+                .task_event_capacity
+                .unwrap_or(DEFAULT_TASK_EVENT_CAPACITY),
+        );
         let (system_tasks_tx, system_tasks_rx) = tokio::sync::watch::channel(());
+        let (change_events_tx, change_events_rx) = tokio::sync::watch::channel(());
+        let (group_commit_tx, group_commit_rx) = match options.group_commit_window {
+            Some(_) => {
+                let (tx, rx) = mpsc::unbounded_channel();
+                (Some(tx), Some(rx))
+            }
+            None => (None, None),
+        };
         let s = Arc::new(Self {
             db: Arc::new(db),
             state_change_tx: tx,
@@ -135,7 +536,48 @@ impl IndexifyState {
             gc_rx,
             system_tasks_tx,
             system_tasks_rx,
+            change_events_tx,
+            change_events_rx,
+            record_change_events: options.record_change_events,
+            normalize_compute_graph_names: options.normalize_compute_graph_names,
+            auto_create_namespace: options.auto_create_namespace,
+            write_retries: options.write_retries,
+            max_namespaces: options.max_namespaces,
+            indexed_attributes: options.indexed_attributes,
+            prefix_extractor: options.prefix_extractor,
+            last_change_event_id: AtomicU64::new(0),
+            change_event_ids: Mutex::new(()),
+            custom_column_families,
+            cf_configs,
+            transaction_options,
+            key_prefix,
+            group_commit_tx,
+            executor_registrations: tokio::sync::Mutex::new(()),
         });
+        if let (Some(rx), Some(window)) = (group_commit_rx, options.group_commit_window) {
+            tokio::spawn(Self::run_group_commit(Arc::downgrade(&s), rx, window));
+        }
+
+        match s.reader().schema_version()? {
+            Some(recorded) if recorded > STATE_STORE_SCHEMA_VERSION => {
+                tracing::error!(
+                    "state store has schema version {}, newer than the supported version {}",
+                    recorded,
+                    STATE_STORE_SCHEMA_VERSION
+                );
+            }
+            Some(recorded) if recorded == STATE_STORE_SCHEMA_VERSION => {}
+            _ => state_machine::record_schema_version(
+                &s.db,
+                &s.key_prefix,
+                STATE_STORE_SCHEMA_VERSION,
+            )?,
+        }
+
+        if let Some(last_change_event_id) = s.reader().last_change_event_id()? {
+            s.last_change_event_id
+                .store(last_change_event_id, atomic::Ordering::Relaxed);
+        }
 
         let executors = s.reader().get_all_executors()?;
         for executor in executors.iter() {
@@ -149,6 +591,42 @@ impl IndexifyState {
         Ok(s)
     }
 
+    /// Fails with `SchemaVersionMismatch` when the state store was written
+    /// with a schema newer than this server supports, reads of it could
+    /// then be wrong.
+    pub fn check_schema_version(&self) -> Result<()> {
+        let recorded = self.reader().schema_version()?.unwrap_or_default();
+        if recorded > STATE_STORE_SCHEMA_VERSION {
+            return Err(SchemaVersionMismatch {
+                recorded,
+                supported: STATE_STORE_SCHEMA_VERSION,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Makes every write committed so far durable by syncing the write ahead
+    /// log to disk, which is skipped by the writes themselves. Flushing the
+    /// memtables isn't needed as they are rebuilt from the log on recovery.
+    pub fn flush(&self) -> Result<()> {
+        let mut write_options = WriteOptions::default();
+        write_options.set_sync(true);
+        self.db.put_cf_opt(
+            &IndexifyObjectsColumns::StateMachineMetadata.cf_db(&self.db),
+            self.key_prefix
+                .key(state_machine::LAST_FLUSH_KEY.as_bytes()),
+            JsonEncoder::encode(&get_epoch_time_in_ms())?,
+            &write_options,
+        )?;
+        Ok(())
+    }
+
+    /// Overwrites the schema version recorded in the state store.
+    pub fn record_schema_version(&self, version: u32) -> Result<()> {
+        state_machine::record_schema_version(&self.db, &self.key_prefix, version)
+    }
+
     pub fn get_state_change_watcher(&self) -> Receiver<StateChangeId> {
         self.state_change_rx.clone()
     }
@@ -161,10 +639,260 @@ impl IndexifyState {
         self.system_tasks_rx.clone()
     }
 
+    pub fn get_change_events_watcher(&self) -> Receiver<()> {
+        self.change_events_rx.clone()
+    }
+
+    fn transaction(&self) -> PrefixedTransaction<'_> {
+        let txn = self
+            .db
+            .transaction_opt(&WriteOptions::default(), &self.transaction_options);
+        PrefixedTransaction::new(txn, self.key_prefix.clone())
+    }
+
     pub async fn write(&self, request: StateMachineUpdateRequest) -> Result<()> {
-        let mut allocated_tasks_by_executor = Vec::new();
-        let mut tasks_finalized: HashMap<ExecutorId, Vec<TaskId>> = HashMap::new();
-        let txn = self.db.transaction();
+        let registration = matches!(
+            request.payload,
+            requests::RequestPayload::RegisterExecutor(_) |
+                requests::RequestPayload::DeregisterExecutor(_)
+        );
+        let _registration_guard = match registration {
+            true => Some(self.executor_registrations.lock().await),
+            false => None,
+        };
+        if let Some(group_commit_tx) = self.group_commit_tx.as_ref().filter(|_| !registration) {
+            let (result_tx, result_rx) = oneshot::channel();
+            group_commit_tx
+                .send((request, result_tx))
+                .map_err(|_| anyhow!("group commit is not running"))?;
+            return result_rx
+                .await
+                .map_err(|_| anyhow!("group commit dropped the write"))?;
+        }
+        let mut attempt = 1;
+        loop {
+            let mut effects = WriteEffects::default();
+            match self.commit(&request, &mut effects).await {
+                Ok(new_state_changes) => {
+                    self.after_commit(&request, effects, new_state_changes)
+                        .await;
+                    return Ok(());
+                }
+                Err(err) => self.wait_for_retry(err, attempt).await?,
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Applies the writes in a single transaction, so either all or none of
+    /// them are committed. Executor registrations must go through `write`.
+    pub async fn write_all(&self, requests: Vec<StateMachineUpdateRequest>) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            match self.commit_all(&requests).await {
+                Ok(results) => {
+                    for (request, (effects, new_state_changes)) in requests.iter().zip(results) {
+                        self.after_commit(request, effects, new_state_changes).await;
+                    }
+                    return Ok(());
+                }
+                Err(err) => self.wait_for_retry(err, attempt).await?,
+            }
+            attempt += 1;
+        }
+    }
+
+    async fn commit_all(
+        &self,
+        requests: &[StateMachineUpdateRequest],
+    ) -> Result<Vec<(WriteEffects, Vec<StateChange>)>> {
+        let mut txn = self.transaction();
+        let mut results = Vec::with_capacity(requests.len());
+        for request in requests {
+            let mut effects = WriteEffects::default();
+            let new_state_changes = self.apply(&mut txn, request, &mut effects).await?;
+            results.push((effects, new_state_changes));
+        }
+        self.commit_txn(txn, results.iter_mut().map(|(effects, _)| effects))?;
+        Ok(results)
+    }
+
+    /// Applies a write in its own transaction and commits it.
+    async fn commit(
+        &self,
+        request: &StateMachineUpdateRequest,
+        effects: &mut WriteEffects,
+    ) -> Result<Vec<StateChange>> {
+        let mut txn = self.transaction();
+        let new_state_changes = self.apply(&mut txn, request, effects).await?;
+        self.commit_txn(txn, [effects])?;
+        Ok(new_state_changes)
+    }
+
+    /// Numbers the change events recorded by the writes of the transaction
+    /// and commits it. Ids are handed out and committed under one lock, so
+    /// change events are committed in id order and a reader never sees an id
+    /// before a lower one.
+    fn commit_txn<'e>(
+        &self,
+        txn: PrefixedTransaction<'_>,
+        effects: impl IntoIterator<Item = &'e mut WriteEffects>,
+    ) -> Result<()> {
+        let mut change_events: Vec<&mut ChangeEvent> = effects
+            .into_iter()
+            .flat_map(|effects| effects.change_events.iter_mut())
+            .collect();
+        if change_events.is_empty() {
+            txn.commit()?;
+            return Ok(());
+        }
+        let _ids = self.change_event_ids.lock().unwrap();
+        let mut last_id = self.last_change_event_id.load(atomic::Ordering::Relaxed);
+        for event in change_events.iter_mut() {
+            last_id += 1;
+            event.id = last_id;
+        }
+        state_machine::save_change_events(
+            self.db.clone(),
+            &txn,
+            change_events.iter().map(|event| &**event),
+        )?;
+        txn.commit()?;
+        self.last_change_event_id
+            .store(last_id, atomic::Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Waits before retrying a write which failed on its `attempt`th try.
+    /// Returns the error to fail the write with instead when it isn't
+    /// transient or the retries are exhausted.
+    async fn wait_for_retry(&self, err: anyhow::Error, attempt: u32) -> Result<()> {
+        let Some(source) = state_machine::transient_rocksdb_error(&err) else {
+            return Err(err);
+        };
+        if attempt > self.write_retries {
+            return Err(TransientWriteError {
+                attempts: attempt,
+                source: source.clone(),
+            }
+            .into());
+        }
+        tracing::warn!(
+            "write failed with a transient error on attempt {}, retrying: {}",
+            attempt,
+            source
+        );
+        tokio::time::sleep(WRITE_RETRY_BACKOFF * 2u32.saturating_pow(attempt - 1)).await;
+        Ok(())
+    }
+
+    /// Commits a batch of writes in a single transaction. A failed write is
+    /// rolled back to its savepoint without affecting the others, unless it
+    /// failed with a transient error, in which case the whole batch is
+    /// retried.
+    async fn write_batch(&self, batch: Vec<PendingWrite>) {
+        let (requests, result_txs): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+        let mut attempt = 1;
+        let results = loop {
+            let err = match self.commit_batch(&requests).await {
+                Ok(results) => break results,
+                Err(err) => err,
+            };
+            if let Err(err) = self.wait_for_retry(err, attempt).await {
+                for result_tx in result_txs {
+                    let err = match err.downcast_ref::<TransientWriteError>() {
+                        Some(transient) => transient.clone().into(),
+                        None => anyhow!("failed to commit writes: {}", err),
+                    };
+                    let _ = result_tx.send(Err(err));
+                }
+                return;
+            }
+            attempt += 1;
+        };
+        for ((request, result), result_tx) in requests.iter().zip(results).zip(result_txs) {
+            match result {
+                Ok((effects, new_state_changes)) => {
+                    self.after_commit(request, effects, new_state_changes).await;
+                    let _ = result_tx.send(Ok(()));
+                }
+                Err(err) => {
+                    let _ = result_tx.send(Err(err));
+                }
+            }
+        }
+    }
+
+    /// Applies a batch of writes in a single transaction and commits it,
+    /// returning the outcome of every write.
+    async fn commit_batch(
+        &self,
+        requests: &[StateMachineUpdateRequest],
+    ) -> Result<Vec<Result<(WriteEffects, Vec<StateChange>)>>> {
+        let mut txn = self.transaction();
+        let mut results = Vec::with_capacity(requests.len());
+        for request in requests {
+            txn.set_savepoint();
+            let mut effects = WriteEffects::default();
+            match self.apply(&mut txn, request, &mut effects).await {
+                Ok(new_state_changes) => results.push(Ok((effects, new_state_changes))),
+                Err(err) if state_machine::transient_rocksdb_error(&err).is_some() => {
+                    return Err(err);
+                }
+                Err(err) => {
+                    if let Err(rollback_err) = txn.rollback_to_savepoint() {
+                        tracing::error!("failed to roll back write: {:?}", rollback_err);
+                    }
+                    results.push(Err(err));
+                }
+            }
+        }
+        self.commit_txn(
+            txn,
+            results
+                .iter_mut()
+                .filter_map(|result| result.as_mut().ok())
+                .map(|(effects, _)| effects),
+        )?;
+        Ok(results)
+    }
+
+    /// Batches the writes received within `window` of the first one.
+    async fn run_group_commit(
+        state: Weak<Self>,
+        mut rx: mpsc::UnboundedReceiver<PendingWrite>,
+        window: Duration,
+    ) {
+        while let Some(first) = rx.recv().await {
+            let mut batch = vec![first];
+            let deadline = tokio::time::sleep(window);
+            tokio::pin!(deadline);
+            while batch.len() < MAX_GROUP_COMMIT_WRITES {
+                tokio::select! {
+                    _ = &mut deadline => break,
+                    pending = rx.recv() => match pending {
+                        Some(pending) => batch.push(pending),
+                        None => break,
+                    },
+                }
+            }
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+            state.write_batch(batch).await;
+        }
+    }
+
+    /// Applies a write to the transaction and returns the state changes it
+    /// produced. Effects which must wait for the commit are added to
+    /// `effects`.
+    async fn apply(
+        &self,
+        txn: &mut PrefixedTransaction<'_>,
+        request: &StateMachineUpdateRequest,
+        effects: &mut WriteEffects,
+    ) -> Result<Vec<StateChange>> {
+        let mut change_events = Vec::new();
         let new_state_changes = match &request.payload {
             requests::RequestPayload::InvokeComputeGraph(invoke_compute_graph_request) => {
                 let state_changes = self
@@ -172,8 +900,9 @@ impl IndexifyState {
                     .await?;
                 state_machine::create_graph_input(
                     self.db.clone(),
-                    &txn,
+                    txn,
                     &invoke_compute_graph_request,
+                    &self.indexed_attributes,
                 )?;
                 state_changes
             }
@@ -184,16 +913,16 @@ impl IndexifyState {
                 );
                 state_machine::rerun_compute_graph(
                     self.db.clone(),
-                    &txn,
+                    txn,
                     rerun_compute_graph_request.clone(),
                 )?;
-                let _ = self.system_tasks_tx.send(());
+                effects.notify_system_tasks = true;
                 vec![]
             }
             requests::RequestPayload::UpdateSystemTask(update_system_task_request) => {
                 state_machine::update_system_task(
                     self.db.clone(),
-                    &txn,
+                    txn,
                     update_system_task_request.clone(),
                 )?;
                 vec![]
@@ -201,7 +930,7 @@ impl IndexifyState {
             requests::RequestPayload::RemoveSystemTask(remove_system_task_request) => {
                 state_machine::remove_system_task(
                     self.db.clone(),
-                    &txn,
+                    txn,
                     remove_system_task_request.clone(),
                 )?;
                 vec![]
@@ -209,7 +938,7 @@ impl IndexifyState {
             requests::RequestPayload::RerunInvocation(rerun_invocation_request) => {
                 let mut state_changes = state_machine::rerun_invocation(
                     self.db.clone(),
-                    &txn,
+                    txn,
                     rerun_invocation_request.clone(),
                 )?;
                 for state_change in &mut state_changes {
@@ -223,62 +952,147 @@ impl IndexifyState {
             requests::RequestPayload::FinalizeTask(finalize_task) => {
                 let state_changes = if state_machine::mark_task_completed(
                     self.db.clone(),
-                    &txn,
+                    txn,
                     finalize_task.clone(),
                 )? {
+                    for output in &finalize_task.node_outputs {
+                        change_events.push(output_produced(output));
+                    }
                     self.finalize_task(&finalize_task).await?
                 } else {
                     Vec::new()
                 };
-                tasks_finalized
+                effects
+                    .tasks_finalized
                     .entry(finalize_task.executor_id.clone())
                     .or_default()
                     .push(finalize_task.task_id.clone());
                 state_changes
             }
             requests::RequestPayload::CreateNameSpace(namespace_request) => {
-                state_machine::create_namespace(self.db.clone(), &namespace_request)?;
+                state_machine::create_namespace(
+                    self.db.clone(),
+                    txn,
+                    namespace_request,
+                    self.max_namespaces,
+                )?;
                 vec![]
             }
             requests::RequestPayload::CreateComputeGraph(req) => {
-                state_machine::create_compute_graph(self.db.clone(), req.compute_graph.clone())?;
+                let normalized;
+                let req = if self.normalize_compute_graph_names {
+                    let mut compute_graph = req.compute_graph.clone();
+                    compute_graph.name = compute_graph.name.to_lowercase();
+                    compute_graph.display_name = Some(req.compute_graph.name.clone());
+                    normalized = requests::CreateComputeGraphRequest {
+                        namespace: req.namespace.clone(),
+                        compute_graph,
+                        if_match: req.if_match.clone(),
+                        if_changed: req.if_changed,
+                    };
+                    &normalized
+                } else {
+                    req
+                };
+                if self.auto_create_namespace {
+                    let (_, created) = state_machine::upsert_namespace(
+                        self.db.clone(),
+                        txn,
+                        &NamespaceRequest {
+                            name: req.namespace.clone(),
+                            feature_flags: Default::default(),
+                        },
+                        self.max_namespaces,
+                    )?;
+                    if created {
+                        tracing::info!("created namespace {} for compute graph", req.namespace);
+                    }
+                }
+                let created = state_machine::create_compute_graph(self.db.clone(), txn, req)?;
+                let kind = if created {
+                    ChangeEventKind::GraphCreated
+                } else {
+                    ChangeEventKind::GraphUpdated
+                };
+                change_events.push((req.namespace.clone(), req.compute_graph.name.clone(), kind));
                 vec![]
             }
             requests::RequestPayload::DeleteComputeGraph(request) => {
                 state_machine::delete_compute_graph(
                     self.db.clone(),
-                    &txn,
+                    txn,
                     &request.namespace,
                     &request.name,
+                    request.if_match.as_deref(),
                 )?;
-                self.gc_tx.send(()).unwrap();
+                effects.notify_gc = true;
+                change_events.push((
+                    request.namespace.clone(),
+                    request.name.clone(),
+                    ChangeEventKind::GraphDeleted,
+                ));
+                vec![]
+            }
+            requests::RequestPayload::DeleteNamespace(request) => {
+                let compute_graphs =
+                    state_machine::delete_namespace(self.db.clone(), txn, request)?;
+                effects.notify_gc = !compute_graphs.is_empty();
+                for name in compute_graphs {
+                    change_events.push((request.name.clone(), name, ChangeEventKind::GraphDeleted));
+                }
+                vec![]
+            }
+            requests::RequestPayload::SetComputeGraphEnabled(request) => {
+                state_machine::set_compute_graph_enabled(self.db.clone(), txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::SetComputeGraphFrozen(request) => {
+                state_machine::set_compute_graph_frozen(self.db.clone(), txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::SwapComputeGraphCode(request) => {
+                effects.notify_gc =
+                    state_machine::swap_compute_graph_code(self.db.clone(), txn, request)?;
+                change_events.push((
+                    request.namespace.clone(),
+                    request.name.clone(),
+                    ChangeEventKind::GraphUpdated,
+                ));
+                vec![]
+            }
+            requests::RequestPayload::SetNamespacePolicy(request) => {
+                state_machine::set_namespace_policy(self.db.clone(), txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::SetNamespaceFlags(request) => {
+                state_machine::set_namespace_flags(self.db.clone(), txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::AttachOutput(request) => {
+                state_machine::attach_output(self.db.clone(), txn, request)?;
+                change_events.push(output_produced(&request.output));
                 vec![]
             }
             requests::RequestPayload::DeleteInvocation(request) => {
-                state_machine::delete_input_data_object(self.db.clone(), &request)?;
+                state_machine::delete_input_data_object(self.db.clone(), txn, &request)?;
                 vec![]
             }
             requests::RequestPayload::SchedulerUpdate(request) => {
                 let new_state_changes = self.change_events_for_scheduler_update(&request);
                 for req in &request.task_requests {
-                    match state_machine::create_tasks(self.db.clone(), &txn, req)? {
+                    match state_machine::create_tasks(self.db.clone(), txn, req)? {
                         Some(completion) => {
-                            if let Err(err) = self.task_event_tx.send(
+                            effects.invocation_events.push(
                                 InvocationStateChangeEvent::InvocationFinished(
                                     InvocationFinishedEvent {
                                         id: req.invocation_id.clone(),
                                     },
                                 ),
-                            ) {
-                                tracing::error!(
-                                    "failed to send invocation state change: {:?}",
-                                    err
-                                );
-                            }
+                            );
                             if completion == InvocationCompletion::System {
                                 // Notify the system task handler that it can start new tasks since
                                 // a task was completed
-                                let _ = self.system_tasks_tx.send(());
+                                effects.notify_system_tasks = true;
                             }
                         }
                         None => {}
@@ -286,66 +1100,97 @@ impl IndexifyState {
                 }
                 state_machine::processed_reduction_tasks(
                     self.db.clone(),
-                    &txn,
+                    txn,
                     &request.reduction_tasks,
                 )?;
                 for allocation in &request.allocations {
                     state_machine::allocate_tasks(
                         self.db.clone(),
-                        &txn,
+                        txn,
                         &allocation.task,
                         &allocation.executor,
                     )?;
-                    allocated_tasks_by_executor.push(allocation.executor.clone());
+                    effects
+                        .allocated_tasks_by_executor
+                        .push(allocation.executor.clone());
                 }
                 new_state_changes
             }
             requests::RequestPayload::RegisterExecutor(request) => {
-                {
-                    let mut states = self.executor_states.write().await;
-                    let entry = states.entry(request.executor.id.clone()).or_default();
-                    entry.num_registered += 1;
-                }
-                state_machine::register_executor(self.db.clone(), &txn, &request)?;
+                state_machine::register_executor(self.db.clone(), txn, &request)?;
+                effects.executor_registered = Some(request.executor.id.clone());
                 self.register_executor(&request)
             }
             requests::RequestPayload::DeregisterExecutor(request) => {
                 let state_changes = self.deregister_executor_events(&request);
-                let removed = {
-                    let mut states = self.executor_states.write().await;
-                    if let Some(s) = states.get_mut(&request.executor_id) {
-                        s.num_registered -= 1;
-                        if s.num_registered == 0 {
-                            states.remove(&request.executor_id);
-                            true
-                        } else {
-                            false
-                        }
-                    } else {
-                        true
-                    }
+                // The executor is removed along with its last registration.
+                let removed = match self.executor_states.read().await.get(&request.executor_id) {
+                    Some(s) => s.num_registered <= 1,
+                    None => true,
                 };
+                effects.executor_deregistered = Some(request.executor_id.clone());
                 if removed {
                     tracing::info!("de-registering executor: {}", request.executor_id);
-                    state_machine::deregister_executor(self.db.clone(), &txn, &request)?;
+                    state_machine::deregister_executor(self.db.clone(), txn, &request)?;
                 }
                 state_changes
             }
             requests::RequestPayload::RemoveGcUrls(urls) => {
-                state_machine::remove_gc_urls(self.db.clone(), &txn, urls.clone())?;
+                state_machine::remove_gc_urls(self.db.clone(), txn, urls.clone())?;
+                vec![]
+            }
+            requests::RequestPayload::RemoveChangeEvents(event_ids) => {
+                state_machine::remove_change_events(self.db.clone(), txn, event_ids)?;
+                vec![]
+            }
+            requests::RequestPayload::DeadLetterChangeEvents(events) => {
+                state_machine::dead_letter_change_events(self.db.clone(), txn, events)?;
                 vec![]
             }
         };
+        if self.record_change_events {
+            let created_at = get_epoch_time_in_ms();
+            effects.change_events.extend(change_events.into_iter().map(
+                |(namespace, compute_graph, kind)| ChangeEvent {
+                    id: 0,
+                    namespace,
+                    compute_graph,
+                    kind,
+                    created_at,
+                },
+            ));
+        }
         if !new_state_changes.is_empty() {
-            state_machine::save_state_changes(self.db.clone(), &txn, &new_state_changes)?;
+            state_machine::save_state_changes(self.db.clone(), txn, &new_state_changes)?;
         }
         state_machine::mark_state_changes_processed(
             self.db.clone(),
-            &txn,
+            txn,
             &request.state_changes_processed.clone(),
         )?;
-        txn.commit()?;
-        for executor_id in allocated_tasks_by_executor {
+        Ok(new_state_changes)
+    }
+
+    async fn after_commit(
+        &self,
+        request: &StateMachineUpdateRequest,
+        effects: WriteEffects,
+        new_state_changes: Vec<StateChange>,
+    ) {
+        if let Some(executor_id) = effects.executor_registered {
+            let mut states = self.executor_states.write().await;
+            states.entry(executor_id).or_default().num_registered += 1;
+        }
+        if let Some(executor_id) = effects.executor_deregistered {
+            let mut states = self.executor_states.write().await;
+            if let Some(s) = states.get_mut(&executor_id) {
+                s.num_registered -= 1;
+                if s.num_registered == 0 {
+                    states.remove(&executor_id);
+                }
+            }
+        }
+        for executor_id in effects.allocated_tasks_by_executor {
             self.executor_states
                 .write()
                 .await
@@ -354,7 +1199,7 @@ impl IndexifyState {
                     executor_state.notify();
                 });
         }
-        for (executor_id, tasks) in tasks_finalized {
+        for (executor_id, tasks) in effects.tasks_finalized {
             self.executor_states
                 .write()
                 .await
@@ -365,32 +1210,308 @@ impl IndexifyState {
                     }
                 });
         }
-        self.handle_invocation_state_changes(&request).await;
+        if !effects.change_events.is_empty() {
+            let _ = self.change_events_tx.send(());
+        }
+        for ev in effects.invocation_events {
+            if let Err(err) = self.task_event_tx.send(ev) {
+                tracing::error!("failed to send invocation state change: {:?}", err);
+            }
+        }
+        if effects.notify_system_tasks {
+            let _ = self.system_tasks_tx.send(());
+        }
+        if effects.notify_gc {
+            self.gc_tx.send(()).unwrap();
+        }
+        self.handle_invocation_state_changes(request).await;
         for state_change in new_state_changes {
             self.state_change_tx.send(state_change.id).unwrap();
         }
-        Ok(())
     }
 
-    async fn handle_invocation_state_changes(&self, update_request: &StateMachineUpdateRequest) {
-        if self.task_event_tx.receiver_count() == 0 {
-            return;
+    /// Creates the namespace if it doesn't exist yet, in a single transaction.
+    /// Returns the stored namespace and whether this call created it.
+    pub fn upsert_namespace(&self, req: &NamespaceRequest) -> Result<(Namespace, bool)> {
+        let txn = self.transaction();
+        let res = state_machine::upsert_namespace(self.db.clone(), &txn, req, self.max_namespaces)?;
+        txn.commit()?;
+        Ok(res)
+    }
+
+    /// Returns the options every column family was opened with, core column
+    /// families first.
+    pub fn cf_configs(&self) -> Vec<CfConfig> {
+        self.cf_configs.clone()
+    }
+
+    /// Lists the SST files of the state store for backup tooling. The
+    /// transaction db doesn't expose them, so they are read from the current
+    /// manifest by a read-only instance. Writes which are only in the
+    /// write-ahead log aren't in any of the files yet.
+    pub fn live_files(&self) -> Result<Vec<LiveFileInfo>> {
+        self.reader().live_files()
+    }
+
+    /// Rebuilds the secondary indexes from the primary rows: the unallocated
+    /// tasks, the compute graphs by update time and the namespaces by
+    /// creation time. Tasks and their entries are checked in batched
+    /// transactions, compute graphs and namespaces each in a single one, so
+    /// the server can keep running, and running it again changes nothing.
+    pub fn reindex(&self) -> Result<ReindexReport> {
+        self.reindex_in_batches(REINDEX_BATCH_SIZE)
+    }
+
+    fn reindex_in_batches(&self, batch_size: usize) -> Result<ReindexReport> {
+        let mut report = ReindexReport::default();
+        // Tasks are checked in key order, each batch resuming after the last
+        // task key of the previous one.
+        let mut last_key: Option<Vec<u8>> = None;
+        loop {
+            let txn = self.transaction();
+            let mut task_keys = BTreeSet::new();
+            for column in [
+                IndexifyObjectsColumns::Tasks,
+                IndexifyObjectsColumns::UnallocatedTasks,
+            ] {
+                let mode = match &last_key {
+                    Some(key) => IteratorMode::From(key, Direction::Forward),
+                    None => IteratorMode::Start,
+                };
+                // Seeking past the keys of the namespace extracted from the
+                // last key needs a total order seek.
+                let mut read_options = ReadOptions::default();
+                read_options.set_total_order_seek(true);
+                for kv in txn.iterator_cf_opt(&column.cf_db(&self.db), read_options, mode) {
+                    let (key, _) = kv?;
+                    if last_key.as_deref() == Some(&key[..]) {
+                        continue;
+                    }
+                    task_keys.insert(key.to_vec());
+                    if task_keys.len() > batch_size {
+                        task_keys.pop_last();
+                        break;
+                    }
+                }
+            }
+            let Some(batch_last_key) = task_keys.last().cloned() else {
+                break;
+            };
+            let task_keys: Vec<Vec<u8>> = task_keys.into_iter().collect();
+            let (added, removed) =
+                state_machine::reindex_unallocated_tasks(self.db.clone(), &txn, &task_keys)?;
+            txn.commit()?;
+            report.tasks_scanned += task_keys.len();
+            report.entries_added += added;
+            report.entries_removed += removed;
+            last_key = Some(batch_last_key);
         }
-        match &update_request.payload {
-            requests::RequestPayload::FinalizeTask(task_finished_event) => {
-                let ev =
-                    InvocationStateChangeEvent::from_task_finished(task_finished_event.clone());
-                if let Err(err) = self.task_event_tx.send(ev) {
-                    tracing::error!("failed to send invocation state change: {:?}", err);
+
+        let txn = self.transaction();
+        let (scanned, added, removed) =
+            state_machine::reindex_compute_graph_updates(self.db.clone(), &txn)?;
+        txn.commit()?;
+        report.compute_graphs_scanned = scanned;
+        report.entries_added += added;
+        report.entries_removed += removed;
+
+        let txn = self.transaction();
+        let (scanned, added, removed) =
+            state_machine::reindex_namespace_creations(self.db.clone(), &txn)?;
+        txn.commit()?;
+        report.namespaces_scanned = scanned;
+        report.entries_added += added;
+        report.entries_removed += removed;
+        Ok(report)
+    }
+
+    /// Checks that the code blob of every compute graph is still present in
+    /// blob storage and returns the references that are missing. Nothing is
+    /// modified. Graphs which can't be decoded are logged and skipped.
+    pub async fn verify_blob_references(
+        &self,
+        blob_storage: &BlobStorage,
+    ) -> Result<Vec<DanglingRef>> {
+        let (rows, _) = self.reader().get_raw_rows_from_cf_with_limits(
+            &[],
+            None,
+            IndexifyObjectsColumns::ComputeGraphs,
+            None,
+        )?;
+        let mut dangling_refs = Vec::new();
+        for (key, value) in rows {
+            let compute_graph = match JsonEncoder::decode::<ComputeGraph>(&value) {
+                Ok(compute_graph) => compute_graph,
+                Err(e) => {
+                    tracing::warn!(
+                        "skipping compute graph {} which can't be decoded: {:?}",
+                        String::from_utf8_lossy(&key),
+                        e
+                    );
+                    continue;
                 }
+            };
+            if !blob_storage.exists(&compute_graph.code.path).await? {
+                dangling_refs.push(DanglingRef {
+                    namespace: compute_graph.namespace,
+                    compute_graph: compute_graph.name,
+                    url: compute_graph.code.path,
+                });
             }
-            requests::RequestPayload::SchedulerUpdate(sched_update) => {
-                for task_request in &sched_update.task_requests {
-                    for task in task_request.tasks.iter() {
-                        if let Err(err) =
-                            self.task_event_tx
-                                .send(InvocationStateChangeEvent::TaskCreated(
-                                    invocation_events::TaskCreated {
+        }
+        Ok(dangling_refs)
+    }
+
+    /// Returns the names of the compute graphs of the namespace whose code
+    /// blob is missing from blob storage, in name order.
+    pub async fn list_graphs_with_missing_code(
+        &self,
+        namespace: &str,
+        blob_storage: &BlobStorage,
+    ) -> Result<Vec<String>> {
+        let (compute_graphs, _) =
+            self.reader()
+                .filter_compute_graphs(namespace, |_| true, None, None)?;
+        let mut missing = Vec::new();
+        for compute_graph in compute_graphs {
+            if !blob_storage.exists(&compute_graph.code.path).await? {
+                missing.push(compute_graph.name);
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Deletes the blobs no row references and which were last modified more
+    /// than `min_age` ago. On dry runs the orphans are only reported.
+    pub async fn gc_orphan_blobs(
+        &self,
+        blob_storage: &BlobStorage,
+        min_age: Duration,
+        dry_run: bool,
+    ) -> Result<GcReport> {
+        let cutoff = get_epoch_time_in_ms().saturating_sub(min_age.as_millis() as u64);
+        // The store is listed a page at a time and only the candidates are
+        // kept, so memory is bounded by the number of orphans rather than
+        // the number of blobs.
+        let referenced = self.reader().referenced_blob_urls()?;
+        let mut report = GcReport::default();
+        let mut candidates = Vec::new();
+        let mut continuation = None;
+        loop {
+            let (page, next) = blob_storage.list("", continuation.as_deref()).await?;
+            report.scanned += page.len();
+            candidates.extend(
+                page.into_iter()
+                    .filter(|blob| blob.last_modified < cutoff && !referenced.contains(&blob.url))
+                    .map(|blob| blob.url),
+            );
+            match next {
+                Some(next) => continuation = Some(next),
+                None => break,
+            }
+        }
+        if candidates.is_empty() {
+            return Ok(report);
+        }
+
+        // References are read again after listing, so a blob which became
+        // referenced while the store was being listed is kept.
+        let referenced = self.reader().referenced_blob_urls()?;
+        for url in candidates {
+            if referenced.contains(&url) {
+                continue;
+            }
+            if !dry_run {
+                blob_storage.delete(&url).await?;
+                report.deleted.push(url.clone());
+            }
+            report.orphans.push(url);
+        }
+        Ok(report)
+    }
+
+    fn custom_cf(&self, cf: &str) -> Result<Arc<BoundColumnFamily<'_>>> {
+        if !self.custom_column_families.contains(cf) {
+            return Err(anyhow!(
+                "column family {} is not a registered custom column family",
+                cf
+            ));
+        }
+        self.db
+            .cf_handle(cf)
+            .ok_or_else(|| anyhow!("failed to get column family handle for {}", cf))
+    }
+
+    /// Stores a value in a custom column family.
+    pub fn put_cf<V: Serialize + Debug>(&self, cf: &str, key: &str, value: &V) -> Result<()> {
+        let serialized = JsonEncoder::encode(value)?;
+        self.db.put_cf(
+            &self.custom_cf(cf)?,
+            self.key_prefix.key(key.as_bytes()),
+            serialized,
+        )?;
+        Ok(())
+    }
+
+    /// Reads a value from a custom column family.
+    pub fn get_cf<V: DeserializeOwned>(&self, cf: &str, key: &str) -> Result<Option<V>> {
+        match self
+            .db
+            .get_cf(&self.custom_cf(cf)?, self.key_prefix.key(key.as_bytes()))?
+        {
+            Some(bytes) => Ok(Some(JsonEncoder::decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns all the rows of a custom column family whose key starts with
+    /// the given prefix, in key order.
+    pub fn scan_cf<V: DeserializeOwned>(&self, cf: &str, prefix: &str) -> Result<Vec<(String, V)>> {
+        let cf_handle = self.custom_cf(cf)?;
+        let mut read_options = ReadOptions::default();
+        self.key_prefix.bound(&mut read_options, prefix.as_bytes());
+        let iter = self.key_prefix.strip(self.db.iterator_cf_opt(
+            &cf_handle,
+            read_options,
+            IteratorMode::From(&self.key_prefix.key(prefix.as_bytes()), Direction::Forward),
+        ));
+        let mut rows = Vec::new();
+        for kv in iter {
+            let (key, value) = kv?;
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            let key = String::from_utf8(key.to_vec())?;
+            rows.push((key, JsonEncoder::decode(&value)?));
+        }
+        Ok(rows)
+    }
+
+    async fn handle_invocation_state_changes(&self, update_request: &StateMachineUpdateRequest) {
+        if self.task_event_tx.receiver_count() == 0 {
+            return;
+        }
+        match &update_request.payload {
+            requests::RequestPayload::FinalizeTask(task_finished_event) => {
+                for output in &task_finished_event.node_outputs {
+                    let ev = InvocationStateChangeEvent::from_node_output(output);
+                    if let Err(err) = self.task_event_tx.send(ev) {
+                        tracing::error!("failed to send invocation state change: {:?}", err);
+                    }
+                }
+                let ev =
+                    InvocationStateChangeEvent::from_task_finished(task_finished_event.clone());
+                if let Err(err) = self.task_event_tx.send(ev) {
+                    tracing::error!("failed to send invocation state change: {:?}", err);
+                }
+            }
+            requests::RequestPayload::SchedulerUpdate(sched_update) => {
+                for task_request in &sched_update.task_requests {
+                    for task in task_request.tasks.iter() {
+                        if let Err(err) =
+                            self.task_event_tx
+                                .send(InvocationStateChangeEvent::TaskCreated(
+                                    invocation_events::TaskCreated {
                                         invocation_id: task.invocation_id.clone(),
                                         fn_name: task.compute_fn_name.clone(),
                                         task_id: task.id.to_string(),
@@ -429,6 +1550,12 @@ impl IndexifyState {
                     }
                 }
             }
+            requests::RequestPayload::AttachOutput(request) => {
+                let ev = InvocationStateChangeEvent::from_node_output(&request.output);
+                if let Err(err) = self.task_event_tx.send(ev) {
+                    tracing::error!("failed to send invocation state change: {:?}", err);
+                }
+            }
             _ => {}
         }
     }
@@ -534,8 +1661,27 @@ impl IndexifyState {
         vec![state_change]
     }
 
-    pub fn reader(&self) -> scanner::StateReader {
-        scanner::StateReader::new(self.db.clone())
+    /// Returns the name under which a compute graph named `name` is stored.
+    pub fn compute_graph_key_name(&self, name: &str) -> String {
+        if self.normalize_compute_graph_names {
+            name.to_lowercase()
+        } else {
+            name.to_string()
+        }
+    }
+
+    pub fn reader(&self) -> scanner::StateReader<'static> {
+        scanner::StateReader::new(
+            self.db.clone(),
+            self.prefix_extractor,
+            self.key_prefix.clone(),
+        )
+    }
+
+    /// Returns a reader pinned to a snapshot of the state store, so that
+    /// several reads see the same state even while writes are committed.
+    pub fn snapshot_reader(&self) -> scanner::SnapshotReader<'_> {
+        scanner::SnapshotReader::new(&self.db, self.prefix_extractor, self.key_prefix.clone())
     }
 
     pub fn task_event_stream(&self) -> broadcast::Receiver<InvocationStateChangeEvent> {
@@ -593,17 +1739,37 @@ pub fn task_stream(state: Arc<IndexifyState>, executor: ExecutorId, limit: usize
 mod tests {
     use std::collections::HashMap;
 
+    use blob_store::BlobStorageConfig;
+    use bytes::Bytes;
     use data_model::{
-        test_objects::tests::{create_mock_task, mock_graph_a, TEST_NAMESPACE},
+        test_objects::tests::{
+            create_mock_task,
+            mock_executor,
+            mock_graph_a,
+            mock_graph_b,
+            TEST_NAMESPACE,
+        },
         ComputeGraph,
+        DataPayload,
         GraphInvocationCtxBuilder,
+        InvocationPayloadBuilder,
+        InvocationStatus,
         Namespace,
+        NodeOutputBuilder,
+        OutputPayload,
+        TaskOutcome,
     };
     use futures::StreamExt;
     use requests::{
         CreateComputeGraphRequest,
+        CreateTasksRequest,
         DeleteComputeGraphRequest,
+        DeleteInvocationRequest,
+        DeleteNamespaceRequest,
+        FinalizeTaskRequest,
+        InvokeComputeGraphRequest,
         ReductionTasks,
+        RegisterExecutorRequest,
         SchedulerUpdateRequest,
         TaskPlacement,
     };
@@ -612,6 +1778,8 @@ mod tests {
 
     use super::{
         requests::{NamespaceRequest, RequestPayload},
+        scanner::NamespaceDiff,
+        state_machine::ComputeGraphEtagMismatch,
         *,
     };
     use crate::serializer::{JsonEncode, JsonEncoder};
@@ -626,6 +1794,7 @@ mod tests {
             .write(StateMachineUpdateRequest {
                 payload: RequestPayload::CreateNameSpace(NamespaceRequest {
                     name: "namespace1".to_string(),
+                    feature_flags: Default::default(),
                 }),
                 state_changes_processed: vec![],
             })
@@ -634,6 +1803,7 @@ mod tests {
             .write(StateMachineUpdateRequest {
                 payload: RequestPayload::CreateNameSpace(NamespaceRequest {
                     name: "namespace2".to_string(),
+                    feature_flags: Default::default(),
                 }),
                 state_changes_processed: vec![],
             })
@@ -665,10 +1835,12 @@ mod tests {
         let compute_graph = mock_graph_a();
         indexify_state
             .write(StateMachineUpdateRequest {
-                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
                     namespace: TEST_NAMESPACE.to_string(),
                     compute_graph: compute_graph.clone(),
-                }),
+                    if_match: None,
+                    if_changed: false,
+                })),
                 state_changes_processed: vec![],
             })
             .await?;
@@ -692,6 +1864,7 @@ mod tests {
                 payload: RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
                     namespace: TEST_NAMESPACE.to_string(),
                     name: "graph_A".to_string(),
+                    if_match: None,
                 }),
                 state_changes_processed: vec![],
             })
@@ -712,6 +1885,115 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_delete_invocation_keeps_other_invocations() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let cg = mock_graph_a();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        // The ids of the other invocations start with, or sort after, the id
+        // of the deleted one.
+        for id in ["abc", "abcd", "abd"] {
+            let mut invocation_payload = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name(cg.name.clone())
+                .payload(DataPayload {
+                    path: format!("test_{}", id),
+                    size: 23,
+                    sha256_hash: "hash1232".to_string(),
+                })
+                .build()?;
+            invocation_payload.id = id.to_string();
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph_name: cg.name.clone(),
+                        invocation_payload,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeleteInvocation(DeleteInvocationRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.name.clone(),
+                    invocation_id: "abc".to_string(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let (invocations, _) =
+            indexify_state
+                .reader()
+                .list_invocations(TEST_NAMESPACE, &cg.name, None, None)?;
+        let ids = invocations
+            .iter()
+            .map(|invocation| invocation.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["abcd", "abd"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_reader() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let create = |compute_graph: ComputeGraph| StateMachineUpdateRequest {
+            payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph,
+                if_match: None,
+                if_changed: false,
+            })),
+            state_changes_processed: vec![],
+        };
+        indexify_state.write(create(mock_graph_a())).await?;
+
+        let snapshot_reader = indexify_state.snapshot_reader();
+        indexify_state.write(create(mock_graph_b())).await?;
+
+        // The snapshot only sees the graph written before it was taken.
+        assert!(snapshot_reader
+            .get_compute_graph(TEST_NAMESPACE, "graph_A")?
+            .is_some());
+        assert!(snapshot_reader
+            .get_compute_graph(TEST_NAMESPACE, "graph_B")?
+            .is_none());
+        let (graphs, _) = snapshot_reader.list_compute_graphs(TEST_NAMESPACE, None, None)?;
+        assert_eq!(graphs.len(), 1);
+        assert_eq!(
+            snapshot_reader
+                .list_recently_updated(TEST_NAMESPACE, 10)?
+                .len(),
+            1
+        );
+
+        // The main reader sees both.
+        let (graphs, _) =
+            indexify_state
+                .reader()
+                .list_compute_graphs(TEST_NAMESPACE, None, None)?;
+        assert_eq!(graphs.len(), 2);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_task_stream() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -825,4 +2107,1497 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_list_invocations_by_status() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let cg = mock_graph_a();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let mut invocation_ids = vec![];
+        for i in 0..4 {
+            let invocation_payload = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name(cg.name.clone())
+                .payload(DataPayload {
+                    path: format!("test_{}", i),
+                    size: 23,
+                    sha256_hash: "hash1232".to_string(),
+                })
+                .build()?;
+            invocation_ids.push(invocation_payload.id.clone());
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph_name: cg.name.clone(),
+                        invocation_payload,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let create_tasks = |invocation_id: &str, tasks: Vec<Task>| CreateTasksRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+            invocation_id: invocation_id.to_string(),
+            tasks,
+        };
+        let scheduler_update = |task_requests: Vec<CreateTasksRequest>| StateMachineUpdateRequest {
+            payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                task_requests,
+                allocations: vec![],
+                reduction_tasks: ReductionTasks::default(),
+                diagnostic_msgs: vec![],
+            }),
+            state_changes_processed: vec![],
+        };
+
+        // invocation 0 stays pending, 1 is processing, 2 completes and 3 fails
+        let processing_task = create_mock_task(&cg, "fn_a", "", &invocation_ids[1]);
+        let failed_task = create_mock_task(&cg, "fn_a", "", &invocation_ids[3]);
+        indexify_state
+            .write(scheduler_update(vec![
+                create_tasks(&invocation_ids[1], vec![processing_task]),
+                create_tasks(&invocation_ids[2], vec![]),
+                create_tasks(&invocation_ids[3], vec![failed_task.clone()]),
+            ]))
+            .await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.name.clone(),
+                    compute_fn: failed_task.compute_fn_name.clone(),
+                    invocation_id: failed_task.invocation_id.clone(),
+                    task_id: failed_task.id.clone(),
+                    node_outputs: vec![],
+                    task_outcome: TaskOutcome::Failure,
+                    executor_id: ExecutorId::new("executor1".to_string()),
+                    diagnostics: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        indexify_state
+            .write(scheduler_update(vec![create_tasks(
+                &invocation_ids[3],
+                vec![],
+            )]))
+            .await?;
+
+        let reader = indexify_state.reader();
+        let expected = [
+            (InvocationStatus::Pending, &invocation_ids[0]),
+            (InvocationStatus::Processing, &invocation_ids[1]),
+            (InvocationStatus::Completed, &invocation_ids[2]),
+            (InvocationStatus::Failed, &invocation_ids[3]),
+        ];
+        for (status, invocation_id) in expected {
+            let (invocations, cursor) = reader.list_invocations_with_status(
+                TEST_NAMESPACE,
+                &cg.name,
+                Some(status),
+                None,
+                None,
+            )?;
+            assert_eq!(invocations.len(), 1);
+            assert_eq!(&invocations[0].0.id, invocation_id);
+            assert_eq!(invocations[0].1, status);
+            assert!(cursor.is_none());
+        }
+
+        let (invocations, _) =
+            reader.list_invocations_with_status(TEST_NAMESPACE, &cg.name, None, None, None)?;
+        assert_eq!(invocations.len(), 4);
+
+        let counts = reader.invocation_status_counts(TEST_NAMESPACE, &cg.name)?;
+        assert_eq!(counts.len(), 4);
+        assert!(counts.values().all(|count| *count == 1));
+
+        // Contexts written before the status was tracked have no status.
+        let ctx = reader.invocation_ctx(TEST_NAMESPACE, &cg.name, &invocation_ids[0])?;
+        let mut legacy_ctx = serde_json::to_value(&ctx)?;
+        legacy_ctx.as_object_mut().unwrap().remove("status");
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
+            ctx.key(),
+            serde_json::to_vec(&legacy_ctx)?,
+        )?;
+        let (invocations, _) = reader.list_invocations_with_status(
+            TEST_NAMESPACE,
+            &cg.name,
+            Some(InvocationStatus::Unknown),
+            None,
+            None,
+        )?;
+        assert_eq!(invocations.len(), 1);
+        assert_eq!(&invocations[0].0.id, &invocation_ids[0]);
+        let counts = reader.invocation_status_counts(TEST_NAMESPACE, &cg.name)?;
+        assert_eq!(counts.get(&InvocationStatus::Unknown), Some(&1));
+        assert_eq!(counts.get(&InvocationStatus::Pending), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upsert_namespace() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let req = NamespaceRequest {
+            name: "namespace1".to_string(),
+            feature_flags: Default::default(),
+        };
+
+        let (created, is_new) = indexify_state.upsert_namespace(&req)?;
+        assert!(is_new);
+
+        let (existing, is_new) = indexify_state.upsert_namespace(&req)?;
+        assert!(!is_new);
+        assert_eq!(existing.created_at, created.created_at);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_namespace_count() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        // Written before the number of namespaces was kept.
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::Namespaces.cf_db(&indexify_state.db),
+            "legacy",
+            br#"{"name":"legacy","created_at":10}"#,
+        )?;
+        assert_eq!(indexify_state.reader().count_namespaces()?, 1);
+
+        for name in ["namespace1", "namespace2"] {
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                        name: name.to_string(),
+                        feature_flags: Default::default(),
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        indexify_state.upsert_namespace(&NamespaceRequest {
+            name: "namespace1".to_string(),
+            feature_flags: Default::default(),
+        })?;
+        assert_eq!(indexify_state.reader().count_namespaces()?, 3);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeleteNamespace(DeleteNamespaceRequest {
+                    name: "namespace1".to_string(),
+                    force: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert_eq!(indexify_state.reader().count_namespaces()?, 2);
+
+        // Namespaces are counted one transaction at a time, a concurrent
+        // creation can't count the same slot.
+        let txn = indexify_state.transaction();
+        state_machine::count_new_namespace(&indexify_state.db, &txn, Some(3))?;
+        let concurrent_txn = indexify_state.transaction();
+        assert!(
+            state_machine::count_new_namespace(&indexify_state.db, &concurrent_txn, Some(3))
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_unversioned_namespace() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        // Written before namespaces had feature flags or a schema version.
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::Namespaces.cf_db(&indexify_state.db),
+            "legacy",
+            br#"{"name":"legacy","created_at":10}"#,
+        )?;
+
+        let namespace = indexify_state.reader().get_namespace("legacy")?.unwrap();
+        assert_eq!(namespace.created_at, 10);
+        assert!(namespace.feature_flags.is_empty());
+        assert_eq!(
+            namespace.schema_version,
+            data_model::schema::NAMESPACE_SCHEMA_VERSION
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_live_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("state");
+        let indexify_state = IndexifyState::new(path.clone()).await?;
+        indexify_state.upsert_namespace(&NamespaceRequest {
+            name: "namespace1".to_string(),
+            feature_flags: Default::default(),
+        })?;
+        // Reopening flushes the write-ahead log recovered on open to an SST.
+        drop(indexify_state);
+        let indexify_state = IndexifyState::new(path).await?;
+
+        let live_files = indexify_state.live_files()?;
+        let namespace_file = live_files
+            .iter()
+            .find(|file| file.column_family == IndexifyObjectsColumns::Namespaces.as_ref())
+            .unwrap();
+        assert!(namespace_file.name.ends_with(".sst"));
+        assert!(namespace_file.size_bytes > 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cf_disk_size() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("state");
+        let indexify_state = IndexifyState::new(path.clone()).await?;
+        let mut compute_graph = mock_graph_a();
+        compute_graph.description = "a".repeat(64 * 1024);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph,
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        // Reopening flushes the write-ahead log recovered on open to an SST.
+        drop(indexify_state);
+        let indexify_state = IndexifyState::new(path).await?;
+
+        let reader = indexify_state.reader();
+        assert!(reader.cf_disk_size(IndexifyObjectsColumns::ComputeGraphs)? > 0);
+        assert_eq!(
+            reader.cf_disk_size(IndexifyObjectsColumns::ReductionTasks)?,
+            0
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reindex() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+
+        let executor_id = ExecutorId::new("executor1".to_string());
+        let cg = mock_graph_a();
+        let allocated_task = create_mock_task(
+            &cg,
+            "fn",
+            "namespace|graph|ingested_id|fn|id_1",
+            "ingested_id",
+        );
+        let pending_task = create_mock_task(
+            &cg,
+            "fn",
+            "namespace|graph|ingested_id|fn|id_2",
+            "ingested_id",
+        );
+        let graph_invocation_ctx = GraphInvocationCtxBuilder::default()
+            .namespace(allocated_task.namespace.clone())
+            .compute_graph_name(allocated_task.compute_graph_name.clone())
+            .invocation_id(allocated_task.invocation_id.clone())
+            .fn_task_analytics(HashMap::new())
+            .build(cg.clone())?;
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
+            graph_invocation_ctx.key(),
+            &JsonEncoder::encode(&graph_invocation_ctx)?,
+        )?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: requests::RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: allocated_task.namespace.clone(),
+                        compute_graph: allocated_task.compute_graph_name.clone(),
+                        invocation_id: allocated_task.invocation_id.clone(),
+                        tasks: vec![allocated_task.clone(), pending_task.clone()],
+                    }],
+                    allocations: vec![TaskPlacement {
+                        task: allocated_task.clone(),
+                        executor: executor_id.clone(),
+                    }],
+                    reduction_tasks: ReductionTasks::default(),
+                    diagnostic_msgs: vec![],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        // Drop the index and leave an entry behind for a task which is gone.
+        let unallocated_tasks_cf =
+            IndexifyObjectsColumns::UnallocatedTasks.cf_db(&indexify_state.db);
+        indexify_state
+            .db
+            .delete_cf(&unallocated_tasks_cf, pending_task.key())?;
+        indexify_state.db.put_cf(
+            &unallocated_tasks_cf,
+            "namespace|graph|ingested_id|fn|gone",
+            [],
+        )?;
+
+        // Tasks are checked one per transaction, the batches have to pick up
+        // where the previous one stopped.
+        let report = indexify_state.reindex_in_batches(1)?;
+        assert_eq!(
+            report,
+            ReindexReport {
+                tasks_scanned: 3,
+                compute_graphs_scanned: 0,
+                namespaces_scanned: 0,
+                entries_added: 1,
+                entries_removed: 1,
+            }
+        );
+        let unallocated_tasks = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(unallocated_tasks.len(), 1);
+        assert_eq!(unallocated_tasks[0].id, pending_task.id);
+
+        let report = indexify_state.reindex()?;
+        assert_eq!(
+            report,
+            ReindexReport {
+                tasks_scanned: 2,
+                compute_graphs_scanned: 0,
+                namespaces_scanned: 0,
+                entries_added: 0,
+                entries_removed: 0,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reindex_compute_graph_updates() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let compute_graph = indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, "graph_A")?
+            .unwrap();
+
+        // Replace the entry with one for an older update, as left by a graph
+        // written before the index existed.
+        let updates_cf = IndexifyObjectsColumns::ComputeGraphUpdates.cf_db(&indexify_state.db);
+        let mut stale = compute_graph.clone();
+        stale.updated_at = compute_graph.created_at;
+        indexify_state
+            .db
+            .delete_cf(&updates_cf, compute_graph.update_index_key())?;
+        indexify_state
+            .db
+            .put_cf(&updates_cf, stale.update_index_key(), [])?;
+
+        let report = indexify_state.reindex()?;
+        assert_eq!(
+            report,
+            ReindexReport {
+                tasks_scanned: 0,
+                compute_graphs_scanned: 1,
+                namespaces_scanned: 0,
+                entries_added: 1,
+                entries_removed: 1,
+            }
+        );
+        let recent = indexify_state
+            .reader()
+            .list_recently_updated(TEST_NAMESPACE, 10)?;
+        assert_eq!(recent, vec![compute_graph]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_custom_column_families() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("state"),
+            IndexifyStateOptions {
+                custom_column_families: vec!["Widgets".to_string()],
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        indexify_state.put_cf("Widgets", "ns|a", &"first".to_string())?;
+        indexify_state.put_cf("Widgets", "ns|b", &"second".to_string())?;
+        indexify_state.put_cf("Widgets", "other|c", &"third".to_string())?;
+
+        let value: Option<String> = indexify_state.get_cf("Widgets", "ns|a")?;
+        assert_eq!(value, Some("first".to_string()));
+        let missing: Option<String> = indexify_state.get_cf("Widgets", "ns|z")?;
+        assert!(missing.is_none());
+
+        let rows: Vec<(String, String)> = indexify_state.scan_cf("Widgets", "ns|")?;
+        assert_eq!(
+            rows,
+            vec![
+                ("ns|a".to_string(), "first".to_string()),
+                ("ns|b".to_string(), "second".to_string()),
+            ]
+        );
+
+        // Core column families are not reachable through the generic helpers.
+        assert!(indexify_state
+            .get_cf::<String>(IndexifyObjectsColumns::Namespaces.as_ref(), "ns")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_change_events_commit_in_id_order() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("state"),
+            IndexifyStateOptions {
+                record_change_events: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+        let writers = (0..32).map(|i| {
+            let indexify_state = indexify_state.clone();
+            tokio::spawn(async move {
+                let mut compute_graph = mock_graph_a();
+                compute_graph.name = format!("graph_{}", i);
+                indexify_state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::CreateComputeGraph(Box::new(
+                            CreateComputeGraphRequest {
+                                namespace: TEST_NAMESPACE.to_string(),
+                                compute_graph,
+                                if_match: None,
+                                if_changed: false,
+                            },
+                        )),
+                        state_changes_processed: vec![],
+                    })
+                    .await
+            })
+        });
+        let writers = futures::future::join_all(writers);
+        tokio::pin!(writers);
+
+        // A reader never sees an id before a lower one is committed, which
+        // would make readers resuming after the highest id skip it.
+        loop {
+            let ids: Vec<u64> = indexify_state
+                .reader()
+                .get_change_events(None, None)?
+                .into_iter()
+                .map(|event| event.id)
+                .collect();
+            assert_eq!(ids, (1..=ids.len() as u64).collect::<Vec<_>>());
+            if ids.len() == 32 {
+                break;
+            }
+            if let Some(results) = futures::FutureExt::now_or_never(&mut writers) {
+                for result in results {
+                    result??;
+                }
+            }
+            tokio::task::yield_now().await;
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_instance_prefix() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("state");
+        let open = |instance_prefix: &str| {
+            IndexifyState::new_with_options(
+                path.clone(),
+                IndexifyStateOptions {
+                    instance_prefix: instance_prefix.to_string(),
+                    ..Default::default()
+                },
+            )
+        };
+        // RocksDB locks its directory, so the instances open it in turn.
+        for instance in ["blue", "green"] {
+            let indexify_state = open(instance).await?;
+            let mut compute_graph = mock_graph_a();
+            compute_graph.namespace = format!("{}_ns", instance);
+            indexify_state.upsert_namespace(&NamespaceRequest {
+                name: compute_graph.namespace.clone(),
+                feature_flags: Default::default(),
+            })?;
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: compute_graph.namespace.clone(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        for instance in ["blue", "green"] {
+            let reader = open(instance).await?.reader();
+            let namespaces: Vec<String> = reader
+                .get_all_namespaces()?
+                .into_iter()
+                .map(|namespace| namespace.name)
+                .collect();
+            assert_eq!(namespaces, vec![format!("{}_ns", instance)]);
+            let (compute_graphs, _) =
+                reader.filter_compute_graphs(&format!("{}_ns", instance), |_| true, None, None)?;
+            assert_eq!(compute_graphs.len(), 1);
+            assert_eq!(
+                reader.get_compute_graph("blue_ns", "graph_A")?.is_some(),
+                instance == "blue"
+            );
+            assert_eq!(reader.schema_version()?, Some(STATE_STORE_SCHEMA_VERSION));
+        }
+        assert!(open("blue/green").await.is_err());
+
+        // Instances with and without a prefix can't share a directory.
+        let err = open("").await.err().unwrap();
+        assert!(
+            err.to_string().contains("instances with a prefix"),
+            "{}",
+            err
+        );
+        let unprefixed_path = temp_dir.path().join("unprefixed");
+        IndexifyState::new(unprefixed_path.clone()).await?;
+        let prefixed = IndexifyState::new_with_options(
+            unprefixed_path,
+            IndexifyStateOptions {
+                instance_prefix: "blue".to_string(),
+                ..Default::default()
+            },
+        )
+        .await;
+        let err = prefixed.err().unwrap();
+        assert!(err.to_string().contains("without a prefix"), "{}", err);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cf_configs() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let cf_override = CfOverride {
+            compression: Some(CfCompression::None),
+            block_cache_size: Some(1 << 20),
+        };
+        let indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("state"),
+            IndexifyStateOptions {
+                custom_column_families: vec!["Widgets".to_string()],
+                cf_overrides: HashMap::from([
+                    ("Tasks".to_string(), cf_override.clone()),
+                    ("Widgets".to_string(), cf_override),
+                ]),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        let cf_configs = indexify_state.cf_configs();
+        assert_eq!(cf_configs.len(), IndexifyObjectsColumns::iter().count() + 1);
+        let cf_config = |name: &str| cf_configs.iter().find(|cf| cf.name == name).unwrap();
+        assert_eq!(
+            cf_config("Tasks"),
+            &CfConfig {
+                name: "Tasks".to_string(),
+                custom: false,
+                compression: Some(CfCompression::None),
+                block_cache_size: Some(1 << 20),
+                prefix_extractor: PrefixExtractor::Namespace,
+            }
+        );
+        assert_eq!(
+            cf_config("Namespaces"),
+            &CfConfig {
+                name: "Namespaces".to_string(),
+                custom: false,
+                compression: None,
+                block_cache_size: None,
+                prefix_extractor: PrefixExtractor::None,
+            }
+        );
+        assert!(cf_config("Widgets").custom);
+        assert_eq!(cf_config("Widgets").compression, Some(CfCompression::None));
+
+        let err = IndexifyState::new_with_options(
+            temp_dir.path().join("other_state"),
+            IndexifyStateOptions {
+                cf_overrides: HashMap::from([("Unknown".to_string(), CfOverride::default())]),
+                ..Default::default()
+            },
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("Unknown"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_blob_references() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let blob_storage = BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().join("blobs").to_str().unwrap(),
+        ))?;
+
+        let code = blob_storage
+            .put(
+                "cg_code",
+                futures::stream::iter(vec![Ok(Bytes::from_static(b"code"))]),
+            )
+            .await?;
+        let mut compute_graph = mock_graph_a();
+        compute_graph.code.path = code.url.clone();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: compute_graph.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        assert!(indexify_state
+            .verify_blob_references(&blob_storage)
+            .await?
+            .is_empty());
+
+        fs::remove_file(temp_dir.path().join("blobs").join("cg_code"))?;
+
+        let dangling_refs = indexify_state.verify_blob_references(&blob_storage).await?;
+        assert_eq!(
+            dangling_refs,
+            vec![DanglingRef {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: compute_graph.name.clone(),
+                url: code.url,
+            }]
+        );
+        // The check doesn't touch the graph.
+        assert!(indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, &compute_graph.name)?
+            .is_some());
+
+        // Rows which can't be decoded don't fail the check.
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&indexify_state.db),
+            format!("{}|corrupted", TEST_NAMESPACE),
+            b"not a compute graph",
+        )?;
+        assert_eq!(
+            indexify_state.verify_blob_references(&blob_storage).await?,
+            dangling_refs
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_failed_outputs() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let cg = mock_graph_a();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation_payload = InvocationPayloadBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name(cg.name.clone())
+            .payload(DataPayload {
+                path: "test".to_string(),
+                size: 23,
+                sha256_hash: "hash1232".to_string(),
+            })
+            .build()?;
+        let invocation_id = invocation_payload.id.clone();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: cg.name.clone(),
+                    invocation_payload,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let failed_task = create_mock_task(&cg, "fn_b", "", &invocation_id);
+        let successful_task = create_mock_task(&cg, "fn_c", "", &invocation_id);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![CreateTasksRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph: cg.name.clone(),
+                        invocation_id: invocation_id.clone(),
+                        tasks: vec![failed_task.clone(), successful_task.clone()],
+                    }],
+                    allocations: vec![],
+                    reduction_tasks: ReductionTasks::default(),
+                    diagnostic_msgs: vec![],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let since = get_epoch_time_in_ms();
+        let errors = DataPayload {
+            path: "errors".to_string(),
+            size: 12,
+            sha256_hash: "errors_hash".to_string(),
+        };
+        for (task, outcome) in [
+            (&failed_task, TaskOutcome::Failure),
+            (&successful_task, TaskOutcome::Success),
+        ] {
+            let output = NodeOutputBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name(cg.name.clone())
+                .compute_fn_name(task.compute_fn_name.clone())
+                .invocation_id(invocation_id.clone())
+                .payload(OutputPayload::Fn(DataPayload {
+                    path: format!("{}_output", task.compute_fn_name),
+                    size: 1,
+                    sha256_hash: "hash".to_string(),
+                }))
+                .errors(Some(errors.clone()))
+                .build()?;
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph: cg.name.clone(),
+                        compute_fn: task.compute_fn_name.clone(),
+                        invocation_id: invocation_id.clone(),
+                        task_id: task.id.clone(),
+                        node_outputs: vec![output],
+                        task_outcome: outcome,
+                        executor_id: ExecutorId::new("executor1".to_string()),
+                        diagnostics: None,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let reader = indexify_state.reader();
+        let failed_outputs = reader.list_failed_outputs(TEST_NAMESPACE, since, 10)?;
+        assert_eq!(failed_outputs.len(), 1);
+        assert_eq!(failed_outputs[0].task_id, failed_task.id.to_string());
+        assert_eq!(failed_outputs[0].compute_fn_name, "fn_b");
+        assert_eq!(failed_outputs[0].errors, Some(errors));
+        assert!(failed_outputs[0].failed_at >= since);
+
+        // Failures recorded before the window are not returned.
+        let after = failed_outputs[0].failed_at + 1;
+        assert!(reader
+            .list_failed_outputs(TEST_NAMESPACE, after, 10)?
+            .is_empty());
+        assert!(reader.list_failed_outputs("other", 0, 10)?.is_empty());
+
+        // The failures are deleted along with the graph.
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    name: cg.name.clone(),
+                    if_match: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert!(reader
+            .list_failed_outputs(TEST_NAMESPACE, since, 10)?
+            .is_empty());
+        assert!(!reader.referenced_blob_urls()?.contains("errors"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_latest_outputs_for_inputs() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let cg = mock_graph_a();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let mut invocation_ids = vec![];
+        for i in 0..2 {
+            let invocation_payload = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name(cg.name.clone())
+                .payload(DataPayload {
+                    path: format!("test{}", i),
+                    size: 23,
+                    sha256_hash: "hash1232".to_string(),
+                })
+                .build()?;
+            invocation_ids.push(invocation_payload.id.clone());
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph_name: cg.name.clone(),
+                        invocation_payload,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        // Only the second invocation produces an output.
+        let invocation_id = invocation_ids[1].clone();
+        let task = create_mock_task(&cg, "fn_b", "", &invocation_id);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![CreateTasksRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph: cg.name.clone(),
+                        invocation_id: invocation_id.clone(),
+                        tasks: vec![task.clone()],
+                    }],
+                    allocations: vec![],
+                    reduction_tasks: ReductionTasks::default(),
+                    diagnostic_msgs: vec![],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let output = NodeOutputBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name(cg.name.clone())
+            .compute_fn_name(task.compute_fn_name.clone())
+            .invocation_id(invocation_id.clone())
+            .payload(OutputPayload::Fn(DataPayload {
+                path: "fn_b_output".to_string(),
+                size: 1,
+                sha256_hash: "hash".to_string(),
+            }))
+            .build()?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.name.clone(),
+                    compute_fn: task.compute_fn_name.clone(),
+                    invocation_id: invocation_id.clone(),
+                    task_id: task.id.clone(),
+                    node_outputs: vec![output.clone()],
+                    task_outcome: TaskOutcome::Success,
+                    executor_id: ExecutorId::new("executor1".to_string()),
+                    diagnostics: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let input_ids = vec![
+            invocation_ids[0].clone(),
+            invocation_id.clone(),
+            "unknown".to_string(),
+        ];
+        let outputs = indexify_state.reader().latest_outputs_for_inputs(
+            TEST_NAMESPACE,
+            &cg.name,
+            &input_ids,
+        )?;
+        assert_eq!(outputs.len(), 3);
+        assert!(outputs[0].is_none());
+        assert_eq!(outputs[1].as_ref().map(|o| o.id.clone()), Some(output.id));
+        assert!(outputs[2].is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_output_lineage() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let cg = mock_graph_a();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation_payload = InvocationPayloadBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name(cg.name.clone())
+            .payload(DataPayload {
+                path: "input".to_string(),
+                size: 23,
+                sha256_hash: "hash".to_string(),
+            })
+            .build()?;
+        let invocation_id = invocation_payload.id.clone();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: cg.name.clone(),
+                    invocation_payload,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        // Runs `compute_fn` on `input_key` and returns the output it produced.
+        let run_task = |compute_fn: &'static str, input_key: String| {
+            let indexify_state = indexify_state.clone();
+            let cg = cg.clone();
+            let invocation_id = invocation_id.clone();
+            async move {
+                let task = create_mock_task(&cg, compute_fn, &input_key, &invocation_id);
+                indexify_state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                            task_requests: vec![CreateTasksRequest {
+                                namespace: TEST_NAMESPACE.to_string(),
+                                compute_graph: cg.name.clone(),
+                                invocation_id: invocation_id.clone(),
+                                tasks: vec![task.clone()],
+                            }],
+                            allocations: vec![],
+                            reduction_tasks: ReductionTasks::default(),
+                            diagnostic_msgs: vec![],
+                        }),
+                        state_changes_processed: vec![],
+                    })
+                    .await?;
+                let output = NodeOutputBuilder::default()
+                    .namespace(TEST_NAMESPACE.to_string())
+                    .compute_graph_name(cg.name.clone())
+                    .compute_fn_name(compute_fn.to_string())
+                    .invocation_id(invocation_id.clone())
+                    .payload(OutputPayload::Fn(DataPayload {
+                        path: format!("{}_output", compute_fn),
+                        size: 1,
+                        sha256_hash: "hash".to_string(),
+                    }))
+                    .build()?;
+                indexify_state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph: cg.name.clone(),
+                            compute_fn: compute_fn.to_string(),
+                            invocation_id: invocation_id.clone(),
+                            task_id: task.id.clone(),
+                            node_outputs: vec![output.clone()],
+                            task_outcome: TaskOutcome::Success,
+                            executor_id: ExecutorId::new("executor1".to_string()),
+                            diagnostics: None,
+                        }),
+                        state_changes_processed: vec![],
+                    })
+                    .await?;
+                anyhow::Ok(output)
+            }
+        };
+        // The input goes through fn_a, whose output goes through fn_b.
+        let output_a = run_task("fn_a", invocation_id.clone()).await?;
+        let output_b = run_task("fn_b", output_a.key(&invocation_id)).await?;
+
+        let reader = indexify_state.reader();
+        let lineage = reader
+            .output_lineage(TEST_NAMESPACE, &cg.name, &output_b.id)?
+            .unwrap();
+        assert_eq!(lineage.input.id, invocation_id);
+        let chain: Vec<_> = lineage
+            .outputs
+            .iter()
+            .map(|output| (output.compute_fn_name.as_str(), output.id.as_str()))
+            .collect();
+        assert_eq!(
+            chain,
+            vec![
+                ("fn_a", output_a.id.as_str()),
+                ("fn_b", output_b.id.as_str())
+            ]
+        );
+        assert!(lineage.complete);
+
+        let lineage = reader
+            .output_lineage(TEST_NAMESPACE, &cg.name, &output_a.id)?
+            .unwrap();
+        assert_eq!(lineage.outputs.len(), 1);
+        assert!(lineage.complete);
+        assert!(reader
+            .output_lineage(TEST_NAMESPACE, &cg.name, "unknown")?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transaction_lock_timeout() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("state"),
+            IndexifyStateOptions {
+                transaction_lock_timeout: Some(Duration::from_millis(10)),
+                deadlock_detect: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        let cf = IndexifyObjectsColumns::Namespaces.cf_db(&indexify_state.db);
+        let first = indexify_state.transaction();
+        first.get_for_update_cf(&cf, TEST_NAMESPACE, true)?;
+
+        // The second transaction gives up on the lock held by the first one.
+        let second = indexify_state.transaction();
+        let err = second
+            .get_for_update_cf(&cf, TEST_NAMESPACE, true)
+            .unwrap_err();
+        assert_eq!(err.kind(), rocksdb::ErrorKind::TimedOut);
+
+        second.rollback()?;
+        first.commit()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_retries_transient_errors() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("state"),
+            IndexifyStateOptions {
+                transaction_lock_timeout: Some(Duration::from_millis(10)),
+                write_retries: 3,
+                ..Default::default()
+            },
+        )
+        .await?;
+        let create_namespace = || StateMachineUpdateRequest {
+            payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                name: TEST_NAMESPACE.to_string(),
+                feature_flags: Default::default(),
+            }),
+            state_changes_processed: vec![],
+        };
+        let cf = IndexifyObjectsColumns::Namespaces.cf_db(&indexify_state.db);
+
+        // The write times out on the lock while it's held and succeeds on a
+        // retry once it's released.
+        let lock = indexify_state.transaction();
+        lock.get_for_update_cf(&cf, TEST_NAMESPACE, true)?;
+        let write = tokio::spawn({
+            let indexify_state = indexify_state.clone();
+            async move { indexify_state.write(create_namespace()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        lock.rollback()?;
+        drop(lock);
+        write.await??;
+        assert!(indexify_state
+            .reader()
+            .get_namespace(TEST_NAMESPACE)?
+            .is_some());
+
+        // A lock held for longer than all the retries fails the write with a
+        // retryable error.
+        let lock = indexify_state.transaction();
+        lock.get_for_update_cf(&cf, TEST_NAMESPACE, true)?;
+        let err = indexify_state.write(create_namespace()).await.unwrap_err();
+        let transient = err.downcast_ref::<TransientWriteError>().unwrap();
+        assert_eq!(transient.attempts, 4);
+        assert_eq!(transient.source.kind(), rocksdb::ErrorKind::TimedOut);
+        lock.rollback()?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retried_writes_apply_effects_once() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("state"),
+            IndexifyStateOptions {
+                transaction_lock_timeout: Some(Duration::from_millis(10)),
+                write_retries: 3,
+                ..Default::default()
+            },
+        )
+        .await?;
+        let cg = mock_graph_a();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.clone(),
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation_payload = InvocationPayloadBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name(cg.name.clone())
+            .payload(DataPayload {
+                path: "test".to_string(),
+                size: 23,
+                sha256_hash: "hash1232".to_string(),
+            })
+            .build()?;
+        let invocation_id = invocation_payload.id.clone();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: cg.name.clone(),
+                    invocation_payload,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        // Each write fails once on a lock taken after its effects are
+        // recorded, and succeeds on its retry.
+        let executor = mock_executor();
+        let task = create_mock_task(&cg, "fn_a", "", &invocation_id);
+        let lock = indexify_state.transaction();
+        lock.get_for_update_cf(
+            &IndexifyObjectsColumns::Executors.cf_db(&indexify_state.db),
+            executor.key(),
+            true,
+        )?;
+        lock.get_for_update_cf(
+            &IndexifyObjectsColumns::TaskAllocations.cf_db(&indexify_state.db),
+            task.make_allocation_key(&executor.id),
+            true,
+        )?;
+        let mut events = indexify_state.task_event_stream();
+        let writes = tokio::spawn({
+            let indexify_state = indexify_state.clone();
+            let executor = executor.clone();
+            async move {
+                let register = indexify_state.write(StateMachineUpdateRequest {
+                    payload: RequestPayload::RegisterExecutor(RegisterExecutorRequest {
+                        executor: executor.clone(),
+                    }),
+                    state_changes_processed: vec![],
+                });
+                let schedule = indexify_state.write(StateMachineUpdateRequest {
+                    payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                        task_requests: vec![CreateTasksRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph: cg.name.clone(),
+                            invocation_id,
+                            tasks: vec![],
+                        }],
+                        allocations: vec![TaskPlacement {
+                            task,
+                            executor: executor.id,
+                        }],
+                        reduction_tasks: ReductionTasks::default(),
+                        diagnostic_msgs: vec![],
+                    }),
+                    state_changes_processed: vec![],
+                });
+                let (register, schedule) = tokio::join!(register, schedule);
+                register.and(schedule)
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(15)).await;
+        lock.rollback()?;
+        drop(lock);
+        writes.await??;
+
+        assert_eq!(
+            indexify_state
+                .executor_states
+                .read()
+                .await
+                .get(&executor.id)
+                .unwrap()
+                .num_registered,
+            1
+        );
+        let mut finished = 0;
+        while let Ok(ev) = events.try_recv() {
+            if matches!(ev, InvocationStateChangeEvent::InvocationFinished(_)) {
+                finished += 1;
+            }
+        }
+        assert_eq!(finished, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_namespace_graphs_digest() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let reader = indexify_state.reader();
+
+        let empty = reader.namespace_graphs_digest(TEST_NAMESPACE)?;
+        for compute_graph in [mock_graph_a(), mock_graph_b()] {
+            let before = reader.namespace_graphs_digest(TEST_NAMESPACE)?;
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+            let after = reader.namespace_graphs_digest(TEST_NAMESPACE)?;
+            assert_ne!(before, after);
+            assert_eq!(after, reader.namespace_graphs_digest(TEST_NAMESPACE)?);
+        }
+
+        // Graphs of other namespaces don't contribute to the digest.
+        assert_eq!(reader.namespace_graphs_digest("other")?, empty);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_diff_namespaces() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let graph = |namespace: &str, name: &str, description: &str| {
+            let mut compute_graph = mock_graph_a();
+            compute_graph.namespace = namespace.to_string();
+            compute_graph.name = name.to_string();
+            compute_graph.description = description.to_string();
+            compute_graph
+        };
+        for compute_graph in [
+            graph("staging", "same", "same"),
+            graph("staging", "changed", "new"),
+            graph("staging", "staging_only", "new"),
+            graph("prod", "same", "same"),
+            graph("prod", "changed", "old"),
+            graph("prod", "prod_only", "old"),
+            graph("staging_2", "other", "new"),
+        ] {
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: compute_graph.namespace.clone(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        // Graphs created at different times in different namespaces compare
+        // equal when they run the same thing.
+        let diff = indexify_state.reader().diff_namespaces("staging", "prod")?;
+        assert_eq!(
+            diff,
+            NamespaceDiff {
+                only_in_a: vec!["staging_only".to_string()],
+                only_in_b: vec!["prod_only".to_string()],
+                changed: vec!["changed".to_string()],
+            }
+        );
+
+        let diff = indexify_state.reader().diff_namespaces("prod", "empty")?;
+        assert_eq!(diff.only_in_a, vec!["changed", "prod_only", "same"]);
+        assert!(diff.only_in_b.is_empty() && diff.changed.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gc_orphan_blobs() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let blob_storage = BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().join("blobs").to_str().unwrap(),
+        ))?;
+
+        let mut urls = vec![];
+        for key in ["cg_code", "orphan"] {
+            let put = blob_storage
+                .put(
+                    key,
+                    futures::stream::iter(vec![Ok(Bytes::from_static(b"data"))]),
+                )
+                .await?;
+            urls.push(put.url);
+        }
+        let mut compute_graph = mock_graph_a();
+        compute_graph.code.path = urls[0].clone();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph,
+                    if_match: None,
+                    if_changed: false,
+                })),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        // Blobs within the safety window are left alone.
+        let report = indexify_state
+            .gc_orphan_blobs(&blob_storage, ORPHAN_BLOB_MIN_AGE, false)
+            .await?;
+        assert_eq!(report.scanned, 2);
+        assert!(report.orphans.is_empty());
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let report = indexify_state
+            .gc_orphan_blobs(&blob_storage, Duration::ZERO, true)
+            .await?;
+        assert_eq!(report.orphans, vec![urls[1].clone()]);
+        assert!(report.deleted.is_empty());
+        assert!(blob_storage.exists(&urls[1]).await?);
+
+        let report = indexify_state
+            .gc_orphan_blobs(&blob_storage, Duration::ZERO, false)
+            .await?;
+        assert_eq!(report.deleted, vec![urls[1].clone()]);
+        assert!(!blob_storage.exists(&urls[1]).await?);
+        assert!(blob_storage.exists(&urls[0]).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_group_commit() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new_with_options(
+            temp_dir.path().join("state"),
+            IndexifyStateOptions {
+                group_commit_window: Some(Duration::from_millis(20)),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        // Every third write is conditioned on an etag which can't match and
+        // must fail without affecting the others in its batch.
+        let writes = (0..60).map(|i| {
+            let indexify_state = indexify_state.clone();
+            async move {
+                let mut compute_graph = mock_graph_a();
+                compute_graph.name = format!("graph_{}", i);
+                indexify_state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::CreateComputeGraph(Box::new(
+                            CreateComputeGraphRequest {
+                                namespace: TEST_NAMESPACE.to_string(),
+                                compute_graph,
+                                if_match: (i % 3 == 0).then(|| vec!["stale".to_string()]),
+                                if_changed: false,
+                            },
+                        )),
+                        state_changes_processed: vec![],
+                    })
+                    .await
+            }
+        });
+        let results = futures::future::join_all(writes).await;
+
+        let reader = indexify_state.reader();
+        for (i, result) in results.into_iter().enumerate() {
+            let stored = reader.get_compute_graph(TEST_NAMESPACE, &format!("graph_{}", i))?;
+            if i % 3 == 0 {
+                let err = result.unwrap_err();
+                assert!(err.downcast_ref::<ComputeGraphEtagMismatch>().is_some());
+                assert!(stored.is_none());
+            } else {
+                result?;
+                assert!(stored.is_some());
+            }
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,348 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, Read, Write},
+};
+
+use anyhow::{anyhow, Context, Result};
+use data_model::Namespace;
+use strum::IntoEnumIterator;
+
+use crate::{
+    scanner::row_blob_urls,
+    serializer::{JsonEncode, JsonEncoder},
+    state_machine::{self, IndexifyObjectsColumns},
+    IndexifyState,
+    REINDEX_BATCH_SIZE,
+};
+
+/// First bytes of a namespace export, followed by the name of the namespace
+/// and its rows. Every name, key and value is written as its length in four
+/// big endian bytes followed by its bytes.
+const EXPORT_MAGIC: &[u8] = b"INDEXIFY-NAMESPACE-1\n";
+
+/// Number of rows written in one transaction by an import.
+const IMPORT_BATCH_SIZE: usize = 500;
+
+/// Rows and blobs of a namespace written by `export_namespace_to`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NamespaceExport {
+    pub rows: usize,
+    /// Urls of the blobs referenced by the rows. The blobs themselves aren't
+    /// exported, they must be reachable from the importing server.
+    pub blob_urls: BTreeSet<String>,
+}
+
+/// Rows of a namespace written by `import_namespace_from`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NamespaceImport {
+    pub namespace: String,
+    pub rows: usize,
+}
+
+/// Whether the rows of a namespace in the column family are exported. Task
+/// allocations and the unallocated tasks index are left out and rebuilt on
+/// import, executors of the importing server run the pending tasks.
+fn exported_column(column: &IndexifyObjectsColumns) -> bool {
+    column.keyed_by_namespace() || matches!(column, IndexifyObjectsColumns::Namespaces)
+}
+
+fn write_frame(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    writer.write_all(bytes)
+}
+
+/// Reads a frame, or returns None when the reader is at its end.
+fn read_frame(reader: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    let mut read = 0;
+    while read < len.len() {
+        match reader.read(&mut len[read..])? {
+            0 if read == 0 => return Ok(None),
+            0 => return Err(anyhow!("namespace export is truncated")),
+            n => read += n,
+        }
+    }
+    let mut bytes = vec![0u8; u32::from_be_bytes(len) as usize];
+    reader
+        .read_exact(&mut bytes)
+        .context("namespace export is truncated")?;
+    Ok(Some(bytes))
+}
+
+fn read_required_frame(reader: &mut impl Read) -> Result<Vec<u8>> {
+    read_frame(reader)?.ok_or_else(|| anyhow!("namespace export is truncated"))
+}
+
+impl IndexifyState {
+    /// Writes every row of the namespace to `writer`, read from a snapshot
+    /// of the state store, for `import_namespace_from` to apply to another
+    /// state store. The namespace is left untouched.
+    pub fn export_namespace_to(
+        &self,
+        namespace: &str,
+        writer: &mut impl Write,
+    ) -> Result<NamespaceExport> {
+        let reader = self.snapshot_reader();
+        let row = reader
+            .get_namespace(namespace)?
+            .ok_or_else(|| anyhow!("namespace {} not found", namespace))?;
+        writer.write_all(EXPORT_MAGIC)?;
+        write_frame(writer, namespace.as_bytes())?;
+        let namespaces = IndexifyObjectsColumns::Namespaces;
+        write_frame(writer, namespaces.as_ref().as_bytes())?;
+        write_frame(writer, namespace.as_bytes())?;
+        write_frame(writer, &JsonEncoder::encode(&row)?)?;
+
+        let mut export = NamespaceExport {
+            rows: 1,
+            ..Default::default()
+        };
+        let prefix = format!("{}|", namespace);
+        for column in IndexifyObjectsColumns::iter().filter(|column| column.keyed_by_namespace()) {
+            reader.for_each_row_with_prefix(&column, prefix.as_bytes(), |key, value| {
+                write_frame(writer, column.as_ref().as_bytes())?;
+                write_frame(writer, key)?;
+                write_frame(writer, value)?;
+                export.blob_urls.extend(row_blob_urls(&column, value)?);
+                export.rows += 1;
+                Ok(())
+            })?;
+        }
+        writer.flush()?;
+        Ok(export)
+    }
+
+    /// Applies a namespace written by `export_namespace_to`. The namespace
+    /// must not exist yet. Rows are written in batched transactions and the
+    /// namespace itself last, so an interrupted import leaves no namespace
+    /// behind and can be run again.
+    pub fn import_namespace_from(&self, reader: &mut impl Read) -> Result<NamespaceImport> {
+        let mut magic = vec![0u8; EXPORT_MAGIC.len()];
+        reader
+            .read_exact(&mut magic)
+            .context("not a namespace export")?;
+        if magic != EXPORT_MAGIC {
+            return Err(anyhow!("not a namespace export"));
+        }
+        let namespace = String::from_utf8(read_required_frame(reader)?)?;
+        if self.reader().get_namespace(&namespace)?.is_some() {
+            return Err(anyhow!("namespace {} already exists", namespace));
+        }
+
+        let prefix = format!("{}|", namespace);
+        let mut import = NamespaceImport { namespace, rows: 0 };
+        let mut namespace_row = None;
+        let mut task_keys = Vec::new();
+        let mut batch = Vec::new();
+        while let Some(name) = read_frame(reader)? {
+            let key = read_required_frame(reader)?;
+            let value = read_required_frame(reader)?;
+            let column = IndexifyObjectsColumns::iter()
+                .find(|column| column.as_ref().as_bytes() == name)
+                .filter(exported_column)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "unexpected column family {}",
+                        String::from_utf8_lossy(&name)
+                    )
+                })?;
+            if let IndexifyObjectsColumns::Namespaces = column {
+                if key != import.namespace.as_bytes() {
+                    return Err(anyhow!(
+                        "unexpected namespace {}",
+                        String::from_utf8_lossy(&key)
+                    ));
+                }
+                namespace_row = Some(value);
+                continue;
+            }
+            if !key.starts_with(prefix.as_bytes()) {
+                return Err(anyhow!(
+                    "row {} of {} isn't in namespace {}",
+                    String::from_utf8_lossy(&key),
+                    column,
+                    import.namespace
+                ));
+            }
+            if let IndexifyObjectsColumns::Tasks = column {
+                task_keys.push(key.clone());
+            }
+            batch.push((column, key, value));
+            if batch.len() >= IMPORT_BATCH_SIZE {
+                import.rows += self.put_rows(&mut batch)?;
+            }
+        }
+        let namespace_row = namespace_row
+            .ok_or_else(|| anyhow!("namespace export of {} is incomplete", import.namespace))?;
+        import.rows += self.put_rows(&mut batch)?;
+
+        for task_keys in task_keys.chunks(REINDEX_BATCH_SIZE) {
+            let txn = self.transaction();
+            state_machine::reindex_unallocated_tasks(self.db.clone(), &txn, task_keys)?;
+            txn.commit()?;
+        }
+
+        let txn = self.transaction();
+        let namespaces = IndexifyObjectsColumns::Namespaces.cf_db(&self.db);
+        if txn
+            .get_for_update_cf(&namespaces, &import.namespace, true)?
+            .is_some()
+        {
+            return Err(anyhow!("namespace {} already exists", import.namespace));
+        }
+        state_machine::count_new_namespace(&self.db, &txn, self.max_namespaces)?;
+        let creation_index_key =
+            JsonEncoder::decode::<Namespace>(&namespace_row)?.creation_index_key();
+        txn.put_cf(&namespaces, &import.namespace, namespace_row)?;
+        txn.put_cf(
+            &IndexifyObjectsColumns::NamespaceCreations.cf_db(&self.db),
+            creation_index_key,
+            [],
+        )?;
+        txn.commit()?;
+        import.rows += 1;
+        Ok(import)
+    }
+
+    fn put_rows(
+        &self,
+        rows: &mut Vec<(IndexifyObjectsColumns, Vec<u8>, Vec<u8>)>,
+    ) -> Result<usize> {
+        let count = rows.len();
+        let txn = self.transaction();
+        for (column, key, value) in rows.drain(..) {
+            txn.put_cf(&column.cf_db(&self.db), key, value)?;
+        }
+        txn.commit()?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        test_objects::tests::{
+            create_mock_task,
+            mock_graph_a,
+            mock_graph_b,
+            mock_invocation_payload,
+            TEST_NAMESPACE,
+        },
+        ExecutorId,
+    };
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::requests::{
+        CreateComputeGraphRequest,
+        CreateTasksRequest,
+        InvokeComputeGraphRequest,
+        NamespaceRequest,
+        ReductionTasks,
+        RequestPayload,
+        SchedulerUpdateRequest,
+        StateMachineUpdateRequest,
+        TaskPlacement,
+    };
+
+    async fn write(state: &IndexifyState, payload: RequestPayload) -> Result<()> {
+        state
+            .write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_namespace() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let source = IndexifyState::new(temp_dir.path().join("source")).await?;
+        for namespace in [TEST_NAMESPACE, "other"] {
+            source.upsert_namespace(&NamespaceRequest {
+                name: namespace.to_string(),
+                feature_flags: Default::default(),
+            })?;
+        }
+        let mut other_graph = mock_graph_a();
+        other_graph.namespace = "other".to_string();
+        for compute_graph in [mock_graph_a(), mock_graph_b(), other_graph] {
+            let payload = RequestPayload::CreateComputeGraph(Box::new(CreateComputeGraphRequest {
+                namespace: compute_graph.namespace.clone(),
+                compute_graph,
+                if_match: None,
+                if_changed: false,
+            }));
+            write(&source, payload).await?;
+        }
+        let invocation = mock_invocation_payload();
+        let payload = RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph_name: "graph_A".to_string(),
+            invocation_payload: invocation.clone(),
+        });
+        write(&source, payload).await?;
+        // The task is allocated to an executor of the source only.
+        let task = create_mock_task(&mock_graph_a(), "fn_a", "key", &invocation.id);
+        let payload = RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![CreateTasksRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: "graph_A".to_string(),
+                invocation_id: invocation.id.clone(),
+                tasks: vec![task.clone()],
+            }],
+            allocations: vec![TaskPlacement {
+                task: task.clone(),
+                executor: ExecutorId::new("executor".to_string()),
+            }],
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+        });
+        write(&source, payload).await?;
+
+        let mut export = Vec::new();
+        let exported = source.export_namespace_to(TEST_NAMESPACE, &mut export)?;
+        assert!(exported.blob_urls.contains("cg_path"));
+        assert!(exported.blob_urls.contains(&invocation.payload.path));
+
+        let target = IndexifyState::new(temp_dir.path().join("target")).await?;
+        let imported = target.import_namespace_from(&mut export.as_slice())?;
+        assert_eq!(imported.namespace, TEST_NAMESPACE);
+        assert_eq!(imported.rows, exported.rows);
+
+        let (source_graphs, _) = source
+            .reader()
+            .list_compute_graphs(TEST_NAMESPACE, None, None)?;
+        let (target_graphs, _) = target
+            .reader()
+            .list_compute_graphs(TEST_NAMESPACE, None, None)?;
+        assert_eq!(source_graphs.len(), 2);
+        assert_eq!(target_graphs, source_graphs);
+        assert_eq!(
+            JsonEncoder::encode(&target.reader().get_namespace(TEST_NAMESPACE)?)?,
+            JsonEncoder::encode(&source.reader().get_namespace(TEST_NAMESPACE)?)?
+        );
+        let (invocations, _) =
+            target
+                .reader()
+                .list_invocations(TEST_NAMESPACE, "graph_A", None, None)?;
+        assert_eq!(invocations, vec![invocation]);
+        assert!(target.reader().get_namespace("other")?.is_none());
+        // The task is pending on the target, waiting for one of its executors.
+        let unallocated = target.reader().unallocated_tasks()?;
+        assert_eq!(unallocated.len(), 1);
+        assert_eq!(unallocated[0].id, task.id);
+
+        let err = target
+            .import_namespace_from(&mut export.as_slice())
+            .unwrap_err();
+        assert!(err.to_string().contains("already exists"));
+        let other = IndexifyState::new(temp_dir.path().join("other")).await?;
+        assert!(other
+            .import_namespace_from(&mut &export[..export.len() - 1])
+            .is_err());
+        assert!(other.reader().get_namespace(TEST_NAMESPACE)?.is_none());
+
+        Ok(())
+    }
+}
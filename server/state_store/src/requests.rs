@@ -1,11 +1,16 @@
+use std::collections::BTreeMap;
+
 use data_model::{
     ComputeGraph,
+    ComputeGraphCode,
+    DeadLetteredChangeEvent,
     ExecutorId,
     ExecutorMetadata,
     GraphVersion,
     InvocationPayload,
     NodeOutput,
     ReduceTask,
+    RetentionPolicy,
     StateChangeId,
     Task,
     TaskDiagnostics,
@@ -23,17 +28,58 @@ pub enum RequestPayload {
     RerunInvocation(RerunInvocationRequest),
     FinalizeTask(FinalizeTaskRequest),
     CreateNameSpace(NamespaceRequest),
-    CreateComputeGraph(CreateComputeGraphRequest),
+    DeleteNamespace(DeleteNamespaceRequest),
+    CreateComputeGraph(Box<CreateComputeGraphRequest>),
     DeleteComputeGraph(DeleteComputeGraphRequest),
+    SetComputeGraphEnabled(SetComputeGraphEnabledRequest),
+    SetComputeGraphFrozen(SetComputeGraphFrozenRequest),
+    SwapComputeGraphCode(SwapComputeGraphCodeRequest),
+    SetNamespacePolicy(SetNamespacePolicyRequest),
+    SetNamespaceFlags(SetNamespaceFlagsRequest),
     DeleteInvocation(DeleteInvocationRequest),
+    AttachOutput(AttachOutputRequest),
     SchedulerUpdate(SchedulerUpdateRequest),
     RegisterExecutor(RegisterExecutorRequest),
     DeregisterExecutor(DeregisterExecutorRequest),
     RemoveGcUrls(Vec<String>),
+    RemoveChangeEvents(Vec<u64>),
+    DeadLetterChangeEvents(Vec<DeadLetteredChangeEvent>),
     UpdateSystemTask(UpdateSystemTaskRequest),
     RemoveSystemTask(RemoveSystemTaskRequest),
 }
 
+#[derive(Debug, Clone)]
+pub struct SetComputeGraphEnabledRequest {
+    pub namespace: String,
+    pub name: String,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SetComputeGraphFrozenRequest {
+    pub namespace: String,
+    pub name: String,
+    pub frozen: bool,
+}
+
+/// Replaces the code of an existing compute graph, leaving its definition as
+/// it is.
+#[derive(Debug, Clone)]
+pub struct SwapComputeGraphCodeRequest {
+    pub namespace: String,
+    pub name: String,
+    pub code: ComputeGraphCode,
+    /// Queue the previous code blob for deletion once the swap is committed.
+    pub delete_previous: bool,
+}
+
+/// Replaces the retention policy of an existing namespace.
+#[derive(Debug, Clone)]
+pub struct SetNamespacePolicyRequest {
+    pub namespace: String,
+    pub retention: RetentionPolicy,
+}
+
 #[derive(Debug, Clone)]
 pub struct UpdateSystemTaskRequest {
     pub namespace: String,
@@ -68,6 +114,15 @@ pub struct FinalizeTaskRequest {
     pub diagnostics: Option<TaskDiagnostics>,
 }
 
+/// Stores an output computed outside of Indexify for an existing invocation.
+#[derive(Debug, Clone)]
+pub struct AttachOutputRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub output: NodeOutput,
+}
+
 pub struct InvokeComputeGraphRequest {
     pub namespace: String,
     pub compute_graph_name: String,
@@ -82,16 +137,41 @@ pub struct RerunComputeGraphRequest {
 
 pub struct NamespaceRequest {
     pub name: String,
+    pub feature_flags: BTreeMap<String, bool>,
+}
+
+/// Sets the given feature flags of an existing namespace, leaving the others
+/// as they are.
+#[derive(Debug, Clone)]
+pub struct SetNamespaceFlagsRequest {
+    pub name: String,
+    pub feature_flags: BTreeMap<String, bool>,
 }
 
 pub struct CreateComputeGraphRequest {
     pub namespace: String,
     pub compute_graph: ComputeGraph,
+    /// When set, the graph is only written if the etag of the stored graph
+    /// is still one of these.
+    pub if_match: Option<Vec<String>>,
+    /// When set, a stored graph with the same content is left as it is and
+    /// the write fails with `ComputeGraphUnchanged`.
+    pub if_changed: bool,
+}
+
+pub struct DeleteNamespaceRequest {
+    pub name: String,
+    /// Delete the compute graphs of the namespace along with it. Namespaces
+    /// with compute graphs aren't deleted otherwise.
+    pub force: bool,
 }
 
 pub struct DeleteComputeGraphRequest {
     pub namespace: String,
     pub name: String,
+    /// When set, the graph is only deleted if the etag of the stored graph
+    /// is still one of these.
+    pub if_match: Option<Vec<String>>,
 }
 
 pub struct DeleteComputeGraphOutputRequest {
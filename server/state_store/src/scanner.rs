@@ -1,27 +1,61 @@
-use std::{mem, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    mem,
+    ops::Deref,
+    sync::Arc,
+};
 
 use anyhow::{anyhow, Result};
+use bytes::Bytes;
 use data_model::{
+    ChangeEvent,
     ComputeGraph,
     DataPayload,
+    DeadLetteredChangeEvent,
     ExecutorId,
     ExecutorMetadata,
+    FailedOutput,
     GraphInvocationCtx,
     InvocationPayload,
+    InvocationStatus,
     Namespace,
     NodeOutput,
+    OutputPayload,
     ReduceTask,
     StateChange,
     SystemTask,
     Task,
     TaskAnalytics,
+    TaskDiagnostics,
     TaskFinishedEvent,
 };
-use rocksdb::{Direction, IteratorMode, ReadOptions, TransactionDB};
+use rocksdb::{
+    AsColumnFamilyRef,
+    Direction,
+    IteratorMode,
+    Options,
+    ReadOptions,
+    SnapshotWithThreadMode,
+    TransactionDB,
+    DB,
+};
 use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
 
-use super::state_machine::IndexifyObjectsColumns;
-use crate::serializer::{JsonEncode, JsonEncoder};
+use super::state_machine::{
+    compute_graph_content_hash,
+    compute_graph_etag,
+    IndexifyObjectsColumns,
+    LAST_FLUSH_KEY,
+    NAMESPACE_COUNT_KEY,
+    SCHEMA_VERSION_KEY,
+};
+use crate::{
+    key_prefix::{iterator_mode, KeyPrefix, Row},
+    serializer::{JsonEncode, JsonEncoder},
+    LiveFileInfo,
+    PrefixExtractor,
+};
 #[derive(Debug)]
 pub struct FilterResponse<T> {
     pub items: Vec<T>,
@@ -29,13 +63,268 @@ pub struct FilterResponse<T> {
     pub cursor: Vec<u8>,
 }
 
-pub struct StateReader {
+pub type InvocationWithStatus = (InvocationPayload, InvocationStatus);
+
+/// A namespace and its number of compute graphs.
+pub type NamespaceWithCount = (Namespace, usize);
+
+/// Bytes of blobs referenced by the records of a namespace, as recorded in
+/// the state store.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NamespaceBlobUsage {
+    pub total_bytes: u64,
+    pub compute_graph_code_bytes: u64,
+    pub invocation_payload_bytes: u64,
+}
+
+/// Largest number of invocations read by `StateReader::global_stats`.
+pub const GLOBAL_STATS_INVOCATION_SCAN_LIMIT: usize = 100_000;
+
+/// Summary of every namespace of the server.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GlobalStats {
+    pub namespaces: usize,
+    pub compute_graphs: usize,
+    /// Number of compute graphs of every namespace, including empty ones.
+    pub compute_graphs_per_namespace: BTreeMap<String, usize>,
+    pub blob_usage: NamespaceBlobUsage,
+    pub invocations: usize,
+    /// Whether `invocations` and the invocation payload bytes are exact.
+    /// Only the first `GLOBAL_STATS_INVOCATION_SCAN_LIMIT` invocations are
+    /// read, when there are more both are lower bounds.
+    pub invocations_exact: bool,
+}
+
+/// Provenance of an output, from the invocation input to the output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lineage {
+    pub input: InvocationPayload,
+    /// The outputs leading to the requested output, the output of the start
+    /// function first and the requested output last.
+    pub outputs: Vec<NodeOutput>,
+    /// Whether the chain reaches an output of the start function. It stops
+    /// short at outputs written before their parent was recorded, or whose
+    /// parent was deleted.
+    pub complete: bool,
+}
+
+/// Differences between the compute graphs of two namespaces, by graph name
+/// in name order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NamespaceDiff {
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    /// Graphs in both namespaces whose content hashes differ.
+    pub changed: Vec<String>,
+}
+
+/// Metadata of the code blob of a compute graph, as recorded in the state
+/// store.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeMetadata {
+    pub url: String,
+    pub size_bytes: u64,
+    pub sha256: String,
+    pub content_type: String,
+}
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Width of the buckets of a histogram. Buckets are aligned to the Unix
+/// epoch, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bucket {
+    Day,
+    Week,
+}
+
+impl Bucket {
+    pub fn width_ms(&self) -> u64 {
+        match self {
+            Bucket::Day => DAY_MS,
+            Bucket::Week => 7 * DAY_MS,
+        }
+    }
+}
+
+/// Direction of a sorted listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+fn diagnostics_paths(diagnostics: Option<TaskDiagnostics>) -> impl Iterator<Item = String> {
+    diagnostics
+        .into_iter()
+        .flat_map(|d| [d.exception, d.stdout, d.stderr])
+        .flatten()
+        .map(|payload| payload.path)
+}
+
+/// Urls of the blobs referenced by a row of `column`.
+pub(crate) fn row_blob_urls(column: &IndexifyObjectsColumns, value: &[u8]) -> Result<Vec<String>> {
+    let urls = match column {
+        IndexifyObjectsColumns::ComputeGraphs => {
+            vec![JsonEncoder::decode::<ComputeGraph>(value)?.code.path]
+        }
+        IndexifyObjectsColumns::GraphInvocations => {
+            vec![
+                JsonEncoder::decode::<InvocationPayload>(value)?
+                    .payload
+                    .path,
+            ]
+        }
+        IndexifyObjectsColumns::FnOutputs => {
+            let output: NodeOutput = JsonEncoder::decode(value)?;
+            let mut urls = Vec::new();
+            if let OutputPayload::Fn(payload) = output.payload {
+                urls.push(payload.path);
+            }
+            urls.extend(output.errors.map(|e| e.path));
+            urls
+        }
+        IndexifyObjectsColumns::Tasks => {
+            let task: Task = JsonEncoder::decode(value)?;
+            diagnostics_paths(task.diagnostics).collect()
+        }
+        IndexifyObjectsColumns::FailedOutputs => {
+            let failure: FailedOutput = JsonEncoder::decode(value)?;
+            failure
+                .errors
+                .map(|e| e.path)
+                .into_iter()
+                .chain(diagnostics_paths(failure.diagnostics))
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+    Ok(urls)
+}
+
+/// Returns the smallest key greater than all the keys starting with `prefix`,
+/// or None if there is no such key because the prefix is all 0xff bytes.
+pub(crate) fn prefix_upper_bound(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut upper_bound = prefix.to_vec();
+    while let Some(last) = upper_bound.pop() {
+        if last < u8::MAX {
+            upper_bound.push(last + 1);
+            return Some(upper_bound);
+        }
+    }
+    None
+}
+
+pub struct StateReader<'a> {
     db: Arc<TransactionDB>,
+    snapshot: Option<SnapshotWithThreadMode<'a, TransactionDB>>,
+    prefix_extractor: PrefixExtractor,
+    key_prefix: KeyPrefix,
 }
 
-impl StateReader {
-    pub fn new(db: Arc<TransactionDB>) -> Self {
-        Self { db }
+/// A `StateReader` whose reads all see the state store as it was when the
+/// reader was created, whatever is written afterwards. The snapshot borrows
+/// the database it was taken from and is released when the reader is
+/// dropped.
+pub struct SnapshotReader<'a> {
+    reader: StateReader<'a>,
+}
+
+impl<'a> SnapshotReader<'a> {
+    pub fn new(
+        db: &'a Arc<TransactionDB>,
+        prefix_extractor: PrefixExtractor,
+        key_prefix: KeyPrefix,
+    ) -> Self {
+        Self {
+            reader: StateReader {
+                db: db.clone(),
+                snapshot: Some(db.snapshot()),
+                prefix_extractor,
+                key_prefix,
+            },
+        }
+    }
+}
+
+impl<'a> Deref for SnapshotReader<'a> {
+    type Target = StateReader<'a>;
+
+    fn deref(&self) -> &StateReader<'a> {
+        &self.reader
+    }
+}
+
+impl<'s> StateReader<'s> {
+    pub fn new(
+        db: Arc<TransactionDB>,
+        prefix_extractor: PrefixExtractor,
+        key_prefix: KeyPrefix,
+    ) -> Self {
+        Self {
+            db,
+            snapshot: None,
+            prefix_extractor,
+            key_prefix,
+        }
+    }
+
+    /// Read options of every read, pinned to the snapshot of the reader if
+    /// it has one. Seeks are in total order, so scans crossing namespaces
+    /// aren't cut short by the prefix bloom filters.
+    fn read_options(&self) -> ReadOptions {
+        let mut read_options = ReadOptions::default();
+        read_options.set_total_order_seek(true);
+        if let Some(snapshot) = &self.snapshot {
+            read_options.set_snapshot(snapshot);
+        }
+        read_options
+    }
+
+    fn get_cf<K: AsRef<[u8]>>(
+        &self,
+        cf: &impl AsColumnFamilyRef,
+        key: K,
+    ) -> Result<Option<Vec<u8>>, rocksdb::Error> {
+        self.db
+            .get_cf_opt(cf, self.key_prefix.key(key.as_ref()), &self.read_options())
+    }
+
+    fn multi_get_cf<'b, K, I, W>(&self, keys: I) -> Vec<Result<Option<Vec<u8>>, rocksdb::Error>>
+    where
+        K: AsRef<[u8]>,
+        I: IntoIterator<Item = (&'b W, K)>,
+        W: 'b + AsColumnFamilyRef,
+    {
+        let keys: Vec<_> = keys
+            .into_iter()
+            .map(|(cf, key)| (cf, self.key_prefix.key(key.as_ref()).into_owned()))
+            .collect();
+        self.db.multi_get_cf_opt(keys, &self.read_options())
+    }
+
+    fn iterator_cf<'a>(
+        &'a self,
+        cf: &impl AsColumnFamilyRef,
+        mode: IteratorMode,
+    ) -> impl Iterator<Item = Result<Row, rocksdb::Error>> + 'a {
+        self.iterator_cf_opt(cf, self.read_options(), mode)
+    }
+
+    /// Iterates over the rows of the instance, with keys stripped of the key
+    /// prefix.
+    fn iterator_cf_opt<'a>(
+        &'a self,
+        cf: &impl AsColumnFamilyRef,
+        mut read_options: ReadOptions,
+        mode: IteratorMode,
+    ) -> impl Iterator<Item = Result<Row, rocksdb::Error>> + 'a {
+        self.key_prefix.bound(&mut read_options, &[]);
+        let seek_key = self.key_prefix.seek_key(&mode);
+        let iter = self
+            .db
+            .iterator_cf_opt(cf, read_options, iterator_mode(mode, &seek_key));
+        self.key_prefix.strip(iter)
     }
 
     pub fn get_rows_from_cf_multi_key<V>(
@@ -50,9 +339,10 @@ impl StateReader {
             .db
             .cf_handle(column.as_ref())
             .ok_or(anyhow::anyhow!("Failed to get column family {}", column))?;
+        let values = self.multi_get_cf(keys.iter().map(|key| (&cf_handle, key)));
         let mut items = Vec::new();
-        for key in keys {
-            let value = self.db.get_cf(&cf_handle, key)?.ok_or(anyhow::anyhow!(
+        for (key, value) in keys.iter().zip(values) {
+            let value = value?.ok_or(anyhow::anyhow!(
                 "Key not found {}",
                 String::from_utf8(key.to_vec()).unwrap_or_default()
             ))?;
@@ -62,6 +352,41 @@ impl StateReader {
         Ok(items)
     }
 
+    /// Iterates over the rows of a column family whose key starts with
+    /// `key_prefix`, starting at `restart_key` when paging.
+    ///
+    /// The iterator is bounded to the prefix with the iterate lower and upper
+    /// bounds, so RocksDB can skip the SST files and tombstones outside of it
+    /// instead of reading until the first key which doesn't match. When the
+    /// prefix covers the extracted prefix of the column family the seek is
+    /// in prefix mode, skipping the SST files whose prefix bloom filter
+    /// doesn't match.
+    fn scan_prefix<'a>(
+        &'a self,
+        cf_handle: &impl AsColumnFamilyRef,
+        column: &IndexifyObjectsColumns,
+        key_prefix: &[u8],
+        restart_key: Option<&[u8]>,
+    ) -> impl Iterator<Item = Result<Row, rocksdb::Error>> + 'a {
+        let mut read_options = self.read_options();
+        read_options.set_readahead_size(4_194_304);
+        if column.keyed_by_namespace() &&
+            self.prefix_extractor
+                .covers(&self.key_prefix.key(key_prefix))
+        {
+            read_options.set_total_order_seek(false);
+            read_options.set_prefix_same_as_start(true);
+        }
+        self.key_prefix.bound(&mut read_options, key_prefix);
+        let start = self.key_prefix.key(restart_key.unwrap_or(key_prefix));
+        let iter = self.db.iterator_cf_opt(
+            cf_handle,
+            read_options,
+            IteratorMode::From(&start, Direction::Forward),
+        );
+        self.key_prefix.strip(iter)
+    }
+
     pub fn get_raw_rows_from_cf_with_limits(
         &self,
         key_prefix: &[u8],
@@ -74,15 +399,7 @@ impl StateReader {
             .cf_handle(column.as_ref())
             .ok_or(anyhow::anyhow!("Failed to get column family {}", column))?;
 
-        let mut read_options = ReadOptions::default();
-        read_options.set_readahead_size(4_194_304);
-        let iterator_mode = match restart_key {
-            Some(restart_key) => IteratorMode::From(restart_key, Direction::Forward),
-            None => IteratorMode::From(&key_prefix, Direction::Forward),
-        };
-        let iter = self
-            .db
-            .iterator_cf_opt(&cf_handle, read_options, iterator_mode);
+        let iter = self.scan_prefix(&cf_handle, &column, key_prefix, restart_key);
 
         let mut items = Vec::new();
         let limit = limit.unwrap_or(usize::MAX);
@@ -111,21 +428,30 @@ impl StateReader {
     ) -> Result<(Vec<V>, Option<Vec<u8>>)>
     where
         V: DeserializeOwned,
+    {
+        self.get_filtered_rows_from_cf_with_limits(key_prefix, restart_key, column, limit, |_| true)
+    }
+
+    /// Like `get_rows_from_cf_with_limits` but only returns (and counts
+    /// towards the limit) the rows matching `filter`.
+    pub fn get_filtered_rows_from_cf_with_limits<V, F>(
+        &self,
+        key_prefix: &[u8],
+        restart_key: Option<&[u8]>,
+        column: IndexifyObjectsColumns,
+        limit: Option<usize>,
+        filter: F,
+    ) -> Result<(Vec<V>, Option<Vec<u8>>)>
+    where
+        V: DeserializeOwned,
+        F: Fn(&V) -> bool,
     {
         let cf_handle = self
             .db
             .cf_handle(column.as_ref())
             .ok_or(anyhow::anyhow!("Failed to get column family {}", column))?;
 
-        let mut read_options = ReadOptions::default();
-        read_options.set_readahead_size(4_194_304);
-        let iterator_mode = match restart_key {
-            Some(restart_key) => IteratorMode::From(restart_key, Direction::Forward),
-            None => IteratorMode::From(&key_prefix, Direction::Forward),
-        };
-        let iter = self
-            .db
-            .iterator_cf_opt(&cf_handle, read_options, iterator_mode);
+        let iter = self.scan_prefix(&cf_handle, &column, key_prefix, restart_key);
 
         let mut items = Vec::new();
         let limit = limit.unwrap_or(usize::MAX);
@@ -136,6 +462,9 @@ impl StateReader {
                 break;
             }
             let value = JsonEncoder::decode(&value).map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            if !filter(&value) {
+                continue;
+            }
             if items.len() < limit {
                 items.push(value);
             } else {
@@ -163,19 +492,7 @@ impl StateReader {
     {
         let index_cf = index_column.cf_db(&self.db);
         let data_cf = data_column.cf_db(&self.db);
-        let mut read_options = ReadOptions::default();
-        read_options.set_readahead_size(4_194_304);
-        let mode = match restart_key {
-            Some(restart_key) => IteratorMode::From(restart_key, Direction::Forward),
-            None => {
-                if key_prefix.is_empty() {
-                    IteratorMode::Start
-                } else {
-                    IteratorMode::From(key_prefix, Direction::Forward)
-                }
-            }
-        };
-        let iter = self.db.iterator_cf_opt(&index_cf, read_options, mode);
+        let iter = self.scan_prefix(&index_cf, &index_column, key_prefix, restart_key);
         let mut items = Vec::new();
         let mut total = 0;
         let limit = limit.unwrap_or(usize::MAX);
@@ -184,7 +501,7 @@ impl StateReader {
         let mut keys = Vec::<Box<[u8]>>::new();
 
         let mut get_entries = |lookup_keys, keys: Vec<Box<[u8]>>| -> Result<bool> {
-            let res = &self.db.multi_get_cf(lookup_keys);
+            let res = &self.multi_get_cf(lookup_keys);
             for (index, value) in res.into_iter().enumerate() {
                 if let Ok(Some(value)) = value {
                     let item = JsonEncoder::decode::<T>(&value)?;
@@ -238,13 +555,13 @@ impl StateReader {
         F: Fn(&T) -> bool,
     {
         let cf = column.cf_db(&self.db);
-        let mut read_options = ReadOptions::default();
+        let mut read_options = self.read_options();
         read_options.set_readahead_size(4_194_304);
         let mode = match start {
             Some(start) => IteratorMode::From(start, Direction::Forward),
             None => IteratorMode::Start,
         };
-        let iter = self.db.iterator_cf_opt(&cf, read_options, mode);
+        let iter = self.iterator_cf_opt(&cf, read_options, mode);
         let mut items = Vec::new();
         let mut total = 0;
         let limit = limit.unwrap_or(usize::MAX);
@@ -285,7 +602,7 @@ impl StateReader {
         T: DeserializeOwned,
         K: AsRef<[u8]>,
     {
-        let result_bytes = match self.db.get_cf(&column.cf_db(&self.db), key)? {
+        let result_bytes = match self.get_cf(&column.cf_db(&self.db), key)? {
             Some(bytes) => bytes,
             None => return Ok(None),
         };
@@ -298,7 +615,7 @@ impl StateReader {
     pub fn get_pending_system_tasks(&self) -> Result<usize> {
         let cf = IndexifyObjectsColumns::Stats.cf_db(&self.db);
         let key = b"pending_system_tasks";
-        let value = self.db.get_cf(&cf, key)?;
+        let value = self.get_cf(&cf, key)?;
         match value {
             Some(value) => {
                 let bytes: [u8; 8] = value
@@ -362,10 +679,127 @@ impl StateReader {
         Ok((tasks, restart_key))
     }
 
+    /// Returns the oldest change events pending webhook delivery, starting
+    /// after the event with id `after`.
+    pub fn get_change_events(
+        &self,
+        after: Option<u64>,
+        limit: Option<usize>,
+    ) -> Result<Vec<ChangeEvent>> {
+        let restart_key = match after {
+            Some(u64::MAX) => return Ok(vec![]),
+            Some(after) => Some((after + 1).to_be_bytes()),
+            None => None,
+        };
+        let (events, _) = self.get_rows_from_cf_with_limits(
+            &[],
+            restart_key.as_ref().map(|key| key.as_slice()),
+            IndexifyObjectsColumns::ChangeEvents,
+            limit,
+        )?;
+        Ok(events)
+    }
+
+    /// Returns the change events which couldn't be delivered to a webhook,
+    /// oldest failure first.
+    pub fn get_dead_lettered_change_events(
+        &self,
+        limit: Option<usize>,
+    ) -> Result<Vec<DeadLetteredChangeEvent>> {
+        let (events, _) = self.get_rows_from_cf_with_limits(
+            &[],
+            None,
+            IndexifyObjectsColumns::ChangeEventDeadLetters,
+            limit,
+        )?;
+        Ok(events)
+    }
+
+    /// Schema version recorded by the last server which opened the state
+    /// store, `None` for stores created before versions were recorded.
+    pub fn schema_version(&self) -> Result<Option<u32>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::StateMachineMetadata,
+            SCHEMA_VERSION_KEY,
+        )
+    }
+
+    /// Time in milliseconds since the epoch of the last
+    /// `IndexifyState::flush`.
+    pub fn last_flush_at(&self) -> Result<Option<u64>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::StateMachineMetadata,
+            LAST_FLUSH_KEY,
+        )
+    }
+
+    /// Lists the SST files of the state store, see
+    /// `IndexifyState::live_files`.
+    ///
+    /// The rocksdb binding doesn't expose `GetLiveFiles` on a
+    /// `TransactionDB`, so the list is read from the MANIFEST by a read-only
+    /// instance. Table files are opened lazily and their stats aren't
+    /// loaded, so the instance only reads the MANIFEST and the WAL.
+    pub fn live_files(&self) -> Result<Vec<LiveFileInfo>> {
+        let path = self.db.path();
+        let mut options = Options::default();
+        options.set_max_open_files(20);
+        options.set_skip_stats_update_on_db_open(true);
+        options.set_skip_checking_sst_file_sizes_on_db_open(true);
+        let column_families = DB::list_cf(&options, path)?;
+        let db = DB::open_cf_for_read_only(&options, path, column_families, false)?;
+        Ok(db
+            .live_files()?
+            .into_iter()
+            .map(|file| LiveFileInfo {
+                column_family: file.column_family_name,
+                name: file.name,
+                size_bytes: file.size as u64,
+                level: file.level,
+            })
+            .collect())
+    }
+
+    /// Bytes taken on disk by the SST files of a column family, which is what
+    /// RocksDB's approximate sizes report for the full range of the column
+    /// family. Writes which are only in the memtables aren't counted, and the
+    /// size covers every instance sharing the column family.
+    pub fn cf_disk_size(&self, column: IndexifyObjectsColumns) -> Result<u64> {
+        Ok(self
+            .cf_disk_sizes()?
+            .remove(column.as_ref())
+            .unwrap_or_default())
+    }
+
+    /// `cf_disk_size` of every column family, by column family name.
+    pub fn cf_disk_sizes(&self) -> Result<BTreeMap<String, u64>> {
+        let mut sizes = BTreeMap::new();
+        for file in self.live_files()? {
+            *sizes.entry(file.column_family).or_default() += file.size_bytes;
+        }
+        Ok(sizes)
+    }
+
+    /// Returns the id of the newest recorded change event.
+    pub fn last_change_event_id(&self) -> Result<Option<u64>> {
+        let cf = IndexifyObjectsColumns::ChangeEvents.cf_db(&self.db);
+        match self.iterator_cf(&cf, IteratorMode::End).next() {
+            Some(kv) => {
+                let (key, _) = kv?;
+                let key: [u8; 8] = key
+                    .as_ref()
+                    .try_into()
+                    .map_err(|_| anyhow!("invalid change event key"))?;
+                Ok(Some(u64::from_be_bytes(key)))
+            }
+            None => Ok(None),
+        }
+    }
+
     pub fn get_gc_urls(&self, limit: Option<usize>) -> Result<Vec<String>> {
         let limit = limit.unwrap_or(usize::MAX);
         let cf = IndexifyObjectsColumns::GcUrls.cf_db(&self.db);
-        let iter = self.db.iterator_cf(&cf, IteratorMode::Start);
+        let iter = self.iterator_cf(&cf, IteratorMode::Start);
         let mut urls = Vec::new();
         for kv in iter {
             if let Ok((key, _)) = kv {
@@ -379,9 +813,44 @@ impl StateReader {
         Ok(urls)
     }
 
+    /// Returns the url of every blob referenced by a stored row, including
+    /// the urls already queued for deletion.
+    pub fn referenced_blob_urls(&self) -> Result<HashSet<String>> {
+        let mut urls: HashSet<String> = self.get_gc_urls(None)?.into_iter().collect();
+        for column in [
+            IndexifyObjectsColumns::ComputeGraphs,
+            IndexifyObjectsColumns::GraphInvocations,
+            IndexifyObjectsColumns::FnOutputs,
+            IndexifyObjectsColumns::Tasks,
+            IndexifyObjectsColumns::FailedOutputs,
+        ] {
+            for kv in self.iterator_cf(&column.cf_db(&self.db), IteratorMode::Start) {
+                let (_, value) = kv?;
+                urls.extend(row_blob_urls(&column, &value)?);
+            }
+        }
+        Ok(urls)
+    }
+
+    /// Calls `f` with the key and value of every row of `column` whose key
+    /// starts with `key_prefix`, in key order.
+    pub(crate) fn for_each_row_with_prefix(
+        &self,
+        column: &IndexifyObjectsColumns,
+        key_prefix: &[u8],
+        mut f: impl FnMut(&[u8], &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        let cf = column.cf_db(&self.db);
+        for kv in self.scan_prefix(&cf, column, key_prefix, None) {
+            let (key, value) = kv?;
+            f(&key, &value)?;
+        }
+        Ok(())
+    }
+
     pub fn get_unprocessed_state_changes(&self) -> Result<Vec<StateChange>> {
         let cf = IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(&self.db);
-        let iter = self.db.iterator_cf(&cf, IteratorMode::Start);
+        let iter = self.iterator_cf(&cf, IteratorMode::Start);
         let mut state_changes = Vec::new();
         let mut count = 0;
         for kv in iter {
@@ -408,7 +877,7 @@ impl StateReader {
             .db
             .cf_handle(column.as_ref())
             .ok_or(anyhow::anyhow!("Failed to get column family {}", column))?;
-        let iter = self.db.iterator_cf(&cf_handle, IteratorMode::Start);
+        let iter = self.iterator_cf(&cf_handle, IteratorMode::Start);
 
         iter.map(|item| {
             item.map_err(|e| anyhow::anyhow!(e.to_string()))
@@ -433,6 +902,129 @@ impl StateReader {
         Ok(namespaces)
     }
 
+    /// Number of namespaces, as kept along with them. Stores written before
+    /// it was kept count their namespaces.
+    pub fn count_namespaces(&self) -> Result<usize> {
+        let cf = IndexifyObjectsColumns::StateMachineMetadata.cf_db(&self.db);
+        match self.get_cf(&cf, NAMESPACE_COUNT_KEY)? {
+            Some(count) => Ok(JsonEncoder::decode(&count)?),
+            None => self.count_prefix(&IndexifyObjectsColumns::Namespaces, &[]),
+        }
+    }
+
+    /// Number of rows of `column` whose key starts with `key_prefix`,
+    /// counted without decoding them.
+    pub fn count_prefix(
+        &self,
+        column: &IndexifyObjectsColumns,
+        key_prefix: &[u8],
+    ) -> Result<usize> {
+        let mut count = 0;
+        for kv in self.scan_prefix(&column.cf_db(&self.db), column, key_prefix, None) {
+            kv?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    pub fn count_compute_graphs(&self, namespace: &str) -> Result<usize> {
+        self.count_prefix(
+            &IndexifyObjectsColumns::ComputeGraphs,
+            format!("{}|", namespace).as_bytes(),
+        )
+    }
+
+    /// Lists a page of namespaces like `list_namespaces`, each with its
+    /// number of compute graphs. Only the graphs of the namespaces of the page
+    /// are counted.
+    pub fn list_namespaces_with_counts(
+        &self,
+        prefix: &str,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<NamespaceWithCount>, Option<Vec<u8>>)> {
+        let (namespaces, cursor) = self.list_namespaces(prefix, cursor, limit)?;
+        Ok((self.with_compute_graph_counts(namespaces)?, cursor))
+    }
+
+    /// Pairs each namespace with its number of compute graphs.
+    pub fn with_compute_graph_counts(
+        &self,
+        namespaces: Vec<Namespace>,
+    ) -> Result<Vec<NamespaceWithCount>> {
+        namespaces
+            .into_iter()
+            .map(|namespace| {
+                let count = self.count_compute_graphs(&namespace.name)?;
+                Ok((namespace, count))
+            })
+            .collect()
+    }
+
+    pub fn get_namespace(&self, name: &str) -> Result<Option<Namespace>> {
+        self.get_from_cf(&IndexifyObjectsColumns::Namespaces, name)
+    }
+
+    /// Lists namespaces whose name starts with `prefix`. Namespaces are keyed
+    /// by name, so this seeks straight to the prefix rather than filtering.
+    pub fn list_namespaces(
+        &self,
+        prefix: &str,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<Namespace>, Option<Vec<u8>>)> {
+        self.get_rows_from_cf_with_limits::<Namespace>(
+            prefix.as_bytes(),
+            cursor,
+            IndexifyObjectsColumns::Namespaces,
+            limit,
+        )
+    }
+
+    /// Lists namespaces by creation time, oldest first in ascending order,
+    /// using the index of namespaces by creation time. The cursor is the index
+    /// key of the first namespace of the next page.
+    pub fn list_namespaces_by_time(
+        &self,
+        order: SortOrder,
+        limit: Option<usize>,
+        cursor: Option<&[u8]>,
+    ) -> Result<(Vec<Namespace>, Option<Vec<u8>>)> {
+        let snapshot_reader;
+        let reader = match self.snapshot {
+            Some(_) => self,
+            None => {
+                snapshot_reader =
+                    SnapshotReader::new(&self.db, self.prefix_extractor, self.key_prefix.clone());
+                &snapshot_reader.reader
+            }
+        };
+        let mode = match (order, cursor) {
+            (SortOrder::Ascending, None) => IteratorMode::Start,
+            (SortOrder::Descending, None) => IteratorMode::End,
+            (SortOrder::Ascending, Some(cursor)) => IteratorMode::From(cursor, Direction::Forward),
+            (SortOrder::Descending, Some(cursor)) => IteratorMode::From(cursor, Direction::Reverse),
+        };
+        let namespaces_cf = IndexifyObjectsColumns::Namespaces.cf_db(&self.db);
+        let iter = reader.iterator_cf(
+            &IndexifyObjectsColumns::NamespaceCreations.cf_db(&self.db),
+            mode,
+        );
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut namespaces = Vec::new();
+        for kv in iter {
+            let (key, _) = kv?;
+            if namespaces.len() >= limit {
+                return Ok((namespaces, Some(key.into())));
+            }
+            let name = Namespace::name_from_creation_index_key(&key)?;
+            if let Some(namespace) = reader.get_cf(&namespaces_cf, name)? {
+                namespaces.push(JsonEncoder::decode(&namespace)?);
+            }
+        }
+        Ok((namespaces, None))
+    }
+
     pub fn list_invocations(
         &self,
         namespace: &str,
@@ -449,6 +1041,197 @@ impl StateReader {
         )
     }
 
+    /// Lists the invocations of a graph along with their processing status,
+    /// optionally keeping only the ones in the given status.
+    pub fn list_invocations_with_status(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        status: Option<InvocationStatus>,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<InvocationWithStatus>, Option<Vec<u8>>)> {
+        let key = format!("{}|{}|", namespace, compute_graph);
+        let res = self.filter_join_cf::<GraphInvocationCtx, _, _>(
+            IndexifyObjectsColumns::GraphInvocations,
+            IndexifyObjectsColumns::GraphInvocationCtx,
+            |ctx| status.is_none() || status == Some(ctx.status),
+            key.as_bytes(),
+            |key| Ok(key.to_vec()),
+            cursor,
+            limit,
+        )?;
+        self.invocations_with_status(res)
+    }
+
+    /// Reads the inputs of the invocation contexts of a page.
+    fn invocations_with_status(
+        &self,
+        res: FilterResponse<GraphInvocationCtx>,
+    ) -> Result<(Vec<InvocationWithStatus>, Option<Vec<u8>>)> {
+        let keys = res.items.iter().map(|ctx| ctx.key()).collect::<Vec<_>>();
+        let payloads = self.get_rows_from_cf_multi_key::<InvocationPayload>(
+            keys.iter().map(|key| key.as_bytes()).collect(),
+            IndexifyObjectsColumns::GraphInvocations,
+        )?;
+        let invocations = payloads
+            .into_iter()
+            .zip(res.items.iter().map(|ctx| ctx.status))
+            .collect();
+        let cursor = if res.cursor.is_empty() {
+            None
+        } else {
+            Some(res.cursor)
+        };
+        Ok((invocations, cursor))
+    }
+
+    /// Counts the invocations of a graph in each status, reading only their
+    /// contexts.
+    pub fn invocation_status_counts(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<HashMap<InvocationStatus, usize>> {
+        let key = format!("{}|{}|", namespace, compute_graph);
+        let (contexts, _) = self.get_rows_from_cf_with_limits::<GraphInvocationCtx>(
+            key.as_bytes(),
+            None,
+            IndexifyObjectsColumns::GraphInvocationCtx,
+            None,
+        )?;
+        let mut counts = HashMap::new();
+        for ctx in contexts {
+            *counts.entry(ctx.status).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Returns up to `limit` inputs of the namespace created before
+    /// `created_before`, in milliseconds since the epoch, starting from the
+    /// cursor returned along with the previous page. Inputs written before
+    /// their creation time was recorded aren't returned.
+    pub fn inputs_created_before(
+        &self,
+        namespace: &str,
+        created_before: u64,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<InvocationPayload>, Option<Vec<u8>>)> {
+        let key = format!("{}|", namespace);
+        self.get_filtered_rows_from_cf_with_limits(
+            key.as_bytes(),
+            cursor,
+            IndexifyObjectsColumns::GraphInvocations,
+            limit,
+            |input: &InvocationPayload| input.created_at != 0 && input.created_at < created_before,
+        )
+    }
+
+    /// Returns the inputs of a compute graph having an attribute with the
+    /// given key and value along with their processing status, in id order,
+    /// optionally keeping only the ones in the given status. Only attributes
+    /// whose key was indexed when the input was created are found.
+    pub fn find_inputs_by_attribute(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        (key, value): (&str, &str),
+        status: Option<InvocationStatus>,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<InvocationWithStatus>, Option<Vec<u8>>)> {
+        let prefix = InvocationPayload::attribute_key_prefix(namespace, compute_graph, key, value);
+        let ctx_prefix = format!("{}|{}|", namespace, compute_graph);
+        let res = self.filter_join_cf::<GraphInvocationCtx, _, _>(
+            IndexifyObjectsColumns::InputAttributes,
+            IndexifyObjectsColumns::GraphInvocationCtx,
+            |ctx| status.is_none() || status == Some(ctx.status),
+            prefix.as_bytes(),
+            // Entries of keys or values containing a `|` can share the
+            // prefix. What follows it then contains a `|`, which ids never
+            // contain, so they refer to no context and are skipped.
+            |entry| {
+                let mut key = ctx_prefix.clone().into_bytes();
+                key.extend_from_slice(&entry[prefix.len()..]);
+                Ok(key)
+            },
+            cursor,
+            limit,
+        )?;
+        self.invocations_with_status(res)
+    }
+
+    /// Sums the recorded sizes of the code of every graph and the payload of
+    /// every invocation in a namespace. Blob storage isn't consulted.
+    pub fn namespace_blob_usage(&self, namespace: &str) -> Result<NamespaceBlobUsage> {
+        let key = format!("{}|", namespace);
+        let (compute_graphs, _) = self.get_rows_from_cf_with_limits::<ComputeGraph>(
+            key.as_bytes(),
+            None,
+            IndexifyObjectsColumns::ComputeGraphs,
+            None,
+        )?;
+        let (invocations, _) = self.get_rows_from_cf_with_limits::<InvocationPayload>(
+            key.as_bytes(),
+            None,
+            IndexifyObjectsColumns::GraphInvocations,
+            None,
+        )?;
+        let compute_graph_code_bytes = compute_graphs.iter().map(|cg| cg.code.size).sum::<u64>();
+        let invocation_payload_bytes = invocations
+            .iter()
+            .map(|invocation| invocation.payload.size)
+            .sum::<u64>();
+        Ok(NamespaceBlobUsage {
+            total_bytes: compute_graph_code_bytes + invocation_payload_bytes,
+            compute_graph_code_bytes,
+            invocation_payload_bytes,
+        })
+    }
+
+    /// Aggregates the counts and blob usage of every namespace. Namespaces
+    /// and compute graphs are counted exactly, invocations are only read up
+    /// to `GLOBAL_STATS_INVOCATION_SCAN_LIMIT` to bound the cost.
+    pub fn global_stats(&self) -> Result<GlobalStats> {
+        let namespaces = self.get_all_namespaces()?;
+        let mut compute_graphs_per_namespace: BTreeMap<String, usize> = namespaces
+            .iter()
+            .map(|namespace| (namespace.name.clone(), 0))
+            .collect();
+        let compute_graphs =
+            self.get_all_rows_from_cf::<ComputeGraph>(IndexifyObjectsColumns::ComputeGraphs)?;
+        let mut compute_graph_code_bytes = 0;
+        for (_, compute_graph) in &compute_graphs {
+            *compute_graphs_per_namespace
+                .entry(compute_graph.namespace.clone())
+                .or_default() += 1;
+            compute_graph_code_bytes += compute_graph.code.size;
+        }
+        let (invocations, restart_key) = self.get_rows_from_cf_with_limits::<InvocationPayload>(
+            &[],
+            None,
+            IndexifyObjectsColumns::GraphInvocations,
+            Some(GLOBAL_STATS_INVOCATION_SCAN_LIMIT),
+        )?;
+        let invocation_payload_bytes = invocations
+            .iter()
+            .map(|invocation| invocation.payload.size)
+            .sum::<u64>();
+        Ok(GlobalStats {
+            namespaces: namespaces.len(),
+            compute_graphs: compute_graphs.len(),
+            compute_graphs_per_namespace,
+            blob_usage: NamespaceBlobUsage {
+                total_bytes: compute_graph_code_bytes + invocation_payload_bytes,
+                compute_graph_code_bytes,
+                invocation_payload_bytes,
+            },
+            invocations: invocations.len(),
+            invocations_exact: restart_key.is_none(),
+        })
+    }
+
     pub fn list_compute_graphs(
         &self,
         namespace: &str,
@@ -464,12 +1247,369 @@ impl StateReader {
         Ok((compute_graphs, cursor))
     }
 
+    pub fn filter_compute_graphs<F>(
+        &self,
+        namespace: &str,
+        filter: F,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<ComputeGraph>, Option<Vec<u8>>)>
+    where
+        F: Fn(&ComputeGraph) -> bool,
+    {
+        let key = format!("{}|", namespace);
+        self.get_filtered_rows_from_cf_with_limits(
+            key.as_bytes(),
+            cursor,
+            IndexifyObjectsColumns::ComputeGraphs,
+            limit,
+            filter,
+        )
+    }
+
+    /// Returns the compute graphs of the namespace created by `actor`, in
+    /// name order.
+    pub fn list_compute_graphs_by_actor(
+        &self,
+        namespace: &str,
+        actor: &str,
+        limit: Option<usize>,
+        cursor: Option<&[u8]>,
+    ) -> Result<(Vec<ComputeGraph>, Option<Vec<u8>>)> {
+        self.filter_compute_graphs(
+            namespace,
+            |compute_graph| compute_graph.actor.as_deref() == Some(actor),
+            cursor,
+            limit,
+        )
+    }
+
+    /// Returns up to `limit` compute graphs of the namespace, the most
+    /// recently created or updated first. The index and the graphs are read
+    /// from the same snapshot, so a concurrent update doesn't list a graph
+    /// twice.
+    pub fn list_recently_updated(
+        &self,
+        namespace: &str,
+        limit: usize,
+    ) -> Result<Vec<ComputeGraph>> {
+        let snapshot_reader;
+        let reader = match self.snapshot {
+            Some(_) => self,
+            None => {
+                snapshot_reader =
+                    SnapshotReader::new(&self.db, self.prefix_extractor, self.key_prefix.clone());
+                &snapshot_reader.reader
+            }
+        };
+        let prefix = format!("{}|", namespace);
+        let graphs_cf = IndexifyObjectsColumns::ComputeGraphs.cf_db(&self.db);
+        let iter = reader.iterator_cf(
+            &IndexifyObjectsColumns::ComputeGraphUpdates.cf_db(&self.db),
+            IteratorMode::From(prefix.as_bytes(), Direction::Forward),
+        );
+        let mut compute_graphs = Vec::new();
+        for kv in iter {
+            let (key, _) = kv?;
+            if !key.starts_with(prefix.as_bytes()) || compute_graphs.len() >= limit {
+                break;
+            }
+            let graph_key = ComputeGraph::key_from_update_index_key(&key)?;
+            if let Some(compute_graph) = reader.get_cf(&graphs_cf, graph_key)? {
+                compute_graphs.push(JsonEncoder::decode(&compute_graph)?);
+            }
+        }
+        Ok(compute_graphs)
+    }
+
+    /// Returns up to `limit` compute graphs of the namespace ordered by the
+    /// size of their code, graphs of the same size by name.
+    ///
+    /// There is no index by code size, so every graph of the namespace is
+    /// read and sorted in memory whatever the limit. The cost is bounded by
+    /// the number of graphs in the namespace.
+    pub fn list_compute_graphs_by_code_size(
+        &self,
+        namespace: &str,
+        order: SortOrder,
+        limit: usize,
+    ) -> Result<Vec<ComputeGraph>> {
+        let (mut compute_graphs, _) =
+            self.filter_compute_graphs(namespace, |_| true, None, None)?;
+        compute_graphs.sort_by(|a, b| {
+            let by_size = match order {
+                SortOrder::Ascending => a.code.size.cmp(&b.code.size),
+                SortOrder::Descending => b.code.size.cmp(&a.code.size),
+            };
+            by_size.then_with(|| a.name.cmp(&b.name))
+        });
+        compute_graphs.truncate(limit);
+        Ok(compute_graphs)
+    }
+
+    /// Returns every label key used by the compute graphs of the namespace
+    /// with the values it takes. Every graph of the namespace is read.
+    pub fn distinct_labels(&self, namespace: &str) -> Result<BTreeMap<String, BTreeSet<String>>> {
+        let (compute_graphs, _) = self.filter_compute_graphs(namespace, |_| true, None, None)?;
+        let mut labels: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for compute_graph in compute_graphs {
+            for (key, value) in compute_graph.labels {
+                labels.entry(key).or_default().insert(value);
+            }
+        }
+        Ok(labels)
+    }
+
+    /// Returns the compute graphs of the namespace with a node of type
+    /// `node_type`, in name order. Every graph of the namespace is read.
+    pub fn find_graphs_using_node(
+        &self,
+        namespace: &str,
+        node_type: &str,
+    ) -> Result<Vec<ComputeGraph>> {
+        let (compute_graphs, _) = self.filter_compute_graphs(
+            namespace,
+            |compute_graph| {
+                std::iter::once(&compute_graph.start_fn)
+                    .chain(compute_graph.nodes.values())
+                    .any(|node| node.node_type() == node_type)
+            },
+            None,
+            None,
+        )?;
+        Ok(compute_graphs)
+    }
+
+    /// Returns the compute graphs of the namespace without any invocation.
+    pub fn unused_compute_graphs(&self, namespace: &str) -> Result<Vec<ComputeGraph>> {
+        let (compute_graphs, _) = self.filter_compute_graphs(namespace, |_| true, None, None)?;
+        let mut unused = Vec::new();
+        for compute_graph in compute_graphs {
+            let prefix = format!("{}|{}|", namespace, compute_graph.name);
+            if !self.has_key_with_prefix(IndexifyObjectsColumns::GraphInvocations, &prefix)? {
+                unused.push(compute_graph);
+            }
+        }
+        Ok(unused)
+    }
+
+    /// Returns whether any key of the column family starts with the prefix,
+    /// seeking to the prefix without reading further.
+    fn has_key_with_prefix(&self, column: IndexifyObjectsColumns, prefix: &str) -> Result<bool> {
+        let cf = column.cf_db(&self.db);
+        let mode = IteratorMode::From(prefix.as_bytes(), Direction::Forward);
+        match self.iterator_cf(&cf, mode).next() {
+            Some(kv) => Ok(kv?.0.starts_with(prefix.as_bytes())),
+            None => Ok(false),
+        }
+    }
+
+    /// Counts the compute graphs of the namespace created in `[start, end)`,
+    /// by bucket. Returns the start of every non-empty bucket with its count,
+    /// in time order.
+    pub fn compute_graph_creation_histogram(
+        &self,
+        namespace: &str,
+        bucket: Bucket,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<(u64, usize)>> {
+        // Graphs are keyed by name, so every graph of the namespace is read.
+        let (compute_graphs, _) = self.filter_compute_graphs(namespace, |_| true, None, None)?;
+        let width = bucket.width_ms();
+        let mut counts = BTreeMap::new();
+        for compute_graph in compute_graphs {
+            if compute_graph.created_at < start || compute_graph.created_at >= end {
+                continue;
+            }
+            let bucket_start = compute_graph.created_at - compute_graph.created_at % width;
+            *counts.entry(bucket_start).or_insert(0) += 1;
+        }
+        Ok(counts.into_iter().collect())
+    }
+
     pub fn get_compute_graph(&self, namespace: &str, name: &str) -> Result<Option<ComputeGraph>> {
         let key = format!("{}|{}", namespace, name);
         let compute_graph = self.get_from_cf(&IndexifyObjectsColumns::ComputeGraphs, key)?;
         Ok(compute_graph)
     }
 
+    /// Returns a compute graph serialized as it is stored, without decoding
+    /// it. Graphs written by an older server are in the schema version they
+    /// were written with.
+    pub fn get_compute_graph_raw(&self, namespace: &str, name: &str) -> Result<Option<Bytes>> {
+        let key = format!("{}|{}", namespace, name);
+        let cf = IndexifyObjectsColumns::ComputeGraphs.cf_db(&self.db);
+        Ok(self.get_cf(&cf, key)?.map(Bytes::from))
+    }
+
+    /// Returns the url of the code of a compute graph, as recorded when the
+    /// graph was created.
+    pub fn get_code_url(&self, namespace: &str, name: &str) -> Result<Option<String>> {
+        let compute_graph = self.get_compute_graph(namespace, name)?;
+        Ok(compute_graph.map(|compute_graph| compute_graph.code.path))
+    }
+
+    /// Returns the metadata of the code of a compute graph without reading
+    /// the blob.
+    pub fn get_code_metadata(&self, namespace: &str, name: &str) -> Result<Option<CodeMetadata>> {
+        let compute_graph = self.get_compute_graph(namespace, name)?;
+        Ok(compute_graph.map(|compute_graph| CodeMetadata {
+            url: compute_graph.code.path,
+            size_bytes: compute_graph.code.size,
+            sha256: compute_graph.code.sha256_hash,
+            // Code is uploaded as an opaque archive.
+            content_type: "application/octet-stream".to_string(),
+        }))
+    }
+
+    /// Returns the last written output of each invocation in `input_ids`, in
+    /// the same order, with None for invocations without outputs.
+    pub fn latest_outputs_for_inputs(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        input_ids: &[String],
+    ) -> Result<Vec<Option<NodeOutput>>> {
+        let latest_cf = IndexifyObjectsColumns::LatestOutputs.cf_db(&self.db);
+        let pointer_keys = input_ids.iter().map(|id| {
+            (
+                &latest_cf,
+                format!("{}|{}|{}", namespace, compute_graph, id),
+            )
+        });
+        let output_keys = self
+            .multi_get_cf(pointer_keys)
+            .into_iter()
+            .map(|pointer| match pointer? {
+                Some(pointer) => Ok(Some(JsonEncoder::decode::<String>(&pointer)?)),
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let outputs_cf = IndexifyObjectsColumns::FnOutputs.cf_db(&self.db);
+        let lookups = output_keys
+            .iter()
+            .flatten()
+            .map(|output_key| (&outputs_cf, output_key));
+        let mut outputs = self.multi_get_cf(lookups).into_iter();
+
+        let mut results = Vec::with_capacity(input_ids.len());
+        for output_key in &output_keys {
+            let output = match output_key {
+                Some(_) => match outputs.next().transpose()?.flatten() {
+                    Some(output) => Some(JsonEncoder::decode(&output)?),
+                    None => None,
+                },
+                None => None,
+            };
+            results.push(output);
+        }
+        Ok(results)
+    }
+
+    /// Lists the failures of tasks in a namespace recorded at or after
+    /// `since_ms`, oldest first.
+    pub fn list_failed_outputs(
+        &self,
+        namespace: &str,
+        since_ms: u64,
+        limit: usize,
+    ) -> Result<Vec<FailedOutput>> {
+        let key_prefix = format!("{}|", namespace);
+        let start = FailedOutput::key_prefix_from(namespace, since_ms);
+        let (failed_outputs, _) = self.get_rows_from_cf_with_limits(
+            key_prefix.as_bytes(),
+            Some(start.as_bytes()),
+            IndexifyObjectsColumns::FailedOutputs,
+            Some(limit),
+        )?;
+        Ok(failed_outputs)
+    }
+
+    /// Returns the compute graph along with its current etag.
+    pub fn get_compute_graph_with_etag(
+        &self,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<(ComputeGraph, String)>> {
+        let key = format!("{}|{}", namespace, name);
+        let cf = IndexifyObjectsColumns::ComputeGraphs.cf_db(&self.db);
+        let Some(bytes) = self.get_cf(&cf, key)? else {
+            return Ok(None);
+        };
+        let compute_graph = JsonEncoder::decode(&bytes)?;
+        Ok(Some((compute_graph, compute_graph_etag(&bytes))))
+    }
+
+    /// Returns the (namespace, name) of every compute graph, in any namespace,
+    /// whose code has the given sha256 hash.
+    pub fn find_graphs_by_code_sha256(&self, sha256: &str) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .get_all_rows_from_cf::<ComputeGraph>(IndexifyObjectsColumns::ComputeGraphs)?
+            .into_iter()
+            .filter(|(_, compute_graph)| compute_graph.code.sha256_hash == sha256)
+            .map(|(_, compute_graph)| (compute_graph.namespace, compute_graph.name))
+            .collect())
+    }
+
+    /// Returns the distinct namespaces, in order, with a compute graph whose
+    /// code has the given sha256 hash.
+    pub fn namespaces_with_code_sha256(&self, sha256: &str) -> Result<Vec<String>> {
+        let namespaces: BTreeSet<String> = self
+            .find_graphs_by_code_sha256(sha256)?
+            .into_iter()
+            .map(|(namespace, _)| namespace)
+            .collect();
+        Ok(namespaces.into_iter().collect())
+    }
+
+    /// Returns a hash over every compute graph in the namespace. It only
+    /// changes when a graph is created, updated or deleted.
+    pub fn namespace_graphs_digest(&self, namespace: &str) -> Result<String> {
+        let key_prefix = format!("{}|", namespace);
+        let (rows, _) = self.get_raw_rows_from_cf_with_limits(
+            key_prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::ComputeGraphs,
+            None,
+        )?;
+        let mut hasher = Sha256::new();
+        for (key, value) in rows {
+            hasher.update(&key);
+            hasher.update(compute_graph_etag(&value));
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Compares the compute graphs of namespaces `a` and `b` by name and
+    /// content hash.
+    pub fn diff_namespaces(&self, a: &str, b: &str) -> Result<NamespaceDiff> {
+        let content_hashes = |namespace: &str| -> Result<BTreeMap<String, String>> {
+            let (compute_graphs, _) =
+                self.filter_compute_graphs(namespace, |_| true, None, None)?;
+            compute_graphs
+                .into_iter()
+                .map(|compute_graph| {
+                    let hash = compute_graph_content_hash(&compute_graph)?;
+                    Ok((compute_graph.name, hash))
+                })
+                .collect()
+        };
+        let graphs_a = content_hashes(a)?;
+        let mut graphs_b = content_hashes(b)?;
+        let mut diff = NamespaceDiff::default();
+        for (name, hash_a) in graphs_a {
+            match graphs_b.remove(&name) {
+                None => diff.only_in_a.push(name),
+                Some(hash_b) if hash_b != hash_a => diff.changed.push(name),
+                Some(_) => {}
+            }
+        }
+        diff.only_in_b = graphs_b.into_keys().collect();
+        Ok(diff)
+    }
+
     pub fn list_outputs_by_compute_graph(
         &self,
         namespace: &str,
@@ -587,7 +1727,7 @@ impl StateReader {
         invocation_id: &str,
     ) -> Result<GraphInvocationCtx> {
         let key = GraphInvocationCtx::key_from(namespace, compute_graph, invocation_id);
-        let value = self.db.get_cf(
+        let value = self.get_cf(
             &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&self.db),
             &key,
         )?;
@@ -605,7 +1745,7 @@ impl StateReader {
         compute_fn: &str,
     ) -> Result<Option<TaskAnalytics>> {
         let key = GraphInvocationCtx::key_from(namespace, compute_graph, invocation_id);
-        let value = self.db.get_cf(
+        let value = self.get_cf(
             &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&self.db),
             &key,
         )?;
@@ -627,7 +1767,7 @@ impl StateReader {
         invocation_id: &str,
     ) -> Result<InvocationPayload> {
         let key = InvocationPayload::key_from(namespace, compute_graph, invocation_id);
-        let value = self.db.get_cf(
+        let value = self.get_cf(
             &IndexifyObjectsColumns::GraphInvocations.cf_db(&self.db),
             &key,
         )?;
@@ -665,7 +1805,6 @@ impl StateReader {
     ) -> Result<Option<NodeOutput>> {
         let key = NodeOutput::key_from(namespace, compute_graph, invocation_id, compute_fn, id);
         let value = self
-            .db
             .get_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&self.db), &key)
             .map_err(|e| anyhow!("unable to get output payload: {}", e))?;
         match value {
@@ -674,10 +1813,66 @@ impl StateReader {
         }
     }
 
+    /// Returns the lineage of an output of the graph, walking the parent of
+    /// every output back to the input of the invocation. Finding the output
+    /// by id reads the keys of every output of the graph.
+    pub fn output_lineage(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        output_id: &str,
+    ) -> Result<Option<Lineage>> {
+        let prefix = format!("{}|{}|", namespace, compute_graph);
+        let suffix = format!("|{}", output_id);
+        let cf = IndexifyObjectsColumns::FnOutputs.cf_db(&self.db);
+        let mut output = None;
+        for kv in self.scan_prefix(
+            &cf,
+            &IndexifyObjectsColumns::FnOutputs,
+            prefix.as_bytes(),
+            None,
+        ) {
+            let (key, value) = kv?;
+            if key.ends_with(suffix.as_bytes()) {
+                output = Some(JsonEncoder::decode::<NodeOutput>(&value)?);
+                break;
+            }
+        }
+        let Some(output) = output else {
+            return Ok(None);
+        };
+        let input = self.invocation_payload(namespace, compute_graph, &output.invocation_id)?;
+
+        let mut outputs = vec![output];
+        let mut visited = HashSet::new();
+        let mut complete = false;
+        loop {
+            let Some(parent_key) = outputs.last().unwrap().parent_output_key.clone() else {
+                let start_fn = self
+                    .get_compute_graph(namespace, compute_graph)?
+                    .map(|compute_graph| compute_graph.start_fn.name().to_string());
+                complete = start_fn.as_deref() == Some(&outputs.last().unwrap().compute_fn_name);
+                break;
+            };
+            // Parents come from earlier tasks, a cycle means corrupt records.
+            if !visited.insert(parent_key.clone()) {
+                break;
+            }
+            match self.get_cf(&cf, &parent_key)? {
+                Some(value) => outputs.push(JsonEncoder::decode(&value)?),
+                None => break,
+            }
+        }
+        outputs.reverse();
+        Ok(Some(Lineage {
+            input,
+            outputs,
+            complete,
+        }))
+    }
+
     pub fn fn_output_payload_by_key(&self, key: &str) -> Result<NodeOutput> {
-        let value = self
-            .db
-            .get_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&self.db), &key)?;
+        let value = self.get_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&self.db), &key)?;
         match value {
             Some(value) => Ok(JsonEncoder::decode(&value)?),
             None => Err(anyhow!("fn output not found")),
@@ -716,17 +1911,25 @@ impl StateReader {
 mod tests {
     use std::path::PathBuf;
 
-    use data_model::Namespace;
+    use data_model::{
+        test_objects::tests::{mock_graph_a, mock_graph_b, mock_invocation_payload},
+        Namespace,
+    };
     use tempfile::TempDir;
 
     use super::{
         super::{
             requests::{NamespaceRequest, RequestPayload},
             IndexifyState,
+            IndexifyStateOptions,
         },
         *,
     };
-    use crate::requests::StateMachineUpdateRequest;
+    use crate::requests::{
+        CreateComputeGraphRequest,
+        InvokeComputeGraphRequest,
+        StateMachineUpdateRequest,
+    };
 
     #[tokio::test]
     async fn test_get_rows_from_cf_with_limits() {
@@ -740,6 +1943,7 @@ mod tests {
                 .write(StateMachineUpdateRequest {
                     payload: RequestPayload::CreateNameSpace(NamespaceRequest {
                         name: name.clone(),
+                        feature_flags: Default::default(),
                     }),
                     state_changes_processed: vec![],
                 })
@@ -773,4 +1977,233 @@ mod tests {
         assert_eq!(result.0.len(), 2);
         assert_eq!(cursor, None);
     }
+
+    #[tokio::test]
+    async fn test_list_namespaces_with_counts() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        // "ns1" is a prefix of "ns10", whose graphs must not be counted for it.
+        let graphs = [("ns1", 2), ("ns10", 1), ("ns2", 0)];
+        for (namespace, count) in graphs {
+            indexify_state.upsert_namespace(&NamespaceRequest {
+                name: namespace.to_string(),
+                feature_flags: Default::default(),
+            })?;
+            for mut compute_graph in [mock_graph_a(), mock_graph_b()].into_iter().take(count) {
+                compute_graph.namespace = namespace.to_string();
+                indexify_state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::CreateComputeGraph(Box::new(
+                            CreateComputeGraphRequest {
+                                namespace: namespace.to_string(),
+                                compute_graph,
+                                if_match: None,
+                                if_changed: false,
+                            },
+                        )),
+                        state_changes_processed: vec![],
+                    })
+                    .await?;
+            }
+        }
+
+        let reader = indexify_state.reader();
+        let counts = |namespaces: Vec<NamespaceWithCount>| -> Vec<(String, usize)> {
+            namespaces
+                .into_iter()
+                .map(|(namespace, count)| (namespace.name, count))
+                .collect()
+        };
+        let (page, cursor) = reader.list_namespaces_with_counts("", None, Some(2))?;
+        assert_eq!(
+            counts(page),
+            vec![("ns1".to_string(), 2), ("ns10".to_string(), 1)]
+        );
+        let (page, cursor) = reader.list_namespaces_with_counts("", cursor.as_deref(), Some(2))?;
+        assert_eq!(counts(page), vec![("ns2".to_string(), 0)]);
+        assert!(cursor.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_namespace_blob_usage() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+
+        let mut graph_a = mock_graph_a();
+        graph_a.code.size = 100;
+        let mut graph_b = mock_graph_b();
+        graph_b.code.size = 250;
+        let mut other_namespace_graph = mock_graph_a();
+        other_namespace_graph.namespace = "other".to_string();
+        other_namespace_graph.code.size = 1000;
+        for compute_graph in [graph_a.clone(), graph_b, other_namespace_graph] {
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(Box::new(
+                        CreateComputeGraphRequest {
+                            namespace: compute_graph.namespace.clone(),
+                            compute_graph,
+                            if_match: None,
+                            if_changed: false,
+                        },
+                    )),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        for (id, size) in [("inv_1", 7), ("inv_2", 35)] {
+            let mut invocation_payload = mock_invocation_payload();
+            invocation_payload.id = id.to_string();
+            invocation_payload.payload.size = size;
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                        namespace: graph_a.namespace.clone(),
+                        compute_graph_name: graph_a.name.clone(),
+                        invocation_payload,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let usage = indexify_state
+            .reader()
+            .namespace_blob_usage(&graph_a.namespace)?;
+        assert_eq!(
+            usage,
+            NamespaceBlobUsage {
+                total_bytes: 392,
+                compute_graph_code_bytes: 350,
+                invocation_payload_bytes: 42,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefix_upper_bound() {
+        assert_eq!(prefix_upper_bound(b"ns|"), Some(b"ns}".to_vec()));
+        assert_eq!(prefix_upper_bound(b"ns\xff"), Some(b"nt".to_vec()));
+        assert_eq!(prefix_upper_bound(b"\xff\xff"), None);
+    }
+
+    // Pages through a prefix followed by deleted keys, which the iterate
+    // bounds of `scan_prefix` keep the listing from stepping over.
+    #[tokio::test]
+    async fn test_scan_prefix_paging() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let db = indexify_state.db.clone();
+        let cf = IndexifyObjectsColumns::GraphInvocations.cf_db(&db);
+        let expected = (0..100)
+            .map(|i| format!("ns|a|{:06}", i).into_bytes())
+            .collect::<Vec<_>>();
+        for key in &expected {
+            db.put_cf(&cf, key, b"{}")?;
+        }
+        for i in 0..1_000 {
+            let key = format!("ns|b|{:06}", i);
+            db.put_cf(&cf, &key, b"{}")?;
+            db.delete_cf(&cf, &key)?;
+        }
+        db.put_cf(&cf, "ns|c|000000", b"{}")?;
+        drop(cf);
+
+        let reader = indexify_state.reader();
+        let mut keys = Vec::new();
+        let mut cursor = None;
+        let mut pages = 0;
+        loop {
+            let (rows, next) = reader.get_raw_rows_from_cf_with_limits(
+                b"ns|a|",
+                cursor.as_deref(),
+                IndexifyObjectsColumns::GraphInvocations,
+                Some(30),
+            )?;
+            pages += 1;
+            keys.extend(rows.into_iter().map(|(key, _)| key));
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 4);
+        assert_eq!(keys, expected);
+
+        Ok(())
+    }
+
+    // Scans namespaces with and without a prefix extractor, on SST files
+    // each holding a fourth of the namespaces, which the prefix bloom filters
+    // let the prefix seek skip.
+    #[tokio::test]
+    async fn test_scan_prefix_seek() -> Result<()> {
+        let namespaces = (0..20).map(|i| format!("ns{}", i)).collect::<Vec<_>>();
+        for prefix_extractor in [
+            PrefixExtractor::None,
+            PrefixExtractor::Namespace,
+            PrefixExtractor::FixedLength(4),
+        ] {
+            let temp_dir = TempDir::new()?;
+            let path = temp_dir.path().join("state");
+            let options = IndexifyStateOptions {
+                prefix_extractor,
+                ..Default::default()
+            };
+            for batch in 0..4 {
+                let indexify_state =
+                    IndexifyState::new_with_options(path.clone(), options.clone()).await?;
+                let db = indexify_state.db.clone();
+                let cf = IndexifyObjectsColumns::GraphInvocations.cf_db(&db);
+                for namespace in namespaces.iter().skip(batch).step_by(4) {
+                    for i in 0..20 {
+                        db.put_cf(&cf, format!("{}|graph|{:06}", namespace, i), b"{}")?;
+                    }
+                }
+                // Reopening flushes the write-ahead log to an SST.
+            }
+            let indexify_state = IndexifyState::new_with_options(path, options).await?;
+            let reader = indexify_state.reader();
+            let scan = |key_prefix: &[u8]| -> Result<Vec<Vec<u8>>> {
+                let (rows, _) = reader.get_raw_rows_from_cf_with_limits(
+                    key_prefix,
+                    None,
+                    IndexifyObjectsColumns::GraphInvocations,
+                    None,
+                )?;
+                Ok(rows.into_iter().map(|(key, _)| key).collect())
+            };
+
+            let keys = scan(b"ns1|")?;
+            assert_eq!(keys.len(), 20);
+            assert!(keys.iter().all(|key| key.starts_with(b"ns1|")));
+            assert_eq!(scan(b"ns1|graph|00001")?.len(), 10);
+            assert!(scan(b"ns100|")?.is_empty());
+            // Prefixes shorter than the extracted prefix span namespaces.
+            assert_eq!(scan(b"ns1")?.len(), 11 * 20);
+            assert_eq!(scan(b"")?.len(), 20 * 20);
+            // Paging stays within the namespace.
+            let (rows, cursor) = reader.get_raw_rows_from_cf_with_limits(
+                b"ns19|",
+                None,
+                IndexifyObjectsColumns::GraphInvocations,
+                Some(15),
+            )?;
+            assert_eq!(rows.len(), 15);
+            let (rows, cursor) = reader.get_raw_rows_from_cf_with_limits(
+                b"ns19|",
+                cursor.as_deref(),
+                IndexifyObjectsColumns::GraphInvocations,
+                Some(15),
+            )?;
+            assert_eq!(rows.len(), 5);
+            assert!(cursor.is_none());
+        }
+
+        Ok(())
+    }
 }
@@ -1,12 +1,23 @@
-use std::{collections::HashMap, sync::Arc, vec};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+    vec,
+};
 
 use anyhow::{anyhow, Result};
 use data_model::{
+    schema::{COMPUTE_GRAPH_SCHEMA_VERSION, NAMESPACE_SCHEMA_VERSION},
+    ChangeEvent,
     ChangeType,
     ComputeGraph,
+    DeadLetteredChangeEvent,
     ExecutorId,
+    FailedOutput,
     GraphInvocationCtx,
     GraphInvocationCtxBuilder,
+    InvocationPayload,
+    InvocationStatus,
     InvokeComputeGraphEvent,
     Namespace,
     NodeOutput,
@@ -26,26 +37,37 @@ use rocksdb::{
     IteratorMode,
     OptimisticTransactionDB,
     ReadOptions,
-    Transaction,
     TransactionDB,
 };
+use sha2::{Digest, Sha256};
 use strum::AsRefStr;
 use tracing::error;
 
 use super::serializer::{JsonEncode, JsonEncoder};
-use crate::requests::{
-    CreateTasksRequest,
-    DeleteInvocationRequest,
-    DeregisterExecutorRequest,
-    FinalizeTaskRequest,
-    InvokeComputeGraphRequest,
-    NamespaceRequest,
-    ReductionTasks,
-    RegisterExecutorRequest,
-    RemoveSystemTaskRequest,
-    RerunComputeGraphRequest,
-    RerunInvocationRequest,
-    UpdateSystemTaskRequest,
+use crate::{
+    key_prefix::{KeyPrefix, PrefixedTransaction},
+    requests::{
+        AttachOutputRequest,
+        CreateComputeGraphRequest,
+        CreateTasksRequest,
+        DeleteInvocationRequest,
+        DeleteNamespaceRequest,
+        DeregisterExecutorRequest,
+        FinalizeTaskRequest,
+        InvokeComputeGraphRequest,
+        NamespaceRequest,
+        ReductionTasks,
+        RegisterExecutorRequest,
+        RemoveSystemTaskRequest,
+        RerunComputeGraphRequest,
+        RerunInvocationRequest,
+        SetComputeGraphEnabledRequest,
+        SetComputeGraphFrozenRequest,
+        SetNamespaceFlagsRequest,
+        SetNamespacePolicyRequest,
+        SwapComputeGraphCodeRequest,
+        UpdateSystemTaskRequest,
+    },
 };
 
 pub type ContentId = String;
@@ -59,18 +81,23 @@ pub type SchemaId = String;
 
 #[derive(AsRefStr, strum::Display, strum::EnumIter)]
 pub enum IndexifyObjectsColumns {
-    StateMachineMetadata, //  StateMachineMetadata
+    StateMachineMetadata, //  Key -> StateMachineMetadata, e.g. the schema version
     Executors,            //  ExecutorId -> Executor Metadata
     Namespaces,           //  Namespaces
+    NamespaceCreations,   //  CreatedAt_Namespace -> Empty
     ComputeGraphs,        //  Ns_ComputeGraphName -> ComputeGraph
+    ComputeGraphUpdates,  //  Ns_InvertedUpdatedAt_ComputeGraphName -> Empty
 
     Tasks,              //  Ns_CG_<Invocation_Id>_Fn_TaskId -> Task
     GraphInvocationCtx, //  Ns_CG_IngestedId -> GraphInvocationCtx
     ReductionTasks,     //  Ns_CG_Fn_TaskId -> ReduceTask
 
     GraphInvocations, //  Ns_Graph_Id -> InvocationPayload
+    InputAttributes,  //  Ns_Graph_Key_Value_Id -> Empty
     FnOutputs,        //  Ns_Graph_<Ingested_Id>_Fn_Id -> NodeOutput
     TaskOutputs,      //  NS_TaskID -> NodeOutputID
+    FailedOutputs,    //  Ns_FailedAt_TaskId -> FailedOutput
+    LatestOutputs,    //  Ns_Graph_<Ingested_Id> -> Key of the last written NodeOutput
 
     StateChanges, //  StateChangeId -> StateChange
 
@@ -80,12 +107,33 @@ pub enum IndexifyObjectsColumns {
 
     GcUrls, // List of URLs pending deletion
 
+    ChangeEvents,           // EventId -> ChangeEvent pending webhook delivery
+    ChangeEventDeadLetters, // FailedAt_EventId_Url -> DeadLetteredChangeEvent
+
     SystemTasks, // Long running tasks involving multiple invocations
 
     Stats, // Stats
 }
 
 impl IndexifyObjectsColumns {
+    /// Whether every key of the column family starts with `namespace|`.
+    pub fn keyed_by_namespace(&self) -> bool {
+        matches!(
+            self,
+            IndexifyObjectsColumns::ComputeGraphs |
+                IndexifyObjectsColumns::ComputeGraphUpdates |
+                IndexifyObjectsColumns::Tasks |
+                IndexifyObjectsColumns::GraphInvocationCtx |
+                IndexifyObjectsColumns::ReductionTasks |
+                IndexifyObjectsColumns::GraphInvocations |
+                IndexifyObjectsColumns::InputAttributes |
+                IndexifyObjectsColumns::FnOutputs |
+                IndexifyObjectsColumns::TaskOutputs |
+                IndexifyObjectsColumns::FailedOutputs |
+                IndexifyObjectsColumns::LatestOutputs
+        )
+    }
+
     pub fn cf<'a>(&'a self, db: &'a OptimisticTransactionDB) -> Arc<BoundColumnFamily> {
         db.cf_handle(self.as_ref())
             .inspect_none(|| {
@@ -103,23 +151,171 @@ impl IndexifyObjectsColumns {
     }
 }
 
-pub(crate) fn create_namespace(db: Arc<TransactionDB>, req: &NamespaceRequest) -> Result<()> {
+/// Key of the schema version in the `StateMachineMetadata` column family.
+pub(crate) const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// Key of the time of the last flush in the `StateMachineMetadata` column
+/// family.
+pub(crate) const LAST_FLUSH_KEY: &str = "last_flush_at";
+
+/// Key of the number of namespaces in the `StateMachineMetadata` column
+/// family.
+pub(crate) const NAMESPACE_COUNT_KEY: &str = "namespace_count";
+
+pub(crate) fn record_schema_version(
+    db: &TransactionDB,
+    key_prefix: &KeyPrefix,
+    version: u32,
+) -> Result<()> {
+    db.put_cf(
+        &IndexifyObjectsColumns::StateMachineMetadata.cf_db(db),
+        key_prefix.key(SCHEMA_VERSION_KEY.as_bytes()),
+        JsonEncoder::encode(&version)?,
+    )?;
+    Ok(())
+}
+
+/// Counts the rows of a column family visible to the transaction.
+pub(crate) fn count_cf(
+    db: &TransactionDB,
+    txn: &PrefixedTransaction,
+    column: IndexifyObjectsColumns,
+) -> Result<usize> {
+    let mut count = 0;
+    for kv in txn.iterator_cf(&column.cf_db(db), IteratorMode::Start) {
+        kv?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Returns the number of namespaces, locking it until the transaction ends
+/// so namespaces are created and deleted one at a time. Stores written
+/// before the number was kept count their namespaces.
+fn lock_namespace_count(db: &TransactionDB, txn: &PrefixedTransaction) -> Result<usize> {
+    let cf = IndexifyObjectsColumns::StateMachineMetadata.cf_db(db);
+    match txn.get_for_update_cf(&cf, NAMESPACE_COUNT_KEY, true)? {
+        Some(count) => Ok(JsonEncoder::decode(&count)?),
+        None => count_cf(db, txn, IndexifyObjectsColumns::Namespaces),
+    }
+}
+
+fn put_namespace_count(db: &TransactionDB, txn: &PrefixedTransaction, count: usize) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::StateMachineMetadata.cf_db(db),
+        NAMESPACE_COUNT_KEY,
+        JsonEncoder::encode(&count)?,
+    )?;
+    Ok(())
+}
+
+/// Counts a namespace about to be created. Fails with
+/// `NamespaceLimitReached` when there are already `max_namespaces`
+/// namespaces.
+pub(crate) fn count_new_namespace(
+    db: &TransactionDB,
+    txn: &PrefixedTransaction,
+    max_namespaces: Option<usize>,
+) -> Result<()> {
+    let count = lock_namespace_count(db, txn)?;
+    if let Some(max_namespaces) = max_namespaces {
+        if count >= max_namespaces {
+            return Err(NamespaceLimitReached { max_namespaces }.into());
+        }
+    }
+    put_namespace_count(db, txn, count + 1)
+}
+
+/// Writes a new namespace along with its entry in the index of namespaces by
+/// creation time.
+pub(crate) fn put_new_namespace(
+    db: &TransactionDB,
+    txn: &PrefixedTransaction,
+    ns: &Namespace,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::Namespaces.cf_db(db),
+        &ns.name,
+        JsonEncoder::encode(ns)?,
+    )?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::NamespaceCreations.cf_db(db),
+        ns.creation_index_key(),
+        [],
+    )?;
+    Ok(())
+}
+
+/// Creates the namespace, replacing it if it already exists.
+pub(crate) fn create_namespace(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    req: &NamespaceRequest,
+    max_namespaces: Option<usize>,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::Namespaces.cf_db(&db);
+    match txn.get_for_update_cf(&cf, &req.name, true)? {
+        Some(existing) => txn.delete_cf(
+            &IndexifyObjectsColumns::NamespaceCreations.cf_db(&db),
+            JsonEncoder::decode::<Namespace>(&existing)?.creation_index_key(),
+        )?,
+        None => count_new_namespace(&db, txn, max_namespaces)?,
+    }
+    let ns = Namespace {
+        name: req.name.clone(),
+        created_at: get_epoch_time_in_ms(),
+        feature_flags: req.feature_flags.clone(),
+        retention: Default::default(),
+        schema_version: NAMESPACE_SCHEMA_VERSION,
+    };
+    put_new_namespace(&db, txn, &ns)?;
+    Ok(())
+}
+
+/// Creates the namespace unless it already exists. Returns the stored
+/// namespace and whether it was created.
+pub(crate) fn upsert_namespace(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    req: &NamespaceRequest,
+    max_namespaces: Option<usize>,
+) -> Result<(Namespace, bool)> {
+    let cf = IndexifyObjectsColumns::Namespaces.cf_db(&db);
+    if let Some(existing) = txn.get_for_update_cf(&cf, &req.name, true)? {
+        return Ok((JsonEncoder::decode(&existing)?, false));
+    }
+    count_new_namespace(&db, txn, max_namespaces)?;
     let ns = Namespace {
         name: req.name.clone(),
         created_at: get_epoch_time_in_ms(),
+        feature_flags: req.feature_flags.clone(),
+        retention: Default::default(),
+        schema_version: NAMESPACE_SCHEMA_VERSION,
     };
-    let serialized_namespace = JsonEncoder::encode(&ns)?;
-    db.put_cf(
-        &IndexifyObjectsColumns::Namespaces.cf_db(&db),
-        &ns.name,
-        serialized_namespace,
-    )?;
+    put_new_namespace(&db, txn, &ns)?;
+    Ok((ns, true))
+}
+
+/// Merges the feature flags of the request into the namespace and returns
+/// the updated namespace.
+pub(crate) fn set_namespace_flags(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    req: &SetNamespaceFlagsRequest,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::Namespaces.cf_db(&db);
+    let namespace = txn
+        .get_for_update_cf(&cf, &req.name, true)?
+        .ok_or(anyhow!("Namespace not found: {}", req.name))?;
+    let mut namespace: Namespace = JsonEncoder::decode(&namespace)?;
+    namespace.feature_flags.extend(req.feature_flags.clone());
+    txn.put_cf(&cf, &namespace.name, JsonEncoder::encode(&namespace)?)?;
     Ok(())
 }
 
 pub fn remove_system_task(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     req: RemoveSystemTaskRequest,
 ) -> Result<()> {
     let task_key = SystemTask::key_from(&req.namespace, &req.compute_graph_name);
@@ -129,7 +325,7 @@ pub fn remove_system_task(
 
 pub fn update_system_task(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     req: UpdateSystemTaskRequest,
 ) -> Result<()> {
     let key = SystemTask::key_from(&req.namespace, &req.compute_graph_name);
@@ -149,7 +345,7 @@ pub fn update_system_task(
 
 pub fn rerun_compute_graph(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     req: RerunComputeGraphRequest,
 ) -> Result<()> {
     let key = format!("{}|{}", req.namespace, req.compute_graph_name);
@@ -190,7 +386,7 @@ pub fn rerun_compute_graph(
 
 pub fn rerun_invocation(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     req: RerunInvocationRequest,
 ) -> Result<Vec<StateChange>> {
     let graph_ctx_key =
@@ -258,6 +454,10 @@ pub fn rerun_invocation(
         let (key, _) = output?;
         txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&db), key)?;
     }
+    txn.delete_cf(
+        &IndexifyObjectsColumns::LatestOutputs.cf_db(&db),
+        &graph_ctx_key,
+    )?;
     txn.delete_cf(
         &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
         graph_ctx_key,
@@ -310,10 +510,49 @@ pub fn rerun_invocation(
     Ok(vec![state_change])
 }
 
+pub(crate) fn attach_output(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    req: &AttachOutputRequest,
+) -> Result<()> {
+    let invocation_key =
+        InvocationPayload::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    txn.get_for_update_cf(
+        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
+        &invocation_key,
+        false,
+    )?
+    .ok_or(anyhow!("invocation not found: {}", req.invocation_id))?;
+
+    let graph_ctx_key =
+        GraphInvocationCtx::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    let mut output = req.output.clone();
+    if let Some(graph_ctx) = txn.get_cf(
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        &graph_ctx_key,
+    )? {
+        let graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
+        output.graph_version = graph_ctx.graph_version;
+    }
+    let output_key = output.key(&req.invocation_id);
+    txn.put_cf(
+        &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+        &output_key,
+        JsonEncoder::encode(&output)?,
+    )?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::LatestOutputs.cf_db(&db),
+        &graph_ctx_key,
+        JsonEncoder::encode(&output_key)?,
+    )?;
+    Ok(())
+}
+
 pub fn create_graph_input(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     req: &InvokeComputeGraphRequest,
+    indexed_attributes: &HashSet<String>,
 ) -> Result<()> {
     let compute_graph_key = format!("{}|{}", req.namespace, req.compute_graph_name);
     let cg = txn
@@ -324,12 +563,28 @@ pub fn create_graph_input(
         )?
         .ok_or(anyhow::anyhow!("Compute graph not found"))?;
     let cg: ComputeGraph = JsonEncoder::decode(&cg)?;
+    if !cg.enabled {
+        return Err(ComputeGraphDisabled {
+            namespace: req.namespace.clone(),
+            name: req.compute_graph_name.clone(),
+        }
+        .into());
+    }
     let serialized_data_object = JsonEncoder::encode(&req.invocation_payload)?;
     txn.put_cf(
         &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
         req.invocation_payload.key(),
         &serialized_data_object,
     )?;
+    for (key, value) in &req.invocation_payload.attributes {
+        if indexed_attributes.contains(key) {
+            txn.put_cf(
+                &IndexifyObjectsColumns::InputAttributes.cf_db(&db),
+                req.invocation_payload.attribute_key(key, value),
+                [],
+            )?;
+        }
+    }
 
     let graph_invocation_ctx = GraphInvocationCtxBuilder::default()
         .namespace(req.namespace.to_string())
@@ -348,23 +603,23 @@ pub fn create_graph_input(
 
 pub(crate) fn delete_input_data_object(
     db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
     req: &DeleteInvocationRequest,
 ) -> Result<()> {
-    let mut read_options = ReadOptions::default();
-    read_options.set_readahead_size(4_194_304);
-    let prefix = format!(
-        "{}|{}|{}",
-        req.namespace, req.compute_graph, req.invocation_id
-    );
-    let iterator_mode = IteratorMode::From(prefix.as_bytes(), Direction::Forward);
-    let iter = db.iterator_cf_opt(
-        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
-        read_options,
-        iterator_mode,
-    );
-    for key in iter {
-        let key = key?;
-        db.delete_cf(&IndexifyObjectsColumns::GraphInvocations.cf_db(&db), &key.0)?;
+    // Only the invocation's own row is deleted, the ids of other invocations
+    // of the graph may start with its id.
+    let key = InvocationPayload::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    let cf = IndexifyObjectsColumns::GraphInvocations.cf_db(&db);
+    if let Some(value) = txn.get_for_update_cf(&cf, &key, true)? {
+        // The indexed keys may have changed since the input was created.
+        let invocation: InvocationPayload = JsonEncoder::decode(&value)?;
+        for (attribute, value) in &invocation.attributes {
+            txn.delete_cf(
+                &IndexifyObjectsColumns::InputAttributes.cf_db(&db),
+                invocation.attribute_key(attribute, value),
+            )?;
+        }
+        txn.delete_cf(&cf, &key)?;
     }
 
     // FIXME - Delete the data objects which are outputs of the compute functions of
@@ -372,17 +627,318 @@ pub(crate) fn delete_input_data_object(
     Ok(())
 }
 
+/// Returned when a compute graph update is conditioned on an etag which no
+/// longer matches the stored graph.
+#[derive(Debug)]
+pub struct ComputeGraphEtagMismatch {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl fmt::Display for ComputeGraphEtagMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "compute graph {} in namespace {} has been modified",
+            self.name, self.namespace
+        )
+    }
+}
+
+impl std::error::Error for ComputeGraphEtagMismatch {}
+
+/// Returned when a frozen compute graph is updated or deleted.
+#[derive(Debug)]
+pub struct ComputeGraphFrozen {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl fmt::Display for ComputeGraphFrozen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "compute graph {} in namespace {} is frozen",
+            self.name, self.namespace
+        )
+    }
+}
+
+impl std::error::Error for ComputeGraphFrozen {}
+
+/// Returned when a compute graph is only to be written if it changed and the
+/// stored graph has the same content.
+#[derive(Debug)]
+pub struct ComputeGraphUnchanged {
+    pub compute_graph: ComputeGraph,
+}
+
+impl fmt::Display for ComputeGraphUnchanged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "compute graph {} in namespace {} is unchanged",
+            self.compute_graph.name, self.compute_graph.namespace
+        )
+    }
+}
+
+impl std::error::Error for ComputeGraphUnchanged {}
+
+/// Returned when a disabled compute graph is invoked.
+#[derive(Debug)]
+pub struct ComputeGraphDisabled {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl fmt::Display for ComputeGraphDisabled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "compute graph {} in namespace {} is disabled",
+            self.name, self.namespace
+        )
+    }
+}
+
+impl std::error::Error for ComputeGraphDisabled {}
+
+/// Returned when a namespace which still has compute graphs is deleted
+/// without force.
+#[derive(Debug)]
+pub struct NamespaceNotEmpty {
+    pub namespace: String,
+    pub compute_graphs: usize,
+}
+
+impl fmt::Display for NamespaceNotEmpty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "namespace {} has {} compute graphs, delete them first or force the deletion",
+            self.namespace, self.compute_graphs
+        )
+    }
+}
+
+impl std::error::Error for NamespaceNotEmpty {}
+
+/// Returned when creating a namespace would exceed the number of namespaces
+/// the server is limited to.
+#[derive(Debug)]
+pub struct NamespaceLimitReached {
+    pub max_namespaces: usize,
+}
+
+impl fmt::Display for NamespaceLimitReached {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the server is limited to {} namespaces",
+            self.max_namespaces
+        )
+    }
+}
+
+impl std::error::Error for NamespaceLimitReached {}
+
+/// Returned when the state store was written by a server with a newer
+/// schema than the running one supports, e.g. after a downgrade.
+#[derive(Debug)]
+pub struct SchemaVersionMismatch {
+    pub recorded: u32,
+    pub supported: u32,
+}
+
+impl fmt::Display for SchemaVersionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "state store has schema version {}, this server supports up to version {}",
+            self.recorded, self.supported
+        )
+    }
+}
+
+impl std::error::Error for SchemaVersionMismatch {}
+
+/// Returned when a write kept failing with a transient RocksDB error, such as
+/// a lock timeout under contention, after all its retries. The write can be
+/// retried later.
+#[derive(Debug, Clone)]
+pub struct TransientWriteError {
+    pub attempts: u32,
+    pub source: rocksdb::Error,
+}
+
+impl fmt::Display for TransientWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "write failed after {} attempts: {}",
+            self.attempts, self.source
+        )
+    }
+}
+
+impl std::error::Error for TransientWriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Returns the RocksDB error behind a failed write if it is expected to go
+/// away when the transaction is retried.
+pub fn transient_rocksdb_error(err: &anyhow::Error) -> Option<&rocksdb::Error> {
+    err.chain()
+        .filter_map(|err| err.downcast_ref::<rocksdb::Error>())
+        .find(|err| {
+            matches!(
+                err.kind(),
+                rocksdb::ErrorKind::Busy |
+                    rocksdb::ErrorKind::TimedOut |
+                    rocksdb::ErrorKind::TryAgain
+            )
+        })
+}
+
+/// Returned when the normalized name of a created compute graph is already
+/// used by a graph created with a different name.
+#[derive(Debug)]
+pub struct ComputeGraphNameCollision {
+    pub namespace: String,
+    pub name: String,
+    pub existing_name: String,
+}
+
+impl fmt::Display for ComputeGraphNameCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "compute graph {} in namespace {} collides with existing compute graph {}",
+            self.name, self.namespace, self.existing_name
+        )
+    }
+}
+
+impl std::error::Error for ComputeGraphNameCollision {}
+
+/// The etag of a compute graph is the hash of its stored representation, so
+/// it changes with every update.
+pub fn compute_graph_etag(serialized_compute_graph: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(serialized_compute_graph))
+}
+
+/// The content hash of a compute graph covers what it runs: its description,
+/// code, nodes, edges, runtime and schemas, along with its labels. Unlike the
+/// etag it leaves out the namespace, the timestamps and the version, so a graph
+/// copied to another namespace has the same content hash.
+pub fn compute_graph_content_hash(compute_graph: &ComputeGraph) -> Result<String> {
+    // Maps of json values are sorted by key, which makes the hash independent
+    // of the iteration order of the graph's hash maps.
+    let mut content = serde_json::json!({
+        "description": compute_graph.description,
+        "code": compute_graph.code.sha256_hash,
+        "start_fn": compute_graph.start_fn,
+        "nodes": compute_graph.nodes,
+        "edges": compute_graph.edges,
+        "runtime_information": compute_graph.runtime_information,
+    });
+    // Graphs without schemas keep the hash they had before graphs had them.
+    for (field, schema) in [
+        ("input_schema", &compute_graph.input_schema),
+        ("output_schema", &compute_graph.output_schema),
+    ] {
+        if let Some(schema) = schema {
+            content[field] = schema.clone();
+        }
+    }
+    if !compute_graph.labels.is_empty() {
+        content["labels"] = serde_json::json!(compute_graph.labels);
+    }
+    Ok(format!(
+        "{:x}",
+        Sha256::digest(serde_json::to_vec(&content)?)
+    ))
+}
+
+/// Creates or updates a compute graph. Returns true if the graph didn't exist.
 pub(crate) fn create_compute_graph(
     db: Arc<TransactionDB>,
-    mut compute_graph: ComputeGraph,
-) -> Result<()> {
-    let existing_compute_graph = db.get_cf(
-        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
-        compute_graph.key(),
+    txn: &PrefixedTransaction,
+    req: &CreateComputeGraphRequest,
+) -> Result<bool> {
+    let mut compute_graph = req.compute_graph.clone();
+    compute_graph.schema_version = COMPUTE_GRAPH_SCHEMA_VERSION;
+    // Schemas the validator doesn't fully understand would accept values
+    // they are meant to reject.
+    compute_graph.check_schemas()?;
+    // A shared lock on the namespace keeps it from being deleted without
+    // counting this graph.
+    txn.get_for_update_cf(
+        &IndexifyObjectsColumns::Namespaces.cf_db(&db),
+        &req.namespace,
+        false,
     )?;
+    let cf = IndexifyObjectsColumns::ComputeGraphs.cf_db(&db);
+    let existing_compute_graph = txn.get_for_update_cf(&cf, compute_graph.key(), true)?;
+
+    if let Some(if_match) = &req.if_match {
+        let matches = existing_compute_graph
+            .as_ref()
+            .is_some_and(|existing| if_match.contains(&compute_graph_etag(existing)));
+        if !matches {
+            return Err(ComputeGraphEtagMismatch {
+                namespace: compute_graph.namespace.clone(),
+                name: compute_graph.name.clone(),
+            }
+            .into());
+        }
+    }
+
+    if req.if_changed {
+        if let Some(existing_compute_graph) = &existing_compute_graph {
+            let existing_compute_graph: ComputeGraph = JsonEncoder::decode(existing_compute_graph)?;
+            if compute_graph_content_hash(&existing_compute_graph)? ==
+                compute_graph_content_hash(&compute_graph)?
+            {
+                return Err(ComputeGraphUnchanged {
+                    compute_graph: existing_compute_graph,
+                }
+                .into());
+            }
+        }
+    }
 
+    let updates_cf = IndexifyObjectsColumns::ComputeGraphUpdates.cf_db(&db);
+    let created = existing_compute_graph.is_none();
     if let Some(existing_compute_graph) = existing_compute_graph {
         let existing_compute_graph: ComputeGraph = JsonEncoder::decode(&existing_compute_graph)?;
+        txn.delete_cf(&updates_cf, existing_compute_graph.update_index_key())?;
+        if existing_compute_graph.frozen {
+            return Err(ComputeGraphFrozen {
+                namespace: compute_graph.namespace.clone(),
+                name: compute_graph.name.clone(),
+            }
+            .into());
+        }
+        let existing_name = existing_compute_graph
+            .display_name
+            .as_ref()
+            .unwrap_or(&existing_compute_graph.name);
+        let name = compute_graph
+            .display_name
+            .as_ref()
+            .unwrap_or(&compute_graph.name);
+        if existing_name != name {
+            return Err(ComputeGraphNameCollision {
+                namespace: compute_graph.namespace.clone(),
+                name: name.clone(),
+                existing_name: existing_name.clone(),
+            }
+            .into());
+        }
         if compute_graph.code.sha256_hash != existing_compute_graph.code.sha256_hash ||
             compute_graph.edges != existing_compute_graph.edges ||
             compute_graph.nodes != existing_compute_graph.nodes ||
@@ -390,19 +946,120 @@ pub(crate) fn create_compute_graph(
         {
             compute_graph.version = existing_compute_graph.version.next();
         }
+        compute_graph.enabled = existing_compute_graph.enabled;
+        compute_graph.created_at = existing_compute_graph.created_at;
+        compute_graph.actor = existing_compute_graph.actor;
+    } else if compute_graph.created_at == 0 {
+        compute_graph.created_at = get_epoch_time_in_ms();
     };
+    compute_graph.updated_at = get_epoch_time_in_ms();
 
     let serialized_compute_graph = JsonEncoder::encode(&compute_graph)?;
-    db.put_cf(
-        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
-        compute_graph.key(),
-        &serialized_compute_graph,
+    txn.put_cf(&cf, compute_graph.key(), &serialized_compute_graph)?;
+    txn.put_cf(&updates_cf, compute_graph.update_index_key(), [])?;
+    Ok(created)
+}
+
+pub(crate) fn set_compute_graph_enabled(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    req: &SetComputeGraphEnabledRequest,
+) -> Result<()> {
+    let key = format!("{}|{}", req.namespace, req.name);
+    let cf = IndexifyObjectsColumns::ComputeGraphs.cf_db(&db);
+    let compute_graph = txn
+        .get_for_update_cf(&cf, &key, true)?
+        .ok_or(anyhow!("Compute graph not found: {}", req.name))?;
+    let mut compute_graph: ComputeGraph = JsonEncoder::decode(&compute_graph)?;
+    compute_graph.enabled = req.enabled;
+    txn.put_cf(&cf, &key, JsonEncoder::encode(&compute_graph)?)?;
+    Ok(())
+}
+
+/// Replaces the code of a compute graph and returns whether its previous code
+/// blob was queued for deletion. The blob is kept when another compute graph,
+/// in any namespace, still uses it, as instantiated graphs may share the code
+/// of their template.
+pub(crate) fn swap_compute_graph_code(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    req: &SwapComputeGraphCodeRequest,
+) -> Result<bool> {
+    let key = format!("{}|{}", req.namespace, req.name);
+    let cf = IndexifyObjectsColumns::ComputeGraphs.cf_db(&db);
+    let compute_graph = txn
+        .get_for_update_cf(&cf, &key, true)?
+        .ok_or(anyhow!("Compute graph not found: {}", req.name))?;
+    let mut compute_graph: ComputeGraph = JsonEncoder::decode(&compute_graph)?;
+    if compute_graph.frozen {
+        return Err(ComputeGraphFrozen {
+            namespace: req.namespace.clone(),
+            name: req.name.clone(),
+        }
+        .into());
+    }
+    let updates_cf = IndexifyObjectsColumns::ComputeGraphUpdates.cf_db(&db);
+    txn.delete_cf(&updates_cf, compute_graph.update_index_key())?;
+    let previous = std::mem::replace(&mut compute_graph.code, req.code.clone());
+    if previous.sha256_hash != compute_graph.code.sha256_hash {
+        compute_graph.version = compute_graph.version.next();
+    }
+    compute_graph.updated_at = get_epoch_time_in_ms();
+    txn.put_cf(&cf, &key, JsonEncoder::encode(&compute_graph)?)?;
+    txn.put_cf(&updates_cf, compute_graph.update_index_key(), [])?;
+
+    if !req.delete_previous || previous.path == compute_graph.code.path {
+        return Ok(false);
+    }
+    for kv in txn.iterator_cf(&cf, IteratorMode::Start) {
+        let (other_key, value) = kv?;
+        if *other_key != *key.as_bytes() &&
+            JsonEncoder::decode::<ComputeGraph>(&value)?.code.path == previous.path
+        {
+            return Ok(false);
+        }
+    }
+    txn.put_cf(
+        &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+        previous.path.as_bytes(),
+        [],
     )?;
+    Ok(true)
+}
+
+pub(crate) fn set_compute_graph_frozen(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    req: &SetComputeGraphFrozenRequest,
+) -> Result<()> {
+    let key = format!("{}|{}", req.namespace, req.name);
+    let cf = IndexifyObjectsColumns::ComputeGraphs.cf_db(&db);
+    let compute_graph = txn
+        .get_for_update_cf(&cf, &key, true)?
+        .ok_or(anyhow!("Compute graph not found: {}", req.name))?;
+    let mut compute_graph: ComputeGraph = JsonEncoder::decode(&compute_graph)?;
+    compute_graph.frozen = req.frozen;
+    txn.put_cf(&cf, &key, JsonEncoder::encode(&compute_graph)?)?;
+    Ok(())
+}
+
+pub(crate) fn set_namespace_policy(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    req: &SetNamespacePolicyRequest,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::Namespaces.cf_db(&db);
+    let namespace = txn
+        .get_for_update_cf(&cf, &req.namespace, true)?
+        .ok_or(anyhow!("Namespace not found: {}", req.namespace))?;
+    let mut namespace: Namespace = JsonEncoder::decode(&namespace)?;
+    namespace.retention = req.retention.clone();
+    txn.put_cf(&cf, &namespace.name, JsonEncoder::encode(&namespace)?)?;
     Ok(())
 }
 
 fn delete_cf_prefix(
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     cf: &impl AsColumnFamilyRef,
     prefix: &[u8],
 ) -> Result<()> {
@@ -422,14 +1079,41 @@ fn delete_cf_prefix(
 
 pub fn delete_compute_graph(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     namespace: &str,
     name: &str,
+    if_match: Option<&[String]>,
 ) -> Result<()> {
-    txn.delete_cf(
-        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
-        format!("{}|{}", namespace, name),
-    )?;
+    let cf = IndexifyObjectsColumns::ComputeGraphs.cf_db(&db);
+    let key = format!("{}|{}", namespace, name);
+    let existing_compute_graph = txn.get_for_update_cf(&cf, &key, true)?;
+    if let Some(if_match) = if_match {
+        let matches = existing_compute_graph
+            .as_ref()
+            .is_some_and(|existing| if_match.contains(&compute_graph_etag(existing)));
+        if !matches {
+            return Err(ComputeGraphEtagMismatch {
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+            }
+            .into());
+        }
+    }
+    if let Some(compute_graph) = existing_compute_graph {
+        let compute_graph: ComputeGraph = JsonEncoder::decode(&compute_graph)?;
+        if compute_graph.frozen {
+            return Err(ComputeGraphFrozen {
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+            }
+            .into());
+        }
+        txn.delete_cf(
+            &IndexifyObjectsColumns::ComputeGraphUpdates.cf_db(&db),
+            compute_graph.update_index_key(),
+        )?;
+    }
+    txn.delete_cf(&cf, key)?;
     let prefix = format!("{}|{}|", namespace, name);
     delete_cf_prefix(
         txn,
@@ -443,6 +1127,18 @@ pub fn delete_compute_graph(
         prefix.as_bytes(),
     )?;
 
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::InputAttributes.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::LatestOutputs.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+
     for iter in make_prefix_iterator(
         txn,
         &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
@@ -465,12 +1161,69 @@ pub fn delete_compute_graph(
         txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&db), &key)?;
     }
 
+    // Failures are keyed by namespace and time, the ones of the graph are
+    // picked out of the namespace's.
+    let failed_outputs_cf = IndexifyObjectsColumns::FailedOutputs.cf_db(&db);
+    let namespace_prefix = format!("{}|", namespace);
+    for kv in make_prefix_iterator(txn, &failed_outputs_cf, namespace_prefix.as_bytes(), &None) {
+        let (key, value) = kv?;
+        if JsonEncoder::decode::<FailedOutput>(&value)?.compute_graph_name == name {
+            txn.delete_cf(&failed_outputs_cf, &key)?;
+        }
+    }
+
     Ok(())
 }
 
+/// Deletes a namespace, and its compute graphs when the request is forced.
+/// Returns the names of the deleted compute graphs.
+pub(crate) fn delete_namespace(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    req: &DeleteNamespaceRequest,
+) -> Result<Vec<String>> {
+    // Graphs created concurrently hold a shared lock on the namespace until
+    // they are committed, so they are part of the scan below.
+    let namespaces_cf = IndexifyObjectsColumns::Namespaces.cf_db(&db);
+    let namespace = txn.get_for_update_cf(&namespaces_cf, &req.name, true)?;
+    let prefix = format!("{}|", req.name);
+    let mut compute_graphs = Vec::new();
+    for kv in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (_, value) = kv?;
+        compute_graphs.push(JsonEncoder::decode::<ComputeGraph>(&value)?.name);
+    }
+    if !compute_graphs.is_empty() && !req.force {
+        return Err(NamespaceNotEmpty {
+            namespace: req.name.clone(),
+            compute_graphs: compute_graphs.len(),
+        }
+        .into());
+    }
+    for name in &compute_graphs {
+        delete_compute_graph(db.clone(), txn, &req.name, name, None)?;
+    }
+    if namespace.is_some() {
+        let count = lock_namespace_count(&db, txn)?;
+        put_namespace_count(&db, txn, count.saturating_sub(1))?;
+    }
+    txn.delete_cf(&namespaces_cf, &req.name)?;
+    if let Some(namespace) = namespace {
+        txn.delete_cf(
+            &IndexifyObjectsColumns::NamespaceCreations.cf_db(&db),
+            JsonEncoder::decode::<Namespace>(&namespace)?.creation_index_key(),
+        )?;
+    }
+    Ok(compute_graphs)
+}
+
 pub fn remove_gc_urls(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     urls: Vec<String>,
 ) -> Result<()> {
     for url in urls {
@@ -479,8 +1232,44 @@ pub fn remove_gc_urls(
     Ok(())
 }
 
+pub(crate) fn save_change_events<'a>(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    events: impl IntoIterator<Item = &'a ChangeEvent>,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::ChangeEvents.cf_db(&db);
+    for event in events {
+        txn.put_cf(&cf, event.key(), JsonEncoder::encode(event)?)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn remove_change_events(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    event_ids: &[u64],
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::ChangeEvents.cf_db(&db);
+    for event_id in event_ids {
+        txn.delete_cf(&cf, event_id.to_be_bytes())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn dead_letter_change_events(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    events: &[DeadLetteredChangeEvent],
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::ChangeEventDeadLetters.cf_db(&db);
+    for event in events {
+        txn.put_cf(&cf, event.key(), JsonEncoder::encode(event)?)?;
+    }
+    Ok(())
+}
+
 pub fn make_prefix_iterator<'a>(
-    txn: &'a Transaction<TransactionDB>,
+    txn: &'a PrefixedTransaction,
     cf_handle: &impl AsColumnFamilyRef,
     prefix: &'a [u8],
     restart_key: &'a Option<Vec<u8>>,
@@ -504,7 +1293,7 @@ pub fn make_prefix_iterator<'a>(
 
 pub(crate) fn processed_reduction_tasks(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     task: &ReductionTasks,
 ) -> Result<()> {
     let cf = &IndexifyObjectsColumns::ReductionTasks.cf_db(&db);
@@ -527,7 +1316,7 @@ pub(crate) enum InvocationCompletion {
 // returns true if system task has finished
 pub(crate) fn create_tasks(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     req: &CreateTasksRequest,
 ) -> Result<Option<InvocationCompletion>> {
     let ctx_key = format!(
@@ -562,6 +1351,9 @@ pub(crate) fn create_tasks(
             .or_insert_with(|| TaskAnalytics::default());
         analytics.pending();
     }
+    if !req.tasks.is_empty() {
+        graph_ctx.status = InvocationStatus::Processing;
+    }
     graph_ctx.outstanding_tasks += req.tasks.len() as u64;
     // Subtract reference for completed state change event
     graph_ctx.outstanding_tasks -= 1;
@@ -586,7 +1378,7 @@ pub(crate) fn create_tasks(
 
 pub fn allocate_tasks(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     task: &Task,
     executor_id: &ExecutorId,
 ) -> Result<()> {
@@ -606,7 +1398,7 @@ pub fn allocate_tasks(
 /// If task was already completed, returns false.
 pub fn mark_task_completed(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     req: FinalizeTaskRequest,
 ) -> Result<bool> {
     let task_key = format!(
@@ -635,9 +1427,33 @@ pub fn mark_task_completed(
             &req.task_id
         ))?;
     let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
+
+    if req.task_outcome == data_model::TaskOutcome::Failure {
+        let failed_output = FailedOutput {
+            namespace: req.namespace.clone(),
+            compute_graph_name: req.compute_graph.clone(),
+            compute_fn_name: req.compute_fn.clone(),
+            invocation_id: req.invocation_id.clone(),
+            task_id: req.task_id.to_string(),
+            failed_at: get_epoch_time_in_ms(),
+            errors: req.node_outputs.iter().find_map(|o| o.errors.clone()),
+            diagnostics: req.diagnostics.clone(),
+        };
+        txn.put_cf(
+            &IndexifyObjectsColumns::FailedOutputs.cf_db(&db),
+            failed_output.key(),
+            JsonEncoder::encode(&failed_output)?,
+        )?;
+    }
+
     for mut output in req.node_outputs {
         // Update with correct graph version
         output.graph_version = graph_ctx.graph_version;
+        // Tasks of the start function consume the invocation input, whose
+        // key is the invocation id.
+        if task.input_node_output_key != req.invocation_id {
+            output.parent_output_key = Some(task.input_node_output_key.clone());
+        }
 
         let serialized_output = JsonEncoder::encode(&output)?;
         // Create an output key
@@ -647,6 +1463,11 @@ pub fn mark_task_completed(
             &output_key,
             serialized_output,
         )?;
+        txn.put_cf(
+            &IndexifyObjectsColumns::LatestOutputs.cf_db(&db),
+            &graph_ctx_key,
+            JsonEncoder::encode(&output_key)?,
+        )?;
 
         // Create a key to store the pointer to the node output to the task
         // NS_TASK_ID_<OutputID> -> Output Key
@@ -693,7 +1514,7 @@ pub fn mark_task_completed(
 
 pub(crate) fn save_state_changes(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     state_changes: &Vec<StateChange>,
 ) -> Result<()> {
     for state_change in state_changes {
@@ -722,7 +1543,7 @@ pub(crate) fn save_state_changes(
 
 pub(crate) fn mark_state_changes_processed(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     state_change_ids: &Vec<StateChangeId>,
 ) -> Result<()> {
     let mut state_changes = Vec::new();
@@ -747,7 +1568,7 @@ pub(crate) fn mark_state_changes_processed(
 // Returns true if the invocation was a system task
 fn mark_invocation_finished(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     namespace: &str,
     compute_graph: &str,
     invocation_id: &str,
@@ -767,6 +1588,11 @@ fn mark_invocation_finished(
         ))?;
     let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
     graph_ctx.completed = true;
+    graph_ctx.status = if graph_ctx.has_failed_tasks() {
+        InvocationStatus::Failed
+    } else {
+        InvocationStatus::Completed
+    };
     let serialized_graph_ctx = JsonEncoder::encode(&graph_ctx)?;
     txn.put_cf(
         &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
@@ -797,7 +1623,7 @@ fn mark_invocation_finished(
 
 pub(crate) fn register_executor(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     req: &RegisterExecutorRequest,
 ) -> Result<()> {
     let serialized_executor_metadata = JsonEncoder::encode(&req.executor)?;
@@ -811,7 +1637,7 @@ pub(crate) fn register_executor(
 
 pub(crate) fn deregister_executor(
     db: Arc<TransactionDB>,
-    txn: &Transaction<TransactionDB>,
+    txn: &PrefixedTransaction,
     req: &DeregisterExecutorRequest,
 ) -> Result<()> {
     let mut read_options = ReadOptions::default();
@@ -839,3 +1665,146 @@ pub(crate) fn deregister_executor(
     )?;
     Ok(())
 }
+
+/// Brings the UnallocatedTasks entries of the given task keys in line with
+/// the Tasks and TaskAllocations column families: a task is indexed when it
+/// exists, hasn't finished and isn't allocated to an executor. The entries and
+/// tasks are locked before the allocations are read, so allocations and
+/// completions committed concurrently are never undone. Returns the number of
+/// entries added and removed.
+pub(crate) fn reindex_unallocated_tasks(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+    task_keys: &[Vec<u8>],
+) -> Result<(usize, usize)> {
+    let unallocated_tasks_cf = IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db);
+    let tasks_cf = IndexifyObjectsColumns::Tasks.cf_db(&db);
+    let mut tasks = Vec::with_capacity(task_keys.len());
+    for task_key in task_keys {
+        let indexed = txn
+            .get_for_update_cf(&unallocated_tasks_cf, task_key, true)?
+            .is_some();
+        let pending = match txn.get_for_update_cf(&tasks_cf, task_key, true)? {
+            Some(task) => !JsonEncoder::decode::<Task>(&task)?.terminal_state(),
+            None => false,
+        };
+        tasks.push((task_key, indexed, pending));
+    }
+
+    let mut allocated = HashSet::new();
+    let iter = txn.iterator_cf(
+        &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+        IteratorMode::Start,
+    );
+    for kv in iter {
+        let (key, _) = kv?;
+        allocated.insert(Task::key_from_allocation_key(&key)?);
+    }
+
+    let (mut added, mut removed) = (0, 0);
+    for (task_key, indexed, pending) in tasks {
+        let should_index = pending && !allocated.contains(task_key);
+        if should_index && !indexed {
+            txn.put_cf(&unallocated_tasks_cf, task_key, [])?;
+            added += 1;
+        } else if !should_index && indexed {
+            txn.delete_cf(&unallocated_tasks_cf, task_key)?;
+            removed += 1;
+        }
+    }
+    Ok((added, removed))
+}
+
+/// Brings the index of namespaces by creation time and the number of
+/// namespaces in line with the namespaces, locking them like
+/// `reindex_compute_graph_updates` locks the graphs. Returns the number of
+/// namespaces scanned and of entries added and removed.
+pub(crate) fn reindex_namespace_creations(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+) -> Result<(usize, usize, usize)> {
+    let namespaces_cf = IndexifyObjectsColumns::Namespaces.cf_db(&db);
+    let creations_cf = IndexifyObjectsColumns::NamespaceCreations.cf_db(&db);
+    // Namespaces can't be created or deleted once the count is locked, so
+    // the scanned namespaces are all of them.
+    lock_namespace_count(&db, txn)?;
+    let mut names = Vec::new();
+    for kv in txn.iterator_cf(&namespaces_cf, IteratorMode::Start) {
+        names.push(kv?.0);
+    }
+    put_namespace_count(&db, txn, names.len())?;
+    let mut indexed = HashSet::new();
+    for name in &names {
+        if let Some(namespace) = txn.get_for_update_cf(&namespaces_cf, name, true)? {
+            indexed.insert(JsonEncoder::decode::<Namespace>(&namespace)?.creation_index_key());
+        }
+    }
+    let mut entries = HashSet::new();
+    for kv in txn.iterator_cf(&creations_cf, IteratorMode::Start) {
+        entries.insert(kv?.0.to_vec());
+    }
+
+    let (mut added, mut removed) = (0, 0);
+    for key in indexed.difference(&entries) {
+        txn.put_cf(&creations_cf, key, [])?;
+        added += 1;
+    }
+    for key in entries.difference(&indexed) {
+        // Namespaces created since they were scanned.
+        if let Ok(name) = Namespace::name_from_creation_index_key(key) {
+            if let Some(namespace) = txn.get_for_update_cf(&namespaces_cf, name, true)? {
+                if JsonEncoder::decode::<Namespace>(&namespace)?.creation_index_key() == *key {
+                    continue;
+                }
+            }
+        }
+        txn.delete_cf(&creations_cf, key)?;
+        removed += 1;
+    }
+    Ok((names.len(), added, removed))
+}
+
+/// Brings the index of compute graphs by update time in line with the
+/// compute graphs. The graphs are locked before their entries are checked, so
+/// entries written by concurrent updates are kept. Returns the number of
+/// graphs scanned and of entries added and removed.
+pub(crate) fn reindex_compute_graph_updates(
+    db: Arc<TransactionDB>,
+    txn: &PrefixedTransaction,
+) -> Result<(usize, usize, usize)> {
+    let graphs_cf = IndexifyObjectsColumns::ComputeGraphs.cf_db(&db);
+    let updates_cf = IndexifyObjectsColumns::ComputeGraphUpdates.cf_db(&db);
+    let mut graph_keys = Vec::new();
+    for kv in txn.iterator_cf(&graphs_cf, IteratorMode::Start) {
+        graph_keys.push(kv?.0);
+    }
+    let mut indexed = HashSet::new();
+    for graph_key in &graph_keys {
+        if let Some(compute_graph) = txn.get_for_update_cf(&graphs_cf, graph_key, true)? {
+            indexed.insert(JsonEncoder::decode::<ComputeGraph>(&compute_graph)?.update_index_key());
+        }
+    }
+    let mut entries = HashSet::new();
+    for kv in txn.iterator_cf(&updates_cf, IteratorMode::Start) {
+        entries.insert(kv?.0.to_vec());
+    }
+
+    let (mut added, mut removed) = (0, 0);
+    for key in indexed.difference(&entries) {
+        txn.put_cf(&updates_cf, key, [])?;
+        added += 1;
+    }
+    for key in entries.difference(&indexed) {
+        // Graphs created or updated since they were scanned.
+        if let Ok(graph_key) = ComputeGraph::key_from_update_index_key(key) {
+            if let Some(compute_graph) = txn.get_for_update_cf(&graphs_cf, graph_key, true)? {
+                if JsonEncoder::decode::<ComputeGraph>(&compute_graph)?.update_index_key() == *key {
+                    continue;
+                }
+            }
+        }
+        txn.delete_cf(&updates_cf, key)?;
+        removed += 1;
+    }
+    Ok((graph_keys.len(), added, removed))
+}
@@ -48,10 +48,12 @@ pub mod tests {
             let cg_request = CreateComputeGraphRequest {
                 namespace: TEST_NAMESPACE.to_string(),
                 compute_graph: tests::mock_graph_a(),
+                if_match: None,
+                if_changed: false,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
-                    payload: RequestPayload::CreateComputeGraph(cg_request),
+                    payload: RequestPayload::CreateComputeGraph(Box::new(cg_request)),
                     state_changes_processed: vec![],
                 })
                 .await
@@ -76,10 +78,12 @@ pub mod tests {
             let cg_request = CreateComputeGraphRequest {
                 namespace: TEST_NAMESPACE.to_string(),
                 compute_graph: tests::mock_graph_b(),
+                if_match: None,
+                if_changed: false,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
-                    payload: RequestPayload::CreateComputeGraph(cg_request),
+                    payload: RequestPayload::CreateComputeGraph(Box::new(cg_request)),
                     state_changes_processed: vec![],
                 })
                 .await
@@ -105,10 +109,12 @@ pub mod tests {
             let cg_request = CreateComputeGraphRequest {
                 namespace: TEST_NAMESPACE.to_string(),
                 compute_graph: tests::mock_graph_with_reducer(),
+                if_match: None,
+                if_changed: false,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
-                    payload: RequestPayload::CreateComputeGraph(cg_request),
+                    payload: RequestPayload::CreateComputeGraph(Box::new(cg_request)),
                     state_changes_processed: vec![],
                 })
                 .await